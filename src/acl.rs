@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use rmcp::model::Tool;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::tenant::Tenant;
+
+/// What a grant lets a caller do with a table. Each level includes the ones
+/// below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    /// Search, list and inspect
+    Read,
+    /// Add documents
+    Write,
+    /// Change the table config, swap or otherwise destroy data
+    Admin,
+}
+
+impl Access {
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Admin => "admin",
+        }
+    }
+
+    /// The access a tool needs on the table it names, from its annotations:
+    /// read-only tools read, tools that are not destructive write, the rest
+    /// (destructive by default, as in MCP) administer. `None` for tools that
+    /// name no table.
+    fn required(tool: &Tool) -> Option<Self> {
+        let properties = tool.input_schema.get("properties").and_then(|p| p.as_object());
+        if !properties.is_some_and(|p| p.contains_key("table_name")) {
+            return None;
+        }
+        let annotations = tool.annotations.as_ref();
+        Some(if annotations.and_then(|a| a.read_only_hint) == Some(true) {
+            Access::Read
+        } else if annotations.and_then(|a| a.destructive_hint) == Some(false) {
+            Access::Write
+        } else {
+            Access::Admin
+        })
+    }
+}
+
+/// One `[[grant]]` of the policy file: `access` to the `tables` for the
+/// callers with `tenant` or the token whose SHA-256 is `token_sha256`, or
+/// for every caller when neither is given.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Grant {
+    tenant: Option<String>,
+    token_sha256: Option<String>,
+    /// Table names, `*` at the end matches any suffix
+    tables: Vec<String>,
+    access: Access,
+}
+
+impl Grant {
+    fn applies(&self, tenant: Option<&Tenant>, token: Option<&str>) -> bool {
+        let tenant_matches = self.tenant.as_deref().is_none_or(|t| tenant.is_some_and(|tenant| tenant.id() == t));
+        let token_matches = self.token_sha256.as_deref().is_none_or(|t| token == Some(t));
+        tenant_matches && token_matches
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default, rename = "grant")]
+    grants: Vec<Grant>,
+}
+
+/// The `--acl` policy: which callers may read, write or administer which
+/// tables. Anything not granted is denied.
+pub struct Acl {
+    grants: Vec<Grant>,
+    /// Tool name -> access it needs on the table it names
+    tools: HashMap<String, Access>,
+}
+
+impl Acl {
+    pub fn load(path: &Path, tools: &[Tool]) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read ACL file {}", path.display()))?;
+        let file: PolicyFile = toml::from_str(&text).with_context(|| format!("Invalid ACL file {}", path.display()))?;
+        let mut grants = file.grants;
+        for grant in &mut grants {
+            if let Some(tenant) = &grant.tenant {
+                Tenant::new(tenant).with_context(|| format!("In ACL file {}", path.display()))?;
+            }
+            if let Some(token) = &mut grant.token_sha256 {
+                if token.len() != 64 || !token.chars().all(|c| c.is_ascii_hexdigit()) {
+                    anyhow::bail!("Invalid token_sha256 '{}' in {}, expected 64 hex digits", token, path.display());
+                }
+                token.make_ascii_lowercase();
+            }
+        }
+        Ok(Self {
+            grants,
+            tools: tools.iter().filter_map(|t| Some((t.name.to_string(), Access::required(t)?))).collect(),
+        })
+    }
+
+    /// The access `tool` needs on the table it names, `None` if it names none.
+    pub fn required(&self, tool: &str) -> Option<Access> {
+        self.tools.get(tool).copied()
+    }
+
+    /// What a session opened with this tenant and token may do.
+    pub fn permissions(&self, tenant: Option<&Tenant>, token: Option<&str>) -> Permissions {
+        let grants = self
+            .grants
+            .iter()
+            .filter(|g| g.applies(tenant, token))
+            .flat_map(|g| g.tables.iter().map(|t| (t.clone(), g.access)))
+            .collect();
+        Permissions {
+            token: token.map(str::to_string),
+            grants,
+        }
+    }
+}
+
+/// The grants that apply to one session.
+pub struct Permissions {
+    /// Fingerprint of the token the session was opened with
+    token: Option<String>,
+    /// Table pattern -> access granted on it
+    grants: Vec<(String, Access)>,
+}
+
+impl Permissions {
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn allows(&self, table: &str, access: Access) -> bool {
+        self.grants.iter().any(|(pattern, granted)| {
+            let matches = match pattern.strip_suffix('*') {
+                Some(prefix) => table.starts_with(prefix),
+                None => table == pattern,
+            };
+            matches && *granted >= access
+        })
+    }
+}
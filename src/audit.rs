@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::tenant::Tenant;
+
+/// One line of the audit log: either an applied mutation, which carries the
+/// full arguments so it can be replayed, or the record of a tool call.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub arguments: serde_json::Value,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub call: Option<CallRecord>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The tool ran and returned a result
+    Ok,
+    /// The tool ran and returned an error response
+    Error,
+    /// The call never reached the tool, e.g. unknown tool or malformed arguments
+    Rejected,
+}
+
+/// Who called which tool, on what and how it went. Arguments are only kept
+/// as a hash, so the log never holds document text or queries.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CallRecord {
+    /// MCP session the call came from
+    pub session: u64,
+    /// Tenant the session belongs to, with --multi-tenant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Client name the session announced when it initialized
+    pub client: Option<String>,
+    pub table: Option<String>,
+    /// SHA-256 of the JSON arguments
+    pub arguments_sha256: String,
+    pub duration_ms: f64,
+    pub outcome: Outcome,
+    /// Error code of a failed call
+    pub code: Option<i64>,
+}
+
+impl CallRecord {
+    pub fn hash_arguments(arguments: Option<&serde_json::Map<String, serde_json::Value>>) -> String {
+        // serde_json maps are sorted, so equal arguments always hash the same
+        let json = arguments.map(|a| serde_json::to_string(a).unwrap_or_default()).unwrap_or_default();
+        format!("{:x}", Sha256::digest(json.as_bytes()))
+    }
+}
+
+/// Append-only JSONL log of the mutating tool calls that succeeded, in the
+/// order they were applied, and of every MCP tool call. `replay` re-executes
+/// the mutations against another database.
+///
+/// Every tenant gets a log of its own next to the configured one, e.g.
+/// `audit.acme.jsonl` for `audit.jsonl`, so no tenant's entries ever end up
+/// in a file another tenant reads. Sessions without a tenant write to the
+/// configured path.
+pub struct AuditLog {
+    path: PathBuf,
+    files: Mutex<HashMap<Option<Tenant>, tokio::fs::File>>,
+}
+
+impl AuditLog {
+    pub async fn open(path: &Path) -> Result<Self> {
+        let file = open_append(path).await?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            files: Mutex::new(HashMap::from([(None, file)])),
+        })
+    }
+
+    /// Path of `tenant`'s log, the configured path for `None`.
+    pub fn path(&self, tenant: Option<&Tenant>) -> PathBuf {
+        let Some(tenant) = tenant else {
+            return self.path.clone();
+        };
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, tenant.id(), ext.to_string_lossy()),
+            None => format!("{}.{}", stem, tenant.id()),
+        };
+        self.path.with_file_name(name)
+    }
+
+    pub async fn record(&self, tenant: Option<&Tenant>, tool: &str, arguments: serde_json::Value) -> Result<()> {
+        self.append(tenant, tool, arguments, None).await
+    }
+
+    pub async fn record_call(&self, tenant: Option<&Tenant>, tool: &str, call: CallRecord) -> Result<()> {
+        self.append(tenant, tool, serde_json::Value::Null, Some(call)).await
+    }
+
+    async fn append(&self, tenant: Option<&Tenant>, tool: &str, arguments: serde_json::Value, call: Option<CallRecord>) -> Result<()> {
+        let entry = AuditEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            tool: tool.to_string(),
+            arguments,
+            call,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let mut files = self.files.lock().await;
+        let file = match files.entry(tenant.cloned()) {
+            std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+            std::collections::hash_map::Entry::Vacant(e) => e.insert(open_append(&self.path(tenant)).await?),
+        };
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open audit log {}", path.display()))
+}
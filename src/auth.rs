@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::tenant::Tenant;
+
+/// Bearer tokens accepted by the HTTP transport. Any one of them grants
+/// access, so tokens can be rotated by adding the new one before removing
+/// the old.
+pub struct BearerAuth {
+    tokens: Vec<AcceptedToken>,
+}
+
+/// Hex SHA-256 of the bearer token a request was accepted with, which
+/// names the token in the ACL file without storing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenFingerprint(pub String);
+
+struct AcceptedToken {
+    /// SHA-256 of the token, compared in constant time
+    digest: [u8; 32],
+    /// Tenant whose tables the token gives access to
+    tenant: Option<Tenant>,
+}
+
+impl BearerAuth {
+    /// Collect the tokens given on the command line and, one per line, in
+    /// token files. A line may name the token's tenant first, as
+    /// `<tenant> <token>`. `None` when no token is configured at all.
+    pub fn new(tokens: &[String], files: &[String]) -> Result<Option<Self>> {
+        let mut accepted = Vec::new();
+        for token in tokens {
+            accepted.push(accepted_token(None, token)?);
+        }
+        for file in files {
+            let content = std::fs::read_to_string(Path::new(file))
+                .with_context(|| format!("Failed to read auth token file {}", file))?;
+            for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+                let token = match line.split_once(char::is_whitespace) {
+                    Some((tenant, token)) => {
+                        let tenant = Tenant::new(tenant).with_context(|| format!("In auth token file {}", file))?;
+                        accepted_token(Some(tenant), token.trim())?
+                    }
+                    None => accepted_token(None, line)?,
+                };
+                accepted.push(token);
+            }
+        }
+        if accepted.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self { tokens: accepted }))
+    }
+
+    /// Whether any token names a tenant.
+    pub fn has_tenants(&self) -> bool {
+        self.tokens.iter().any(|t| t.tenant.is_some())
+    }
+
+    fn find(&self, token: &str) -> Option<&AcceptedToken> {
+        let presented = digest(token);
+        // Check every token so the time taken does not reveal which one matched
+        self.tokens
+            .iter()
+            .fold(None, |found, known| if constant_time_eq(&known.digest, &presented) { Some(known) } else { found })
+    }
+}
+
+fn accepted_token(tenant: Option<Tenant>, token: &str) -> Result<AcceptedToken> {
+    if token.trim().is_empty() {
+        anyhow::bail!("--auth-token must not be empty");
+    }
+    Ok(AcceptedToken {
+        digest: digest(token.trim()),
+        tenant,
+    })
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Reject requests without an `Authorization: Bearer <token>` header carrying
+/// one of the configured tokens, and tag accepted requests with the token's
+/// fingerprint and tenant.
+pub async fn require_bearer(State(auth): State<Arc<BearerAuth>>, mut request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")))
+        .map(str::trim);
+    match token.and_then(|token| auth.find(token)) {
+        Some(accepted) => {
+            let fingerprint = accepted.digest.iter().map(|b| format!("{:02x}", b)).collect();
+            request.extensions_mut().insert(TokenFingerprint(fingerprint));
+            if let Some(tenant) = &accepted.tenant {
+                request.extensions_mut().insert(tenant.clone());
+            }
+            next.run(request).await
+        }
+        None => {
+            tracing::warn!(path = %request.uri().path(), "Rejected unauthenticated HTTP request");
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                "missing or invalid bearer token",
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+
+use crate::db::VectorDB;
+use crate::error::ErrorCode;
+use crate::table_config::TableConfig;
+
+const MANIFEST_FILE: &str = "manifest.json";
+/// Bump when the layout of a backup changes incompatibly.
+const FORMAT_VERSION: u32 = 1;
+
+/// `manifest.json` of a backup: what it holds and the checksum of every file.
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Manifest {
+    pub format_version: u32,
+    /// RFC 3339 time the table versions were pinned
+    pub created_at: String,
+    /// Version of the server that wrote the backup
+    pub server_version: String,
+    pub tables: Vec<TableBackup>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TableBackup {
+    pub name: String,
+    /// Table version the rows were read at
+    pub version: u64,
+    pub rows: usize,
+    /// Parquet file in the backup directory
+    pub file: String,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// The table's stored config, including the embedding model its vectors
+    /// came from
+    #[serde(default)]
+    pub config: TableConfig,
+}
+
+/// Write a backup of `tables` to the new directory `dir`. Each entry is the
+/// stored table name and the name to record it under. All tables are read
+/// as of the version they had when the backup started, so writes made while
+/// it runs are left out rather than half included. The directory appears
+/// only once the backup is complete.
+pub async fn backup(
+    db: &VectorDB,
+    tables: &[(String, String)],
+    dir: &Path,
+    cancel: &CancellationToken,
+    progress: impl Fn(usize, &str),
+) -> Result<Manifest> {
+    if dir.exists() {
+        return Err(anyhow::anyhow!("{} already exists", dir.display())).context(ErrorCode::InvalidArgument);
+    }
+    let partial = partial_dir(dir);
+    let _ = std::fs::remove_dir_all(&partial);
+    std::fs::create_dir_all(&partial).with_context(|| format!("Failed to create {}", partial.display()))?;
+
+    match write_backup(db, tables, &partial, cancel, progress).await {
+        Ok(manifest) => {
+            std::fs::rename(&partial, dir).with_context(|| format!("Failed to move backup into place at {}", dir.display()))?;
+            Ok(manifest)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&partial);
+            Err(e)
+        }
+    }
+}
+
+async fn write_backup(
+    db: &VectorDB,
+    tables: &[(String, String)],
+    dir: &Path,
+    cancel: &CancellationToken,
+    progress: impl Fn(usize, &str),
+) -> Result<Manifest> {
+    let stored: Vec<String> = tables.iter().map(|(stored, _)| stored.clone()).collect();
+    let created_at = chrono::Utc::now().to_rfc3339();
+    let versions = db.pin_versions(&stored).await?;
+
+    let mut manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        created_at,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        tables: Vec::with_capacity(tables.len()),
+    };
+    for (i, ((stored, name), version)) in tables.iter().zip(versions).enumerate() {
+        progress(i, name);
+        let file = format!("{}.parquet", name);
+        let path = dir.join(&file);
+        let rows = write_table(db, stored, version, &path, cancel).await?;
+        manifest.tables.push(TableBackup {
+            name: name.clone(),
+            version,
+            rows,
+            sha256: checksum(path).await?,
+            file,
+            config: db.stored_table_config(stored).await?,
+        });
+    }
+
+    let json = serde_json::to_vec_pretty(&manifest)?;
+    std::fs::write(dir.join(MANIFEST_FILE), json)?;
+    Ok(manifest)
+}
+
+async fn write_table(db: &VectorDB, table_name: &str, version: u64, path: &Path, cancel: &CancellationToken) -> Result<usize> {
+    let mut stream = db.stream_version(table_name, version).await?;
+    let file = std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(file, stream.schema(), Some(props))?;
+    let mut rows = 0;
+    while let Some(batch) = stream.try_next().await? {
+        if cancel.is_cancelled() {
+            return Err(anyhow::anyhow!("Backup cancelled while reading '{}'", table_name)).context(ErrorCode::Cancelled);
+        }
+        writer.write(&batch)?;
+        rows += batch.num_rows();
+    }
+    writer.close()?;
+    Ok(rows)
+}
+
+/// Read the manifest of the backup in `dir` and check every file against
+/// its checksum.
+pub async fn verify(dir: &Path) -> Result<Manifest> {
+    let invalid = |message: String| -> Result<Manifest> { Err(anyhow::anyhow!(message)).context(ErrorCode::InvalidArgument) };
+    let path = dir.join(MANIFEST_FILE);
+    let json = match std::fs::read(&path) {
+        Ok(json) => json,
+        Err(e) => return invalid(format!("{} is not a backup: {}: {}", dir.display(), path.display(), e)),
+    };
+    let manifest: Manifest = match serde_json::from_slice(&json) {
+        Ok(manifest) => manifest,
+        Err(e) => return invalid(format!("Invalid backup manifest {}: {}", path.display(), e)),
+    };
+    if manifest.format_version != FORMAT_VERSION {
+        return invalid(format!(
+            "Backup {} has format version {}, this server reads version {}",
+            dir.display(),
+            manifest.format_version,
+            FORMAT_VERSION
+        ));
+    }
+
+    for table in &manifest.tables {
+        // The file name comes from the manifest; never read outside the backup
+        if Path::new(&table.file).file_name() != Some(std::ffi::OsStr::new(&table.file)) {
+            return invalid(format!("Backup manifest names an invalid file '{}'", table.file));
+        }
+        let path = dir.join(&table.file);
+        let actual = checksum(path.clone())
+            .await
+            .with_context(|| format!("Backup file {} of table '{}' is unreadable", path.display(), table.name))
+            .context(ErrorCode::InvalidArgument)?;
+        if actual != table.sha256 {
+            return invalid(format!(
+                "Backup file {} of table '{}' is corrupt: its SHA-256 is {}, the manifest records {}",
+                path.display(),
+                table.name,
+                actual,
+                table.sha256
+            ));
+        }
+    }
+    Ok(manifest)
+}
+
+/// Restore the tables of a verified backup. `target` maps a table name in
+/// the backup to the stored name to restore it as. Existing tables are
+/// replaced only with `overwrite`; without it nothing is restored when any
+/// of them exists.
+pub async fn restore(
+    db: &VectorDB,
+    dir: &Path,
+    manifest: &Manifest,
+    target: impl Fn(&str) -> String,
+    overwrite: bool,
+    cancel: &CancellationToken,
+    progress: impl Fn(usize, &str),
+) -> Result<()> {
+    if !overwrite {
+        for table in &manifest.tables {
+            if db.table_exists(&target(&table.name)).await? {
+                return Err(anyhow::anyhow!(
+                    "Table '{}' already exists; pass overwrite to replace it with the backup",
+                    table.name
+                ))
+                .context(ErrorCode::InvalidArgument);
+            }
+        }
+    }
+
+    for (i, table) in manifest.tables.iter().enumerate() {
+        if cancel.is_cancelled() {
+            return Err(anyhow::anyhow!("Restore cancelled after {} of {} tables", i, manifest.tables.len()))
+                .context(ErrorCode::Cancelled);
+        }
+        progress(i, &table.name);
+        let stored = target(&table.name);
+        let file = std::fs::File::open(dir.join(&table.file))?;
+        let rows = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+        db.replace_table(&stored, Box::new(rows))
+            .await
+            .with_context(|| format!("Failed to restore table '{}'", table.name))?;
+        db.set_table_config(&stored, &table.config).await?;
+    }
+    Ok(())
+}
+
+/// Hex SHA-256 of a file, read off the async workers.
+async fn checksum(path: PathBuf) -> Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await?
+}
+
+fn partial_dir(dir: &Path) -> PathBuf {
+    let mut name = dir.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    dir.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backup directory holding one table file and a manifest recording
+    /// it as `file` with `sha256`.
+    fn backup_dir(file: &str, sha256: &str, format_version: u32) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("docs.parquet"), b"rows").unwrap();
+        let manifest = Manifest {
+            format_version,
+            created_at: "2024-03-05T10:00:00+00:00".to_string(),
+            server_version: "0.0.0".to_string(),
+            tables: vec![TableBackup {
+                name: "docs".to_string(),
+                version: 1,
+                rows: 1,
+                file: file.to_string(),
+                sha256: sha256.to_string(),
+                config: TableConfig::default(),
+            }],
+        };
+        std::fs::write(dir.path().join(MANIFEST_FILE), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        dir
+    }
+
+    fn rows_sha256() -> String {
+        format!("{:x}", Sha256::digest(b"rows"))
+    }
+
+    fn is_invalid_argument(e: &anyhow::Error) -> bool {
+        e.downcast_ref::<ErrorCode>() == Some(&ErrorCode::InvalidArgument)
+    }
+
+    #[tokio::test]
+    async fn accepts_an_intact_backup() {
+        let dir = backup_dir("docs.parquet", &rows_sha256(), FORMAT_VERSION);
+        let manifest = verify(dir.path()).await.unwrap();
+        assert_eq!(manifest.tables[0].name, "docs");
+    }
+
+    #[tokio::test]
+    async fn rejects_files_outside_the_backup() {
+        for file in ["../docs.parquet", "/etc/passwd", "sub/docs.parquet", "..", ""] {
+            let dir = backup_dir(file, &rows_sha256(), FORMAT_VERSION);
+            let e = verify(dir.path()).await.unwrap_err();
+            assert!(is_invalid_argument(&e), "{}", file);
+            assert!(format!("{:#}", e).contains("invalid file"), "{}: {:#}", file, e);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_corrupt_and_missing_files() {
+        let dir = backup_dir("docs.parquet", &"0".repeat(64), FORMAT_VERSION);
+        let e = verify(dir.path()).await.unwrap_err();
+        assert!(is_invalid_argument(&e));
+        assert!(format!("{:#}", e).contains("corrupt"));
+
+        let dir = backup_dir("other.parquet", &rows_sha256(), FORMAT_VERSION);
+        assert!(is_invalid_argument(&verify(dir.path()).await.unwrap_err()));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_unknown_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(is_invalid_argument(&verify(dir.path()).await.unwrap_err()));
+
+        std::fs::write(dir.path().join(MANIFEST_FILE), b"{").unwrap();
+        assert!(is_invalid_argument(&verify(dir.path()).await.unwrap_err()));
+
+        let dir = backup_dir("docs.parquet", &rows_sha256(), FORMAT_VERSION + 1);
+        assert!(is_invalid_argument(&verify(dir.path()).await.unwrap_err()));
+    }
+}
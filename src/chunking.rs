@@ -0,0 +1,45 @@
+/// Default maximum chunk length, in characters.
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+/// Default number of characters shared between consecutive chunks.
+pub const DEFAULT_CHUNK_OVERLAP: usize = 100;
+
+/// Split text into chunks of at most `chunk_size` characters.
+///
+/// Chunk boundaries prefer a line break, then whitespace, in the second half of
+/// the window so words and paragraphs are not cut in the middle when possible.
+/// Consecutive chunks share `overlap` characters of context.
+pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let chunk_size = chunk_size.max(1);
+    let overlap = overlap.min(chunk_size / 2);
+    let chars: Vec<char> = text.chars().collect();
+    let len = chars.len();
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < len {
+        let mut end = (start + chunk_size).min(len);
+
+        if end < len {
+            let window = &chars[start + chunk_size / 2..end];
+            if let Some(pos) = window.iter().rposition(|c| *c == '\n') {
+                end = start + chunk_size / 2 + pos + 1;
+            } else if let Some(pos) = window.iter().rposition(|c| c.is_whitespace()) {
+                end = start + chunk_size / 2 + pos + 1;
+            }
+        }
+
+        let chunk: String = chars[start..end].iter().collect();
+        let chunk = chunk.trim();
+        if !chunk.is_empty() {
+            chunks.push(chunk.to_string());
+        }
+
+        if end >= len {
+            break;
+        }
+        start = (end - overlap).max(start + 1);
+    }
+
+    chunks
+}
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result};
+use arrow::array::{Array, ArrayRef, BooleanArray, BooleanBuilder, Float64Array, Float64Builder, Int64Array, Int64Builder, RecordBatch, StringArray, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema};
+use std::sync::Arc;
+
+use crate::error::ErrorCode;
+
+/// The vector column every table has. Others can be added per model or
+/// field, see `VectorDB::embed_into_column`.
+pub const VECTOR_COLUMN: &str = "vector";
+
+/// Marks tombstoned rows. Added to a table by its first soft delete.
+pub const DELETED_COLUMN: &str = "deleted";
+
+/// What a row's vector was embedded from, `text` or `image`. Added to a
+/// table by its first image.
+pub const MODALITY_COLUMN: &str = "modality";
+pub const MODALITY_TEXT: &str = "text";
+pub const MODALITY_IMAGE: &str = "image";
+
+/// Sparse vectors of rows written while a sparse model ran: the vocabulary
+/// terms and their weights, as two lists of equal length.
+pub const SPARSE_INDICES_COLUMN: &str = "sparse_indices";
+pub const SPARSE_VALUES_COLUMN: &str = "sparse_values";
+
+/// Sign bits of the `vector` column, eight dimensions to a byte, kept on
+/// tables with binary quantization.
+pub const BITS_COLUMN: &str = "vector_bits";
+
+/// Columns of the server's own. Any other column holds a metadata key,
+/// copied there by `create_scalar_index` or added by `add_column`.
+pub const CORE_COLUMNS: [&str; 10] = [
+    "id",
+    "text",
+    VECTOR_COLUMN,
+    "metadata",
+    "importance",
+    DELETED_COLUMN,
+    MODALITY_COLUMN,
+    SPARSE_INDICES_COLUMN,
+    SPARSE_VALUES_COLUMN,
+    BITS_COLUMN,
+];
+
+/// Type of a metadata column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+impl ColumnType {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "string" => Ok(Self::String),
+            "integer" => Ok(Self::Integer),
+            "float" => Ok(Self::Float),
+            "boolean" => Ok(Self::Boolean),
+            other => Err(anyhow::anyhow!(
+                "Unknown column type '{}', expected 'string', 'integer', 'float' or 'boolean'",
+                other
+            ))
+            .context(ErrorCode::InvalidArgument),
+        }
+    }
+
+    /// The type of a default value; `None` for null.
+    pub fn of(value: &serde_json::Value) -> Result<Option<Self>> {
+        match value {
+            serde_json::Value::Null => Ok(None),
+            serde_json::Value::String(_) => Ok(Some(Self::String)),
+            serde_json::Value::Bool(_) => Ok(Some(Self::Boolean)),
+            serde_json::Value::Number(n) if n.is_i64() => Ok(Some(Self::Integer)),
+            serde_json::Value::Number(_) => Ok(Some(Self::Float)),
+            _ => Err(anyhow::anyhow!("A column default must be a string, number, boolean or null"))
+                .context(ErrorCode::InvalidArgument),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::Boolean => "boolean",
+        }
+    }
+
+    fn from_arrow(data_type: &DataType) -> Option<Self> {
+        match data_type {
+            DataType::Utf8 => Some(Self::String),
+            DataType::Int64 => Some(Self::Integer),
+            DataType::Float64 => Some(Self::Float),
+            DataType::Boolean => Some(Self::Boolean),
+            _ => None,
+        }
+    }
+
+    pub fn data_type(self) -> DataType {
+        match self {
+            Self::String => DataType::Utf8,
+            Self::Integer => DataType::Int64,
+            Self::Float => DataType::Float64,
+            Self::Boolean => DataType::Boolean,
+        }
+    }
+
+    /// A SQL expression filling a new column with `value`, which must be
+    /// null or of this type.
+    pub fn sql_literal(self, value: &serde_json::Value) -> Result<String> {
+        let sql_type = match self {
+            Self::String => "STRING",
+            Self::Integer => "BIGINT",
+            Self::Float => "DOUBLE",
+            Self::Boolean => "BOOLEAN",
+        };
+        let literal = match (self, value) {
+            (_, serde_json::Value::Null) => "NULL".to_string(),
+            (Self::String, serde_json::Value::String(s)) => format!("'{}'", s.replace('\'', "''")),
+            (Self::Integer, serde_json::Value::Number(n)) if n.is_i64() => n.to_string(),
+            (Self::Float, serde_json::Value::Number(n)) => n.to_string(),
+            (Self::Boolean, serde_json::Value::Bool(b)) => b.to_string(),
+            _ => {
+                return Err(anyhow::anyhow!("Default {} does not fit a {} column", value, self.name())).context(ErrorCode::InvalidArgument);
+            }
+        };
+        Ok(format!("CAST({} AS {})", literal, sql_type))
+    }
+}
+
+/// Fail unless `key` can name a metadata column: lowercase letters, digits
+/// and underscores, so it needs no quoting in filters, and not a core column.
+pub fn check_key(key: &str) -> Result<()> {
+    if !is_plain_name(key) {
+        return Err(anyhow::anyhow!(
+            "Metadata key '{}' cannot be a column: use lowercase letters, digits and underscores",
+            key
+        ))
+        .context(ErrorCode::InvalidArgument);
+    }
+    if CORE_COLUMNS.contains(&key) {
+        return Err(anyhow::anyhow!("'{}' is a built-in column, not a metadata key", key)).context(ErrorCode::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Fail unless `name` can name a new vector column, by the rules of
+/// `check_key`.
+pub fn check_vector_column(name: &str) -> Result<()> {
+    if !is_plain_name(name) || CORE_COLUMNS.contains(&name) {
+        return Err(anyhow::anyhow!(
+            "'{}' cannot name a vector column: use lowercase letters, digits and underscores, not a built-in column",
+            name
+        ))
+        .context(ErrorCode::InvalidArgument);
+    }
+    Ok(())
+}
+
+fn is_plain_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Whether `field` holds vectors, dense or sparse, rather than row data.
+pub fn is_vector(field: &Field) -> bool {
+    matches!(field.data_type(), DataType::FixedSizeList(..))
+        || field.name() == SPARSE_INDICES_COLUMN
+        || field.name() == SPARSE_VALUES_COLUMN
+}
+
+/// The metadata columns of a table, in schema order.
+pub fn metadata_columns(schema: &Schema) -> Vec<(String, ColumnType)> {
+    schema
+        .fields()
+        .iter()
+        .filter(|f| !CORE_COLUMNS.contains(&f.name().as_str()) && !f.name().starts_with('_'))
+        .filter_map(|f| Some((f.name().clone(), ColumnType::from_arrow(f.data_type())?)))
+        .collect()
+}
+
+/// A column of `values`. Values that do not fit the type are stored as null,
+/// except that a string column keeps any value as JSON text.
+pub fn build_array(values: &[Option<&serde_json::Value>], column_type: ColumnType) -> ArrayRef {
+    match column_type {
+        ColumnType::String => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                builder.append_option(value.and_then(|v| match v {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                }));
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Integer => {
+            let mut builder = Int64Builder::new();
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_i64()));
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Float => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_f64()));
+            }
+            Arc::new(builder.finish())
+        }
+        ColumnType::Boolean => {
+            let mut builder = BooleanBuilder::new();
+            for value in values {
+                builder.append_option(value.and_then(|v| v.as_bool()));
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
+
+/// Fill in the metadata keys of row `row` of `batch` that its serialized
+/// metadata lacks, e.g. defaults of columns added after it was written.
+pub fn merge_into(meta: &mut serde_json::Value, batch: &RecordBatch, row: usize) {
+    let serde_json::Value::Object(map) = meta else {
+        return;
+    };
+    for (key, column_type) in metadata_columns(&batch.schema()) {
+        if map.contains_key(&key) {
+            continue;
+        }
+        let Some(column) = batch.column_by_name(&key).filter(|c| c.is_valid(row)) else {
+            continue;
+        };
+        let value = match column_type {
+            ColumnType::String => column.as_any().downcast_ref::<StringArray>().map(|c| serde_json::json!(c.value(row))),
+            ColumnType::Integer => column.as_any().downcast_ref::<Int64Array>().map(|c| serde_json::json!(c.value(row))),
+            ColumnType::Float => column.as_any().downcast_ref::<Float64Array>().map(|c| serde_json::json!(c.value(row))),
+            ColumnType::Boolean => column.as_any().downcast_ref::<BooleanArray>().map(|c| serde_json::json!(c.value(row))),
+        };
+        if let Some(value) = value {
+            map.insert(key, value);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Serialize;
+
+use crate::db::SearchResult;
+
+/// Which side of a comparison a result came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    A,
+    B,
+    Both,
+}
+
+/// A result of either search, with its rank and score on each side it was
+/// found on.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ComparedResult {
+    pub found_in: Side,
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    /// 1-based rank in the search of the first table
+    pub rank_a: Option<usize>,
+    pub score_a: Option<f32>,
+    /// 1-based rank in the search of the second table
+    pub rank_b: Option<usize>,
+    pub score_b: Option<f32>,
+}
+
+/// Interleave the results of two searches, taking the next result of each
+/// side in turn, starting with `a`. A result both found, by id or by
+/// identical document and text, is listed once, where it first comes up.
+pub fn interleave(a: &[SearchResult], b: &[SearchResult]) -> Vec<ComparedResult> {
+    let mut merged: Vec<ComparedResult> = Vec::with_capacity(a.len() + b.len());
+    for i in 0..a.len().max(b.len()) {
+        for (side, results, others) in [(Side::A, a, b), (Side::B, b, a)] {
+            let Some(result) = results.get(i) else {
+                continue;
+            };
+            if merged.iter().any(|m| m.id == result.id || (m.name == result.name && m.content == result.content)) {
+                continue;
+            }
+            let other = others.iter().position(|r| same(result, r));
+            let here = (Some(i + 1), Some(result.score));
+            let there = (other.map(|j| j + 1), other.map(|j| others[j].score));
+            let ((rank_a, score_a), (rank_b, score_b)) = if side == Side::A { (here, there) } else { (there, here) };
+            merged.push(ComparedResult {
+                found_in: if other.is_some() { Side::Both } else { side },
+                id: result.id.clone(),
+                name: result.name.clone(),
+                content: result.content.clone(),
+                rank_a,
+                score_a,
+                rank_b,
+                score_b,
+            });
+        }
+    }
+    merged
+}
+
+fn same(x: &SearchResult, y: &SearchResult) -> bool {
+    x.id == y.id || (x.name == y.name && x.content == y.content)
+}
@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+use crate::optimize::ScheduleOverride;
+use crate::table_config::TableConfig;
+
+/// A `--config` TOML file. Top-level keys are command-line flags, spelled
+/// like the flag without its dashes (`db-path` or `db_path`); the sections
+/// group settings that have no single flag or read better together.
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigFile {
+    /// Per-table defaults, used wherever a table's stored config leaves a
+    /// setting unset
+    #[serde(default)]
+    pub tables: HashMap<String, TableConfig>,
+    #[serde(default)]
+    chunking: ChunkingSection,
+    #[serde(default)]
+    tools: ToolsSection,
+    #[serde(default)]
+    maintenance: MaintenanceSection,
+    #[serde(flatten)]
+    flags: toml::Table,
+}
+
+/// Default chunking of add_url, add_file and --watch-dir.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChunkingSection {
+    size: Option<usize>,
+    overlap: Option<usize>,
+}
+
+/// Which tools are registered.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToolsSection {
+    enable: Option<Vec<String>>,
+    disable: Option<Vec<String>>,
+    read_only: Option<bool>,
+}
+
+/// Scheduled compaction and version cleanup.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaintenanceSection {
+    interval: Option<String>,
+    at: Option<String>,
+    retain_versions_days: Option<f64>,
+    target_rows_per_fragment: Option<usize>,
+    /// Per-table schedules, keyed by stored table name
+    #[serde(default)]
+    tables: HashMap<String, ScheduleOverride>,
+}
+
+impl ConfigFile {
+    /// The `[maintenance.tables.<name>]` sections.
+    pub fn maintenance_tables(&self) -> HashMap<String, ScheduleOverride> {
+        self.maintenance.tables.clone()
+    }
+
+    fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config file {}", path))
+    }
+
+    /// Every setting as a flag name and its value, sections included.
+    fn flags(&self) -> Vec<(String, toml::Value)> {
+        let mut flags: Vec<(String, toml::Value)> = self.flags.iter().map(|(k, v)| (k.replace('_', "-"), v.clone())).collect();
+        let sections = [
+            ("chunk-size", self.chunking.size.map(|n| toml::Value::Integer(n as i64))),
+            ("chunk-overlap", self.chunking.overlap.map(|n| toml::Value::Integer(n as i64))),
+            ("enable-tools", self.tools.enable.clone().map(strings)),
+            ("disable-tools", self.tools.disable.clone().map(strings)),
+            ("read-only", self.tools.read_only.map(toml::Value::Boolean)),
+            ("maintenance-interval", self.maintenance.interval.clone().map(toml::Value::String)),
+            ("maintenance-at", self.maintenance.at.clone().map(toml::Value::String)),
+            ("maintenance-retain-days", self.maintenance.retain_versions_days.map(toml::Value::Float)),
+            ("maintenance-target-rows", self.maintenance.target_rows_per_fragment.map(|n| toml::Value::Integer(n as i64))),
+        ];
+        for (flag, value) in sections {
+            if let Some(value) = value {
+                flags.push((flag.to_string(), value));
+            }
+        }
+        flags
+    }
+}
+
+fn strings(items: Vec<String>) -> toml::Value {
+    toml::Value::Array(items.into_iter().map(toml::Value::String).collect())
+}
+
+/// Parse the command line, filling in every flag it does not set (directly
+/// or through its environment variable) from the `--config` file, if one is
+/// given.
+pub fn parse<C: CommandFactory + FromArgMatches>() -> Result<(C, ConfigFile)> {
+    parse_from(std::env::args_os().collect())
+}
+
+fn parse_from<C: CommandFactory + FromArgMatches>(argv: Vec<OsString>) -> Result<(C, ConfigFile)> {
+    let matches = C::command().get_matches_from(&argv);
+    let Some(path) = matches.get_one::<String>("config").cloned() else {
+        return Ok((C::from_arg_matches(&matches)?, ConfigFile::default()));
+    };
+    let file = ConfigFile::load(&path)?;
+
+    let command = C::command();
+    let mut args: Vec<OsString> = argv.iter().take(1).cloned().collect();
+    for (flag, value) in file.flags() {
+        let id = flag.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|a| a.get_id() == id.as_str() && a.get_long().is_some() && id != "config")
+            .with_context(|| format!("Unknown setting '{}' in {}", flag, path))?;
+        if matches!(matches.value_source(&id), Some(ValueSource::CommandLine | ValueSource::EnvVariable)) {
+            continue;
+        }
+        let long = arg.get_long().unwrap_or_default();
+        if !arg.get_action().takes_values() {
+            match value {
+                toml::Value::Boolean(true) => args.push(format!("--{}", long).into()),
+                toml::Value::Boolean(false) => {}
+                _ => anyhow::bail!("Setting '{}' in {} must be true or false", flag, path),
+            }
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(items) => items,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => value.to_string(),
+                _ => anyhow::bail!("Setting '{}' in {} must be a string, number or boolean", flag, path),
+            };
+            args.push(format!("--{}={}", long, value).into());
+        }
+    }
+    // Flags before the command line, so they come before any subcommand
+    args.extend(argv.into_iter().skip(1));
+
+    let matches = C::command().get_matches_from(args);
+    Ok((C::from_arg_matches(&matches)?, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser, Debug)]
+    struct TestCli {
+        #[arg(long)]
+        config: Option<String>,
+        #[arg(long, default_value = "./lancedb_data")]
+        db_path: String,
+        #[arg(long)]
+        read_only: bool,
+        #[arg(long, value_delimiter = ',')]
+        enable_tools: Vec<String>,
+        #[arg(long)]
+        chunk_size: Option<usize>,
+    }
+
+    fn parse_with(config: &str, flags: &[&str]) -> Result<TestCli> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, config).unwrap();
+        let mut argv: Vec<OsString> = vec!["lancedb-mcp-server".into(), "--config".into(), path.into()];
+        argv.extend(flags.iter().map(OsString::from));
+        parse_from::<TestCli>(argv).map(|(cli, _)| cli)
+    }
+
+    #[test]
+    fn fills_flags_from_the_file() {
+        let cli = parse_with(
+            "db-path = \"/data\"\n[chunking]\nsize = 500\n[tools]\nread_only = true\nenable = [\"search\", \"list_tables\"]\n",
+            &[],
+        )
+        .unwrap();
+        assert!(cli.config.is_some());
+        assert_eq!(cli.db_path, "/data");
+        assert_eq!(cli.chunk_size, Some(500));
+        assert!(cli.read_only);
+        assert_eq!(cli.enable_tools, ["search", "list_tables"]);
+    }
+
+    #[test]
+    fn accepts_snake_case_keys() {
+        let cli = parse_with("db_path = \"/data\"\n", &[]).unwrap();
+        assert_eq!(cli.db_path, "/data");
+    }
+
+    #[test]
+    fn command_line_wins_over_the_file() {
+        let cli = parse_with("db-path = \"/data\"\nenable-tools = [\"search\"]\n", &["--db-path", "/cli", "--enable-tools=embed"]).unwrap();
+        assert_eq!(cli.db_path, "/cli");
+        assert_eq!(cli.enable_tools, ["embed"]);
+    }
+
+    #[test]
+    fn false_switches_stay_off() {
+        let cli = parse_with("read-only = false\n", &[]).unwrap();
+        assert!(!cli.read_only);
+    }
+
+    #[test]
+    fn rejects_unknown_and_mistyped_settings() {
+        assert!(parse_with("no-such-flag = 1\n", &[]).is_err());
+        assert!(parse_with("config = \"other.toml\"\n", &[]).is_err());
+        assert!(parse_with("read-only = \"yes\"\n", &[]).is_err());
+        assert!(parse_with("db-path = { nested = 1 }\n", &[]).is_err());
+        assert!(parse_with("[chunking]\nwidth = 5\n", &[]).is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use rmcp::service::RequestContext;
+use rmcp::RoleServer;
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+
+use crate::db::IngestProgress;
+use crate::error::{ApiError, ErrorCode};
+use crate::progress::ProgressReporter;
+
+/// Per-call state threaded through long-running operations: where to report
+/// progress and how to notice that the client cancelled the request.
+///
+/// Calls that do not come from an MCP client (REPL, replay, the directory
+/// watcher) use `CallContext::default()`, which never reports and is never
+/// cancelled.
+#[derive(Clone, Default)]
+pub struct CallContext {
+    pub progress: Option<ProgressReporter>,
+    pub cancel: CancellationToken,
+}
+
+impl CallContext {
+    pub fn from_request(ctx: &RequestContext<RoleServer>) -> Self {
+        Self {
+            progress: ProgressReporter::from_context(ctx),
+            cancel: ctx.ct.clone(),
+        }
+    }
+
+    pub fn report(&self, progress: f64, total: Option<f64>, message: impl Into<String>) {
+        if let Some(reporter) = &self.progress {
+            reporter.report(progress, total, message);
+        }
+    }
+
+    pub fn ingest_progress(&self, progress: IngestProgress) {
+        if let Some(reporter) = &self.progress {
+            reporter.ingest(progress);
+        }
+    }
+
+    /// Run `fut` unless the call is cancelled first, in which case `fut` is
+    /// dropped at its next await point.
+    pub async fn cancellable<T>(&self, fut: impl Future<Output = Result<T, ApiError>>) -> Result<T, ApiError> {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => Err(ApiError::new(ErrorCode::Cancelled, "Request cancelled")),
+            result = fut => result,
+        }
+    }
+}
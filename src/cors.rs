@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+/// Request headers browser MCP clients send: JSON bodies, bearer tokens and
+/// the streamable HTTP session and protocol headers.
+const DEFAULT_HEADERS: &[&str] = &["content-type", "authorization", "mcp-session-id", "mcp-protocol-version", "last-event-id"];
+const DEFAULT_METHODS: &[&str] = &["GET", "POST", "DELETE"];
+/// Response headers browser clients must be able to read; without
+/// `mcp-session-id` they cannot continue a streamable HTTP session.
+const EXPOSED_HEADERS: &[&str] = &["mcp-session-id"];
+
+/// Build the CORS layer for the HTTP transports. `None` when no origin is
+/// allowed, leaving cross-origin requests to fail as before.
+///
+/// `*` in `origins` allows any origin; `headers` and `methods` replace the
+/// defaults when non-empty.
+pub fn layer(origins: &[String], headers: &[String], methods: &[String]) -> Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|o| HeaderValue::from_str(o.trim_end_matches('/')).with_context(|| format!("Invalid --cors-origin '{}'", o)))
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let allow_headers = if headers.iter().any(|h| h == "*") {
+        AllowHeaders::any()
+    } else {
+        let headers = names(headers, DEFAULT_HEADERS)
+            .into_iter()
+            .map(|h| HeaderName::try_from(h).with_context(|| format!("Invalid --cors-header '{}'", h)))
+            .collect::<Result<Vec<_>>>()?;
+        AllowHeaders::list(headers)
+    };
+
+    let allow_methods = if methods.iter().any(|m| m == "*") {
+        AllowMethods::any()
+    } else {
+        let methods = names(methods, DEFAULT_METHODS)
+            .into_iter()
+            .map(|m| Method::from_bytes(m.to_ascii_uppercase().as_bytes()).with_context(|| format!("Invalid --cors-method '{}'", m)))
+            .collect::<Result<Vec<_>>>()?;
+        AllowMethods::list(methods)
+    };
+
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_headers(allow_headers)
+            .allow_methods(allow_methods)
+            .expose_headers(EXPOSED_HEADERS.iter().map(|&h| HeaderName::from_static(h)).collect::<Vec<_>>()),
+    ))
+}
+
+fn names<'a>(configured: &'a [String], defaults: &[&'a str]) -> Vec<&'a str> {
+    if configured.is_empty() {
+        defaults.to_vec()
+    } else {
+        configured.iter().map(String::as_str).collect()
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Detach from the controlling terminal and continue running in the background.
+///
+/// Must be called before the tokio runtime is started: forking a multi-threaded
+/// process only carries over the calling thread.
+#[cfg(unix)]
+pub fn daemonize() -> Result<()> {
+    // SAFETY: no other threads exist yet, so fork/setsid/dup2 are safe to call here.
+    unsafe {
+        match libc::fork() {
+            -1 => anyhow::bail!("fork failed: {}", std::io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+
+        if libc::setsid() == -1 {
+            anyhow::bail!("setsid failed: {}", std::io::Error::last_os_error());
+        }
+
+        // Fork again so the daemon can never reacquire a controlling terminal
+        match libc::fork() {
+            -1 => anyhow::bail!("fork failed: {}", std::io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+
+        libc::umask(0o027);
+
+        let dev_null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+        if dev_null == -1 {
+            anyhow::bail!("failed to open /dev/null: {}", std::io::Error::last_os_error());
+        }
+        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            libc::dup2(dev_null, fd);
+        }
+        if dev_null > libc::STDERR_FILENO {
+            libc::close(dev_null);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemonize() -> Result<()> {
+    anyhow::bail!("--daemon is only supported on unix platforms")
+}
+
+/// A pid file that is removed again when dropped.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Create the pid file exclusively, so of two instances started at once
+    /// only one gets it. A pid file left behind by a dead process is replaced.
+    ///
+    /// The pid is written to a private file first and hard linked into place,
+    /// so the pid file never exists without its pid: another instance
+    /// starting at the same moment cannot mistake it for a stale one.
+    pub fn create(path: &Path) -> Result<Self> {
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = fs::File::create(&temp).and_then(|mut file| writeln!(file, "{}", std::process::id()));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to write pid file {}", temp.display()));
+        }
+        let linked = Self::link(&temp, path);
+        let _ = fs::remove_file(&temp);
+        linked?;
+        Ok(Self { path: path.to_path_buf() })
+    }
+
+    fn link(temp: &Path, path: &Path) -> Result<()> {
+        match fs::hard_link(temp, path) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                if let Ok(existing) = fs::read_to_string(path) {
+                    if let Ok(pid) = existing.trim().parse::<u32>() {
+                        if process_alive(pid) {
+                            anyhow::bail!("Another instance is already running (pid {}, pid file {})", pid, path.display());
+                        }
+                    }
+                }
+                tracing::warn!("Removing stale pid file {}", path.display());
+                fs::remove_file(path).with_context(|| format!("Failed to remove stale pid file {}", path.display()))?;
+                // Another instance replacing the same stale file wins the race here
+                fs::hard_link(temp, path).with_context(|| {
+                    format!("Failed to create pid file {}, is another instance starting?", path.display())
+                })
+            }
+            result => result.with_context(|| format!("Failed to create pid file {}", path.display())),
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only performs the permission/existence check
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+/// Tell systemd (Type=notify) that the service finished starting up.
+pub fn notify_ready() {
+    sd_notify("READY=1");
+}
+
+/// Tell systemd that the service is shutting down.
+pub fn notify_stopping() {
+    sd_notify("STOPPING=1");
+}
+
+/// Send a state string to the socket in `$NOTIFY_SOCKET`. A no-op when not
+/// running under systemd.
+#[cfg(unix)]
+fn sd_notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let socket_path = socket_path.to_string_lossy().into_owned();
+
+    let result = (|| -> std::io::Result<()> {
+        let socket = UnixDatagram::unbound()?;
+        if let Some(name) = socket_path.strip_prefix('@') {
+            // Abstract namespace socket
+            #[cfg(target_os = "linux")]
+            {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+                socket.send_to_addr(state.as_bytes(), &addr)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = name;
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "abstract notify sockets are only supported on linux",
+                ));
+            }
+        } else {
+            socket.send_to(state.as_bytes(), &socket_path)?;
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        tracing::warn!("sd_notify({}) failed: {}", state, e);
+    }
+}
+
+#[cfg(not(unix))]
+fn sd_notify(_state: &str) {}
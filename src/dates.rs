@@ -0,0 +1,355 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+
+use crate::db::SearchResult;
+use crate::error::ErrorCode;
+
+/// Filter field that refers to the ingestion time in the chunk's provenance.
+pub const INGESTED_AT_FIELD: &str = "ingested_at";
+
+/// Formats accepted by `parse`, listed in error messages.
+const SUPPORTED: &str = "RFC 3339 ('2024-03-05T10:00:00Z'), 'YYYY-MM-DD[ HH:MM[:SS]]', \
+     'DD/MM/YYYY' or 'MM/DD/YYYY' (with date_locale when ambiguous), epoch seconds or milliseconds, \
+     'now', 'today', 'yesterday', 'tomorrow', 'N days ago' and 'in N days' (seconds to years) and 'last <weekday>'";
+
+/// Epoch values at or above this are taken as milliseconds (year 5138 in seconds).
+const EPOCH_MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+/// Order of the parts of a numeric date such as `03/04/2024`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+}
+
+impl DateOrder {
+    /// Date order of a BCP 47 locale tag, e.g. `en-US` or `de`. Locales that
+    /// write numeric dates year first need no hint, so they are not listed.
+    fn for_locale(locale: &str) -> Result<Self> {
+        let tag = locale.trim().replace('_', "-").to_ascii_lowercase();
+        let language = tag.split('-').next().unwrap_or_default();
+        match tag.as_str() {
+            "en-us" | "en-ph" | "en-ca" | "es-us" => return Ok(DateOrder::MonthDayYear),
+            _ => {}
+        }
+        match language {
+            "en" | "de" | "fr" | "es" | "it" | "pt" | "nl" | "ru" | "pl" | "tr" | "id" | "vi" | "da" | "nb" | "sv"
+            | "fi" | "cs" | "el" | "uk" => Ok(DateOrder::DayMonthYear),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported date_locale '{}', use a tag such as 'en-US' (month first) or 'en-GB' (day first)",
+                locale
+            ))
+            .context(ErrorCode::InvalidArgument),
+        }
+    }
+}
+
+/// Parse a date given by a user, e.g. as a search filter bound. Dates without
+/// a time mean midnight UTC; relative dates are resolved against `now`.
+/// Numeric dates whose day and month could be swapped need `locale`.
+pub fn parse(input: &str, now: DateTime<Utc>, locale: Option<&str>) -> Result<DateTime<Utc>> {
+    let order = locale.map(DateOrder::for_locale).transpose()?;
+    let text = input.trim().to_lowercase();
+    if text.is_empty() {
+        return invalid(input, "the date is empty");
+    }
+    if let Some(date) = parse_relative(&text, now)? {
+        return Ok(date);
+    }
+    parse_absolute(input.trim(), order).unwrap_or_else(|| invalid(input, "unrecognised date"))
+}
+
+/// Parse a date stored in metadata. Only absolute, unambiguous formats are
+/// accepted; `None` when the value is not such a date.
+pub fn parse_stored(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    match value {
+        serde_json::Value::Number(n) => n.as_i64().and_then(from_epoch),
+        serde_json::Value::String(s) => parse_absolute(s.trim(), None)?.ok(),
+        _ => None,
+    }
+}
+
+fn parse_relative(text: &str, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+    let today = now.date_naive();
+    let date = match text {
+        "now" => return Ok(Some(now)),
+        "today" => today,
+        "yesterday" => today - Duration::days(1),
+        "tomorrow" => today + Duration::days(1),
+        _ => {
+            let words: Vec<&str> = text.split_whitespace().collect();
+            match words.as_slice() {
+                [amount, unit, "ago"] => return shift(text, amount, unit, now, false).map(Some),
+                ["in", amount, unit] => return shift(text, amount, unit, now, true).map(Some),
+                ["last", word] => match parse_weekday(word) {
+                    Some(weekday) => {
+                        // Strictly before today: on a Tuesday, "last tuesday" is a week ago
+                        let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday() - 1) % 7 + 1;
+                        today - Duration::days(back as i64)
+                    }
+                    None => {
+                        return invalid(text, "ambiguous relative date, use e.g. '7 days ago' or an explicit date");
+                    }
+                },
+                [word] if parse_weekday(word).is_some() => {
+                    return invalid(text, "ambiguous weekday, use 'last <weekday>' or an explicit date");
+                }
+                _ => return Ok(None),
+            }
+        }
+    };
+    Ok(Some(midnight(date)))
+}
+
+/// `now` moved `amount` `unit`s into the past, or with `forward` the future.
+fn shift(text: &str, amount: &str, unit: &str, now: DateTime<Utc>, forward: bool) -> Result<DateTime<Utc>> {
+    let Ok(amount) = amount.parse::<u32>() else {
+        return invalid(text, "the amount must be a whole number");
+    };
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    let by = |duration: Duration| {
+        if forward {
+            now.checked_add_signed(duration)
+        } else {
+            now.checked_sub_signed(duration)
+        }
+    };
+    let by_months = |months: u32| {
+        if forward {
+            now.checked_add_months(Months::new(months))
+        } else {
+            now.checked_sub_months(Months::new(months))
+        }
+    };
+    let date = match unit {
+        "second" | "sec" => by(Duration::seconds(amount.into())),
+        "minute" | "min" => by(Duration::minutes(amount.into())),
+        "hour" => by(Duration::hours(amount.into())),
+        "day" => by(Duration::days(amount.into())),
+        "week" => by(Duration::weeks(amount.into())),
+        "month" => by_months(amount),
+        "year" => amount.checked_mul(12).and_then(by_months),
+        _ => return invalid(text, "unknown unit, use seconds, minutes, hours, days, weeks, months or years"),
+    };
+    match date {
+        Some(date) => Ok(date),
+        None => invalid(text, "the date is out of range"),
+    }
+}
+
+/// Absolute formats; `None` when `text` looks like none of them.
+fn parse_absolute(text: &str, order: Option<DateOrder>) -> Option<Result<DateTime<Utc>>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(Ok(date.with_timezone(&Utc)));
+    }
+    if let Ok(epoch) = text.parse::<i64>() {
+        return Some(from_epoch(epoch).context("epoch out of range").context(ErrorCode::InvalidArgument));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(Ok(Utc.from_utc_datetime(&date)));
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(Ok(midnight(date)));
+    }
+    parse_numeric(text, order)
+}
+
+/// `a/b/c` or `a.b.c` with a four digit year first or last.
+fn parse_numeric(text: &str, order: Option<DateOrder>) -> Option<Result<DateTime<Utc>>> {
+    let parts: Vec<&str> = text.split(['/', '.']).collect();
+    let [a, b, c] = parts.as_slice() else {
+        return None;
+    };
+    if ![a, b, c].iter().all(|p| !p.is_empty() && p.chars().all(|ch| ch.is_ascii_digit())) {
+        return None;
+    }
+    let (a_num, b_num, c_num) = (a.parse::<u32>().ok()?, b.parse::<u32>().ok()?, c.parse::<u32>().ok()?);
+
+    let (year, month, day) = if a.len() == 4 {
+        (a_num as i32, b_num, c_num)
+    } else if c.len() == 4 {
+        let order = match order {
+            Some(order) => order,
+            None if a_num > 12 => DateOrder::DayMonthYear,
+            None if b_num > 12 => DateOrder::MonthDayYear,
+            None if a_num == b_num => DateOrder::DayMonthYear,
+            None => {
+                return Some(invalid(
+                    text,
+                    "ambiguous day and month, pass date_locale (e.g. 'en-US' or 'en-GB') or use YYYY-MM-DD",
+                ));
+            }
+        };
+        match order {
+            DateOrder::DayMonthYear => (c_num as i32, b_num, a_num),
+            DateOrder::MonthDayYear => (c_num as i32, a_num, b_num),
+        }
+    } else {
+        return Some(invalid(text, "the year must have four digits"));
+    };
+
+    Some(match NaiveDate::from_ymd_opt(year, month, day) {
+        Some(date) => Ok(midnight(date)),
+        None => invalid(text, "no such date"),
+    })
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn from_epoch(epoch: i64) -> Option<DateTime<Utc>> {
+    if epoch.abs() >= EPOCH_MILLIS_THRESHOLD {
+        DateTime::from_timestamp_millis(epoch)
+    } else {
+        DateTime::from_timestamp(epoch, 0)
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN))
+}
+
+fn invalid<T>(input: &str, reason: &str) -> Result<T> {
+    Err(anyhow::anyhow!("Cannot parse date '{}': {}. Supported: {}", input, reason, SUPPORTED))
+        .context(ErrorCode::InvalidArgument)
+}
+
+/// Date bounds on a metadata field, applied to search results.
+pub struct DateFilter {
+    field: String,
+    /// Inclusive lower bound
+    after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound
+    before: Option<DateTime<Utc>>,
+}
+
+impl DateFilter {
+    /// Build a filter from request parameters; `None` when no bound is given.
+    /// `field` defaults to the chunk's ingestion time.
+    pub fn new(field: Option<&str>, after: Option<&str>, before: Option<&str>, locale: Option<&str>) -> Result<Option<Self>> {
+        if after.is_none() && before.is_none() {
+            return Ok(None);
+        }
+        let now = Utc::now();
+        let after = after.map(|a| parse(a, now, locale)).transpose()?;
+        let before = before.map(|b| parse(b, now, locale)).transpose()?;
+        if let (Some(after), Some(before)) = (after, before) {
+            if after >= before {
+                return Err(anyhow::anyhow!(
+                    "'after' ({}) must be earlier than 'before' ({})",
+                    after.to_rfc3339(),
+                    before.to_rfc3339()
+                ))
+                .context(ErrorCode::InvalidArgument);
+            }
+        }
+        Ok(Some(Self {
+            field: field.unwrap_or(INGESTED_AT_FIELD).to_string(),
+            after,
+            before,
+        }))
+    }
+
+    /// Whether the result's date lies within the bounds. Results without a
+    /// readable date in the field never match.
+    pub fn matches(&self, result: &SearchResult) -> bool {
+        let date = if self.field == INGESTED_AT_FIELD {
+            result.provenance.ingested_at.as_deref().and_then(|s| parse_absolute(s, None)?.ok())
+        } else {
+            result.metadata.get(&self.field).and_then(parse_stored)
+        };
+        let Some(date) = date else {
+            return false;
+        };
+        self.after.is_none_or(|after| date >= after) && self.before.is_none_or(|before| date < before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Wednesday
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 6, 12, 0, 0).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_relative_dates() {
+        assert_eq!(parse("now", now(), None).unwrap(), now());
+        assert_eq!(parse("Today", now(), None).unwrap(), at(2024, 3, 6, 0));
+        assert_eq!(parse("yesterday", now(), None).unwrap(), at(2024, 3, 5, 0));
+        assert_eq!(parse("tomorrow", now(), None).unwrap(), at(2024, 3, 7, 0));
+        assert_eq!(parse("3 days ago", now(), None).unwrap(), at(2024, 3, 3, 12));
+        assert_eq!(parse("in 2 hours", now(), None).unwrap(), at(2024, 3, 6, 14));
+        assert_eq!(parse("1 week ago", now(), None).unwrap(), at(2024, 2, 28, 12));
+        assert_eq!(parse("1 month ago", now(), None).unwrap(), at(2024, 2, 6, 12));
+        assert_eq!(parse("in 1 year", now(), None).unwrap(), at(2025, 3, 6, 12));
+    }
+
+    #[test]
+    fn last_weekday_is_strictly_before_today() {
+        assert_eq!(parse("last monday", now(), None).unwrap(), at(2024, 3, 4, 0));
+        assert_eq!(parse("last wed", now(), None).unwrap(), at(2024, 2, 28, 0));
+        assert_eq!(parse("last thursday", now(), None).unwrap(), at(2024, 2, 29, 0));
+    }
+
+    #[test]
+    fn rejects_ambiguous_or_malformed_relative_dates() {
+        for input in ["friday", "last month", "1.5 days ago", "3 fortnights ago", ""] {
+            assert!(parse(input, now(), None).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_absolute_dates() {
+        assert_eq!(parse("2024-03-05T10:00:00+02:00", now(), None).unwrap(), at(2024, 3, 5, 8));
+        assert_eq!(parse("2024-03-05", now(), None).unwrap(), at(2024, 3, 5, 0));
+        assert_eq!(parse("2024-03-05 10:00", now(), None).unwrap(), at(2024, 3, 5, 10));
+        assert_eq!(parse("1709632800", now(), None).unwrap(), at(2024, 3, 5, 10));
+        assert_eq!(parse("1709632800000", now(), None).unwrap(), at(2024, 3, 5, 10));
+        assert_eq!(parse("2024/03/05", now(), None).unwrap(), at(2024, 3, 5, 0));
+    }
+
+    #[test]
+    fn ambiguous_numeric_dates_need_a_locale() {
+        assert!(parse("03/04/2024", now(), None).is_err());
+        assert_eq!(parse("03/04/2024", now(), Some("en-US")).unwrap(), at(2024, 3, 4, 0));
+        assert_eq!(parse("03/04/2024", now(), Some("en_GB")).unwrap(), at(2024, 4, 3, 0));
+        assert_eq!(parse("05.03.2024", now(), Some("de")).unwrap(), at(2024, 3, 5, 0));
+        assert!(parse("03/04/2024", now(), Some("ja")).is_err());
+    }
+
+    #[test]
+    fn unambiguous_numeric_dates_need_no_locale() {
+        assert_eq!(parse("13/04/2024", now(), None).unwrap(), at(2024, 4, 13, 0));
+        assert_eq!(parse("04/13/2024", now(), None).unwrap(), at(2024, 4, 13, 0));
+        assert_eq!(parse("05/05/2024", now(), None).unwrap(), at(2024, 5, 5, 0));
+        assert!(parse("31/02/2024", now(), None).is_err());
+        assert!(parse("1/2/24", now(), None).is_err());
+    }
+
+    #[test]
+    fn stored_dates_are_absolute_only() {
+        assert_eq!(parse_stored(&serde_json::json!("2024-03-05")), Some(at(2024, 3, 5, 0)));
+        assert_eq!(parse_stored(&serde_json::json!(1709632800)), Some(at(2024, 3, 5, 10)));
+        assert_eq!(parse_stored(&serde_json::json!("yesterday")), None);
+        assert_eq!(parse_stored(&serde_json::json!(true)), None);
+    }
+}
@@ -1,33 +1,242 @@
-use anyhow::Result;
-use arrow::array::{FixedSizeListBuilder, Float32Builder, RecordBatch, RecordBatchIterator, StringArray, StringBuilder};
-use arrow::datatypes::{DataType, Field, Schema};
+use anyhow::{Context, Result};
+use arrow::array::{new_null_array, Array, ArrayRef, AsArray, BooleanArray, FixedSizeListBuilder, Float32Array, Float32Builder, ListBuilder, RecordBatch, RecordBatchIterator, StringArray, StringBuilder, UInt32Builder, UInt8Array, UInt8Builder};
+use arrow::datatypes::{DataType, Field, Float32Type, Schema, UInt32Type, UInt8Type};
+use arrow::record_batch::RecordBatchReader;
 use futures::TryStreamExt;
 use lancedb::connection::Connection;
-use lancedb::query::{ExecutableQuery, QueryBase};
+use lancedb::query::{ExecutableQuery, QueryBase, Select, VectorQuery};
+use lancedb::index::Index;
+use lancedb::index::scalar::{BTreeIndexBuilder, BitmapIndexBuilder, FtsIndexBuilder, FullTextSearchQuery};
+use lancedb::index::vector::IvfHnswSqIndexBuilder;
+use lancedb::table::{ColumnAlteration, CompactionOptions, NewColumnTransform, OptimizeAction, OptimizeOptions};
+use lancedb::arrow::SendableRecordBatchStream;
 use lancedb::{connect, Table, DistanceType};
+use std::collections::{BTreeSet, HashMap, HashSet};
+#[cfg(all(target_os = "linux", feature = "local-files"))]
+use std::ffi::CString;
+#[cfg(feature = "local-files")]
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use crate::embeddings::EmbeddingModel;
+use tokio_util::sync::CancellationToken;
+use crate::columns::{self, ColumnType};
+use crate::dates;
+use crate::embeddings::{EmbeddingModel, SparseEncoder, SparseVector};
+use crate::error::ErrorCode;
+use crate::expiry;
+use crate::provenance::{self, Provenance};
+use crate::rows;
+use crate::storage::{ConnectOptions, Consistency};
+use crate::table_config::{FusionWeights, Quantization, TableConfig, VectorColumn};
+
+/// Prefix of tables used for the server's own bookkeeping, hidden from `list_tables`.
+pub const INTERNAL_TABLE_PREFIX: &str = "_mcp_";
+const TABLE_CONFIG_TABLE: &str = "_mcp_table_config";
+
+/// Number of chunks embedded and written per step of `add_texts`.
+const INGEST_BATCH_SIZE: usize = 64;
+/// Rows `embed_into_column` embeds before writing them in one merge.
+const FILL_BATCH_SIZE: usize = 4096;
+/// Passes `reembed` makes to catch up with writes before giving up.
+const REEMBED_MAX_PASSES: usize = 5;
+/// Candidates per wanted result that quantized searches rescore with the
+/// full-precision vectors.
+const RESCORE_FACTOR: usize = 4;
+/// Most bytes of text and metadata one search reads into memory; rows past
+/// it are dropped, so pathological queries cannot balloon the server.
+const MAX_RESULT_BYTES: usize = 16 * 1024 * 1024;
+/// Rows an int8 index is trained on at least; smaller tables are searched exactly.
+const MIN_QUANTIZED_INDEX_ROWS: usize = 256;
+/// How often, and how long apart, a read looks again for a version this
+/// process wrote that the store does not list yet.
+const READ_YOUR_WRITES_ATTEMPTS: usize = 10;
+const READ_YOUR_WRITES_WAIT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Progress of an `add_texts` call, reported after every embedded and every
+/// written batch.
+#[derive(Debug, Clone, Copy)]
+pub struct IngestProgress {
+    pub total: usize,
+    pub embedded: usize,
+    pub written: usize,
+}
+
+pub type ProgressFn<'a> = &'a (dyn Fn(IngestProgress) + Send + Sync);
+
+/// What to do when a table was embedded with a different model than the one
+/// the server now uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelMismatchPolicy {
+    /// Fail adds and searches on the table
+    Reject,
+    /// Log a warning once per table and carry on
+    Warn,
+}
+
+/// What a search reads: a version of the table, one of its vector columns
+/// and optionally only the rows of one modality.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchTarget<'a> {
+    /// Version to search, the current one when `None`
+    pub version: Option<u64>,
+    /// Vector column to search, `vector` when `None`
+    pub vector_column: Option<&'a str>,
+    /// `text` or `image`, both when `None`
+    pub modality: Option<&'a str>,
+    /// Rescore the candidates of a quantized search with the full-precision vectors
+    pub rescore: bool,
+    /// Where to record how the search went, when the caller asked
+    pub diagnostics: Option<&'a Diagnostics>,
+}
+
+impl SearchTarget<'_> {
+    fn column(&self) -> &str {
+        self.vector_column.unwrap_or(columns::VECTOR_COLUMN)
+    }
+
+    /// Update the diagnostics, if any are being recorded.
+    pub fn record(&self, update: impl FnOnce(&mut SearchDiagnostics)) {
+        if let Some(diagnostics) = self.diagnostics {
+            diagnostics.record(update);
+        }
+    }
+}
+
+/// Where the time of a search went and how the vector search ran. Times
+/// add up over every table a routed search reads.
+#[derive(Debug, Clone, Default, serde::Serialize, schemars::JsonSchema)]
+pub struct SearchDiagnostics {
+    /// Embedding the query, dense and sparse
+    pub embed_ms: f64,
+    /// Opening the table and running the vector, keyword and sparse searches
+    pub db_ms: f64,
+    /// Fusing, boosting by importance and the post-processing stages of a pipeline
+    pub rerank_ms: f64,
+    /// Distance the vector search ranked by, e.g. `cosine`
+    pub distance_type: Option<String>,
+    /// Whether the vector search went through an ANN index rather than a flat scan
+    pub ann_index: bool,
+    /// Name of that index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_name: Option<String>,
+    /// Candidates read to find the results within a date filter's bounds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_filter_candidates: Option<usize>,
+    /// Results missing from the limit because too few of the most candidates
+    /// a date-filtered search reads fell within its bounds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_filter_shortfall: Option<usize>,
+}
+
+impl SearchDiagnostics {
+    fn note_vector_search(&mut self, distance_type: &str, index: Option<String>) {
+        self.distance_type = Some(distance_type.to_string());
+        self.ann_index = index.is_some();
+        self.index_name = index;
+    }
+}
+
+/// Collects `SearchDiagnostics` while a search runs.
+#[derive(Debug, Default)]
+pub struct Diagnostics(std::sync::Mutex<SearchDiagnostics>);
+
+impl Diagnostics {
+    pub fn snapshot(&self) -> SearchDiagnostics {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn record(&self, update: impl FnOnce(&mut SearchDiagnostics)) {
+        update(&mut self.0.lock().unwrap());
+    }
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
 
 pub struct VectorDB {
     connection: Connection,
+    names: std::sync::Mutex<NameIndex>,
+    model_mismatch: ModelMismatchPolicy,
+    /// Vector columns already checked against the current embedding model,
+    /// keyed by `model_key`
+    checked_models: std::sync::Mutex<HashMap<String, String>>,
+    /// Rows written per table since its indices were last optimized
+    unindexed_rows: std::sync::Mutex<HashMap<String, usize>>,
+    rows_written: tokio::sync::Notify,
+    /// Settings from the config file that apply to a table wherever its
+    /// stored config leaves them unset
+    table_defaults: HashMap<String, TableConfig>,
+    /// Sparse model whose vectors are stored with every write and used by
+    /// hybrid search
+    sparse: Option<Arc<SparseEncoder>>,
+    /// How soon reads see new versions of a table
+    consistency: Consistency,
+    /// Latest version of each table this process wrote, which its reads wait for
+    written_versions: std::sync::Mutex<HashMap<String, u64>>,
+    /// Open handles of the tables, so requests do not reopen them. Cleared
+    /// for a table when it is dropped, replaced or swapped.
+    tables: std::sync::Mutex<HashMap<String, Table>>,
+}
+
+/// Table and document names used for argument completion. Filled lazily and
+/// kept current by the writes that go through `VectorDB`.
+#[derive(Default)]
+struct NameIndex {
+    tables: Option<Vec<String>>,
+    /// Distinct document names per table
+    documents: HashMap<String, BTreeSet<String>>,
 }
 
 impl VectorDB {
-    pub async fn new(path: &str) -> Result<Self> {
-        let connection = connect(path).execute().await?;
-        Ok(Self { connection })
+    /// Connect to a local directory, an object store URI or LanceDB Cloud.
+    pub async fn new(path: &str, options: ConnectOptions, model_mismatch: ModelMismatchPolicy) -> Result<Self> {
+        let mut builder = connect(path).storage_options(options.storage);
+        #[cfg(feature = "cloud")]
+        if let Some(cloud) = &options.cloud {
+            builder = builder.api_key(&cloud.api_key).region(&cloud.region);
+            if let Some(host) = &cloud.host_override {
+                builder = builder.host_override(host);
+            }
+        }
+        // LanceDB Cloud keeps its own tables consistent
+        if let (Some(interval), None) = (options.consistency.read_interval(), &options.cloud) {
+            builder = builder.read_consistency_interval(interval);
+        }
+        let connection = builder.execute().await?;
+        Ok(Self {
+            connection,
+            names: std::sync::Mutex::new(NameIndex::default()),
+            model_mismatch,
+            checked_models: std::sync::Mutex::new(HashMap::new()),
+            unindexed_rows: std::sync::Mutex::new(HashMap::new()),
+            rows_written: tokio::sync::Notify::new(),
+            table_defaults: HashMap::new(),
+            sparse: None,
+            consistency: options.consistency,
+            written_versions: std::sync::Mutex::new(HashMap::new()),
+            tables: std::sync::Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn with_table_defaults(self, table_defaults: HashMap<String, TableConfig>) -> Self {
+        Self { table_defaults, ..self }
+    }
+
+    pub fn with_sparse_encoder(self, sparse: Option<SparseEncoder>) -> Self {
+        Self { sparse: sparse.map(Arc::new), ..self }
     }
 
     pub async fn create_table(&self, name: &str, dim: usize) -> Result<Table> {
-        // Define schema: id, text, vector, metadata (json string)
+        // Define schema: id, text, vector, metadata (json string), importance
         let schema = Arc::new(Schema::new(vec![
             Field::new("id", DataType::Utf8, false),
             Field::new("text", DataType::Utf8, false),
-            Field::new("vector", DataType::FixedSizeList(
+            Field::new(columns::VECTOR_COLUMN, DataType::FixedSizeList(
                 Arc::new(Field::new("item", DataType::Float32, true)),
                 dim as i32
             ), false),
             Field::new("metadata", DataType::Utf8, true),
+            Field::new("importance", DataType::Float32, true),
         ]));
 
         // Create empty table if not exists
@@ -35,154 +244,2689 @@ impl VectorDB {
         // create_empty_table is available in newer versions.
         
         // If table exists, open it.
-        if self.connection.table_names().execute().await?.contains(&name.to_string()) {
-            return Ok(self.connection.open_table(name).execute().await?);
+        if self.table_exists(name).await? {
+            let table = self.open(name).await?;
+            Self::migrate_table(&table).await?;
+            return Ok(table);
+        }
+
+        let table = self.connection.create_empty_table(name, schema).execute().await.map_err(|e| anyhow::anyhow!(e))?;
+        self.forget_names(name);
+        self.tables.lock().unwrap().insert(name.to_string(), table.clone());
+        Ok(table)
+    }
+
+    /// The handle of a table at its latest version, opened on first use.
+    /// How soon it sees other processes' writes is up to `--consistency`.
+    async fn open(&self, name: &str) -> Result<Table> {
+        if let Some(table) = self.tables.lock().unwrap().get(name) {
+            return Ok(table.clone());
+        }
+        let table = self.connection.open_table(name).execute().await?;
+        self.tables.lock().unwrap().insert(name.to_string(), table.clone());
+        Ok(table)
+    }
+
+    /// Drop the cached handle of a table whose data was replaced or removed.
+    fn forget_table(&self, name: &str) {
+        self.tables.lock().unwrap().remove(name);
+    }
+
+    /// Bring a table created by an older version up to the current schema.
+    async fn migrate_table(table: &Table) -> Result<()> {
+        let schema = table.schema().await?;
+        if schema.field_with_name("importance").is_err() {
+            tracing::info!("Adding importance column to table '{}'", table.name());
+            table
+                .add_columns(
+                    NewColumnTransform::SqlExpressions(vec![(
+                        "importance".to_string(),
+                        "CAST(0.0 AS FLOAT)".to_string(),
+                    )]),
+                    None,
+                )
+                .await?;
         }
+        Ok(())
+    }
+
+    /// Where the database lives: a local path or an object store URI.
+    pub fn uri(&self) -> &str {
+        self.connection.uri()
+    }
 
-        self.connection.create_empty_table(name, schema).execute().await.map_err(|e| anyhow::anyhow!(e))
+    /// Whether the database is a local directory rather than on an object store.
+    pub fn is_local(&self) -> bool {
+        let uri = self.connection.uri();
+        !uri.contains("://") || uri.starts_with("file://")
     }
 
     pub async fn list_tables(&self) -> Result<Vec<String>> {
-        Ok(self.connection.table_names().execute().await?)
+        let names = self.connection.table_names().execute().await?;
+        Ok(names.into_iter().filter(|n| !n.starts_with(INTERNAL_TABLE_PREFIX)).collect())
     }
 
-    pub async fn add_texts(
-        &self,
-        table_name: &str,
-        texts: Vec<String>,
-        metadatas: Vec<serde_json::Value>,
-        model: &EmbeddingModel,
-    ) -> Result<()> {
-        if texts.is_empty() {
-            return Ok(());
+    /// The settings in effect for a table: its stored config, completed by
+    /// the config file's defaults for it.
+    pub async fn get_table_config(&self, table_name: &str) -> Result<TableConfig> {
+        let config = self.stored_table_config(table_name).await?;
+        Ok(match self.table_defaults.get(table_name) {
+            Some(defaults) => config.with_defaults(defaults),
+            None => config,
+        })
+    }
+
+    /// The config stored for a table, for read-modify-write updates that must
+    /// not persist the file defaults.
+    pub async fn stored_table_config(&self, table_name: &str) -> Result<TableConfig> {
+        if !self.table_exists(TABLE_CONFIG_TABLE).await? {
+            return Ok(TableConfig::default());
+        }
+        let table = self.open(TABLE_CONFIG_TABLE).await?;
+        let batches: Vec<RecordBatch> = table
+            .query()
+            .only_if(format!("table_name = '{}'", table_name.replace('\'', "''")))
+            .execute()
+            .await?
+            .try_collect()
+            .await?;
+
+        for batch in batches {
+            let config_col = rows::required_strings(&batch, "config")?;
+            if batch.num_rows() > 0 {
+                return Ok(serde_json::from_str(config_col.value(0))?);
+            }
         }
+        Ok(TableConfig::default())
+    }
 
-        // 1. Compute embeddings
-        let embeddings = model.embed(texts.clone()).await?;
-        if embeddings.is_empty() {
-            return Ok(());
+    /// Configs in effect of all tables that have a stored config or defaults.
+    pub async fn list_table_configs(&self) -> Result<HashMap<String, TableConfig>> {
+        let mut configs = self.stored_table_configs().await?;
+        for (name, defaults) in &self.table_defaults {
+            let config = configs.remove(name).unwrap_or_default().with_defaults(defaults);
+            configs.insert(name.clone(), config);
         }
-        let dim = embeddings[0].len();
+        Ok(configs)
+    }
 
-        // 2. Ensure table exists
-        let table = self.create_table(table_name, dim).await?;
+    async fn stored_table_configs(&self) -> Result<HashMap<String, TableConfig>> {
+        let mut configs = HashMap::new();
+        if !self.table_exists(TABLE_CONFIG_TABLE).await? {
+            return Ok(configs);
+        }
+        let table = self.open(TABLE_CONFIG_TABLE).await?;
+        let batches: Vec<RecordBatch> = table.query().execute().await?.try_collect().await?;
 
-        // 3. Create RecordBatch
-        let len = texts.len();
-        
-        // ID Builder
-        let mut id_builder = StringBuilder::new();
-        // Text Builder
-        let mut text_builder = StringBuilder::new();
-        // Metadata Builder
-        let mut meta_builder = StringBuilder::new();
-        // Vector Builder
-        let values_builder = Float32Builder::new();
-        let mut vector_builder = FixedSizeListBuilder::new(values_builder, dim as i32);
-
-        for i in 0..len {
-            id_builder.append_value(uuid::Uuid::new_v4().to_string());
-            text_builder.append_value(&texts[i]);
-            meta_builder.append_value(metadatas.get(i).map(|v| v.to_string()).unwrap_or("{}".to_string()));
-            
-            // Vector
-            let vec_ref = &embeddings[i];
-            vector_builder.values().append_slice(vec_ref);
-            vector_builder.append(true);
+        for batch in batches {
+            let name_col = rows::required_strings(&batch, "table_name")?;
+            let config_col = rows::required_strings(&batch, "config")?;
+            for i in 0..batch.num_rows() {
+                if let Ok(config) = serde_json::from_str(config_col.value(i)) {
+                    configs.insert(name_col.value(i).to_string(), config);
+                }
+            }
         }
+        Ok(configs)
+    }
+
+    pub async fn set_table_config(&self, table_name: &str, config: &TableConfig) -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("table_name", DataType::Utf8, false),
+            Field::new("config", DataType::Utf8, false),
+        ]));
+        let table = if self.table_exists(TABLE_CONFIG_TABLE).await? {
+            self.open(TABLE_CONFIG_TABLE).await?
+        } else {
+            self.connection.create_empty_table(TABLE_CONFIG_TABLE, schema.clone()).execute().await?
+        };
+
+        table.delete(&format!("table_name = '{}'", table_name.replace('\'', "''"))).await?;
 
-        let schema = table.schema().await?;
         let batch = RecordBatch::try_new(
             schema.clone(),
             vec![
-                Arc::new(id_builder.finish()),
-                Arc::new(text_builder.finish()),
-                Arc::new(vector_builder.finish()),
-                Arc::new(meta_builder.finish()),
+                Arc::new(StringArray::from(vec![table_name.to_string()])),
+                Arc::new(StringArray::from(vec![serde_json::to_string(config)?])),
             ],
         )?;
-
-        // 4. Add to table
-        // We need an iterator of RecordBatches
-        let stream = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let stream = RecordBatchIterator::new(vec![Ok(batch)], schema);
         table.add(stream).execute().await?;
 
         Ok(())
     }
 
-    pub async fn search(
+    /// Append `batch` to the internal table `name`, creating it with the
+    /// batch's schema on first use.
+    pub async fn append_rows(&self, name: &str, batch: RecordBatch) -> Result<()> {
+        let schema = batch.schema();
+        let table = if self.table_exists(name).await? {
+            self.open(name).await?
+        } else {
+            self.connection.create_empty_table(name, schema.clone()).execute().await?
+        };
+        table.add(RecordBatchIterator::new(vec![Ok(batch)], schema)).execute().await?;
+        Ok(())
+    }
+
+    /// The rows of the internal table `name` matching `filter`, `None` when
+    /// nothing was written to it yet.
+    pub async fn scan_rows(&self, name: &str, filter: Option<String>) -> Result<Option<SendableRecordBatchStream>> {
+        if !self.table_exists(name).await? {
+            return Ok(None);
+        }
+        let mut query = self.open(name).await?.query();
+        if let Some(filter) = filter {
+            query = query.only_if(filter);
+        }
+        Ok(Some(query.execute().await?))
+    }
+
+    /// (Re)build the full-text index on the `text` column with the table's
+    /// language and stemming settings.
+    pub async fn create_fts_index(&self, table_name: &str, config: &TableConfig) -> Result<()> {
+        let table = self.open(table_name).await?;
+        let mut params = FtsIndexBuilder::default().stem(config.fts_stem.unwrap_or(true));
+        if let Some(language) = &config.fts_language {
+            params = params.language(language)?;
+        }
+        table
+            .create_index(&["text"], Index::FTS(params))
+            .replace(true)
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Add the rows written since the last optimization to the table's
+    /// existing indices, without rebuilding them. Returns `false` when the
+    /// table has no index to update.
+    pub async fn optimize_indices(&self, table_name: &str) -> Result<bool> {
+        let table = self.open(table_name).await?;
+        if self.build_due_index(&table).await? {
+            return Ok(true);
+        }
+        if table.list_indices().await?.is_empty() {
+            return Ok(false);
+        }
+        table.optimize(OptimizeAction::Index(OptimizeOptions::default())).await?;
+        Ok(true)
+    }
+
+    /// Merge small fragments of a table into larger ones, bring its indices
+    /// up to date with the rewritten data and delete the versions older than
+    /// `retain`. `target_rows_per_fragment` defaults to LanceDB's own.
+    pub async fn compact_and_cleanup(
         &self,
         table_name: &str,
-        query: &str,
-        limit: usize,
-        model: &EmbeddingModel,
-    ) -> Result<Vec<serde_json::Value>> {
-        let table = self.connection.open_table(table_name).execute().await?;
-        
-        // Embed query
-        let query_vecs = model.embed(vec![query.to_string()]).await?;
-        let query_vec = &query_vecs[0];
+        target_rows_per_fragment: Option<usize>,
+        retain: chrono::Duration,
+    ) -> Result<CompactionReport> {
+        let table = self.open(table_name).await?;
+        let fragments_before = table.stats().await?.fragment_stats.num_fragments;
 
-        // Search
-        let results = table
-            .vector_search(query_vec.clone())?
-            .distance_type(DistanceType::Cosine)
-            .limit(limit)
-            .execute()
+        let mut options = CompactionOptions::default();
+        if let Some(rows) = target_rows_per_fragment {
+            options.target_rows_per_fragment = rows;
+        }
+        let compaction = table
+            .optimize(OptimizeAction::Compact { options, remap_options: None })
+            .await?
+            .compaction;
+        if !self.build_due_index(&table).await? && !table.list_indices().await?.is_empty() {
+            table.optimize(OptimizeAction::Index(OptimizeOptions::default())).await?;
+        }
+        let prune = table
+            .optimize(OptimizeAction::Prune {
+                older_than: Some(retain),
+                delete_unverified: None,
+                error_if_tagged_old_versions: None,
+            })
+            .await?
+            .prune;
+
+        Ok(CompactionReport {
+            fragments_before,
+            fragments_removed: compaction.as_ref().map_or(0, |c| c.fragments_removed),
+            fragments_added: compaction.as_ref().map_or(0, |c| c.fragments_added),
+            versions_removed: prune.as_ref().map_or(0, |p| p.old_versions),
+            bytes_removed: prune.as_ref().map_or(0, |p| p.bytes_removed),
+        })
+    }
+
+    /// Bring the storage of `table_name` in line with `quantization`: build
+    /// its int8 index, or store the sign bits of every vector for binary
+    /// search, and drop what an earlier quantization left behind. Returns
+    /// the number of rows quantized, 0 while a table is too small to index.
+    pub async fn quantize(
+        &self,
+        table_name: &str,
+        quantization: Option<Quantization>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<usize> {
+        let table = self.open(table_name).await?;
+        self.quantize_table(&table, quantization, cancel).await
+    }
+
+    async fn quantize_table(
+        &self,
+        table: &Table,
+        quantization: Option<Quantization>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<usize> {
+        if quantization != Some(Quantization::Binary) && table.schema().await?.field_with_name(columns::BITS_COLUMN).is_ok() {
+            table.drop_columns(&[columns::BITS_COLUMN]).await?;
+        }
+        if quantization != Some(Quantization::Int8) {
+            if let Some(index) = vector_index(table).await? {
+                table.drop_index(&index).await?;
+            }
+        }
+        match quantization {
+            None => Ok(0),
+            Some(Quantization::Int8) => build_int8_index(table).await,
+            Some(Quantization::Binary) => fill_bits(table, cancel).await,
+        }
+    }
+
+    /// Build the int8 index of a table configured for one once it has
+    /// enough rows to train it. Returns whether it was built.
+    async fn build_due_index(&self, table: &Table) -> Result<bool> {
+        if self.get_table_config(table.name()).await?.quantization != Some(Quantization::Int8)
+            || vector_index(table).await?.is_some()
+        {
+            return Ok(false);
+        }
+        Ok(build_int8_index(table).await? > 0)
+    }
+
+    /// Build a scalar index on `column`, replacing any index it has. `id` and
+    /// `importance` are indexed as they are; any other name is a metadata
+    /// key, which is first copied into a column of its own, filled for the
+    /// existing rows and kept filled by later writes. Returns the number of
+    /// rows filled.
+    pub async fn create_scalar_index(&self, table_name: &str, column: &str, kind: ScalarIndexKind) -> Result<usize> {
+        let table = self.open(table_name).await?;
+        let mut filled = 0;
+        if !matches!(column, "id" | "importance") {
+            columns::check_key(column)?;
+            if !columns::metadata_columns(&table.schema().await?).iter().any(|(name, _)| name == column) {
+                tracing::info!("Copying metadata key '{}' of table '{}' into a column", column, table.name());
+                table
+                    .add_columns(
+                        NewColumnTransform::SqlExpressions(vec![(column.to_string(), "CAST(NULL AS STRING)".to_string())]),
+                        None,
+                    )
+                    .await?;
+                filled = fill_from_metadata(&table, column, ColumnType::String).await?;
+            }
+        }
+
+        let index = match kind {
+            ScalarIndexKind::BTree => Index::BTree(BTreeIndexBuilder::default()),
+            ScalarIndexKind::Bitmap => Index::Bitmap(BitmapIndexBuilder::default()),
+        };
+        table.create_index(&[column], index).replace(true).execute().await?;
+        Ok(filled)
+    }
+
+    /// Add the metadata key `key` to a table as a column of its own. Rows
+    /// whose metadata has the key keep its value; every other row, now and
+    /// written later without it, gets `default`. `column_type` is needed
+    /// when `default` is null. Returns the table's row count and how many of
+    /// the rows had the key.
+    pub async fn add_column(
+        &self,
+        table_name: &str,
+        key: &str,
+        default: &serde_json::Value,
+        column_type: Option<ColumnType>,
+    ) -> Result<(usize, usize)> {
+        columns::check_key(key)?;
+        let table = self.open(table_name).await?;
+        if table.schema().await?.field_with_name(key).is_ok() {
+            return Err(anyhow::anyhow!("Table '{}' already has a column '{}'", table_name, key)).context(ErrorCode::InvalidArgument);
+        }
+        let column_type = match (column_type, ColumnType::of(default)?) {
+            (Some(column_type), _) | (None, Some(column_type)) => column_type,
+            (None, None) => {
+                return Err(anyhow::anyhow!("Pass column_type: it cannot be told from a null default"))
+                    .context(ErrorCode::InvalidArgument);
+            }
+        };
+
+        // Lance adds the column without rewriting the existing data
+        table
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![(key.to_string(), column_type.sql_literal(default)?)]),
+                None,
+            )
             .await?;
+        let from_metadata = fill_from_metadata(&table, key, column_type).await?;
+        if !default.is_null() {
+            let mut config = self.stored_table_config(table_name).await?;
+            config.column_defaults.get_or_insert_with(Default::default).insert(key.to_string(), default.clone());
+            self.set_table_config(table_name, &config).await?;
+        }
+        Ok((table.count_rows(None).await?, from_metadata))
+    }
 
-        // Parse results
-        let mut output = Vec::new();
-        let record_batches: Vec<RecordBatch> = results.try_collect().await?;
-        
-        for batch in record_batches {
-            let id_col = batch.column_by_name("id").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
-            let text_col = batch.column_by_name("text").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
-            let meta_col = batch.column_by_name("metadata").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
-            // _distance column is added by vector search
-            let dist_col = batch.column_by_name("_distance").unwrap().as_any().downcast_ref::<arrow::array::Float32Array>().unwrap();
-            
-            for i in 0..batch.num_rows() {
-                let id = id_col.value(i);
-                let text = text_col.value(i);
-                let meta_str = meta_col.value(i);
-                let mut meta: serde_json::Value = serde_json::from_str(meta_str).unwrap_or(serde_json::json!({}));
-                let distance = dist_col.value(i);
-                let score = 1.0 - distance; // Convert distance to score (assuming cosine distance)
-
-                // Extract name and description
-                let mut name = String::new();
-                let mut description = None;
-                
-                if let serde_json::Value::Object(ref mut map) = meta {
-                    if let Some(n) = map.remove("name") {
-                        if let Some(s) = n.as_str() {
-                            name = s.to_string();
-                        }
+    /// Record `rows` newly written rows that are not in `table_name`'s indices yet.
+    fn note_unindexed(&self, table_name: &str, rows: usize) {
+        self.requeue_unindexed(table_name, rows);
+        self.rows_written.notify_one();
+    }
+
+    /// Put back rows taken by `take_unindexed` whose optimization failed, so
+    /// the next scheduled run retries them.
+    pub fn requeue_unindexed(&self, table_name: &str, rows: usize) {
+        *self.unindexed_rows.lock().unwrap().entry(table_name.to_string()).or_default() += rows;
+    }
+
+    /// Take the tables with at least `min_rows` unindexed rows, with their counts.
+    pub fn take_unindexed(&self, min_rows: usize) -> Vec<(String, usize)> {
+        let mut unindexed = self.unindexed_rows.lock().unwrap();
+        let tables: Vec<(String, usize)> = unindexed
+            .iter()
+            .filter(|(_, rows)| **rows >= min_rows.max(1))
+            .map(|(name, rows)| (name.clone(), *rows))
+            .collect();
+        for (name, _) in &tables {
+            unindexed.remove(name);
+        }
+        tables
+    }
+
+    /// Resolves after the next write through `add_texts`.
+    pub async fn rows_written(&self) {
+        self.rows_written.notified().await
+    }
+
+    async fn has_fts_index(&self, table: &Table) -> Result<bool> {
+        Ok(table
+            .list_indices()
+            .await?
+            .iter()
+            .any(|index| index.columns == ["text"]))
+    }
+
+    pub async fn table_exists(&self, name: &str) -> Result<bool> {
+        if self.tables.lock().unwrap().contains_key(name) {
+            return Ok(true);
+        }
+        Ok(self.connection.table_names().execute().await?.iter().any(|t| t == name))
+    }
+
+    /// Check that `column` of `table` holds `dim`-dimensional vectors from `model`.
+    ///
+    /// Writes (`write`) record the model in the table's config on first use.
+    /// Tables written before models were recorded get the model named in the
+    /// provenance of their rows, or the writing model if they have none.
+    /// Searches never write: a column whose model is not recorded is checked
+    /// against its rows' provenance, and only warned about when that names
+    /// no model either.
+    async fn check_embedding_model(
+        &self,
+        table: &Table,
+        column: &str,
+        model: &EmbeddingModel,
+        dim: usize,
+        write: bool,
+    ) -> Result<()> {
+        check_dimension(table, column, dim).await?;
+        let name = table.name();
+        let key = model_key(name, column);
+        if self.checked_models.lock().unwrap().get(&key).is_some_and(|m| m == model.model_id()) {
+            return Ok(());
+        }
+
+        let mut config = self.stored_table_config(name).await?;
+        let stored = if column == columns::VECTOR_COLUMN {
+            config.embedding_model.clone()
+        } else {
+            config.vector_columns.as_ref().and_then(|c| c.get(column)).map(|c| c.embedding_model.clone())
+        };
+        let recorded = match stored {
+            Some(recorded) => recorded,
+            None => {
+                // Only the main column predates recording, so its rows' provenance may name the model
+                let sampled = if column == columns::VECTOR_COLUMN {
+                    self.sample_embedding_model(table).await?
+                } else {
+                    None
+                };
+                if !write {
+                    let Some(sampled) = sampled else {
+                        tracing::warn!(
+                            "Column '{}' of table '{}' does not record its embedding model, so searches with '{}' cannot be \
+                             checked against it; re-embed it with reembed_table if it came from another model",
+                            column,
+                            name,
+                            model.model_id()
+                        );
+                        self.checked_models.lock().unwrap().insert(key, model.model_id().to_string());
+                        return Ok(());
+                    };
+                    sampled
+                } else {
+                    if sampled.is_none() && table.count_rows(None).await? > 0 {
+                        tracing::warn!(
+                            "Column '{}' of table '{}' does not record its embedding model; recording '{}' for it",
+                            column,
+                            name,
+                            model.model_id()
+                        );
                     }
-                    if let Some(d) = map.remove("description") {
-                        if let Some(s) = d.as_str() {
-                            description = Some(s.to_string());
-                        }
+                    let recorded = sampled.unwrap_or_else(|| model.model_id().to_string());
+                    if column == columns::VECTOR_COLUMN {
+                        config.embedding_model = Some(recorded.clone());
+                        config.embedding_dim = Some(dim);
+                    } else {
+                        config.vector_columns.get_or_insert_with(Default::default).insert(
+                            column.to_string(),
+                            VectorColumn { embedding_model: recorded.clone(), embedding_dim: dim },
+                        );
                     }
+                    self.set_table_config(name, &config).await?;
+                    recorded
                 }
-                
-                let mut result = serde_json::json!({
-                    "id": id,
-                    "name": name,
-                    "content": text,
-                    "score": score,
-                    "metadata": meta
-                });
-
-                if let Some(desc) = description {
-                    result["description"] = serde_json::Value::String(desc);
+            }
+        };
+
+        if recorded != model.model_id() {
+            match self.model_mismatch {
+                ModelMismatchPolicy::Reject => {
+                    return Err(anyhow::anyhow!(
+                        "column '{}' of table '{}' was embedded with '{}' but the server uses '{}'; re-embed it with reembed_table, \
+                         or run with --embedding-model {} (or --on-model-mismatch warn)",
+                        column,
+                        name,
+                        recorded,
+                        model.model_id(),
+                        recorded
+                    ))
+                    .context(ErrorCode::EmbeddingModelMismatch);
                 }
-                
-                output.push(result);
+                ModelMismatchPolicy::Warn => tracing::warn!(
+                    "Column '{}' of table '{}' was embedded with '{}' but the server uses '{}'; search quality will suffer",
+                    column,
+                    name,
+                    recorded,
+                    model.model_id()
+                ),
+            }
+        }
+        self.checked_models.lock().unwrap().insert(key, model.model_id().to_string());
+        Ok(())
+    }
+
+    /// Forget the models checked for any column of `name`.
+    fn forget_models(&self, name: &str) {
+        let prefix = format!("{}:", name);
+        self.checked_models
+            .lock()
+            .unwrap()
+            .retain(|key, _| key != name && !key.starts_with(&prefix));
+    }
+
+    /// Add the vector column `column` of width `dim` to `table` unless it
+    /// has it. Rows written before hold null in it, so `vector` is made
+    /// nullable too: rows added to another column have none.
+    async fn ensure_vector_column(&self, table: &Table, column: &str, dim: usize) -> Result<()> {
+        let schema = table.schema().await?;
+        if schema.field_with_name(column).is_ok() {
+            if vector_width(&schema, column).is_none() {
+                return Err(anyhow::anyhow!("Column '{}' of table '{}' is not a vector column", column, table.name()))
+                    .context(ErrorCode::InvalidArgument);
+            }
+            return Ok(());
+        }
+        columns::check_vector_column(column)?;
+        let field = Field::new(
+            column,
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim as i32),
+            true,
+        );
+        table
+            .add_columns(NewColumnTransform::AllNulls(Arc::new(Schema::new(vec![field]))), None)
+            .await?;
+        if !schema.field_with_name(columns::VECTOR_COLUMN)?.is_nullable() {
+            table
+                .alter_columns(&[ColumnAlteration::new(columns::VECTOR_COLUMN.to_string()).set_nullable(true)])
+                .await?;
+        }
+        tracing::info!(table = table.name(), column, dim, "Added vector column");
+        Ok(())
+    }
+
+    /// Add the sparse vector columns to `table` unless it has them.
+    async fn ensure_sparse_columns(&self, table: &Table) -> Result<()> {
+        if table.schema().await?.field_with_name(columns::SPARSE_INDICES_COLUMN).is_ok() {
+            return Ok(());
+        }
+        let list = |item: DataType| DataType::List(Arc::new(Field::new("item", item, true)));
+        let schema = Schema::new(vec![
+            Field::new(columns::SPARSE_INDICES_COLUMN, list(DataType::UInt32), true),
+            Field::new(columns::SPARSE_VALUES_COLUMN, list(DataType::Float32), true),
+        ]);
+        table.add_columns(NewColumnTransform::AllNulls(Arc::new(schema)), None).await?;
+        tracing::info!(table = table.name(), "Added sparse vector columns");
+        Ok(())
+    }
+
+    /// Whether the sparse vectors of `table` come from the server's sparse
+    /// model, so it can write and search them. The model is recorded in the
+    /// table's config on first use; vectors of another model's vocabulary
+    /// are left alone, with a warning.
+    async fn sparse_usable(&self, table: &Table) -> Result<bool> {
+        let Some(encoder) = &self.sparse else {
+            return Ok(false);
+        };
+        if table.schema().await?.field_with_name(columns::SPARSE_INDICES_COLUMN).is_err() {
+            return Ok(false);
+        }
+        let name = table.name();
+        let key = model_key(name, columns::SPARSE_INDICES_COLUMN);
+        if let Some(recorded) = self.checked_models.lock().unwrap().get(&key) {
+            return Ok(recorded == encoder.model_id());
+        }
+
+        let mut config = self.stored_table_config(name).await?;
+        let recorded = match config.sparse_model.clone() {
+            Some(recorded) => recorded,
+            None => {
+                config.sparse_model = Some(encoder.model_id().to_string());
+                self.set_table_config(name, &config).await?;
+                encoder.model_id().to_string()
+            }
+        };
+        if recorded != encoder.model_id() {
+            tracing::warn!(
+                "Table '{}' holds sparse vectors of '{}' but the server runs '{}'; they are not written or searched \
+                 until the table is re-embedded",
+                name,
+                recorded,
+                encoder.model_id()
+            );
+        }
+        let usable = recorded == encoder.model_id();
+        self.checked_models.lock().unwrap().insert(key, recorded);
+        Ok(usable)
+    }
+
+    /// The sparse vector of `query` as term weights, when `table` can be
+    /// searched with it.
+    async fn sparse_query(&self, table: &Table, query: &str) -> Result<Option<HashMap<u32, f32>>> {
+        let Some(encoder) = &self.sparse else {
+            return Ok(None);
+        };
+        if !self.sparse_usable(table).await? {
+            return Ok(None);
+        }
+        let vector = encoder
+            .embed(vec![query.to_string()])
+            .await
+            .context(ErrorCode::EmbeddingFailed)?
+            .pop()
+            .unwrap_or_default();
+        Ok(Some(vector.indices.into_iter().zip(vector.values).collect()))
+    }
+
+    /// Embedding model named in the provenance of one of the table's rows.
+    async fn sample_embedding_model(&self, table: &Table) -> Result<Option<String>> {
+        let batches: Vec<RecordBatch> = table
+            .query()
+            .select(Select::Columns(vec!["metadata".to_string()]))
+            .limit(1)
+            .execute()
+            .await?
+            .try_collect()
+            .await?;
+        for batch in batches {
+            let meta_col = rows::required_strings(&batch, "metadata")?;
+            if batch.num_rows() > 0 {
+                let mut meta = serde_json::from_str(meta_col.value(0)).unwrap_or(serde_json::json!({}));
+                return Ok(Provenance::take(&mut meta).embedding_model);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Names of the tables `list_tables` returns, served from the name index.
+    pub async fn cached_table_names(&self) -> Result<Vec<String>> {
+        if let Some(tables) = &self.names.lock().unwrap().tables {
+            return Ok(tables.clone());
+        }
+        let tables = self.list_tables().await?;
+        self.names.lock().unwrap().tables = Some(tables.clone());
+        Ok(tables)
+    }
+
+    /// Distinct document names in a table, served from the name index.
+    pub async fn cached_document_names(&self, table_name: &str) -> Result<Vec<String>> {
+        if let Some(documents) = self.names.lock().unwrap().documents.get(table_name) {
+            return Ok(documents.iter().cloned().collect());
+        }
+        let documents: BTreeSet<String> = self
+            .scan_metadata(table_name, None)
+            .await?
+            .into_iter()
+            .filter_map(|(_, meta)| meta.get("name").and_then(|v| v.as_str()).map(str::to_string))
+            .collect();
+        let names = documents.iter().cloned().collect();
+        self.names.lock().unwrap().documents.insert(table_name.to_string(), documents);
+        Ok(names)
+    }
+
+    /// Drop what the name index knows about `table_name` (and the table list),
+    /// so the next lookup reads it again.
+    fn forget_names(&self, table_name: &str) {
+        let mut names = self.names.lock().unwrap();
+        names.tables = None;
+        names.documents.remove(table_name);
+    }
+
+    /// All tables whose name starts with `prefix`, including internal ones.
+    pub async fn table_names_with_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        let names = self.connection.table_names().execute().await?;
+        Ok(names.into_iter().filter(|n| n.starts_with(prefix)).collect())
+    }
+
+    /// Drop a table together with its stored config. Dropping a table that
+    /// does not exist is not an error.
+    pub async fn drop_table(&self, name: &str) -> Result<()> {
+        if self.table_exists(name).await? {
+            self.connection.drop_table(name, &[]).await?;
+        }
+        self.forget_table(name);
+        if self.table_exists(TABLE_CONFIG_TABLE).await? {
+            let table = self.open(TABLE_CONFIG_TABLE).await?;
+            table.delete(&format!("table_name = '{}'", name.replace('\'', "''"))).await?;
+        }
+        self.forget_names(name);
+        self.forget_models(name);
+        self.unindexed_rows.lock().unwrap().remove(name);
+        self.written_versions.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    /// Replace the contents of `live` with the fully built table `replacement`,
+    /// so readers switch from the old data to the new in one step. The old
+    /// data is dropped afterwards and `replacement` no longer exists.
+    ///
+    /// LanceDB OSS cannot rename tables, so this swaps the table directories of
+    /// a local database: atomically via `RENAME_EXCHANGE` where available, and
+    /// otherwise by moving the live table aside first and moving it back if
+    /// the replacement cannot be put in place.
+    pub async fn swap_tables(&self, live: &str, replacement: &str) -> Result<()> {
+        let replacement_config = self.stored_table_config(replacement).await?;
+        let result = self.swap_table_dirs(live, replacement).await;
+        self.forget_table(live);
+        self.forget_table(replacement);
+        self.forget_names(live);
+        self.forget_names(replacement);
+        self.forget_models(live);
+        self.written_versions.lock().unwrap().retain(|name, _| name != live && name != replacement);
+        result?;
+
+        // The live table now holds the replacement's vectors
+        let mut config = self.stored_table_config(live).await?;
+        config.embedding_model = replacement_config.embedding_model;
+        config.embedding_dim = replacement_config.embedding_dim;
+        config.vector_columns = replacement_config.vector_columns;
+        config.sparse_model = replacement_config.sparse_model;
+        self.set_table_config(live, &config).await?;
+        // The replacement may not be quantized the way the live table is
+        let quantization = self.get_table_config(live).await?.quantization;
+        if quantization.is_some() {
+            self.quantize(live, quantization, None).await?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "local-files"))]
+    async fn swap_table_dirs(&self, live: &str, _replacement: &str) -> Result<()> {
+        Err(anyhow::anyhow!("Cannot swap into '{}': this build has no local-files feature", live))
+            .context(ErrorCode::InvalidArgument)
+    }
+
+    #[cfg(feature = "local-files")]
+    async fn swap_table_dirs(&self, live: &str, replacement: &str) -> Result<()> {
+        if live == replacement {
+            anyhow::bail!("Cannot swap table '{}' with itself", live);
+        }
+        if !self.table_exists(replacement).await? {
+            anyhow::bail!("Replacement table '{}' does not exist", replacement);
+        }
+        let root = self.local_root()?;
+        let live_dir = root.join(format!("{}.lance", live));
+        let replacement_dir = root.join(format!("{}.lance", replacement));
+
+        if !self.table_exists(live).await? {
+            tokio::fs::rename(&replacement_dir, &live_dir)
+                .await
+                .with_context(|| format!("Failed to move '{}' into place as '{}'", replacement, live))?;
+            return self.drop_table(replacement).await;
+        }
+
+        if exchange_dirs(&live_dir, &replacement_dir)? {
+            // The old data now lives under the replacement's name
+            return self.drop_table(replacement).await;
+        }
+
+        let backup = format!("{}swap_{}", INTERNAL_TABLE_PREFIX, uuid::Uuid::new_v4().simple());
+        let backup_dir = root.join(format!("{}.lance", backup));
+        tokio::fs::rename(&live_dir, &backup_dir)
+            .await
+            .with_context(|| format!("Failed to move table '{}' aside", live))?;
+        if let Err(e) = tokio::fs::rename(&replacement_dir, &live_dir).await {
+            if let Err(rollback) = tokio::fs::rename(&backup_dir, &live_dir).await {
+                anyhow::bail!(
+                    "Failed to move '{}' into place ({}) and to restore the previous table ({}); it is kept as '{}'",
+                    replacement,
+                    e,
+                    rollback,
+                    backup
+                );
             }
+            return Err(e).with_context(|| format!("Failed to move '{}' into place as '{}'", replacement, live));
+        }
+
+        self.drop_table(&backup).await?;
+        self.drop_table(replacement).await
+    }
+
+    /// Directory of a database on the local filesystem.
+    #[cfg(feature = "local-files")]
+    fn local_root(&self) -> Result<PathBuf> {
+        let uri = self.connection.uri();
+        if !self.is_local() {
+            anyhow::bail!("Swapping tables is only supported for local databases, not {}", uri);
+        }
+        Ok(PathBuf::from(uri.strip_prefix("file://").unwrap_or(uri)))
+    }
+
+    /// Read the id and parsed metadata of the rows of a table that are not
+    /// tombstoned.
+    ///
+    /// `needle` is a substring of the serialized metadata used as a cheap
+    /// prefilter; callers still need to check the parsed metadata exactly.
+    pub async fn scan_metadata(
+        &self,
+        table_name: &str,
+        needle: Option<&str>,
+    ) -> Result<Vec<(String, serde_json::Value)>> {
+        if !self.table_exists(table_name).await? {
+            return Ok(Vec::new());
+        }
+        let table = self.open(table_name).await?;
+        let filter = needle.map(|needle| format!("metadata LIKE '%{}%'", needle.replace('\'', "''")));
+        scan_metadata_where(&table, all_of(filter, live_filter(&table).await?)).await
+    }
+
+    /// Every row of a table, in storage order, with all columns except the
+    /// vector columns unless `with_vectors` is set.
+    pub async fn stream_rows(&self, table_name: &str, with_vectors: bool) -> Result<SendableRecordBatchStream> {
+        let table = self.open(table_name).await?;
+        let columns = table
+            .schema()
+            .await?
+            .fields()
+            .iter()
+            .filter(|f| with_vectors || !columns::is_vector(f))
+            .map(|f| f.name().clone())
+            .collect();
+        Ok(table.query().select(Select::Columns(columns)).execute().await?)
+    }
+
+    /// The current version of every table in `names`, so they can all be read
+    /// as of one moment with `stream_version` while writes go on.
+    pub async fn pin_versions(&self, names: &[String]) -> Result<Vec<u64>> {
+        let mut versions = Vec::with_capacity(names.len());
+        for name in names {
+            let table = self.open(name).await?;
+            versions.push(table.version().await?);
+        }
+        Ok(versions)
+    }
+
+    /// Every row and column of a table as of `version`.
+    pub async fn stream_version(&self, table_name: &str, version: u64) -> Result<SendableRecordBatchStream> {
+        // A handle of its own: checking out a version would move the cached one
+        let table = self.connection.open_table(table_name).execute().await?;
+        table.checkout(version).await?;
+        Ok(table.query().execute().await?)
+    }
+
+    /// Create `table_name` from `rows`, first dropping the table and its
+    /// config if it exists.
+    pub async fn replace_table(&self, table_name: &str, rows: Box<dyn RecordBatchReader + Send>) -> Result<()> {
+        self.drop_table(table_name).await?;
+        let table = self.connection.create_table(table_name, rows).execute().await?;
+        self.forget_names(table_name);
+        self.tables.lock().unwrap().insert(table_name.to_string(), table);
+        Ok(())
+    }
+
+    /// Every version of a table still kept on disk, oldest first.
+    pub async fn list_versions(&self, table_name: &str) -> Result<Vec<TableVersion>> {
+        let table = self.open(table_name).await?;
+        let mut versions: Vec<TableVersion> = table
+            .list_versions()
+            .await?
+            .into_iter()
+            .map(|v| TableVersion {
+                version: v.version,
+                timestamp: v.timestamp.to_rfc3339(),
+            })
+            .collect();
+        versions.sort_by_key(|v| v.version);
+        Ok(versions)
+    }
+
+    /// Make the contents of `version` the latest version of the table again.
+    /// Later versions are kept, so a restore can itself be undone. Returns
+    /// the new version.
+    pub async fn restore_version(&self, table_name: &str, version: u64) -> Result<TableVersion> {
+        let table = self.connection.open_table(table_name).execute().await?;
+        if !table.list_versions().await?.iter().any(|v| v.version == version) {
+            return Err(anyhow::anyhow!("Table '{}' has no version {}", table_name, version)).context(ErrorCode::NotFound);
         }
+        table.checkout(version).await?;
+        table.restore().await?;
+        self.forget_table(table_name);
+        self.forget_names(table_name);
+        self.forget_models(table_name);
+        current_version(&table).await
+    }
+
+    /// The latest version of a table written at or before `at`.
+    pub async fn version_as_of(&self, table_name: &str, at: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        let table = self.open(table_name).await?;
+        table
+            .list_versions()
+            .await?
+            .into_iter()
+            .filter(|v| v.timestamp <= at)
+            .map(|v| v.version)
+            .max()
+            .with_context(|| format!("Table '{}' has no version as of {}", table_name, at.to_rfc3339()))
+            .context(ErrorCode::NotFound)
+    }
+
+    /// Open a table, checked out at `version` if one is given. The current
+    /// version includes this process's own writes unless the consistency is
+    /// eventual: when the store is slow to list the version they made, the
+    /// read waits for it a little before going ahead with what it sees.
+    async fn open_at(&self, table_name: &str, version: Option<u64>) -> Result<Table> {
+        if let Some(version) = version {
+            // A handle of its own: checking out a version would move the cached one
+            let table = self.connection.open_table(table_name).execute().await?;
+            table
+                .checkout(version)
+                .await
+                .with_context(|| format!("Table '{}' has no version {}", table_name, version))
+                .context(ErrorCode::NotFound)?;
+            return Ok(table);
+        }
+        let table = self.open(table_name).await?;
+        if matches!(self.consistency, Consistency::Eventual) {
+            return Ok(table);
+        }
+        let Some(written) = self.written_versions.lock().unwrap().get(table_name).copied() else {
+            return Ok(table);
+        };
+        for _ in 0..READ_YOUR_WRITES_ATTEMPTS {
+            if table.version().await? >= written {
+                return Ok(table);
+            }
+            tokio::time::sleep(READ_YOUR_WRITES_WAIT).await;
+            table.checkout_latest().await?;
+        }
+        let seen = table.version().await?;
+        if seen < written {
+            tracing::warn!(table = table_name, seen, written, "Reading a version older than this server's last write");
+        }
+        Ok(table)
+    }
+
+    /// Remember the version `table` is at after a write, so later reads
+    /// through `open_at` see it.
+    async fn note_written(&self, table: &Table) -> Result<()> {
+        let version = table.version().await?;
+        let mut written = self.written_versions.lock().unwrap();
+        let latest = written.entry(table.name().to_string()).or_default();
+        *latest = (*latest).max(version);
+        Ok(())
+    }
+
+    /// Row count and current version of a table.
+    pub async fn describe(&self, table_name: &str) -> Result<(usize, TableVersion)> {
+        let table = self.open(table_name).await?;
+        Ok((table.count_rows(None).await?, current_version(&table).await?))
+    }
+
+    /// Texts of the chunks of the document called `name`, in storage order.
+    pub async fn document_texts(&self, table_name: &str, name: &str) -> Result<Vec<String>> {
+        let table = self.open(table_name).await?;
+        let filter = all_of(Some(metadata_filter(&table, "name", name).await?), live_filter(&table).await?);
+        let batches: Vec<RecordBatch> = table
+            .query()
+            .select(Select::Columns(vec!["text".to_string(), "metadata".to_string()]))
+            .only_if(filter.unwrap_or_default())
+            .execute()
+            .await?
+            .try_collect()
+            .await?;
+
+        let mut texts = Vec::new();
+        for batch in batches {
+            let text_col = rows::required_strings(&batch, "text")?;
+            let meta_col = rows::required_strings(&batch, "metadata")?;
+            for i in 0..batch.num_rows() {
+                let meta: serde_json::Value = serde_json::from_str(meta_col.value(i)).unwrap_or_default();
+                if meta.get("name").and_then(|v| v.as_str()) == Some(name) {
+                    texts.push(text_col.value(i).to_string());
+                }
+            }
+        }
+
+        Ok(texts)
+    }
+
+    /// The chunks of a table with these ids, shaped like search results
+    /// with a score of 0. Ids that do not exist are left out.
+    pub async fn get_chunks(&self, table_name: &str, ids: &[String]) -> Result<Vec<SearchResult>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let table = self.open(table_name).await?;
+        let id_list: Vec<String> = ids.iter().map(|id| format!("'{}'", id.replace('\'', "''"))).collect();
+        let stream = table
+            .query()
+            .select(Select::Columns(row_columns(&table.schema().await?)))
+            .only_if(format!("id IN ({})", id_list.join(", ")))
+            .execute()
+            .await?;
+        let hits = collect_hits(stream, ids.len(), |batch| {
+            let score = Float32Array::from(vec![0.0; batch.num_rows()]);
+            parse_hits(&with_score_column(batch, "_score", score)?, "_score")
+        })
+        .await?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| format_result(&hit.id, &hit.text, &hit.metadata, 0.0, hit.importance))
+            .collect())
+    }
+
+    /// Delete rows by id.
+    pub async fn delete_ids(&self, table_name: &str, ids: &[String]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let table = self.open(table_name).await?;
+
+        // Keep predicates at a reasonable size
+        for batch in ids.chunks(500) {
+            let list = batch
+                .iter()
+                .map(|id| format!("'{}'", id.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ");
+            table.delete(&format!("id IN ({})", list)).await?;
+        }
+        self.note_written(&table).await?;
+        self.forget_names(table_name);
+
+        Ok(())
+    }
+
+    /// Tombstone, or with `deleted` false restore, the chunks of the
+    /// document called `name`. Tombstoned chunks stay in the table but are
+    /// left out of searches and document listings until they are restored
+    /// or purged. Returns the number of chunks changed.
+    pub async fn set_document_deleted(&self, table_name: &str, name: &str, deleted: bool) -> Result<usize> {
+        let table = self.open(table_name).await?;
+        if table.schema().await?.field_with_name(columns::DELETED_COLUMN).is_err() {
+            if !deleted {
+                return Ok(0);
+            }
+            table
+                .add_columns(
+                    NewColumnTransform::SqlExpressions(vec![(columns::DELETED_COLUMN.to_string(), "CAST(FALSE AS BOOLEAN)".to_string())]),
+                    None,
+                )
+                .await?;
+        }
+        // Only the chunks whose state changes, so the count is meaningful
+        let state = if deleted { "(deleted IS NULL OR NOT deleted)" } else { "deleted = true" };
+        let filter = format!("{} AND {}", metadata_filter(&table, "name", name).await?, state);
+        let ids = document_ids(&table, filter, name).await?;
+
+        for batch in ids.chunks(500) {
+            let list = batch
+                .iter()
+                .map(|id| format!("'{}'", id.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ");
+            table
+                .update()
+                .only_if(format!("id IN ({})", list))
+                .column(columns::DELETED_COLUMN, if deleted { "true" } else { "false" })
+                .execute()
+                .await?;
+        }
+        self.note_written(&table).await?;
+        self.forget_names(table_name);
+        Ok(ids.len())
+    }
+
+    /// Delete the chunks of the document called `name` outright.
+    pub async fn delete_document(&self, table_name: &str, name: &str) -> Result<usize> {
+        let table = self.open(table_name).await?;
+        let ids = document_ids(&table, metadata_filter(&table, "name", name).await?, name).await?;
+        self.delete_ids(table_name, &ids).await?;
+        Ok(ids.len())
+    }
+
+    /// Delete tombstoned chunks for good: those of the document called
+    /// `name`, or all of them. Returns the number of chunks deleted.
+    pub async fn purge_deleted(&self, table_name: &str, name: Option<&str>) -> Result<usize> {
+        let table = self.open(table_name).await?;
+        if table.schema().await?.field_with_name(columns::DELETED_COLUMN).is_err() {
+            return Ok(0);
+        }
+        let ids = match name {
+            Some(name) => {
+                let filter = format!("{} AND deleted = true", metadata_filter(&table, "name", name).await?);
+                document_ids(&table, filter, name).await?
+            }
+            None => scan_metadata_where(&table, Some("deleted = true".to_string()))
+                .await?
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect(),
+        };
+        self.delete_ids(table_name, &ids).await?;
+        Ok(ids.len())
+    }
+
+    /// The ids of the rows whose metadata `key` is the string `value`.
+    pub async fn ids_by_metadata(&self, table_name: &str, key: &str, value: &str) -> Result<Vec<String>> {
+        if !self.table_exists(table_name).await? {
+            return Ok(Vec::new());
+        }
+        let table = self.open(table_name).await?;
+        let filter = metadata_filter(&table, key, value).await?;
+        Ok(scan_metadata_where(&table, Some(filter))
+            .await?
+            .into_iter()
+            .filter(|(_, meta)| meta.get(key).and_then(|v| v.as_str()) == Some(value))
+            .map(|(id, _)| id)
+            .collect())
+    }
+
+    /// Delete every row whose metadata `expires_at` is at or before `now`.
+    /// Returns the number of rows deleted.
+    pub async fn delete_expired(&self, table_name: &str, now: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        if !self.table_exists(table_name).await? {
+            return Ok(0);
+        }
+        let table = self.open(table_name).await?;
+        let key = expiry::EXPIRES_AT_KEY;
+        let filter = if columns::metadata_columns(&table.schema().await?).iter().any(|(name, _)| name == key) {
+            format!("{} IS NOT NULL", key)
+        } else {
+            format!("metadata LIKE '%\"{}\":%'", key)
+        };
+        let ids: Vec<String> = scan_metadata_where(&table, Some(filter))
+            .await?
+            .into_iter()
+            .filter(|(_, meta)| meta.get(key).and_then(dates::parse_stored).is_some_and(|at| at <= now))
+            .map(|(id, _)| id)
+            .collect();
+
+        self.delete_ids(table_name, &ids).await?;
+        Ok(ids.len())
+    }
+
+    /// Embed and store texts. A numeric `importance` key in a chunk's metadata
+    /// is moved into the `importance` column.
+    pub async fn add_texts(
+        &self,
+        table_name: &str,
+        texts: Vec<String>,
+        metadatas: Vec<serde_json::Value>,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        self.add_texts_into(table_name, columns::VECTOR_COLUMN, texts, metadatas, model, progress, cancel).await
+    }
+
+    /// `add_texts`, storing the vectors in `vector_column`, which is added
+    /// to the table on first use. The rows' other vector columns are null.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_texts_into(
+        &self,
+        table_name: &str,
+        vector_column: &str,
+        texts: Vec<String>,
+        metadatas: Vec<serde_json::Value>,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        self.add_rows(table_name, vector_column, None, texts, None, metadatas, model, progress, cancel).await
+    }
+
+    /// Store texts with vectors computed elsewhere, e.g. by an import. They
+    /// are recorded as `model`'s, so must come from the same model.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_vectors(
+        &self,
+        table_name: &str,
+        texts: Vec<String>,
+        vectors: Vec<Vec<f32>>,
+        metadatas: Vec<serde_json::Value>,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        if vectors.len() != texts.len() {
+            return Err(anyhow::anyhow!("{} vectors for {} texts", vectors.len(), texts.len())).context(ErrorCode::InvalidArgument);
+        }
+        self.add_rows(table_name, columns::VECTOR_COLUMN, None, texts, Some(vectors), metadatas, model, progress, cancel).await
+    }
+
+    /// Store images embedded with `model`, in `vector_column`. `texts`, e.g.
+    /// their descriptions, stand for them in results and keyword search.
+    /// Their metadata names the `image` modality, kept in its own column.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_images(
+        &self,
+        table_name: &str,
+        vector_column: &str,
+        texts: Vec<String>,
+        vectors: Vec<Vec<f32>>,
+        metadatas: Vec<serde_json::Value>,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        let Some(dim) = vectors.first().map(Vec::len) else {
+            return Ok(());
+        };
+        // A table needs its first rows in `vector`, which add_rows reports
+        if vector_column == columns::VECTOR_COLUMN || self.table_exists(table_name).await? {
+            let table = self.create_table(table_name, dim).await?;
+            if table.schema().await?.field_with_name(columns::MODALITY_COLUMN).is_err() {
+                // Everything written so far was text
+                let fill = format!("CAST('{}' AS STRING)", columns::MODALITY_TEXT);
+                table
+                    .add_columns(NewColumnTransform::SqlExpressions(vec![(columns::MODALITY_COLUMN.to_string(), fill)]), None)
+                    .await?;
+            }
+        }
+        self.add_rows(table_name, vector_column, None, texts, Some(vectors), metadatas, model, progress, cancel).await
+    }
+
+    /// Write rows in batches, embedding the texts unless `vectors` are given.
+    /// Rows get new ids unless `ids` are given.
+    #[allow(clippy::too_many_arguments)]
+    async fn add_rows(
+        &self,
+        table_name: &str,
+        vector_column: &str,
+        ids: Option<Vec<String>>,
+        texts: Vec<String>,
+        vectors: Option<Vec<Vec<f32>>>,
+        metadatas: Vec<serde_json::Value>,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        if texts.is_empty() {
+            return Ok(());
+        }
+
+        let total = texts.len();
+        let mut table: Option<Table> = None;
+        // Set once the table is open, when its sparse vectors can be written
+        let mut sparse: Option<&SparseEncoder> = None;
+        let ingested_at = chrono::Utc::now().to_rfc3339();
+        let started = std::time::Instant::now();
+        let config = self.get_table_config(table_name).await?;
+        let quantization = config.quantization;
+        // Filled in for rows written without a key added with add_column
+        let column_defaults = config.column_defaults.unwrap_or_default();
+
+        // Embed and write in batches so progress can be reported and a
+        // failure part way through keeps what was already written
+        for start in (0..total).step_by(INGEST_BATCH_SIZE) {
+            if cancel.is_some_and(|c| c.is_cancelled()) {
+                return Err(anyhow::anyhow!("Ingest cancelled after {} of {} chunks were written", start, total))
+                    .context(ErrorCode::Cancelled);
+            }
+            let end = (start + INGEST_BATCH_SIZE).min(total);
+            let batch_texts = &texts[start..end];
+
+            // 1. Compute embeddings, unless the caller brought them
+            let (embeddings, truncated) = match &vectors {
+                Some(vectors) => (vectors[start..end].to_vec(), Vec::new()),
+                None => model.embed_documents(batch_texts.to_vec()).await.context(ErrorCode::EmbeddingFailed)?,
+            };
+            let Some(dim) = embeddings.first().map(Vec::len) else {
+                return Err(anyhow::anyhow!("the embedding model returned no vectors for {} chunks", batch_texts.len()))
+                    .context(ErrorCode::EmbeddingFailed);
+            };
+            if let Some(report) = progress {
+                report(IngestProgress { total, embedded: end, written: start });
+            }
+
+            // 2. Ensure table exists
+            if table.is_none() {
+                if vector_column != columns::VECTOR_COLUMN && !self.table_exists(table_name).await? {
+                    return Err(anyhow::anyhow!(
+                        "Table '{}' does not exist; add its first documents without vector_column",
+                        table_name
+                    ))
+                    .context(ErrorCode::NotFound);
+                }
+                let created = self.create_table(table_name, dim).await?;
+                self.ensure_vector_column(&created, vector_column, dim).await?;
+                self.check_embedding_model(&created, vector_column, model, dim, true).await?;
+                if self.sparse.is_some() {
+                    self.ensure_sparse_columns(&created).await?;
+                    if self.sparse_usable(&created).await? {
+                        sparse = self.sparse.as_deref();
+                    }
+                }
+                // Configured before the table existed, or in the config file
+                if quantization == Some(Quantization::Binary)
+                    && created.schema().await?.field_with_name(columns::BITS_COLUMN).is_err()
+                {
+                    self.quantize_table(&created, quantization, cancel).await?;
+                }
+                table = Some(created);
+            }
+            let table = table.as_ref().expect("table was created above");
+            let schema = table.schema().await?;
+            check_batch_dimensions(table.name(), &schema, vector_column, &embeddings, batch_texts.len(), start)?;
+            let sparse_vectors = match sparse {
+                Some(encoder) => encoder.embed(batch_texts.to_vec()).await.context(ErrorCode::EmbeddingFailed)?,
+                None => Vec::new(),
+            };
+
+            // 3. Create RecordBatch
+            // ID Builder
+            let mut id_builder = StringBuilder::new();
+            // Text Builder
+            let mut text_builder = StringBuilder::new();
+            // Metadata Builder
+            let mut meta_builder = StringBuilder::new();
+            // Importance Builder
+            let mut importance_builder = Float32Builder::new();
+            // Modality Builder
+            let mut modality_builder = StringBuilder::new();
+            // Vector Builder
+            let values_builder = Float32Builder::new();
+            let mut vector_builder = FixedSizeListBuilder::new(values_builder, dim as i32);
+            // Metadata keys stored in columns of their own
+            let metadata_columns = columns::metadata_columns(&schema);
+            let mut stored_metas = Vec::with_capacity(batch_texts.len());
+
+            for (offset, text) in batch_texts.iter().enumerate() {
+                match &ids {
+                    Some(ids) => id_builder.append_value(&ids[start + offset]),
+                    None => id_builder.append_value(uuid::Uuid::new_v4().to_string()),
+                }
+                text_builder.append_value(text);
+                // Importance travels in the metadata but is stored in its own column
+                let mut meta = metadatas.get(start + offset).cloned().unwrap_or_else(|| serde_json::json!({}));
+                let importance = match &mut meta {
+                    serde_json::Value::Object(map) => map.remove("importance").and_then(|v| v.as_f64()),
+                    _ => None,
+                };
+                // Only part of the text is represented by its vector
+                if let (Some(true), serde_json::Value::Object(map)) = (truncated.get(offset), &mut meta) {
+                    map.insert("truncated".to_string(), serde_json::Value::Bool(true));
+                }
+                provenance::stamp(&mut meta, &ingested_at, model.model_id());
+                meta_builder.append_value(meta.to_string());
+                importance_builder.append_value(importance.unwrap_or(0.0) as f32);
+                modality_builder.append_value(
+                    meta.get(columns::MODALITY_COLUMN)
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(columns::MODALITY_TEXT),
+                );
+                if !metadata_columns.is_empty() {
+                    stored_metas.push(meta);
+                }
+
+                // Vector
+                let vec_ref = &embeddings[offset];
+                vector_builder.values().append_slice(vec_ref);
+                vector_builder.append(true);
+            }
+
+            let mut arrays: HashMap<&str, ArrayRef> = HashMap::from([
+                ("id", Arc::new(id_builder.finish()) as ArrayRef),
+                ("text", Arc::new(text_builder.finish()) as ArrayRef),
+                (vector_column, Arc::new(vector_builder.finish()) as ArrayRef),
+                ("metadata", Arc::new(meta_builder.finish()) as ArrayRef),
+                ("importance", Arc::new(importance_builder.finish()) as ArrayRef),
+                (columns::DELETED_COLUMN, Arc::new(BooleanArray::from(vec![false; end - start])) as ArrayRef),
+                (columns::MODALITY_COLUMN, Arc::new(modality_builder.finish()) as ArrayRef),
+            ]);
+            if let (columns::VECTOR_COLUMN, Some(width)) = (vector_column, vector_width(&schema, columns::BITS_COLUMN)) {
+                arrays.insert(columns::BITS_COLUMN, bits_array(&embeddings, width));
+            }
+            if !sparse_vectors.is_empty() {
+                let (indices, values) = sparse_arrays(&sparse_vectors);
+                arrays.insert(columns::SPARSE_INDICES_COLUMN, indices);
+                arrays.insert(columns::SPARSE_VALUES_COLUMN, values);
+            }
+            for (key, column_type) in &metadata_columns {
+                let values: Vec<Option<&serde_json::Value>> = stored_metas
+                    .iter()
+                    .map(|meta| meta.get(key).or_else(|| column_defaults.get(key)))
+                    .collect();
+                arrays.insert(key.as_str(), columns::build_array(&values, *column_type));
+            }
+            let arrays = schema
+                .fields()
+                .iter()
+                .map(|field| {
+                    // Other vector columns are left empty
+                    if !arrays.contains_key(field.name().as_str()) && columns::is_vector(field) {
+                        return Ok(new_null_array(field.data_type(), end - start));
+                    }
+                    arrays
+                        .remove(field.name().as_str())
+                        .with_context(|| format!("Table '{}' has a column '{}' of an unsupported type", table_name, field.name()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+            // 4. Add to table
+            // We need an iterator of RecordBatches
+            let stream = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+            table.add(stream).execute().await?;
+            self.note_written(table).await?;
+            self.note_unindexed(table_name, end - start);
+            if let Some(documents) = self.names.lock().unwrap().documents.get_mut(table_name) {
+                documents.extend(
+                    metadatas
+                        .iter()
+                        .skip(start)
+                        .take(end - start)
+                        .filter_map(|meta| meta.get("name").and_then(|v| v.as_str()).map(str::to_string)),
+                );
+            }
+
+            if let Some(report) = progress {
+                report(IngestProgress { total, embedded: end, written: end });
+            }
+        }
+
+        tracing::info!(
+            table = table_name,
+            rows = total,
+            duration_ms = started.elapsed().as_secs_f64() * 1000.0,
+            "Added rows"
+        );
+        Ok(())
+    }
+
+    /// Embed the text of every row of `source` again with `model` and write
+    /// the rows, with their ids and metadata, to `target`. Rows written to
+    /// `source` meanwhile are picked up by further passes, and rows deleted
+    /// from it are deleted from `target`, until a pass finds `source`
+    /// unchanged. Returns the number of rows in `target`.
+    pub async fn reembed(
+        &self,
+        source: &str,
+        target: &str,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<usize> {
+        let table = self.open(source).await?;
+        let mut copied: HashSet<String> = HashSet::new();
+        for pass in 0..REEMBED_MAX_PASSES {
+            table.checkout_latest().await?;
+            let version = table.version().await?;
+            let rows = read_chunks(&table).await?;
+            if pass == 0 && rows.is_empty() {
+                return Err(anyhow::anyhow!("Table '{}' has no rows to re-embed", source)).context(ErrorCode::InvalidArgument);
+            }
+
+            let current: HashSet<&str> = rows.iter().map(|row| row.id.as_str()).collect();
+            let deleted: Vec<String> = copied.iter().filter(|id| !current.contains(id.as_str())).cloned().collect();
+            self.delete_ids(target, &deleted).await?;
+            copied.retain(|id| current.contains(id.as_str()));
+
+            let mut ids = Vec::new();
+            let mut texts = Vec::new();
+            let mut metadatas = Vec::new();
+            for mut row in rows.into_iter().filter(|row| !copied.contains(&row.id)) {
+                if let serde_json::Value::Object(map) = &mut row.metadata {
+                    // Whether the new model truncates the text is decided afresh
+                    map.remove("truncated");
+                    map.insert("importance".to_string(), serde_json::json!(row.importance));
+                }
+                ids.push(row.id);
+                texts.push(row.text);
+                metadatas.push(row.metadata);
+            }
+            let added = ids.clone();
+            self.add_rows(target, columns::VECTOR_COLUMN, Some(ids), texts, None, metadatas, model, progress, cancel).await?;
+            copied.extend(added);
+
+            if self.open(source).await?.version().await? == version {
+                // Carry the table's settings over, but not the old model
+                let embedded = self.stored_table_config(target).await?;
+                let config = TableConfig {
+                    embedding_model: embedded.embedding_model,
+                    embedding_dim: embedded.embedding_dim,
+                    // Only `vector` is re-embedded into the new table
+                    vector_columns: None,
+                    sparse_model: embedded.sparse_model,
+                    ..self.stored_table_config(source).await?
+                };
+                self.set_table_config(target, &config).await?;
+                if config.quantization.is_some() {
+                    self.quantize(target, config.quantization, cancel).await?;
+                }
+                return Ok(copied.len());
+            }
+            tracing::info!(table = source, pass = pass + 1, "Table changed while re-embedding, catching up");
+        }
+        Err(anyhow::anyhow!(
+            "'{}' kept changing while it was re-embedded; pause writes to it and try again",
+            source
+        ))
+    }
+
+    /// Embed the text of the rows of `table_name` that have no vector in
+    /// `column` with `model` and store the vectors there, adding the column
+    /// on first use. The rows keep everything else, so an interrupted fill
+    /// resumes where it stopped. Returns the number of rows filled.
+    pub async fn embed_into_column(
+        &self,
+        table_name: &str,
+        column: &str,
+        model: &EmbeddingModel,
+        progress: Option<ProgressFn<'_>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<usize> {
+        if column == columns::VECTOR_COLUMN {
+            return Err(anyhow::anyhow!("Re-embed the '{}' column without vector_column", column)).context(ErrorCode::InvalidArgument);
+        }
+        let table = self.open(table_name).await?;
+        let schema = table.schema().await?;
+        let missing = match schema.field_with_name(column) {
+            Ok(_) if vector_width(&schema, column).is_none() => {
+                return Err(anyhow::anyhow!("Column '{}' of table '{}' is not a vector column", column, table_name))
+                    .context(ErrorCode::InvalidArgument);
+            }
+            Ok(_) => Some(format!("{} IS NULL", column)),
+            Err(_) => {
+                columns::check_vector_column(column)?;
+                None
+            }
+        };
+        // Tombstoned rows are filled too, so they are searchable once undeleted
+        let mut query = table.query().select(Select::Columns(vec!["id".to_string(), "text".to_string()]));
+        if let Some(filter) = missing {
+            query = query.only_if(filter);
+        }
+        let batches: Vec<RecordBatch> = query.execute().await?.try_collect().await?;
+        let mut rows = Vec::new();
+        for batch in &batches {
+            let id_col = rows::required_strings(batch, "id")?;
+            let text_col = rows::required_strings(batch, "text")?;
+            for i in 0..batch.num_rows() {
+                rows.push((id_col.value(i).to_string(), text_col.value(i).to_string()));
+            }
+        }
+
+        let total = rows.len();
+        let mut checked = false;
+        for (fill_start, fill) in rows.chunks(FILL_BATCH_SIZE).enumerate().map(|(i, fill)| (i * FILL_BATCH_SIZE, fill)) {
+            let mut vectors = Vec::with_capacity(fill.len());
+            for (offset, batch) in fill.chunks(INGEST_BATCH_SIZE).enumerate() {
+                let start = fill_start + offset * INGEST_BATCH_SIZE;
+                if cancel.is_some_and(|c| c.is_cancelled()) {
+                    return Err(anyhow::anyhow!(
+                        "Embedding cancelled after {} of {} rows were filled; run it again to resume",
+                        fill_start,
+                        total
+                    ))
+                    .context(ErrorCode::Cancelled);
+                }
+                let texts = batch.iter().map(|(_, text)| text.clone()).collect();
+                let (embeddings, _) = model.embed_documents(texts).await.context(ErrorCode::EmbeddingFailed)?;
+                let Some(dim) = embeddings.first().map(Vec::len) else {
+                    return Err(anyhow::anyhow!("the embedding model returned no vectors for {} chunks", batch.len()))
+                        .context(ErrorCode::EmbeddingFailed);
+                };
+                if !checked {
+                    self.ensure_vector_column(&table, column, dim).await?;
+                    self.check_embedding_model(&table, column, model, dim, true).await?;
+                    checked = true;
+                }
+                check_batch_dimensions(table_name, &table.schema().await?, column, &embeddings, batch.len(), start)?;
+                vectors.extend(embeddings);
+                if let Some(report) = progress {
+                    report(IngestProgress { total, embedded: start + batch.len(), written: fill_start });
+                }
+            }
+
+            let width = vectors[0].len();
+            let field = Field::new(
+                column,
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), width as i32),
+                true,
+            );
+            let update_schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Utf8, false), field]));
+            let mut vector_builder = FixedSizeListBuilder::new(Float32Builder::new(), width as i32);
+            for vector in &vectors {
+                vector_builder.values().append_slice(vector);
+                vector_builder.append(true);
+            }
+            let ids = StringArray::from_iter_values(fill.iter().map(|(id, _)| id.as_str()));
+            let update = RecordBatch::try_new(
+                update_schema.clone(),
+                vec![Arc::new(ids) as ArrayRef, Arc::new(vector_builder.finish()) as ArrayRef],
+            )?;
+            // Only the vector column is written, matched to the rows by id
+            let mut merge = table.merge_insert(&["id"]);
+            merge.when_matched_update_all(None);
+            merge
+                .execute(Box::new(RecordBatchIterator::new(vec![Ok(update)], update_schema)))
+                .await?;
+            if let Some(report) = progress {
+                report(IngestProgress { total, embedded: fill_start + fill.len(), written: fill_start + fill.len() });
+            }
+        }
+        self.note_unindexed(table_name, total);
+        tracing::info!(table = table_name, column, rows = total, "Filled vector column");
+        Ok(total)
+    }
+
+    /// Search `target`'s version and vector column of `table_name`.
+    pub async fn search(
+        &self,
+        table_name: &str,
+        target: SearchTarget<'_>,
+        query: &str,
+        limit: usize,
+        model: &EmbeddingModel,
+        importance_weight: f32,
+    ) -> Result<Vec<SearchResult>> {
+        let started = std::time::Instant::now();
+        let table = self.open_at(table_name, target.version).await?;
+        let column = target.column();
+        let filter = all_of(searchable_filter(&table, column).await?, modality_filter(&table, target.modality).await?);
+
+        // Embed query
+        let embedding = std::time::Instant::now();
+        let query_vec = model.embed_query(query).await.context(ErrorCode::EmbeddingFailed)?;
+        let embed_time = embedding.elapsed();
+        self.check_embedding_model(&table, column, model, query_vec.len(), false).await?;
+
+        // Search
+        let scored = nearest(&table, &target, query_vec, filter, candidate_count(limit, importance_weight))
+            .await?
+            .into_iter()
+            .map(|hit| {
+                let score = 1.0 - hit.score; // Convert distance to score (assuming cosine distance)
+                (hit, score)
+            })
+            .collect();
+
+        let ranking = std::time::Instant::now();
+        let results = rank(scored, limit, importance_weight);
+        target.record(|d| {
+            d.embed_ms += millis(embed_time);
+            d.db_ms += millis(ranking - started - embed_time);
+            d.rerank_ms += millis(ranking.elapsed());
+        });
+        Ok(results)
+    }
+
+    /// Hybrid search: fuse cosine similarity from vector search with BM25 from
+    /// full-text search, weighted per `weights`. Builds the full-text index
+    /// with the table's settings on first use; an earlier `version` must
+    /// already have one.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn hybrid_search(
+        &self,
+        table_name: &str,
+        target: SearchTarget<'_>,
+        query: &str,
+        limit: usize,
+        model: &EmbeddingModel,
+        weights: FusionWeights,
+        importance_weight: f32,
+    ) -> Result<Vec<SearchResult>> {
+        let started = std::time::Instant::now();
+        let table = self.open_at(table_name, target.version).await?;
+        let column = target.column();
+        if !self.has_fts_index(&table).await? {
+            if let Some(version) = target.version {
+                return Err(anyhow::anyhow!(
+                    "Version {} of table '{}' has no full-text index; search it in vector mode",
+                    version,
+                    table_name
+                ))
+                .context(ErrorCode::InvalidArgument);
+            }
+            let config = self.get_table_config(table_name).await?;
+            self.create_fts_index(table_name, &config).await?;
+        }
+        let embedding = std::time::Instant::now();
+        let sparse_query = self.sparse_query(&table, query).await?;
+        let weights = weights.normalized(sparse_query.is_some());
+        // Over-fetch from both sides so fusion can promote results ranked lower by one of them
+        let candidates = candidate_count(limit, importance_weight) * 2;
+
+        let query_vec = model.embed_query(query).await.context(ErrorCode::EmbeddingFailed)?;
+        let embed_time = embedding.elapsed();
+        self.check_embedding_model(&table, column, model, query_vec.len(), false).await?;
+        let modality = modality_filter(&table, target.modality).await?;
+        let mut keyword_search = table
+            .query()
+            .full_text_search(FullTextSearchQuery::new(query.to_string()))
+            .limit(candidates);
+        if let Some(filter) = all_of(live_filter(&table).await?, modality.clone()) {
+            keyword_search = keyword_search.only_if(filter);
+        }
+        let vector_filter = all_of(searchable_filter(&table, column).await?, modality.clone());
+        let vector_hits = nearest(&table, &target, query_vec, vector_filter, candidates).await?;
+
+        let keyword_hits = collect_hits(keyword_search.execute().await?, candidates, |batch| {
+            parse_hits(batch, "_score")
+        })
+        .await?;
+        let sparse_hits = match &sparse_query {
+            Some(terms) => {
+                let filter = all_of(live_filter(&table).await?, modality);
+                sparse_search(&table, terms, filter, candidates).await?
+            }
+            None => Vec::new(),
+        };
+        let ranking = std::time::Instant::now();
+        // BM25 and sparse dot products are unbounded, scale them into 0..1
+        // relative to the best match
+        let max_bm25 = keyword_hits.iter().map(|h| h.score).fold(0.0f32, f32::max);
+        let max_sparse = sparse_hits.iter().map(|h| h.score).fold(0.0f32, f32::max);
+        let sparse_weight = weights.sparse.unwrap_or(0.0);
+
+        let mut fused: HashMap<String, (Hit, f32)> = HashMap::new();
+        for hit in vector_hits {
+            let score = weights.vector * (1.0 - hit.score);
+            fused.insert(hit.id.clone(), (hit, score));
+        }
+        for hit in keyword_hits {
+            let score = if max_bm25 > 0.0 { weights.keyword * hit.score / max_bm25 } else { 0.0 };
+            fused
+                .entry(hit.id.clone())
+                .and_modify(|(_, s)| *s += score)
+                .or_insert((hit, score));
+        }
+        for hit in sparse_hits {
+            let score = if max_sparse > 0.0 { sparse_weight * hit.score / max_sparse } else { 0.0 };
+            fused
+                .entry(hit.id.clone())
+                .and_modify(|(_, s)| *s += score)
+                .or_insert((hit, score));
+        }
+
+        let results = rank(fused.into_values().collect(), limit, importance_weight);
+        target.record(|d| {
+            d.embed_ms += millis(embed_time);
+            d.db_ms += millis(ranking - started - embed_time);
+            d.rerank_ms += millis(ranking.elapsed());
+        });
+        Ok(results)
+    }
+
+    /// LanceDB's plan for the searches a query would run, without running
+    /// them. `filter` is an SQL condition joined with the search's own
+    /// filters. Without a `query` this plans a plain filtered scan, and
+    /// `hybrid` adds the full-text search, which needs the index to exist.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn explain_search(
+        &self,
+        table_name: &str,
+        target: SearchTarget<'_>,
+        query: Option<&str>,
+        filter: Option<&str>,
+        limit: usize,
+        hybrid: bool,
+        model: &EmbeddingModel,
+        verbose: bool,
+    ) -> Result<QueryPlan> {
+        let table = self.open(table_name).await?;
+        let column = target.column();
+        let modality = modality_filter(&table, target.modality).await?;
+        let filter = filter.map(|f| format!("({})", f));
+        let mut plan = QueryPlan::default();
+
+        let Some(query) = query else {
+            let filter = all_of(all_of(live_filter(&table).await?, modality), filter);
+            let mut scan = table.query().limit(limit);
+            if let Some(filter) = &filter {
+                scan = scan.only_if(filter.clone());
+            }
+            plan.scan_plan = Some(scan.explain_plan(verbose).await?);
+            plan.filter = filter;
+            return Ok(plan);
+        };
+
+        if hybrid {
+            if !self.has_fts_index(&table).await? {
+                return Err(anyhow::anyhow!(
+                    "Table '{}' has no full-text index yet; the first hybrid search builds it",
+                    table_name
+                ))
+                .context(ErrorCode::InvalidArgument);
+            }
+            let mut keyword = table
+                .query()
+                .full_text_search(FullTextSearchQuery::new(query.to_string()))
+                .limit(limit);
+            if let Some(filter) = all_of(all_of(live_filter(&table).await?, modality.clone()), filter.clone()) {
+                keyword = keyword.only_if(filter);
+            }
+            plan.keyword_plan = Some(keyword.explain_plan(verbose).await?);
+        }
+
+        let query_vec = model.embed_query(query).await.context(ErrorCode::EmbeddingFailed)?;
+        self.check_embedding_model(&table, column, model, query_vec.len(), false).await?;
+        let vector_filter = all_of(all_of(searchable_filter(&table, column).await?, modality), filter);
+        let search = if searches_bits(&table, column).await? {
+            let fetch = if target.rescore { limit * RESCORE_FACTOR } else { limit };
+            plan.index_name = index_on(&table, columns::BITS_COLUMN).await?;
+            bits_query(&table, &pack_bits(&query_vec), vector_filter.clone(), fetch)?
+        } else {
+            plan.index_name = index_on(&table, column).await?;
+            dense_query(&table, &target, query_vec, vector_filter.clone(), limit)?
+        };
+        plan.vector_plan = Some(search.explain_plan(verbose).await?);
+        plan.filter = vector_filter;
+        Ok(plan)
+    }
+}
+
+/// The sparse vector columns of a batch of rows.
+fn sparse_arrays(vectors: &[SparseVector]) -> (ArrayRef, ArrayRef) {
+    let mut indices = ListBuilder::new(UInt32Builder::new());
+    let mut values = ListBuilder::new(Float32Builder::new());
+    for vector in vectors {
+        indices.values().append_slice(&vector.indices);
+        indices.append(true);
+        values.values().append_slice(&vector.values);
+        values.append(true);
+    }
+    (Arc::new(indices.finish()), Arc::new(values.finish()))
+}
+
+/// The `limit` rows of `table` matching `filter` whose sparse vectors score
+/// highest against the query `terms`. Sparse vectors have no index, so this
+/// scans them all and then reads the winning rows.
+async fn sparse_search(
+    table: &Table,
+    terms: &HashMap<u32, f32>,
+    filter: Option<String>,
+    limit: usize,
+) -> Result<Vec<Hit>> {
+    let filter = all_of(filter, Some(format!("{} IS NOT NULL", columns::SPARSE_INDICES_COLUMN)));
+    let mut stream = table
+        .query()
+        .select(Select::Columns(vec![
+            "id".to_string(),
+            columns::SPARSE_INDICES_COLUMN.to_string(),
+            columns::SPARSE_VALUES_COLUMN.to_string(),
+        ]))
+        .only_if(filter.unwrap_or_default())
+        .execute()
+        .await?;
+
+    let mut scored: Vec<(f32, String)> = Vec::new();
+    let by_score = |a: &(f32, String), b: &(f32, String)| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1));
+    while let Some(batch) = stream.try_next().await? {
+        let ids = rows::required_strings(&batch, "id")?;
+        let indices = rows::lists(&batch, columns::SPARSE_INDICES_COLUMN)?;
+        let values = rows::lists(&batch, columns::SPARSE_VALUES_COLUMN)?;
+        for i in 0..batch.num_rows() {
+            if !indices.is_valid(i) || !values.is_valid(i) {
+                continue;
+            }
+            let vector = SparseVector {
+                indices: indices.value(i).as_primitive::<UInt32Type>().values().to_vec(),
+                values: values.value(i).as_primitive::<Float32Type>().values().to_vec(),
+            };
+            let score = vector.dot(terms);
+            if score > 0.0 {
+                scored.push((score, ids.value(i).to_string()));
+            }
+        }
+        // Keep only the leaders as the scan goes on, not every match
+        if scored.len() > limit * 4 {
+            scored.sort_by(by_score);
+            scored.truncate(limit);
+        }
+    }
+    scored.sort_by(by_score);
+    scored.truncate(limit);
+    if scored.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let scores: HashMap<String, f32> = scored.iter().map(|(score, id)| (id.clone(), *score)).collect();
+    let id_list: Vec<String> = scored.iter().map(|(_, id)| format!("'{}'", id.replace('\'', "''"))).collect();
+    let stream = table
+        .query()
+        .select(Select::Columns(row_columns(&table.schema().await?)))
+        .only_if(format!("id IN ({})", id_list.join(", ")))
+        .execute()
+        .await?;
+    // Attach the scores so the rows parse like those of the other searches
+    collect_hits(stream, limit, |batch| {
+        let ids = rows::required_strings(batch, "id")?;
+        let score: Float32Array = ids.iter().map(|id| id.and_then(|id| scores.get(id).copied())).collect();
+        parse_hits(&with_score_column(batch, "_sparse_score", score)?, "_sparse_score")
+    })
+    .await
+}
+
+/// The columns of `schema` a search returns: all but the vectors.
+fn row_columns(schema: &Schema) -> Vec<String> {
+    schema
+        .fields()
+        .iter()
+        .filter(|f| !columns::is_vector(f))
+        .map(|f| f.name().clone())
+        .collect()
+}
+
+/// Read the hits of a search as its batches arrive, parsing each with
+/// `parse`, until `limit` hits or `MAX_RESULT_BYTES` of text and metadata.
+/// The rest of the stream is never read.
+async fn collect_hits(
+    mut stream: SendableRecordBatchStream,
+    limit: usize,
+    parse: impl Fn(&RecordBatch) -> Result<Vec<Hit>>,
+) -> Result<Vec<Hit>> {
+    let mut hits = Vec::new();
+    let mut bytes = 0;
+    while let Some(batch) = stream.try_next().await? {
+        for hit in parse(&batch)? {
+            bytes += hit.text.len() + hit.metadata.len();
+            if bytes > MAX_RESULT_BYTES {
+                tracing::warn!(hits = hits.len(), "Search results reached {} bytes, dropping the rest", MAX_RESULT_BYTES);
+                return Ok(hits);
+            }
+            hits.push(hit);
+            if hits.len() >= limit {
+                return Ok(hits);
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// `batch` with the column `name` of scores appended.
+fn with_score_column(batch: &RecordBatch, name: &str, scores: Float32Array) -> Result<RecordBatch> {
+    let mut fields: Vec<Field> = batch.schema().fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(name, DataType::Float32, true));
+    let mut arrays = batch.columns().to_vec();
+    arrays.push(Arc::new(scores));
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+/// The `limit` rows of `target`'s vector column nearest to `query_vec`,
+/// scored by cosine distance. Binary-quantized tables are searched by the
+/// sign bits of their vectors and int8 ones through their index; both
+/// rescore their candidates with the full-precision vectors when
+/// `target.rescore` is set.
+async fn nearest(
+    table: &Table,
+    target: &SearchTarget<'_>,
+    query_vec: Vec<f32>,
+    filter: Option<String>,
+    limit: usize,
+) -> Result<Vec<Hit>> {
+    let column = target.column();
+    if searches_bits(table, column).await? {
+        if target.diagnostics.is_some() {
+            let index = index_on(table, columns::BITS_COLUMN).await?;
+            let distance = if target.rescore { "hamming, rescored by cosine" } else { "hamming" };
+            target.record(|d| d.note_vector_search(distance, index));
+        }
+        return nearest_by_bits(table, &query_vec, filter, limit, target.rescore).await;
+    }
+    if target.diagnostics.is_some() {
+        let index = index_on(table, column).await?;
+        target.record(|d| d.note_vector_search("cosine", index));
+    }
+    let search = dense_query(table, target, query_vec, filter, limit)?;
+    // _distance column is added by vector search
+    collect_hits(search.execute().await?, limit, |batch| parse_hits(batch, "_distance")).await
+}
+
+/// Whether a search of `column` goes through the sign bits of a
+/// binary-quantized table.
+async fn searches_bits(table: &Table, column: &str) -> Result<bool> {
+    Ok(column == columns::VECTOR_COLUMN && table.schema().await?.field_with_name(columns::BITS_COLUMN).is_ok())
+}
+
+/// The cosine search of `nearest` on a full-precision or int8 column.
+fn dense_query(
+    table: &Table,
+    target: &SearchTarget<'_>,
+    query_vec: Vec<f32>,
+    filter: Option<String>,
+    limit: usize,
+) -> Result<VectorQuery> {
+    let mut search = table
+        .vector_search(query_vec)?
+        .column(target.column())
+        .distance_type(DistanceType::Cosine)
+        .limit(limit);
+    if target.rescore {
+        // Only takes effect when the search goes through an index
+        search = search.refine_factor(RESCORE_FACTOR as u32);
+    }
+    if let Some(filter) = filter {
+        search = search.only_if(filter);
+    }
+    Ok(search)
+}
+
+/// The Hamming search of `nearest_by_bits`, fetching `fetch` rows.
+fn bits_query(table: &Table, query_bits: &[u8], filter: Option<String>, fetch: usize) -> Result<VectorQuery> {
+    let filter = all_of(filter, Some(format!("{} IS NOT NULL", columns::BITS_COLUMN)));
+    Ok(table
+        .vector_search(Arc::new(UInt8Array::from(query_bits.to_vec())) as ArrayRef)?
+        .column(columns::BITS_COLUMN)
+        .distance_type(DistanceType::Hamming)
+        .limit(fetch)
+        .only_if(filter.unwrap_or_default()))
+}
+
+/// Binary search: the rows whose sign bits are nearest to those of
+/// `query_vec` by Hamming distance, either rescored exactly or scored by
+/// the angle the Hamming distance estimates.
+async fn nearest_by_bits(
+    table: &Table,
+    query_vec: &[f32],
+    filter: Option<String>,
+    limit: usize,
+    rescore: bool,
+) -> Result<Vec<Hit>> {
+    let query_bits = pack_bits(query_vec);
+    let fetch = if rescore { limit * RESCORE_FACTOR } else { limit };
+    let stream = bits_query(table, &query_bits, filter, fetch)?.execute().await?;
+
+    let mut hits = collect_hits(stream, fetch, |batch| {
+        let distances = if rescore {
+            let vectors = rows::vectors(batch, columns::VECTOR_COLUMN)?;
+            Float32Array::from_iter_values((0..batch.num_rows()).map(|i| {
+                cosine_distance(query_vec, vectors.value(i).as_primitive::<Float32Type>().values())
+            }))
+        } else {
+            let bits = rows::vectors(batch, columns::BITS_COLUMN)?;
+            Float32Array::from_iter_values((0..batch.num_rows()).map(|i| {
+                let differing = hamming(&query_bits, bits.value(i).as_primitive::<UInt8Type>().values());
+                // Each differing bit stands for 1/dim of a half turn between the vectors
+                1.0 - (std::f32::consts::PI * differing as f32 / query_vec.len() as f32).cos()
+            }))
+        };
+        parse_hits(&with_score_column(batch, "_quantized_distance", distances)?, "_quantized_distance")
+    })
+    .await?;
+    hits.sort_by(|a, b| a.score.total_cmp(&b.score).then_with(|| a.id.cmp(&b.id)));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Sign bits of `vector`, the first dimension in the highest bit.
+fn pack_bits(vector: &[f32]) -> Vec<u8> {
+    vector
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| **v > 0.0)
+                .fold(0u8, |byte, (bit, _)| byte | (0x80 >> bit))
+        })
+        .collect()
+}
+
+fn hamming(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum()
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norms = a.iter().map(|v| v * v).sum::<f32>().sqrt() * b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norms > 0.0 { 1.0 - dot / norms } else { 1.0 }
+}
+
+/// The `vector_bits` column of a batch of rows.
+fn bits_array(vectors: &[Vec<f32>], width: usize) -> ArrayRef {
+    let mut builder = FixedSizeListBuilder::new(UInt8Builder::new(), width as i32);
+    for vector in vectors {
+        builder.values().append_slice(&pack_bits(vector));
+        builder.append(true);
+    }
+    Arc::new(builder.finish())
+}
+
+/// Name of the index on the `vector` column, if it has one.
+async fn vector_index(table: &Table) -> Result<Option<String>> {
+    index_on(table, columns::VECTOR_COLUMN).await
+}
+
+/// Name of the index on `column`, if it has one.
+async fn index_on(table: &Table, column: &str) -> Result<Option<String>> {
+    Ok(table
+        .list_indices()
+        .await?
+        .into_iter()
+        .find(|index| index.columns == [column])
+        .map(|index| index.name))
+}
+
+/// Train an IVF-HNSW index of int8-quantized vectors on the `vector`
+/// column. Returns the rows indexed, 0 when there are too few to train on.
+async fn build_int8_index(table: &Table) -> Result<usize> {
+    let rows = table.count_rows(Some(format!("{} IS NOT NULL", columns::VECTOR_COLUMN))).await?;
+    if rows < MIN_QUANTIZED_INDEX_ROWS {
+        tracing::info!(
+            table = table.name(),
+            rows,
+            "Too few rows for an int8 index, building it once the table has {}",
+            MIN_QUANTIZED_INDEX_ROWS
+        );
+        return Ok(0);
+    }
+    let index = IvfHnswSqIndexBuilder::default().distance_type(DistanceType::Cosine);
+    table
+        .create_index(&[columns::VECTOR_COLUMN], Index::IvfHnswSq(index))
+        .replace(true)
+        .execute()
+        .await?;
+    tracing::info!(table = table.name(), rows, "Built int8 vector index");
+    Ok(rows)
+}
+
+/// Add the `vector_bits` column to `table` unless it has it, and fill it
+/// from the `vector` column of every row. Returns the rows filled.
+async fn fill_bits(table: &Table, cancel: Option<&CancellationToken>) -> Result<usize> {
+    let schema = table.schema().await?;
+    let Some(dim) = vector_width(&schema, columns::VECTOR_COLUMN) else {
+        return Ok(0);
+    };
+    let width = dim.div_ceil(8);
+    let bits_type = DataType::FixedSizeList(Arc::new(Field::new("item", DataType::UInt8, true)), width as i32);
+    if schema.field_with_name(columns::BITS_COLUMN).is_err() {
+        let bits_schema = Schema::new(vec![Field::new(columns::BITS_COLUMN, bits_type.clone(), true)]);
+        table.add_columns(NewColumnTransform::AllNulls(Arc::new(bits_schema)), None).await?;
+    }
+
+    let update_schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new(columns::BITS_COLUMN, bits_type, true),
+    ]));
+    let mut stream = table
+        .query()
+        .select(Select::Columns(vec!["id".to_string(), columns::VECTOR_COLUMN.to_string()]))
+        .only_if(format!("{} IS NOT NULL", columns::VECTOR_COLUMN))
+        .execute()
+        .await?;
+    let mut ids: Vec<String> = Vec::new();
+    let mut vectors: Vec<Vec<f32>> = Vec::new();
+    let mut filled = 0;
+    loop {
+        let batch = stream.try_next().await?;
+        if let Some(batch) = &batch {
+            let id_col = rows::required_strings(batch, "id")?;
+            let vector_col = rows::vectors(batch, columns::VECTOR_COLUMN)?;
+            for i in 0..batch.num_rows() {
+                ids.push(id_col.value(i).to_string());
+                vectors.push(vector_col.value(i).as_primitive::<Float32Type>().values().to_vec());
+            }
+        }
+        if ids.len() >= FILL_BATCH_SIZE || (batch.is_none() && !ids.is_empty()) {
+            if cancel.is_some_and(|c| c.is_cancelled()) {
+                return Err(anyhow::anyhow!("Quantization cancelled after {} rows; run it again to finish", filled))
+                    .context(ErrorCode::Cancelled);
+            }
+            let update = RecordBatch::try_new(
+                update_schema.clone(),
+                vec![Arc::new(StringArray::from(std::mem::take(&mut ids))) as ArrayRef, bits_array(&vectors, width)],
+            )?;
+            filled += vectors.len();
+            vectors.clear();
+            // Only the bits are written, matched to the rows by id
+            let mut merge = table.merge_insert(&["id"]);
+            merge.when_matched_update_all(None);
+            merge
+                .execute(Box::new(RecordBatchIterator::new(vec![Ok(update)], update_schema.clone())))
+                .await?;
+        }
+        if batch.is_none() {
+            break;
+        }
+    }
+    tracing::info!(table = table.name(), rows = filled, "Stored sign bits for binary search");
+    Ok(filled)
+}
+
+/// Key of `checked_models` for a vector column of a table.
+fn model_key(table_name: &str, column: &str) -> String {
+    if column == columns::VECTOR_COLUMN {
+        table_name.to_string()
+    } else {
+        format!("{}:{}", table_name, column)
+    }
+}
+
+/// Length of the vectors stored in a table's vector column `column`, from its width.
+fn vector_width(schema: &Schema, column: &str) -> Option<usize> {
+    match schema.field_with_name(column).ok()?.data_type() {
+        DataType::FixedSizeList(_, size) => Some(*size as usize),
+        _ => None,
+    }
+}
+
+/// Fail with `DimensionMismatch` when `table` stores vectors of a different
+/// length than `dim`, e.g. after switching embedding models.
+async fn check_dimension(table: &Table, column: &str, dim: usize) -> Result<()> {
+    let schema = table.schema().await?;
+    if let Some(width) = vector_width(&schema, column) {
+        if width != dim {
+            return Err(anyhow::anyhow!(
+                "table '{}' stores {}-dimensional vectors but the embedding model produces {}; \
+                 run with --embedding-dimensions {} if the model supports truncating to it",
+                table.name(),
+                width,
+                dim,
+                width
+            ))
+            .context(ErrorCode::DimensionMismatch);
+        }
+    }
+    Ok(())
+}
+
+async fn current_version(table: &Table) -> Result<TableVersion> {
+    let version = table.version().await?;
+    let timestamp = table
+        .list_versions()
+        .await?
+        .into_iter()
+        .find(|v| v.version == version)
+        .map(|v| v.timestamp.to_rfc3339())
+        .unwrap_or_default();
+    Ok(TableVersion { version, timestamp })
+}
+
+/// A stored chunk without its vector.
+struct Chunk {
+    id: String,
+    text: String,
+    metadata: serde_json::Value,
+    importance: f32,
+}
+
+/// Every chunk of the checked out version of a table that is not tombstoned.
+async fn read_chunks(table: &Table) -> Result<Vec<Chunk>> {
+    let schema = table.schema().await?;
+    let mut select = vec!["id".to_string(), "text".to_string(), "metadata".to_string()];
+    if schema.field_with_name("importance").is_ok() {
+        select.push("importance".to_string());
+    }
+    select.extend(columns::metadata_columns(&schema).into_iter().map(|(name, _)| name));
+    let mut query = table.query().select(Select::Columns(select));
+    // Tombstoned chunks are not carried over
+    if let Some(filter) = live_filter(table).await? {
+        query = query.only_if(filter);
+    }
+    let batches: Vec<RecordBatch> = query.execute().await?.try_collect().await?;
+
+    let mut chunks = Vec::new();
+    for batch in batches {
+        let id_col = rows::required_strings(&batch, "id")?;
+        let text_col = rows::required_strings(&batch, "text")?;
+        let meta_col = rows::required_strings(&batch, "metadata")?;
+        let importance_col = rows::floats(&batch, "importance")?;
+        for i in 0..batch.num_rows() {
+            let mut metadata = serde_json::from_str(meta_col.value(i)).unwrap_or(serde_json::json!({}));
+            columns::merge_into(&mut metadata, &batch, i);
+            chunks.push(Chunk {
+                id: id_col.value(i).to_string(),
+                text: text_col.value(i).to_string(),
+                metadata,
+                importance: importance_col.as_ref().filter(|c| c.is_valid(i)).map_or(0.0, |c| c.value(i)),
+            });
+        }
+    }
+    Ok(chunks)
+}
+
+/// The id and parsed metadata of the rows of `table` matching `filter`.
+async fn scan_metadata_where(table: &Table, filter: Option<String>) -> Result<Vec<(String, serde_json::Value)>> {
+    let mut select = vec!["id".to_string(), "metadata".to_string()];
+    select.extend(columns::metadata_columns(&table.schema().await?).into_iter().map(|(name, _)| name));
+    let mut query = table.query().select(Select::Columns(select));
+    if let Some(filter) = filter {
+        query = query.only_if(filter);
+    }
+    let batches: Vec<RecordBatch> = query.execute().await?.try_collect().await?;
+
+    let mut rows = Vec::new();
+    for batch in batches {
+        let id_col = rows::required_strings(&batch, "id")?;
+        let meta_col = rows::required_strings(&batch, "metadata")?;
+        for i in 0..batch.num_rows() {
+            let mut meta = serde_json::from_str(meta_col.value(i)).unwrap_or(serde_json::json!({}));
+            columns::merge_into(&mut meta, &batch, i);
+            rows.push((id_col.value(i).to_string(), meta));
+        }
+    }
+    Ok(rows)
+}
+
+/// A filter leaving out tombstoned rows, when the table has any.
+async fn live_filter(table: &Table) -> Result<Option<String>> {
+    let schema = table.schema().await?;
+    Ok(schema
+        .field_with_name(columns::DELETED_COLUMN)
+        .is_ok()
+        .then(|| "(deleted IS NULL OR NOT deleted)".to_string()))
+}
+
+/// A filter leaving out the rows `column` cannot find: tombstoned ones and
+/// ones without a vector in it. Fails when `table` has no such vector column.
+async fn searchable_filter(table: &Table, column: &str) -> Result<Option<String>> {
+    let schema = table.schema().await?;
+    if vector_width(&schema, column).is_none() {
+        return Err(anyhow::anyhow!("Table '{}' has no vector column '{}'", table.name(), column)).context(ErrorCode::NotFound);
+    }
+    let present = schema
+        .field_with_name(column)?
+        .is_nullable()
+        .then(|| format!("{} IS NOT NULL", column));
+    Ok(all_of(live_filter(table).await?, present))
+}
+
+/// A filter keeping the rows of `modality`. Tables without images have no
+/// modality column and hold only text.
+async fn modality_filter(table: &Table, modality: Option<&str>) -> Result<Option<String>> {
+    let Some(modality) = modality else {
+        return Ok(None);
+    };
+    if modality != columns::MODALITY_TEXT && modality != columns::MODALITY_IMAGE {
+        return Err(anyhow::anyhow!("Unknown modality '{}', expected 'text' or 'image'", modality)).context(ErrorCode::InvalidArgument);
+    }
+    if table.schema().await?.field_with_name(columns::MODALITY_COLUMN).is_ok() {
+        return Ok(Some(format!("{} = '{}'", columns::MODALITY_COLUMN, modality)));
+    }
+    if modality == columns::MODALITY_IMAGE {
+        return Err(anyhow::anyhow!("Table '{}' has no images; add some with add_images", table.name())).context(ErrorCode::NotFound);
+    }
+    Ok(None)
+}
+
+/// Both filters, either of which may be absent.
+fn all_of(a: Option<String>, b: Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(format!("({}) AND {}", a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Ids of the rows matching `filter` that belong to the document `name`.
+async fn document_ids(table: &Table, filter: String, name: &str) -> Result<Vec<String>> {
+    Ok(scan_metadata_where(table, Some(filter))
+        .await?
+        .into_iter()
+        .filter(|(_, meta)| meta.get("name").and_then(|v| v.as_str()) == Some(name))
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// A filter for the rows whose metadata `key` is the string `value`: a match
+/// on the key's own column when it has one, so an index on it is used, and
+/// otherwise a substring prefilter on the serialized metadata. Callers still
+/// check the parsed metadata exactly.
+async fn metadata_filter(table: &Table, key: &str, value: &str) -> Result<String> {
+    let schema = table.schema().await?;
+    if columns::metadata_columns(&schema).contains(&(key.to_string(), ColumnType::String)) {
+        return Ok(format!("{} = '{}'", key, value.replace('\'', "''")));
+    }
+    let needle = format!("{}:{}", serde_json::json!(key), serde_json::json!(value));
+    Ok(format!("metadata LIKE '%{}%'", needle.replace('\'', "''")))
+}
+
+/// Copy metadata `key` of the rows that have it into the new column `key`.
+/// Rows written meanwhile see the column and fill it themselves. Returns
+/// the number of rows copied.
+async fn fill_from_metadata(table: &Table, key: &str, column_type: ColumnType) -> Result<usize> {
+    let needle = format!("{}:", serde_json::json!(key));
+    let batches: Vec<RecordBatch> = table
+        .query()
+        .select(Select::Columns(vec!["id".to_string(), "metadata".to_string()]))
+        .only_if(format!("metadata LIKE '%{}%'", needle.replace('\'', "''")))
+        .execute()
+        .await?
+        .try_collect()
+        .await?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new(key, column_type.data_type(), true),
+    ]));
+    let mut updates = Vec::with_capacity(batches.len());
+    let mut filled = 0;
+    for batch in batches {
+        let id_col = rows::required_strings(&batch, "id")?;
+        let meta_col = rows::required_strings(&batch, "metadata")?;
+        let mut ids = StringBuilder::new();
+        let mut metas = Vec::new();
+        for i in 0..batch.num_rows() {
+            let meta: serde_json::Value = serde_json::from_str(meta_col.value(i)).unwrap_or_default();
+            if meta.get(key).is_some() {
+                ids.append_value(id_col.value(i));
+                metas.push(meta);
+            }
+        }
+        if metas.is_empty() {
+            continue;
+        }
+        filled += metas.len();
+        let values: Vec<Option<&serde_json::Value>> = metas.iter().map(|meta| meta.get(key)).collect();
+        updates.push(RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(ids.finish()), columns::build_array(&values, column_type)],
+        ));
+    }
+    if filled > 0 {
+        // Only the new column is written, matched to the rows by id
+        let mut merge = table.merge_insert(&["id"]);
+        merge.when_matched_update_all(None);
+        merge
+            .execute(Box::new(RecordBatchIterator::new(updates, schema)))
+            .await?;
+    }
+    Ok(filled)
+}
+
+/// Check every embedding of a batch against the table's vector width before
+/// it is appended, so a bad vector fails with a clear error instead of an
+/// Arrow error from building the `RecordBatch`. `offset` is the index of the
+/// batch's first chunk in the whole ingest.
+fn check_batch_dimensions(
+    table_name: &str,
+    schema: &Schema,
+    column: &str,
+    embeddings: &[Vec<f32>],
+    expected: usize,
+    offset: usize,
+) -> Result<()> {
+    if embeddings.len() != expected {
+        return Err(anyhow::anyhow!(
+            "the embedding model returned {} vectors for {} chunks",
+            embeddings.len(),
+            expected
+        ))
+        .context(ErrorCode::EmbeddingFailed);
+    }
+    let Some(width) = vector_width(schema, column) else {
+        return Ok(());
+    };
+    if let Some((i, vector)) = embeddings.iter().enumerate().find(|(_, v)| v.len() != width) {
+        return Err(anyhow::anyhow!(
+            "table '{}' stores {}-dimensional vectors but chunk {} was embedded with {} dimensions",
+            table_name,
+            width,
+            offset + i,
+            vector.len()
+        ))
+        .context(ErrorCode::DimensionMismatch);
+    }
+    Ok(())
+}
+
+/// Atomically exchange two directories. Returns `false` when the platform or
+/// filesystem does not support it, so the caller can fall back.
+#[cfg(all(target_os = "linux", feature = "local-files"))]
+fn exchange_dirs(a: &Path, b: &Path) -> Result<bool> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    // SAFETY: both paths are valid NUL-terminated strings that outlive the call.
+    let rc = unsafe {
+        libc::renameat2(libc::AT_FDCWD, a.as_ptr(), libc::AT_FDCWD, b.as_ptr(), libc::RENAME_EXCHANGE)
+    };
+    if rc == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EINVAL) | Some(libc::ENOSYS) | Some(libc::ENOTSUP) => Ok(false),
+        _ => Err(err.into()),
+    }
+}
+
+#[cfg(all(not(target_os = "linux"), feature = "local-files"))]
+fn exchange_dirs(_a: &Path, _b: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// How many rows to fetch for `limit` results. Boosting by importance can
+/// promote rows from below the cut, so fetch extra when it is enabled.
+fn candidate_count(limit: usize, importance_weight: f32) -> usize {
+    if importance_weight > 0.0 { limit * 2 } else { limit }
+}
+
+/// Blend importance into the relevance scores, then sort and keep the top `limit`.
+/// Equal scores are ordered by id, so the ranking does not depend on the
+/// order rows came back in.
+fn rank(scored: Vec<(Hit, f32)>, limit: usize, importance_weight: f32) -> Vec<SearchResult> {
+    let mut ranked: Vec<(Hit, f32)> = scored
+        .into_iter()
+        .map(|(hit, score)| {
+            let boosted = score + importance_weight * hit.importance;
+            (hit, boosted)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.id.cmp(&b.0.id)));
+    ranked.truncate(limit);
+
+    ranked
+        .into_iter()
+        .map(|(hit, score)| format_result(&hit.id, &hit.text, &hit.metadata, score, hit.importance))
+        .collect()
+}
+
+/// Kind of index `create_scalar_index` builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarIndexKind {
+    /// For columns with many distinct values and range filters, e.g. dates
+    BTree,
+    /// For columns with few distinct values, e.g. a source or category
+    Bitmap,
+}
+
+impl ScalarIndexKind {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "btree" => Ok(Self::BTree),
+            "bitmap" => Ok(Self::Bitmap),
+            other => Err(anyhow::anyhow!("Unknown index type '{}', expected 'btree' or 'bitmap'", other))
+                .context(ErrorCode::InvalidArgument),
+        }
+    }
+}
+
+/// What `compact_and_cleanup` did to a table.
+#[derive(Debug, Clone, serde::Serialize, schemars::JsonSchema)]
+pub struct CompactionReport {
+    /// Data fragments the table had before compaction
+    pub fragments_before: usize,
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    /// Old versions deleted; they can no longer be searched or restored
+    pub versions_removed: u64,
+    pub bytes_removed: u64,
+}
+
+/// LanceDB's plans for the parts of a search, from `explain_search`.
+#[derive(Debug, Clone, Default, serde::Serialize, schemars::JsonSchema)]
+pub struct QueryPlan {
+    /// Filter the vector search (or scan) applies: the request's, joined with
+    /// the ones leaving out deleted rows, rows without vectors and other modalities
+    pub filter: Option<String>,
+    /// ANN index the vector search goes through; a flat scan when absent
+    pub index_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_plan: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword_plan: Option<String>,
+    /// Plan of the filtered scan run when there is no query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_plan: Option<String>,
+}
+
+/// A version of a table: every write creates one.
+#[derive(Debug, Clone, serde::Serialize, schemars::JsonSchema)]
+pub struct TableVersion {
+    pub version: u64,
+    /// RFC 3339 time the version was written
+    pub timestamp: String,
+}
+
+/// A search hit as returned to clients.
+#[derive(Debug, Clone, serde::Serialize, schemars::JsonSchema)]
+pub struct SearchResult {
+    pub id: String,
+    /// Name of the document the chunk belongs to
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Text of the chunk
+    pub content: String,
+    /// Relevance score including the importance boost; higher is better
+    pub score: f32,
+    pub importance: f32,
+    /// Remaining custom metadata of the chunk
+    pub metadata: serde_json::Value,
+    /// Where the chunk came from and how it was processed
+    pub provenance: Provenance,
+    /// Table the hit came from, set when the search was routed automatically
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+}
+
+impl SearchResult {
+    /// Ranking order of results: higher score first, ties broken by table
+    /// and then id so identical searches return identical rankings.
+    pub fn ranking_order(a: &Self, b: &Self) -> std::cmp::Ordering {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.table.cmp(&b.table))
+            .then_with(|| a.id.cmp(&b.id))
+    }
+}
+
+/// A result row before formatting; `score` is the raw value of the score column.
+struct Hit {
+    id: String,
+    text: String,
+    metadata: String,
+    score: f32,
+    importance: f32,
+}
+
+/// The rows of a search result batch, scored by `score_column`. Only `id`,
+/// `text` and the score are required; columns of other types are converted
+/// where possible, and an error names the column that could not be read.
+fn parse_hits(batch: &RecordBatch, score_column: &str) -> Result<Vec<Hit>> {
+    let id_col = rows::required_strings(batch, "id")?;
+    let text_col = rows::required_strings(batch, "text")?;
+    let score_col = rows::required_floats(batch, score_column)?;
+    let meta_col = rows::strings(batch, "metadata")?;
+    // Tables written before the importance column existed read as 0
+    let importance_col = rows::floats(batch, "importance")?;
+    let has_metadata_columns = !columns::metadata_columns(&batch.schema()).is_empty();
+
+    let mut hits = Vec::with_capacity(batch.num_rows());
+    for i in 0..batch.num_rows() {
+        let stored = meta_col.as_ref().filter(|c| c.is_valid(i)).map_or("{}", |c| c.value(i));
+        let metadata = if has_metadata_columns {
+            let mut meta: serde_json::Value = serde_json::from_str(stored).unwrap_or(serde_json::json!({}));
+            columns::merge_into(&mut meta, batch, i);
+            meta.to_string()
+        } else {
+            stored.to_string()
+        };
+        hits.push(Hit {
+            id: id_col.value(i).to_string(),
+            text: text_col.value(i).to_string(),
+            metadata,
+            score: score_col.value(i),
+            importance: importance_col
+                .as_ref()
+                .filter(|c| c.is_valid(i))
+                .map(|c| c.value(i))
+                .unwrap_or(0.0),
+        });
+    }
+    Ok(hits)
+}
+
+/// Shape a row into the search result returned to clients, lifting `name`
+/// and `description` out of the metadata.
+fn format_result(id: &str, text: &str, meta_str: &str, score: f32, importance: f32) -> SearchResult {
+    let mut meta: serde_json::Value = serde_json::from_str(meta_str).unwrap_or(serde_json::json!({}));
+
+    // Extract name and description
+    let mut name = String::new();
+    let mut description = None;
+
+    if let serde_json::Value::Object(ref mut map) = meta {
+        if let Some(n) = map.remove("name") {
+            if let Some(s) = n.as_str() {
+                name = s.to_string();
+            }
+        }
+        if let Some(d) = map.remove("description") {
+            if let Some(s) = d.as_str() {
+                description = Some(s.to_string());
+            }
+        }
+    }
+    let provenance = Provenance::take(&mut meta);
 
-        Ok(output)
+    SearchResult {
+        id: id.to_string(),
+        name,
+        description,
+        content: text.to_string(),
+        score,
+        importance,
+        metadata: meta,
+        provenance,
+        table: None,
     }
 }
@@ -1,187 +1,1862 @@
 use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use crate::http::HttpOptions;
+#[cfg(feature = "local-embeddings")]
 use std::cmp::max;
-use std::sync::Arc;
+#[cfg(feature = "local-embeddings")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "local-embeddings")]
 use tokio::sync::Mutex;
+#[cfg(feature = "local-embeddings")]
 use text_embeddings_backend::{ModelType, Pool};
+#[cfg(feature = "local-embeddings")]
 use text_embeddings_backend_core::{Backend, Batch, Embedding};
+#[cfg(feature = "local-embeddings")]
 use tokenizers::Tokenizer;
+#[cfg(feature = "fastembed")]
+use fastembed::{ImageEmbedding, ImageInitOptions, InitOptions, SparseInitOptions, SparseTextEmbedding, TextEmbedding};
+#[cfg(feature = "llama-cpp")]
+use llama_cpp_2::{
+    context::params::{LlamaContextParams, LlamaPoolingType},
+    llama_backend::LlamaBackend,
+    llama_batch::LlamaBatch,
+    model::{params::LlamaModelParams, AddBos, LlamaModel},
+    token::LlamaToken,
+};
 
 pub enum EmbeddingEngine {
-    Api {
-        client: reqwest::Client,
-        base_url: String,
+    Api(ApiClient),
+    #[cfg(feature = "local-embeddings")]
+    Local {
+        // A backend runs one batch at a time, so concurrent requests are
+        // spread over a pool of them
+        backends: Vec<Mutex<Box<dyn Backend + Send>>>,
+        next_backend: AtomicUsize,
+        tokenizer: Tokenizer,
+        backend: LocalBackend,
+        pooling: Pooling,
+        device: Device,
+        dtype: DType,
+    },
+    /// A small ONNX model run by fastembed, without the candle dependencies.
+    /// CLIP text models come with the vision model embedding into the same space
+    #[cfg(feature = "fastembed")]
+    FastEmbed {
+        model: TextEmbedding,
+        images: Option<ImageEmbedding>,
+    },
+    /// A Titan or Cohere model on AWS Bedrock, called through the AWS SDK
+    #[cfg(feature = "bedrock")]
+    Bedrock {
+        client: aws_sdk_bedrockruntime::Client,
         model_id: String,
     },
-    Local {
-        // We use Arc<Mutex<>> because the backend might not be Send/Sync or we need mutability
-        backend: Arc<Mutex<text_embeddings_backend_candle::CandleBackend>>,
-        tokenizer: Arc<Tokenizer>,
+    /// A GGUF model run by llama.cpp, which brings its own tokenizer
+    #[cfg(feature = "llama-cpp")]
+    LlamaCpp {
+        backend: LlamaBackend,
+        model: LlamaModel,
+        pooling: Option<Pooling>,
     },
 }
 
+pub const DEFAULT_EMBEDDING_WORKERS: usize = 1;
+/// Model used with `--embedding-provider ollama` when none is given.
+const DEFAULT_OLLAMA_MODEL: &str = "nomic-embed-text";
+/// Model used with `--embedding-provider cohere` when none is given.
+const DEFAULT_COHERE_MODEL: &str = "embed-english-v3.0";
+/// Model used with `--embedding-provider bedrock` when none is given.
+const DEFAULT_BEDROCK_MODEL: &str = "amazon.titan-embed-text-v2:0";
+/// Model used with `--backend fastembed` when none is given.
+const DEFAULT_FASTEMBED_MODEL: &str = "BAAI/bge-small-en-v1.5";
+
+/// How the local backend turns token embeddings into one vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pooling {
+    Mean,
+    Cls,
+    LastToken,
+}
+
+impl Pooling {
+    #[cfg_attr(not(any(feature = "local-embeddings", feature = "llama-cpp")), allow(dead_code))]
+    fn as_str(self) -> &'static str {
+        match self {
+            Pooling::Mean => "mean",
+            Pooling::Cls => "cls",
+            Pooling::LastToken => "last-token",
+        }
+    }
+
+    /// The pooling a sentence-transformers model declares in
+    /// `1_Pooling/config.json`, if it has one.
+    #[cfg(feature = "local-embeddings")]
+    fn from_sentence_transformers(model_dir: &std::path::Path) -> Option<Self> {
+        let raw = std::fs::read_to_string(model_dir.join("1_Pooling/config.json")).ok()?;
+        let config: serde_json::Value = serde_json::from_str(&raw).ok()?;
+        let enabled = |mode: &str| config.get(mode).and_then(|v| v.as_bool()).unwrap_or(false);
+        if enabled("pooling_mode_cls_token") {
+            Some(Pooling::Cls)
+        } else if enabled("pooling_mode_lasttoken") {
+            Some(Pooling::LastToken)
+        } else if enabled("pooling_mode_mean_tokens") {
+            Some(Pooling::Mean)
+        } else {
+            None
+        }
+    }
+
+    /// The pooling the model family was trained with.
+    #[cfg_attr(not(feature = "local-embeddings"), allow(dead_code))]
+    fn for_model(model_id: &str) -> Self {
+        if model_id.to_lowercase().contains("qwen3-embedding") {
+            Pooling::LastToken
+        } else {
+            Pooling::Mean
+        }
+    }
+}
+
+/// Settings that only apply to a model run locally.
+#[cfg_attr(not(feature = "local-embeddings"), allow(dead_code))]
+pub struct LocalOptions {
+    /// Model instances serving requests concurrently
+    pub workers: usize,
+    /// Pooling, defaulting to the model's sentence-transformers config or
+    /// else the model family's own
+    pub pooling: Option<Pooling>,
+    pub backend: LocalBackend,
+    pub device: Device,
+    pub dtype: DType,
+    /// Directory holding the model files (a `.gguf` file for llama.cpp),
+    /// used instead of the Hugging Face Hub
+    pub model_path: Option<PathBuf>,
+    /// Only use models already in the Hugging Face cache
+    pub offline: bool,
+    /// Hugging Face cache, defaulting to `$HF_HOME/hub`
+    pub hf_cache_dir: Option<PathBuf>,
+    /// Hugging Face Hub or mirror to download from
+    pub hf_endpoint: Option<String>,
+}
+
+/// Inference engine of a local model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalBackend {
+    /// candle with safetensors weights, on the CPU or a GPU
+    Candle,
+    /// ONNX Runtime with `onnx/model.onnx`, on the CPU
+    Onnx,
+    /// llama.cpp with a GGUF file on disk, no download
+    LlamaCpp,
+    /// fastembed with one of its small models, on the CPU
+    FastEmbed,
+}
+
+/// Device the local backend runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Cpu,
+    Cuda,
+    Metal,
+}
+
+impl Device {
+    #[cfg_attr(not(feature = "local-embeddings"), allow(dead_code))]
+    fn as_str(self) -> &'static str {
+        match self {
+            Device::Cpu => "cpu",
+            Device::Cuda => "cuda",
+            Device::Metal => "metal",
+        }
+    }
+
+    /// Parse `--device`, falling back to the CPU when this build lacks
+    /// support for the requested GPU. Without a request, the GPU compiled
+    /// in is used; the backend itself falls back to the CPU when none is
+    /// present at runtime.
+    pub fn select(requested: Option<&str>) -> Result<Self> {
+        let compiled = if cfg!(feature = "cuda") {
+            Device::Cuda
+        } else if cfg!(feature = "metal") {
+            Device::Metal
+        } else {
+            Device::Cpu
+        };
+        let device = match requested {
+            None => return Ok(compiled),
+            Some("cpu") => Device::Cpu,
+            Some("cuda") => Device::Cuda,
+            Some("metal") => Device::Metal,
+            Some(other) => anyhow::bail!("Unknown --device '{}', expected 'cpu', 'cuda' or 'metal'", other),
+        };
+        let supported = match device {
+            Device::Cpu => true,
+            Device::Cuda => cfg!(feature = "cuda"),
+            Device::Metal => cfg!(feature = "metal"),
+        };
+        if !supported {
+            let feature = if device == Device::Cuda { "cuda" } else { "metal" };
+            tracing::warn!("This build has no {} support (cargo feature '{}'), running on the CPU", feature, feature);
+            return Ok(Device::Cpu);
+        }
+        Ok(device)
+    }
+
+    /// Keep the backend on this device. The backend picks a GPU by itself
+    /// whenever one is available, so running on the CPU hides the CUDA
+    /// devices from it; Metal cannot be hidden.
+    ///
+    /// # Safety
+    ///
+    /// Modifies the environment, so no other thread may read or write it
+    /// at the same time.
+    pub unsafe fn pin(self) {
+        if self == Device::Cpu && cfg!(feature = "cuda") {
+            // SAFETY: upheld by the caller
+            unsafe { std::env::set_var("CUDA_VISIBLE_DEVICES", "") };
+        }
+        if self == Device::Cpu && cfg!(feature = "metal") {
+            tracing::warn!("The CPU cannot be forced in a Metal build, a present GPU is still used");
+        }
+    }
+}
+
+/// Precision of the local model's weights and activations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DType {
+    Float32,
+    Float16,
+    BFloat16,
+}
+
+impl DType {
+    /// Parse `--dtype`, falling back to what `device` supports: the CPU
+    /// backend computes in float32 and Metal has no bfloat16.
+    pub fn select(requested: &str, device: Device) -> Result<Self> {
+        let dtype = match requested {
+            "float32" => DType::Float32,
+            "float16" => DType::Float16,
+            "bfloat16" => DType::BFloat16,
+            other => anyhow::bail!("Unknown --dtype '{}', expected 'float32', 'float16' or 'bfloat16'", other),
+        };
+        let fallback = match (device, dtype) {
+            (Device::Cpu, DType::Float16 | DType::BFloat16) => DType::Float32,
+            (Device::Metal, DType::BFloat16) => DType::Float16,
+            _ => dtype,
+        };
+        if fallback != dtype {
+            tracing::warn!(
+                "{} is not supported on {:?}, using {}",
+                dtype.as_str(),
+                device,
+                fallback.as_str()
+            );
+        }
+        Ok(fallback)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            DType::Float32 => "float32",
+            DType::Float16 => "float16",
+            DType::BFloat16 => "bfloat16",
+        }
+    }
+}
+
+pub const DEFAULT_QUERY_CACHE_SIZE: usize = 1024;
+pub const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 32;
+pub const DEFAULT_EMBEDDING_BATCH_TOKENS: usize = 16_384;
+/// Batches sent to an embedding API at the same time.
+const API_CONCURRENT_BATCHES: usize = 4;
+/// Characters per token assumed when estimating tokens without a tokenizer.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Which part of a text longer than `--max-tokens` is embedded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truncation {
+    /// Keep the start
+    Head,
+    /// Keep the end
+    Tail,
+    /// Keep the start and the end, dropping the middle
+    Middle,
+}
+
 pub struct EmbeddingModel {
     engine: EmbeddingEngine,
+    model_id: String,
+    query_cache: Option<QueryCache>,
+    max_batch_size: usize,
+    max_batch_tokens: usize,
+    max_tokens: Option<usize>,
+    truncation: Truncation,
+    /// Leading dimensions kept of every embedding
+    dimensions: Option<usize>,
+    /// Scale every embedding to unit length
+    normalize: bool,
+    /// Template wrapping search queries, `{text}` marking the query
+    query_template: Option<String>,
+    /// Template wrapping documents, `{text}` marking the document
+    document_template: Option<String>,
+    /// Width of the embeddings, known after the first one
+    dimension: OnceLock<usize>,
+}
+
+/// Wire format of the embedding API at `--embedding-endpoint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiProvider {
+    /// `POST /v1/embeddings`, spoken by OpenAI and most compatible servers
+    OpenAi,
+    /// Ollama's native `POST /api/embed`, or `/api/embeddings` on older versions
+    Ollama,
+    /// Text Embeddings Inference's native `POST /embed`
+    Tei,
+    /// Cohere's `POST /v2/embed`, told whether it embeds queries or documents
+    Cohere,
+    /// AWS Bedrock `InvokeModel`, signed with credentials from the AWS
+    /// provider chain
+    Bedrock,
+    /// Azure OpenAI: `POST /openai/deployments/{deployment}/embeddings`,
+    /// authenticated with an `api-key` header
+    Azure {
+        /// Deployment name, the model id when not given
+        deployment: Option<String>,
+        api_version: String,
+    },
+}
+
+impl ApiProvider {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ApiProvider::OpenAi => "openai",
+            ApiProvider::Ollama => "ollama",
+            ApiProvider::Tei => "tei",
+            ApiProvider::Cohere => "cohere",
+            ApiProvider::Bedrock => "bedrock",
+            ApiProvider::Azure { .. } => "azure",
+        }
+    }
+}
+
+/// What `EmbeddingModel::info` reports.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ModelInfo {
+    /// "api" or "local"
+    pub engine: &'static str,
+    /// API wire format (openai, ollama, tei, cohere, bedrock, azure) or local
+    /// backend (candle, onnx, llama-cpp, fastembed)
+    pub backend: &'static str,
+    pub model: String,
+    /// Width of the stored embeddings
+    pub dimension: usize,
+    /// Pooling of a local model, unset when the model or service decides
+    pub pooling: Option<&'static str>,
+    pub device: Option<&'static str>,
+    pub dtype: Option<&'static str>,
+    /// Tokens embedded per text, longer texts being truncated
+    pub max_tokens: Option<usize>,
+    /// Part of a text kept when truncating: head, tail or middle
+    pub truncation: &'static str,
+    /// Leading dimensions kept of every embedding (Matryoshka truncation)
+    pub dimensions: Option<usize>,
+    /// Whether the server scales every embedding to unit length
+    pub normalized: bool,
+    /// Template wrapping search queries, `{text}` marking the query
+    pub query_instruction: Option<String>,
+    /// Template wrapping documents, `{text}` marking the document
+    pub document_instruction: Option<String>,
+}
+
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+
+/// What the texts being embedded are, for APIs that embed them differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    Query,
+    Document,
+}
+
+/// Settings of a remote embedding API.
+pub struct ApiOptions {
+    /// Base URL; only optional for Bedrock, which finds its region's endpoint
+    pub endpoint: Option<String>,
+    pub api_key: Option<String>,
+    pub provider: ApiProvider,
+    pub retry: RetryPolicy,
+    /// Most requests started per second, retries included
+    pub requests_per_second: Option<f64>,
+    /// Most requests in flight at once, across all callers
+    pub concurrency: Option<usize>,
+    /// Sent with every request, after (and so replacing) the auth header
+    pub headers: Vec<(String, String)>,
+    /// Replaces the provider's embedding path, e.g. `/v1/embeddings`
+    pub path: Option<String>,
+    pub http: HttpOptions,
+}
+
+/// Parse `Name: value` header flags and a JSON object file of headers into
+/// one list, the flags taking precedence.
+pub fn extra_headers(flags: &[String], file: Option<&std::path::Path>) -> Result<Vec<(String, String)>> {
+    let mut headers = Vec::new();
+    if let Some(path) = file {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read header file {}", path.display()))?;
+        let from_file: BTreeMap<String, String> = serde_json::from_str(&raw)
+            .with_context(|| format!("Invalid header file {}, expected a JSON object of strings", path.display()))?;
+        headers.extend(from_file);
+    }
+    for flag in flags {
+        let (name, value) = flag
+            .split_once(':')
+            .with_context(|| format!("Invalid --embedding-header '{}', expected 'Name: value'", flag))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    Ok(headers)
+}
+
+pub const DEFAULT_EMBEDDING_RETRIES: u32 = 3;
+pub const DEFAULT_EMBEDDING_RETRY_BASE_MS: u64 = 500;
+/// Longest wait between two attempts, also when a server asks for more.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How often and how patiently rate limited (429) and failed (5xx or
+/// connection errors) embedding API requests are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each further one
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter, so concurrent batches that failed
+    /// together do not retry in lockstep.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_RETRY_DELAY);
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        delay.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+/// Token bucket spacing out API requests, with room for one second's worth
+/// of requests as a burst.
+struct RateLimiter {
+    rate: f64,
+    /// Available tokens, negative while callers are waiting, and when they
+    /// were last refilled
+    state: std::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            state: std::sync::Mutex::new((rate.max(1.0), Instant::now())),
+        }
+    }
+
+    /// Take a token, waiting until it has been refilled if the bucket is
+    /// empty. Tokens are reserved in arrival order.
+    async fn acquire(&self) {
+        let wait = self.update(|tokens| tokens - 1.0);
+        if wait > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+
+    /// Empty the bucket so no request starts within `delay`, e.g. after the
+    /// provider answered 429 and asked for a break.
+    fn pause(&self, delay: Duration) {
+        self.update(|tokens| tokens.min(-delay.as_secs_f64() * self.rate));
+    }
+
+    /// Refill, apply `f` to the token count and return the seconds until it is
+    /// no longer negative.
+    fn update(&self, f: impl FnOnce(f64) -> f64) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, refilled) = &mut *state;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*refilled).as_secs_f64() * self.rate).min(self.rate.max(1.0));
+        *refilled = now;
+        *tokens = f(*tokens);
+        -*tokens / self.rate
+    }
 }
 
 impl EmbeddingModel {
-    pub async fn new(
-        endpoint: Option<String>,
-        model_id: Option<String>,
-        api_key: Option<String>,
-    ) -> Result<Self> {
-        let model_id = model_id.unwrap_or_else(|| "Qwen/Qwen3-Embedding-0.6B".to_string());
-
-        if let Some(base_url) = endpoint {
+    /// Use the API in `api` when given, otherwise run the model locally.
+    pub async fn new(model_id: Option<String>, api: Option<ApiOptions>, local: LocalOptions) -> Result<Self> {
+        let model_id = model_id.unwrap_or_else(|| match (&api, local.backend) {
+            (Some(api), _) if api.provider == ApiProvider::Ollama => DEFAULT_OLLAMA_MODEL.to_string(),
+            (Some(api), _) if api.provider == ApiProvider::Cohere => DEFAULT_COHERE_MODEL.to_string(),
+            (Some(api), _) if api.provider == ApiProvider::Bedrock => DEFAULT_BEDROCK_MODEL.to_string(),
+            // A model directory or file is named after the model it holds
+            (None, _) if local.model_path.is_some() => {
+                let path = local.model_path.as_deref().unwrap_or(std::path::Path::new(""));
+                path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+            }
+            (None, LocalBackend::FastEmbed) => DEFAULT_FASTEMBED_MODEL.to_string(),
+            _ => "Qwen/Qwen3-Embedding-0.6B".to_string(),
+        });
+
+        if let Some(ApiOptions { endpoint, provider: ApiProvider::Bedrock, .. }) = api {
+            Self::new_bedrock(model_id, endpoint).await
+        } else if let Some(ApiOptions {
+            endpoint,
+            api_key,
+            provider,
+            retry,
+            requests_per_second,
+            concurrency,
+            headers: extra,
+            path,
+            http,
+        }) = api
+        {
+            let endpoint = endpoint.context("--embedding-endpoint is required")?;
+            if requests_per_second.is_some_and(|rate| !(rate.is_finite() && rate > 0.0)) {
+                anyhow::bail!("--embedding-rps must be a positive number");
+            }
+            if concurrency == Some(0) {
+                anyhow::bail!("--embedding-concurrency must be at least 1");
+            }
             let mut headers = reqwest::header::HeaderMap::new();
             if let Some(key) = api_key {
-                let mut auth_value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", key))?;
+                let (name, value) = match provider {
+                    ApiProvider::Azure { .. } => (reqwest::header::HeaderName::from_static("api-key"), key),
+                    _ => (reqwest::header::AUTHORIZATION, format!("Bearer {}", key)),
+                };
+                let mut auth_value = reqwest::header::HeaderValue::from_str(&value)?;
                 auth_value.set_sensitive(true);
-                headers.insert(reqwest::header::AUTHORIZATION, auth_value);
+                headers.insert(name, auth_value);
             }
+            for (name, value) in extra {
+                let header = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid embedding API header name '{}'", name))?;
+                let mut value = reqwest::header::HeaderValue::from_str(&value)
+                    .with_context(|| format!("Invalid value of embedding API header '{}'", name))?;
+                // Gateways often take their credentials in custom headers
+                value.set_sensitive(true);
+                headers.insert(header, value);
+            }
+            let endpoint = endpoint.trim_end_matches('/');
+            let base_url = match &provider {
+                ApiProvider::Azure { deployment, .. } => {
+                    format!("{}/openai/deployments/{}", endpoint, deployment.as_deref().unwrap_or(&model_id))
+                }
+                _ => endpoint.to_string(),
+            };
 
             Ok(Self {
-                engine: EmbeddingEngine::Api {
-                    client: reqwest::Client::builder()
-                        .default_headers(headers)
-                        .build()?,
+                engine: EmbeddingEngine::Api(ApiClient {
+                    http: http.client_builder()?.default_headers(headers).build()?,
                     base_url,
-                    model_id,
-                },
+                    model_id: model_id.clone(),
+                    provider,
+                    retry,
+                    path: path.map(|path| format!("/{}", path.trim_start_matches('/'))),
+                    rate_limit: requests_per_second.map(RateLimiter::new),
+                    in_flight: concurrency.map(tokio::sync::Semaphore::new),
+                }),
+                query_template: default_query_template(&model_id),
+                document_template: default_document_template(&model_id),
+                model_id,
+                query_cache: None,
+                max_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+                max_batch_tokens: DEFAULT_EMBEDDING_BATCH_TOKENS,
+                max_tokens: None,
+                truncation: Truncation::Head,
+                dimensions: None,
+                normalize: false,
+                dimension: OnceLock::new(),
             })
+        } else if local.backend == LocalBackend::LlamaCpp {
+            let model_path = local.model_path.as_ref().map_or(model_id, |p| p.display().to_string());
+            Self::new_llama_cpp(model_path, local)
+        } else if local.backend == LocalBackend::FastEmbed {
+            if local.model_path.is_some() || local.offline {
+                return Err(anyhow::anyhow!(
+                    "--model-path and --offline are not supported with --backend fastembed, which manages its own downloads"
+                ))
+                .context(crate::error::ErrorCode::InvalidArgument);
+            }
+            Self::new_fastembed(model_id)
         } else {
-            // Local mode
-            // Download model using hf_hub
-            let api = hf_hub::api::tokio::Api::new()?;
-            let repo = api.repo(hf_hub::Repo::new(
-                model_id.clone(),
-                hf_hub::RepoType::Model,
-            ));
-            
-            let model_path = repo.get("model.safetensors").await?;
-            // Ensure other files are present
-            let _ = repo.get("config.json").await?;
-            let tokenizer_path = repo.get("tokenizer.json").await?;
-            
-            let model_dir = model_path.parent().context("No parent dir")?.to_path_buf();
-
-            // Load tokenizer
-            let mut tokenizer = Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
-            // Configure tokenizer as in TEI
-            if let Some(_pre_tokenizer) = tokenizer.get_pre_tokenizer() {
-                // Simplified tokenizer setup for now, assuming standard config works
-            }
-            tokenizer.with_padding(None);
-
-            // CandleBackend::new is synchronous and takes:
-            // path: &Path
-            // dtype: String (e.g., "float32")
-            // model_type: ModelType
-            // trust_remote_code: Option<Vec<String>> (or similar)
-            let backend = text_embeddings_backend_candle::CandleBackend::new(
-                &model_dir,
-                "float32".to_string(),
-                ModelType::Embedding(Pool::Mean),
-                None,
-            )?;
+            Self::new_local(model_id, local).await
+        }
+    }
 
-            Ok(Self {
-                engine: EmbeddingEngine::Local {
-                    backend: Arc::new(Mutex::new(backend)),
-                    tokenizer: Arc::new(tokenizer),
-                },
-            })
+    #[cfg(not(feature = "bedrock"))]
+    async fn new_bedrock(_model_id: String, _endpoint: Option<String>) -> Result<Self> {
+        Err(anyhow::anyhow!("This build has no AWS Bedrock support, rebuild with the 'bedrock' cargo feature"))
+            .context(crate::error::ErrorCode::InvalidArgument)
+    }
+
+    /// Call Bedrock in the region and with the credentials of the standard
+    /// AWS provider chain (environment, profile, SSO, instance role, ...).
+    /// `endpoint` overrides the regional endpoint, e.g. for a VPC endpoint.
+    #[cfg(feature = "bedrock")]
+    async fn new_bedrock(model_id: String, endpoint: Option<String>) -> Result<Self> {
+        if !model_id.starts_with("amazon.titan-embed") && !model_id.starts_with("cohere.embed") {
+            return Err(anyhow::anyhow!(
+                "Unsupported Bedrock model '{}', use an amazon.titan-embed-* or cohere.embed-* model",
+                model_id
+            ))
+            .context(crate::error::ErrorCode::InvalidArgument);
+        }
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(endpoint) = endpoint {
+            loader = loader.endpoint_url(endpoint);
         }
+        let client = aws_sdk_bedrockruntime::Client::new(&loader.load().await);
+
+        Ok(Self {
+            engine: EmbeddingEngine::Bedrock {
+                client,
+                model_id: model_id.clone(),
+            },
+            query_template: default_query_template(&model_id),
+            document_template: default_document_template(&model_id),
+            model_id,
+            query_cache: None,
+            max_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+            max_batch_tokens: DEFAULT_EMBEDDING_BATCH_TOKENS,
+            max_tokens: None,
+            truncation: Truncation::Head,
+            dimensions: None,
+            normalize: false,
+            dimension: OnceLock::new(),
+        })
     }
 
-    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+    #[cfg(not(feature = "fastembed"))]
+    fn new_fastembed(_model_id: String) -> Result<Self> {
+        Err(anyhow::anyhow!("This build has no fastembed support, rebuild with the 'fastembed' cargo feature"))
+            .context(crate::error::ErrorCode::InvalidArgument)
+    }
+
+    /// Load one of the models fastembed supports, matched by its name with
+    /// or without the organisation, e.g. `BAAI/bge-small-en-v1.5`.
+    #[cfg(feature = "fastembed")]
+    fn new_fastembed(model_id: String) -> Result<Self> {
+        // fastembed names some models after their ONNX export, e.g. `Qdrant/all-MiniLM-L6-v2-onnx`
+        let name = |id: &str| id.rsplit('/').next().unwrap_or(id).to_lowercase().trim_end_matches("-onnx").to_string();
+        let supported = TextEmbedding::list_supported_models();
+        let Some(info) = supported.iter().find(|info| name(&info.model_code) == name(&model_id)) else {
+            let names: Vec<&str> = supported.iter().map(|info| info.model_code.as_str()).collect();
+            return Err(anyhow::anyhow!(
+                "fastembed does not support '{}', choose one of: {}",
+                model_id,
+                names.join(", ")
+            ))
+            .context(crate::error::ErrorCode::InvalidArgument);
+        };
+        let model = TextEmbedding::try_new(InitOptions::new(info.model.clone()).with_show_download_progress(false))?;
+        tracing::info!("Running '{}' with fastembed", info.model_code);
+
+        // CLIP exports are pairs, e.g. `Qdrant/clip-ViT-B-32-text` and `Qdrant/clip-ViT-B-32-vision`
+        let images = match info.model_code.strip_suffix("-text") {
+            Some(stem) => {
+                let vision = format!("{}-vision", stem);
+                match ImageEmbedding::list_supported_models().into_iter().find(|image| image.model_code == vision) {
+                    Some(image) => {
+                        let model = ImageEmbedding::try_new(ImageInitOptions::new(image.model).with_show_download_progress(false))?;
+                        tracing::info!("Embedding images with '{}'", image.model_code);
+                        Some(model)
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            engine: EmbeddingEngine::FastEmbed { model, images },
+            query_template: default_query_template(&model_id),
+            document_template: default_document_template(&model_id),
+            model_id,
+            query_cache: None,
+            max_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+            max_batch_tokens: DEFAULT_EMBEDDING_BATCH_TOKENS,
+            max_tokens: None,
+            truncation: Truncation::Head,
+            dimensions: None,
+            normalize: false,
+            dimension: OnceLock::new(),
+        })
+    }
+
+    #[cfg(not(feature = "llama-cpp"))]
+    fn new_llama_cpp(_model_path: String, _local: LocalOptions) -> Result<Self> {
+        Err(anyhow::anyhow!("This build has no llama.cpp support, rebuild with the 'llama-cpp' cargo feature"))
+            .context(crate::error::ErrorCode::InvalidArgument)
+    }
+
+    /// Load a GGUF file from disk; `model_path` is given as the model id.
+    #[cfg(feature = "llama-cpp")]
+    fn new_llama_cpp(model_path: String, local: LocalOptions) -> Result<Self> {
+        if !std::path::Path::new(&model_path).is_file() {
+            return Err(anyhow::anyhow!(
+                "'{}' is not a file, pass the path of a .gguf model with --embedding-model",
+                model_path
+            ))
+            .context(crate::error::ErrorCode::InvalidArgument);
+        }
+        let backend = LlamaBackend::init()?;
+        let model = LlamaModel::load_from_file(&backend, &model_path, &LlamaModelParams::default())?;
+        tracing::info!("Running '{}' with llama.cpp", model_path);
+
+        Ok(Self {
+            engine: EmbeddingEngine::LlamaCpp {
+                backend,
+                model,
+                pooling: local.pooling,
+            },
+            query_template: default_query_template(&model_path),
+            document_template: default_document_template(&model_path),
+            model_id: model_path,
+            query_cache: None,
+            max_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+            max_batch_tokens: DEFAULT_EMBEDDING_BATCH_TOKENS,
+            max_tokens: None,
+            truncation: Truncation::Head,
+            dimensions: None,
+            normalize: false,
+            dimension: OnceLock::new(),
+        })
+    }
+
+    #[cfg(not(feature = "local-embeddings"))]
+    async fn new_local(model_id: String, _local: LocalOptions) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "Cannot load '{}' locally: this build has no local-embeddings feature, pass --embedding-endpoint",
+            model_id
+        ))
+        .context(crate::error::ErrorCode::InvalidArgument)
+    }
+
+    #[cfg(feature = "local-embeddings")]
+    async fn new_local(model_id: String, local: LocalOptions) -> Result<Self> {
+        // Local mode
+        let model_dir = locate_model(&model_id, &local).await?;
+
+        // Load tokenizer
+        let mut tokenizer = Tokenizer::from_file(model_dir.join("tokenizer.json")).map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
+        // Configure tokenizer as in TEI
+        if let Some(_pre_tokenizer) = tokenizer.get_pre_tokenizer() {
+            // Simplified tokenizer setup for now, assuming standard config works
+        }
+        tokenizer.with_padding(None);
+
+        // CandleBackend::new is synchronous and takes:
+        // path: &Path
+        // dtype: String (e.g., "float32")
+        // model_type: ModelType
+        // trust_remote_code: Option<Vec<String>> (or similar)
+        let pooling = local
+            .pooling
+            .or_else(|| Pooling::from_sentence_transformers(&model_dir))
+            .unwrap_or_else(|| Pooling::for_model(&model_id));
+        let pool = match pooling {
+            Pooling::Mean => Pool::Mean,
+            Pooling::Cls => Pool::Cls,
+            Pooling::LastToken => Pool::LastToken,
+        };
+        let backends = (0..local.workers.max(1))
+            .map(|_| new_backend(&local, &model_dir, ModelType::Embedding(pool.clone())).map(Mutex::new))
+            .collect::<Result<Vec<_>>>()?;
+        match local.backend {
+            LocalBackend::Onnx => tracing::info!("Running '{}' with ONNX Runtime", model_id),
+            _ => tracing::info!("Running '{}' on {:?} with {}", model_id, local.device, local.dtype.as_str()),
+        }
+
+        Ok(Self {
+            engine: EmbeddingEngine::Local {
+                backends,
+                next_backend: AtomicUsize::new(0),
+                tokenizer,
+                backend: local.backend,
+                pooling,
+                device: local.device,
+                dtype: local.dtype,
+            },
+            query_template: default_query_template(&model_id),
+            document_template: default_document_template(&model_id),
+            model_id,
+            query_cache: None,
+            max_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+            max_batch_tokens: DEFAULT_EMBEDDING_BATCH_TOKENS,
+            max_tokens: None,
+            truncation: Truncation::Head,
+            dimensions: None,
+            normalize: false,
+            dimension: OnceLock::new(),
+        })
+    }
+
+    /// Keep the embeddings of the `capacity` most recently searched queries,
+    /// so repeated or paginated searches skip the embedding step. 0 disables it.
+    pub fn with_query_cache(mut self, capacity: usize) -> Self {
+        self.query_cache = (capacity > 0).then(|| QueryCache::new(capacity));
+        self
+    }
+
+    /// Limit the inputs and the (approximate) tokens embedded in one batch.
+    pub fn with_batch_limits(mut self, max_batch_size: usize, max_batch_tokens: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self.max_batch_tokens = max_batch_tokens.max(1);
+        self
+    }
+
+    /// Cut texts longer than `max_tokens` tokens before embedding them,
+    /// keeping the part chosen by `truncation`.
+    pub fn with_max_tokens(mut self, max_tokens: Option<usize>, truncation: Truncation) -> Self {
+        self.max_tokens = max_tokens.filter(|max| *max > 0);
+        self.truncation = truncation;
+        self
+    }
+
+    /// Truncate embeddings to their first `dimensions` values and renormalize
+    /// them, for models trained with Matryoshka representation learning.
+    pub fn with_dimensions(mut self, dimensions: Option<usize>) -> Self {
+        self.dimensions = dimensions.filter(|d| *d > 0);
+        self
+    }
+
+    /// Scale every embedding to unit length, so scores are comparable no
+    /// matter which backend produced the vectors.
+    pub fn with_normalization(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Override the instruction templates for queries and documents; `{text}`
+    /// marks where the text goes, otherwise the template is a prefix. An
+    /// empty template embeds the text as is.
+    pub fn with_instructions(mut self, query: Option<String>, document: Option<String>) -> Self {
+        if let Some(query) = query {
+            self.query_template = Some(query).filter(|t| !t.is_empty());
+        }
+        if let Some(document) = document {
+            self.document_template = Some(document).filter(|t| !t.is_empty());
+        }
+        self
+    }
+
+    /// Identifier of the model producing the embeddings.
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    /// Width of the embeddings, embedding a probe text if none was embedded yet.
+    pub async fn dimension(&self) -> Result<usize> {
+        if let Some(dimension) = self.dimension.get() {
+            return Ok(*dimension);
+        }
+        self.embed(vec!["dimension".to_string()]).await?;
+        self.dimension.get().copied().context("The model returned no embedding")
+    }
+
+    /// The embedding configuration, for operators and agents to inspect.
+    pub async fn info(&self) -> Result<ModelInfo> {
+        let mut info = ModelInfo {
+            engine: "local",
+            backend: "",
+            model: self.model_id.clone(),
+            dimension: self.dimension().await?,
+            pooling: None,
+            device: None,
+            dtype: None,
+            max_tokens: self.max_tokens,
+            truncation: match self.truncation {
+                Truncation::Head => "head",
+                Truncation::Tail => "tail",
+                Truncation::Middle => "middle",
+            },
+            dimensions: self.dimensions,
+            normalized: self.normalize || self.dimensions.is_some(),
+            query_instruction: self.query_template.clone(),
+            document_instruction: self.document_template.clone(),
+        };
         match &self.engine {
-            EmbeddingEngine::Api { client, base_url, model_id } => {
-                if texts.is_empty() {
-                    return Ok(Vec::new());
+            EmbeddingEngine::Api(api) => {
+                info.engine = "api";
+                info.backend = api.provider.as_str();
+            }
+            #[cfg(feature = "local-embeddings")]
+            EmbeddingEngine::Local { backend, pooling, device, dtype, .. } => {
+                info.backend = if *backend == LocalBackend::Onnx { "onnx" } else { "candle" };
+                info.pooling = Some(pooling.as_str());
+                // ONNX Runtime always runs on the CPU in full precision
+                if *backend == LocalBackend::Onnx {
+                    info.device = Some(Device::Cpu.as_str());
+                } else {
+                    info.device = Some(device.as_str());
+                    info.dtype = Some(dtype.as_str());
                 }
-                let url = format!("{}/v1/embeddings", base_url);
-                let req = EmbeddingsRequest {
-                    model: model_id.clone(),
-                    input: texts,
-                };
-                let resp: EmbeddingsResponse = client
-                    .post(url)
-                    .json(&req)
-                    .send()
-                    .await?
-                    .error_for_status()?
-                    .json()
+            }
+            #[cfg(feature = "fastembed")]
+            EmbeddingEngine::FastEmbed { .. } => info.backend = "fastembed",
+            #[cfg(feature = "bedrock")]
+            EmbeddingEngine::Bedrock { .. } => {
+                info.engine = "api";
+                info.backend = ApiProvider::Bedrock.as_str();
+            }
+            #[cfg(feature = "llama-cpp")]
+            EmbeddingEngine::LlamaCpp { pooling, .. } => {
+                info.backend = "llama-cpp";
+                info.pooling = pooling.map(Pooling::as_str);
+            }
+        }
+        Ok(info)
+    }
+
+    /// Fail unless the model also embeds images, into the space of its texts.
+    pub fn check_images(&self) -> Result<()> {
+        #[cfg(feature = "fastembed")]
+        if let EmbeddingEngine::FastEmbed { images: Some(_), .. } = &self.engine {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!(
+            "'{}' cannot embed images; run a CLIP model, e.g. --backend fastembed --embedding-model Qdrant/clip-ViT-B-32-text",
+            self.model_id
+        ))
+        .context(crate::error::ErrorCode::InvalidArgument)
+    }
+
+    /// Embed encoded images (PNG, JPEG, ...) for storage next to the texts.
+    /// Only CLIP-style models support it, see `check_images`.
+    pub async fn embed_images(&self, images: Vec<Vec<u8>>) -> Result<Vec<Vec<f32>>> {
+        self.check_images()?;
+        let mut vectors = self.embed_images_with_engine(&images)?;
+        self.finish(&mut vectors)?;
+        Ok(vectors)
+    }
+
+    #[cfg(not(feature = "fastembed"))]
+    fn embed_images_with_engine(&self, _images: &[Vec<u8>]) -> Result<Vec<Vec<f32>>> {
+        unreachable!("only fastembed models embed images")
+    }
+
+    #[cfg(feature = "fastembed")]
+    fn embed_images_with_engine(&self, images: &[Vec<u8>]) -> Result<Vec<Vec<f32>>> {
+        let EmbeddingEngine::FastEmbed { images: Some(model), .. } = &self.engine else {
+            unreachable!("check_images passed")
+        };
+        let mut vectors = Vec::with_capacity(images.len());
+        for batch in images.chunks(self.max_batch_size) {
+            let batch: Vec<&[u8]> = batch.iter().map(Vec::as_slice).collect();
+            // Decoding and the vision model are CPU bound, like text embedding
+            vectors.extend(tokio::task::block_in_place(|| model.embed_bytes(&batch, None))?);
+        }
+        Ok(vectors)
+    }
+
+    /// Embed a search query, served from the query cache when it was seen recently.
+    pub async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        if let Some(vector) = self.query_cache.as_ref().and_then(|c| c.get(&self.model_id, query)) {
+            return Ok(vector);
+        }
+        let vector = self
+            .embed_inputs(vec![apply_template(self.query_template.as_deref(), query)], InputKind::Query)
+            .await?
+            .0
+            .into_iter()
+            .next()
+            .context("The embedding model returned no vector for the query")?;
+        if let Some(cache) = &self.query_cache {
+            cache.insert(&self.model_id, query, vector.clone());
+        }
+        Ok(vector)
+    }
+
+    /// Embed `texts` in batches of at most `max_batch_size` inputs and about
+    /// `max_batch_tokens` tokens, so a large ingest never builds one huge
+    /// batch. Local batches run one after another, API batches concurrently.
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        Ok(self.embed_inputs(texts, InputKind::Document).await?.0)
+    }
+
+    /// Embed documents for storage, wrapped in the document template. Also
+    /// returns for every document whether it was cut to `--max-tokens`.
+    pub async fn embed_documents(&self, texts: Vec<String>) -> Result<(Vec<Vec<f32>>, Vec<bool>)> {
+        let texts = match self.document_template.as_deref() {
+            Some(template) => texts.iter().map(|t| apply_template(Some(template), t)).collect(),
+            None => texts,
+        };
+        self.embed_inputs(texts, InputKind::Document).await
+    }
+
+    /// Like `embed`, also returning for every text whether it was cut to
+    /// `--max-tokens` before embedding.
+    async fn embed_inputs(&self, texts: Vec<String>, kind: InputKind) -> Result<(Vec<Vec<f32>>, Vec<bool>)> {
+        if texts.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let (mut vectors, truncated) = self.embed_with_engine(texts, kind).await?;
+        self.finish(&mut vectors)?;
+        Ok((vectors, truncated))
+    }
+
+    /// Apply `--embedding-dimensions` and `--normalize` to fresh embeddings.
+    fn finish(&self, vectors: &mut [Vec<f32>]) -> Result<()> {
+        if let Some(dimensions) = self.dimensions {
+            for vector in vectors.iter_mut() {
+                shorten(vector, dimensions)?;
+            }
+        } else if self.normalize {
+            vectors.iter_mut().for_each(|v| normalize(v));
+        }
+        if let Some(vector) = vectors.first() {
+            self.dimension.get_or_init(|| vector.len());
+        }
+        Ok(())
+    }
+
+    async fn embed_with_engine(&self, texts: Vec<String>, kind: InputKind) -> Result<(Vec<Vec<f32>>, Vec<bool>)> {
+        match &self.engine {
+            EmbeddingEngine::Api(api) => {
+                let (texts, truncated): (Vec<String>, Vec<bool>) =
+                    texts.into_iter().map(|text| self.truncate_estimated(text)).unzip();
+                let batches = self.batches(texts, |t| estimate_tokens(t));
+                let results: Vec<Vec<Vec<f32>>> = futures::stream::iter(batches)
+                    .map(|batch| api.embed(batch, self.truncation, self.normalize, kind))
+                    .buffered(API_CONCURRENT_BATCHES)
+                    .try_collect()
                     .await?;
-                let vecs = resp
-                    .data
-                    .into_iter()
-                    .map(|d| d.embedding)
-                    .collect();
-                Ok(vecs)
-            }
-            EmbeddingEngine::Local { backend, tokenizer } => {
-                let backend = backend.lock().await;
-                
+                Ok((results.concat(), truncated))
+            }
+            #[cfg(feature = "local-embeddings")]
+            EmbeddingEngine::Local { backends, next_backend, tokenizer, .. } => {
                 // Encode texts
                 let encodings = tokenizer
                     .encode_batch(texts, true)
                     .map_err(|e| anyhow::anyhow!("Tokenization failed: {}", e))?;
+                let (tokens, truncated): (Vec<Tokens>, Vec<bool>) =
+                    encodings.iter().map(|e| Tokens::from_encoding(e, self.max_tokens, self.truncation)).unzip();
+
+                let mut results = Vec::with_capacity(tokens.len());
+                for batch in self.batches(tokens, |t| t.ids.len()) {
+                    // Take an idle backend, or queue on the next one in turn
+                    let backend = match backends.iter().find_map(|b| b.try_lock().ok()) {
+                        Some(backend) => backend,
+                        None => backends[next_backend.fetch_add(1, Ordering::Relaxed) % backends.len()].lock().await,
+                    };
+                    // Backend::embed is synchronous and CPU bound; keep it off the
+                    // async workers so other requests are served meanwhile
+                    results.extend(tokio::task::block_in_place(|| embed_local(&**backend, batch))?);
+                }
+                Ok((results, truncated))
+            }
+            #[cfg(feature = "bedrock")]
+            EmbeddingEngine::Bedrock { client, model_id } => {
+                let (texts, truncated): (Vec<String>, Vec<bool>) =
+                    texts.into_iter().map(|text| self.truncate_estimated(text)).unzip();
+                let batches = if model_id.starts_with("cohere.") {
+                    self.batches(texts, |t| estimate_tokens(t))
+                } else {
+                    // Titan embeds one text per call
+                    texts.into_iter().map(|t| vec![t]).collect()
+                };
+                let results: Vec<Vec<Vec<f32>>> = futures::stream::iter(batches)
+                    .map(|batch| embed_bedrock(client, model_id, self.truncation, kind, batch))
+                    .buffered(API_CONCURRENT_BATCHES)
+                    .try_collect()
+                    .await?;
+                Ok((results.concat(), truncated))
+            }
+            #[cfg(feature = "fastembed")]
+            EmbeddingEngine::FastEmbed { model, .. } => {
+                let (texts, truncated): (Vec<String>, Vec<bool>) =
+                    texts.into_iter().map(|text| self.truncate_estimated(text)).unzip();
+                let mut results = Vec::with_capacity(texts.len());
+                for batch in self.batches(texts, |t| estimate_tokens(t)) {
+                    results.extend(tokio::task::block_in_place(|| model.embed(batch, None))?);
+                }
+                Ok((results, truncated))
+            }
+            #[cfg(feature = "llama-cpp")]
+            EmbeddingEngine::LlamaCpp { backend, model, pooling } => {
+                let mut tokens = Vec::with_capacity(texts.len());
+                let mut truncated = Vec::with_capacity(texts.len());
+                for text in &texts {
+                    let ids = model.str_to_token(text, AddBos::Always)?;
+                    match self.max_tokens {
+                        Some(max) if ids.len() > max => {
+                            tokens.push(truncate(&ids, max, self.truncation));
+                            truncated.push(true);
+                        }
+                        _ => {
+                            tokens.push(ids);
+                            truncated.push(false);
+                        }
+                    }
+                }
 
-                // Create Batch
-                let mut input_ids = Vec::new();
-                let mut token_type_ids = Vec::new();
-                let mut position_ids = Vec::new();
-                let mut cumulative_seq_lengths = Vec::with_capacity(encodings.len() + 1);
-                cumulative_seq_lengths.push(0);
-            
-                let mut max_length = 0;
-                let mut cumulative_length = 0;
-            
-                for encoding in encodings.iter() {
-                    let encoding_length = encoding.len() as u32;
-                    input_ids.extend(encoding.get_ids().to_vec());
-                    token_type_ids.extend(encoding.get_type_ids().to_vec());
-                    position_ids.extend(0..encoding_length);
-                    cumulative_length += encoding_length;
-                    cumulative_seq_lengths.push(cumulative_length);
-                    max_length = max(max_length, encoding_length);
+                let mut results = Vec::with_capacity(tokens.len());
+                for batch in self.batches(tokens, Vec::len) {
+                    results.extend(tokio::task::block_in_place(|| embed_llama_cpp(backend, model, *pooling, &batch))?);
                 }
+                Ok((results, truncated))
+            }
+        }
+    }
+
+    /// Apply `--max-tokens` to the estimated tokens of a text, for engines
+    /// whose tokenizer is out of reach. Also returns whether it was cut.
+    fn truncate_estimated(&self, text: String) -> (String, bool) {
+        match self.max_tokens {
+            Some(max) if estimate_tokens(&text) > max => {
+                let chars: Vec<char> = text.chars().collect();
+                let kept = truncate(&chars, max.saturating_sub(1) * CHARS_PER_TOKEN, self.truncation);
+                (kept.into_iter().collect(), true)
+            }
+            _ => (text, false),
+        }
+    }
 
-                // We want pooled embeddings for all inputs
-                let pooled_indices: Vec<u32> = (0..encodings.len() as u32).collect();
-                let raw_indices = Vec::new();
-            
-                let batch = Batch {
-                    input_ids,
-                    token_type_ids,
-                    position_ids,
-                    cumulative_seq_lengths,
-                    max_length,
-                    pooled_indices,
-                    raw_indices,
+    /// Split `items` into batches within the configured size and token limits.
+    /// An item over the token limit on its own still forms a batch.
+    fn batches<T>(&self, items: Vec<T>, tokens: impl Fn(&T) -> usize) -> Vec<Vec<T>> {
+        let mut batches = Vec::new();
+        let mut current = Vec::new();
+        let mut current_tokens = 0;
+        for item in items {
+            let item_tokens = tokens(&item);
+            if !current.is_empty()
+                && (current.len() >= self.max_batch_size || current_tokens + item_tokens > self.max_batch_tokens)
+            {
+                batches.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+            current_tokens += item_tokens;
+            current.push(item);
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+        batches
+    }
+}
+
+/// A sparse vector: weights of the vocabulary terms a text activates.
+#[derive(Debug, Clone, Default)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    /// Dot product with a query given as term weights.
+    pub fn dot(&self, query: &HashMap<u32, f32>) -> f32 {
+        self.indices
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(index, value)| query.get(index).map(|weight| weight * value))
+            .sum()
+    }
+}
+
+/// A SPLADE-style model expanding texts into sparse vectors over its
+/// vocabulary, stored next to the dense vectors and matched by hybrid search.
+/// Unlike BM25 it weighs related terms the text does not contain too.
+pub struct SparseEncoder {
+    #[cfg(feature = "fastembed")]
+    model: SparseTextEmbedding,
+    model_id: String,
+}
+
+impl SparseEncoder {
+    #[cfg(not(feature = "fastembed"))]
+    pub fn new(model_id: String) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "Cannot load sparse model '{}': this build has no fastembed support, rebuild with the 'fastembed' cargo feature",
+            model_id
+        ))
+        .context(crate::error::ErrorCode::InvalidArgument)
+    }
+
+    /// Load one of the sparse models fastembed supports, matched by its name
+    /// with or without the organisation, e.g. `prithivida/Splade_PP_en_v1`.
+    #[cfg(feature = "fastembed")]
+    pub fn new(model_id: String) -> Result<Self> {
+        let name = |id: &str| id.rsplit('/').next().unwrap_or(id).to_lowercase();
+        let supported = SparseTextEmbedding::list_supported_models();
+        let Some(info) = supported.iter().find(|info| name(&info.model_code) == name(&model_id)) else {
+            let names: Vec<&str> = supported.iter().map(|info| info.model_code.as_str()).collect();
+            return Err(anyhow::anyhow!(
+                "fastembed has no sparse model '{}', choose one of: {}",
+                model_id,
+                names.join(", ")
+            ))
+            .context(crate::error::ErrorCode::InvalidArgument);
+        };
+        let model = SparseTextEmbedding::try_new(SparseInitOptions::new(info.model.clone()).with_show_download_progress(false))?;
+        tracing::info!("Running sparse model '{}' with fastembed", info.model_code);
+        Ok(Self { model, model_id: info.model_code.clone() })
+    }
+
+    pub fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    /// Encode texts, documents and queries alike.
+    #[cfg(not(feature = "fastembed"))]
+    pub async fn embed(&self, _texts: Vec<String>) -> Result<Vec<SparseVector>> {
+        unreachable!("a sparse encoder cannot be built without fastembed")
+    }
+
+    /// Encode texts, documents and queries alike.
+    #[cfg(feature = "fastembed")]
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<SparseVector>> {
+        let embeddings = tokio::task::block_in_place(|| self.model.embed(texts, None))?;
+        Ok(embeddings
+            .into_iter()
+            .map(|embedding| SparseVector {
+                indices: embedding.indices.into_iter().map(|i| i as u32).collect(),
+                values: embedding.values,
+            })
+            .collect())
+    }
+}
+
+/// An HTTP embedding API and how to talk to it.
+pub struct ApiClient {
+    http: reqwest::Client,
+    base_url: String,
+    model_id: String,
+    provider: ApiProvider,
+    retry: RetryPolicy,
+    path: Option<String>,
+    rate_limit: Option<RateLimiter>,
+    in_flight: Option<tokio::sync::Semaphore>,
+}
+
+impl ApiClient {
+    async fn embed(&self, texts: Vec<String>, truncation: Truncation, normalize: bool, kind: InputKind) -> Result<Vec<Vec<f32>>> {
+        match &self.provider {
+            ApiProvider::OpenAi => {
+                let req = EmbeddingsRequest {
+                    model: self.model_id.clone(),
+                    input: texts,
+                };
+                let resp: EmbeddingsResponse =
+                    self.post_json(self.http.post(self.url("/v1/embeddings")).json(&req)).await?;
+                Ok(resp.data.into_iter().map(|d| d.embedding).collect())
+            }
+            ApiProvider::Ollama => self.embed_ollama(texts).await,
+            ApiProvider::Cohere => {
+                let req = CohereEmbedRequest {
+                    model: &self.model_id,
+                    texts,
+                    input_type: match kind {
+                        InputKind::Query => "search_query",
+                        InputKind::Document => "search_document",
+                    },
+                    embedding_types: ["float"],
+                    truncate: if truncation == Truncation::Tail { "START" } else { "END" },
+                };
+                let resp: CohereEmbedResponse = self.post_json(self.http.post(self.url("/v2/embed")).json(&req)).await?;
+                Ok(resp.embeddings.float)
+            }
+            ApiProvider::Bedrock => unreachable!("Bedrock models use their own engine"),
+            ApiProvider::Azure { api_version, .. } => {
+                // The deployment selects the model, so none is sent
+                let request = self
+                    .http
+                    .post(self.url("/embeddings"))
+                    .query(&[("api-version", api_version)])
+                    .json(&serde_json::json!({ "input": texts }));
+                let resp: EmbeddingsResponse = self.post_json(request).await?;
+                Ok(resp.data.into_iter().map(|d| d.embedding).collect())
+            }
+            ApiProvider::Tei => {
+                // Let TEI cut inputs longer than the model accepts instead of
+                // rejecting them; it can only keep the head or the tail
+                let req = TeiEmbedRequest {
+                    inputs: texts,
+                    truncate: true,
+                    truncation_direction: if truncation == Truncation::Tail { "Left" } else { "Right" },
+                    // --normalize-embeddings, so TEI vectors match the other backends'
+                    normalize,
                 };
+                self.post_json(self.http.post(self.url("/embed")).json(&req)).await
+            }
+        }
+    }
 
-                // Backend::embed is synchronous and returns Result<Embeddings>
-                let embeddings_map = backend.embed(batch)?;
-                
-                // Convert map to ordered vector
-                let mut results = vec![Vec::new(); encodings.len()];
-                for (idx, embedding) in embeddings_map {
-                    if idx < results.len() {
-                        match embedding {
-                            Embedding::Pooled(vec) => results[idx] = vec,
-                            Embedding::All(_) => {
-                                // We expect pooled embeddings
-                            }
-                        }
+    async fn embed_ollama(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let req = EmbeddingsRequest {
+            model: self.model_id.clone(),
+            input: texts,
+        };
+        let resp = self.send(self.http.post(self.url("/api/embed")).json(&req)).await?;
+        if resp.status() != reqwest::StatusCode::NOT_FOUND {
+            let resp: OllamaEmbedResponse = resp.error_for_status()?.json().await?;
+            return Ok(resp.embeddings);
+        }
+
+        // Ollama before 0.3 only has the single-text endpoint
+        let mut vectors = Vec::with_capacity(req.input.len());
+        for prompt in req.input {
+            let request = self
+                .http
+                .post(format!("{}/api/embeddings", self.base_url))
+                .json(&serde_json::json!({ "model": self.model_id, "prompt": prompt }));
+            let resp: OllamaEmbeddingResponse = self.post_json(request).await?;
+            vectors.push(resp.embedding);
+        }
+        Ok(vectors)
+    }
+
+    /// URL of the embedding endpoint, whose path is `default_path` unless
+    /// overridden.
+    fn url(&self, default_path: &str) -> String {
+        format!("{}{}", self.base_url, self.path.as_deref().unwrap_or(default_path))
+    }
+
+    async fn post_json<T: serde::de::DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T> {
+        Ok(self.send(request).await?.error_for_status()?.json().await?)
+    }
+
+    /// Send the request, retrying rate limits, server errors and failed
+    /// connections per the retry policy. A `Retry-After` given in seconds
+    /// replaces the backoff. The last response is returned as is.
+    ///
+    /// Every attempt counts against the rate and concurrency limits, but a
+    /// request waiting for its retry holds no concurrency slot.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let permit = match &self.in_flight {
+                Some(in_flight) => Some(in_flight.acquire().await?),
+                None => None,
+            };
+            if let Some(rate_limit) = &self.rate_limit {
+                rate_limit.acquire().await;
+            }
+            let outcome = request.try_clone().context("The embedding request cannot be retried")?.send().await;
+            drop(permit);
+            let delay = match &outcome {
+                Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    let delay = retry_after(resp).unwrap_or_else(|| self.retry.backoff(attempt));
+                    // Hold back the other requests too instead of letting
+                    // them run into the same limit
+                    if let Some(rate_limit) = &self.rate_limit {
+                        rate_limit.pause(delay.min(MAX_RETRY_DELAY));
                     }
+                    delay
+                }
+                Ok(resp) if resp.status().is_server_error() => retry_after(resp).unwrap_or_else(|| self.retry.backoff(attempt)),
+                Err(e) if e.is_connect() || e.is_timeout() => self.retry.backoff(attempt),
+                _ => return Ok(outcome?),
+            };
+            if attempt >= self.retry.max_retries {
+                return Ok(outcome?);
+            }
+            let reason = match &outcome {
+                Ok(resp) => resp.status().to_string(),
+                Err(e) => e.to_string(),
+            };
+            let delay = delay.min(MAX_RETRY_DELAY);
+            attempt += 1;
+            tracing::warn!(
+                "Embedding request failed ({}), retry {}/{} in {:.1}s",
+                reason,
+                attempt,
+                self.retry.max_retries,
+                delay.as_secs_f64()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let value = resp.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[cfg(feature = "bedrock")]
+async fn embed_bedrock(
+    client: &aws_sdk_bedrockruntime::Client,
+    model_id: &str,
+    truncation: Truncation,
+    kind: InputKind,
+    texts: Vec<String>,
+) -> Result<Vec<Vec<f32>>> {
+    let cohere = model_id.starts_with("cohere.");
+    let body = if cohere {
+        serde_json::json!({
+            "texts": texts,
+            "input_type": match kind {
+                InputKind::Query => "search_query",
+                InputKind::Document => "search_document",
+            },
+            "truncate": if truncation == Truncation::Tail { "START" } else { "END" },
+        })
+    } else {
+        serde_json::json!({ "inputText": texts.into_iter().next().unwrap_or_default() })
+    };
+    let resp = client
+        .invoke_model()
+        .model_id(model_id)
+        .content_type("application/json")
+        .accept("application/json")
+        .body(aws_sdk_bedrockruntime::primitives::Blob::new(serde_json::to_vec(&body)?))
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Bedrock InvokeModel failed: {}", aws_sdk_bedrockruntime::error::DisplayErrorContext(e)))?;
+    let body = resp.body.into_inner();
+    if cohere {
+        let resp: BedrockCohereResponse = serde_json::from_slice(&body)?;
+        Ok(resp.embeddings)
+    } else {
+        let resp: EmbeddingData = serde_json::from_slice(&body)?;
+        Ok(vec![resp.embedding])
+    }
+}
+
+/// Index of the shards of weights too large for one safetensors file.
+#[cfg(feature = "local-embeddings")]
+const SAFETENSORS_INDEX: &str = "model.safetensors.index.json";
+
+/// Where the files of a local model come from.
+#[cfg(feature = "local-embeddings")]
+enum ModelFiles {
+    Dir(PathBuf),
+    /// Only the Hugging Face cache, at the given path
+    Cache(hf_hub::CacheRepo, PathBuf),
+    /// The Hugging Face cache, downloading the files the repo lists
+    Hub {
+        cached: hf_hub::CacheRepo,
+        repo: hf_hub::api::tokio::ApiRepo,
+        listed: std::collections::HashSet<String>,
+    },
+}
+
+#[cfg(feature = "local-embeddings")]
+impl ModelFiles {
+    /// Path of `file`, downloaded if needed, or `None` if the model lacks it.
+    async fn get(&self, file: &str) -> Result<Option<PathBuf>> {
+        match self {
+            ModelFiles::Dir(dir) => Ok(Some(dir.join(file)).filter(|path| path.is_file())),
+            ModelFiles::Cache(cached, _) => Ok(cached.get(file)),
+            ModelFiles::Hub { cached, repo, listed } => {
+                if let Some(path) = cached.get(file) {
+                    return Ok(Some(path));
+                }
+                if !listed.contains(file) {
+                    return Ok(None);
+                }
+                let path = repo
+                    .download_with_progress(file, DownloadProgress::default())
+                    .await
+                    .with_context(|| format!("Failed to download {}", file))?;
+                Ok(Some(path))
+            }
+        }
+    }
+
+    fn missing_error(&self, model_id: &str, missing: &[&str]) -> anyhow::Error {
+        let message = match self {
+            ModelFiles::Dir(dir) => format!("Model directory {} is missing {}", dir.display(), missing.join(", ")),
+            ModelFiles::Cache(_, path) => format!(
+                "Running offline, but the Hugging Face cache {} lacks {} of '{}'. Download the model on a connected machine or pass --model-path",
+                path.display(),
+                missing.join(", "),
+                model_id
+            ),
+            ModelFiles::Hub { .. } => format!("'{}' on the Hugging Face Hub has no {}", model_id, missing.join(", ")),
+        };
+        anyhow::anyhow!(message).context(crate::error::ErrorCode::InvalidArgument)
+    }
+}
+
+/// Directory with the files `backend` needs: `model_path` when given, else
+/// the Hugging Face cache, downloading what is missing unless offline.
+/// Weights may be one safetensors file or shards listed in an index.
+#[cfg(feature = "local-embeddings")]
+async fn locate_model(model_id: &str, local: &LocalOptions) -> Result<PathBuf> {
+    let files = if let Some(dir) = &local.model_path {
+        ModelFiles::Dir(dir.clone())
+    } else {
+        let repo = hf_hub::Repo::new(model_id.to_string(), hf_hub::RepoType::Model);
+        let cache = local.hf_cache_dir.clone().map_or_else(hf_hub::Cache::from_env, hf_hub::Cache::new);
+        let cached = cache.repo(repo.clone());
+        if local.offline {
+            ModelFiles::Cache(cached, cache.path().clone())
+        } else {
+            // hf_hub's progress bar is invisible in a log file, so log instead.
+            // Interrupted downloads are kept and resumed on the next start.
+            let mut api = hf_hub::api::tokio::ApiBuilder::from_cache(cache).with_progress(false);
+            if let Some(endpoint) = &local.hf_endpoint {
+                api = api.with_endpoint(endpoint.clone());
+            }
+            let repo = api.build()?.repo(repo);
+            let info = repo.info().await.with_context(|| format!("Failed to look up '{}' on the Hugging Face Hub", model_id))?;
+            let listed = info.siblings.into_iter().map(|sibling| sibling.rfilename).collect();
+            ModelFiles::Hub { cached, repo, listed }
+        }
+    };
+
+    let mut required = vec!["config.json".to_string(), "tokenizer.json".to_string()];
+    if local.backend == LocalBackend::Onnx {
+        required.push("onnx/model.onnx".to_string());
+    } else if files.get("model.safetensors").await?.is_some() {
+        required.push("model.safetensors".to_string());
+    } else if let Some(index) = files.get(SAFETENSORS_INDEX).await? {
+        required.extend(safetensors_shards(&index)?);
+    } else {
+        required.push("model.safetensors".to_string());
+    }
+    let mut missing = Vec::new();
+    for file in &required {
+        if files.get(file).await?.is_none() {
+            missing.push(file.as_str());
+        }
+    }
+    if !missing.is_empty() {
+        return Err(files.missing_error(model_id, &missing));
+    }
+    // sentence-transformers keep the pooling in an extra module, used when present
+    files.get("1_Pooling/config.json").await?;
+
+    // All files of a revision live in one snapshot directory
+    let config = files.get("config.json").await?.context("No config.json")?;
+    Ok(config.parent().context("No parent dir")?.to_path_buf())
+}
+
+/// Shard files named in a safetensors index.
+#[cfg(feature = "local-embeddings")]
+fn safetensors_shards(index: &std::path::Path) -> Result<Vec<String>> {
+    #[derive(Deserialize)]
+    struct Index {
+        weight_map: HashMap<String, String>,
+    }
+    let raw = std::fs::read_to_string(index).with_context(|| format!("Failed to read {}", index.display()))?;
+    let index: Index = serde_json::from_str(&raw).with_context(|| format!("Invalid {}", index.display()))?;
+    let shards: std::collections::BTreeSet<String> = index.weight_map.into_values().collect();
+    Ok(shards.into_iter().collect())
+}
+
+/// Logs a model download at every tenth of its size. Clones share the
+/// state, as hf_hub reports the chunks it downloads in parallel from clones.
+#[cfg(feature = "local-embeddings")]
+#[derive(Clone, Default)]
+struct DownloadProgress(std::sync::Arc<DownloadState>);
+
+#[cfg(feature = "local-embeddings")]
+#[derive(Default)]
+struct DownloadState {
+    file: std::sync::Mutex<String>,
+    total: AtomicUsize,
+    done: AtomicUsize,
+    /// Tenths of the file already logged
+    logged: AtomicUsize,
+}
+
+#[cfg(feature = "local-embeddings")]
+impl hf_hub::api::tokio::Progress for DownloadProgress {
+    async fn init(&mut self, size: usize, filename: &str) {
+        *self.0.file.lock().unwrap() = filename.to_string();
+        self.0.total.store(size, Ordering::Relaxed);
+        tracing::info!("Downloading {} ({:.1} MB)", filename, size as f64 / 1e6);
+    }
+
+    async fn update(&mut self, size: usize) {
+        let done = self.0.done.fetch_add(size, Ordering::Relaxed) + size;
+        let tenths = done * 10 / self.0.total.load(Ordering::Relaxed).max(1);
+        if tenths < 10 && self.0.logged.fetch_max(tenths, Ordering::Relaxed) < tenths {
+            tracing::info!("Downloading {}: {}%", self.0.file.lock().unwrap(), tenths * 10);
+        }
+    }
+
+    async fn finish(&mut self) {
+        tracing::info!("Downloaded {}", self.0.file.lock().unwrap());
+    }
+}
+
+/// Token ids of one input, after truncation.
+#[cfg(feature = "local-embeddings")]
+struct Tokens {
+    ids: Vec<u32>,
+    type_ids: Vec<u32>,
+}
+
+#[cfg(feature = "local-embeddings")]
+impl Tokens {
+    /// Cut the encoding to `max_tokens`, keeping the special tokens the
+    /// tokenizer added at either end. Also returns whether it was cut.
+    fn from_encoding(encoding: &tokenizers::Encoding, max_tokens: Option<usize>, truncation: Truncation) -> (Self, bool) {
+        let ids = encoding.get_ids();
+        let type_ids = encoding.get_type_ids();
+        let Some(max) = max_tokens.filter(|max| ids.len() > *max) else {
+            return (Self { ids: ids.to_vec(), type_ids: type_ids.to_vec() }, false);
+        };
+        let special = encoding.get_special_tokens_mask();
+        let lead = special.iter().take_while(|s| **s == 1).count();
+        let trail = special[lead..].iter().rev().take_while(|s| **s == 1).count();
+        let body = lead..ids.len() - trail;
+        let budget = max.saturating_sub(lead + trail);
+
+        let keep = |values: &[u32]| {
+            let mut kept = values[..lead].to_vec();
+            kept.extend(truncate(&values[body.clone()], budget, truncation));
+            kept.extend_from_slice(&values[values.len() - trail..]);
+            kept
+        };
+        (Self { ids: keep(ids), type_ids: keep(type_ids) }, true)
+    }
+}
+
+#[cfg(feature = "local-embeddings")]
+fn new_backend(local: &LocalOptions, model_dir: &std::path::Path, model_type: ModelType) -> Result<Box<dyn Backend + Send>> {
+    match local.backend {
+        LocalBackend::Candle => {
+            let backend = text_embeddings_backend_candle::CandleBackend::new(
+                model_dir,
+                local.dtype.as_str().to_string(),
+                model_type,
+                None,
+            )?;
+            Ok(Box::new(backend))
+        }
+        #[cfg(feature = "onnx")]
+        LocalBackend::Onnx => {
+            // ONNX Runtime runs on the CPU in float32
+            let backend = text_embeddings_backend_ort::OrtBackend::new(model_dir, "float32".to_string(), model_type)?;
+            Ok(Box::new(backend))
+        }
+        #[cfg(not(feature = "onnx"))]
+        LocalBackend::Onnx => Err(anyhow::anyhow!(
+            "This build has no ONNX Runtime support, rebuild with the 'onnx' cargo feature"
+        ))
+        .context(crate::error::ErrorCode::InvalidArgument),
+        LocalBackend::LlamaCpp | LocalBackend::FastEmbed => {
+            unreachable!("llama.cpp and fastembed models are loaded by their own constructors")
+        }
+    }
+}
+
+/// Decode one batch in a context sized for it, one sequence per text.
+#[cfg(feature = "llama-cpp")]
+fn embed_llama_cpp(
+    backend: &LlamaBackend,
+    model: &LlamaModel,
+    pooling: Option<Pooling>,
+    batch: &[Vec<LlamaToken>],
+) -> Result<Vec<Vec<f32>>> {
+    let total: usize = batch.iter().map(Vec::len).sum::<usize>().max(1);
+    let mut params = LlamaContextParams::default()
+        .with_n_ctx(std::num::NonZeroU32::new(total as u32))
+        .with_n_batch(total as u32)
+        .with_n_ubatch(total as u32)
+        .with_n_seq_max(batch.len() as u32)
+        .with_embeddings(true);
+    if let Some(pooling) = pooling {
+        params = params.with_pooling_type(match pooling {
+            Pooling::Mean => LlamaPoolingType::Mean,
+            Pooling::Cls => LlamaPoolingType::Cls,
+            Pooling::LastToken => LlamaPoolingType::Last,
+        });
+    }
+    let mut ctx = model.new_context(backend, params)?;
+
+    let mut input = LlamaBatch::new(total, batch.len() as i32);
+    for (seq, tokens) in batch.iter().enumerate() {
+        input.add_sequence(tokens, seq as i32, false)?;
+    }
+    ctx.decode(&mut input)?;
+    (0..batch.len())
+        .map(|seq| Ok(ctx.embeddings_seq_ith(seq as i32)?.to_vec()))
+        .collect()
+}
+
+#[cfg(feature = "local-embeddings")]
+fn embed_local(backend: &(dyn Backend + Send), encodings: Vec<Tokens>) -> Result<Vec<Vec<f32>>> {
+    // Create Batch
+    let mut input_ids = Vec::new();
+    let mut token_type_ids = Vec::new();
+    let mut position_ids = Vec::new();
+    let mut cumulative_seq_lengths = Vec::with_capacity(encodings.len() + 1);
+    cumulative_seq_lengths.push(0);
+
+    let mut max_length = 0;
+    let mut cumulative_length = 0;
+
+    for encoding in encodings.iter() {
+        let encoding_length = encoding.ids.len() as u32;
+        input_ids.extend_from_slice(&encoding.ids);
+        token_type_ids.extend_from_slice(&encoding.type_ids);
+        position_ids.extend(0..encoding_length);
+        cumulative_length += encoding_length;
+        cumulative_seq_lengths.push(cumulative_length);
+        max_length = max(max_length, encoding_length);
+    }
+
+    // We want pooled embeddings for all inputs
+    let pooled_indices: Vec<u32> = (0..encodings.len() as u32).collect();
+    let raw_indices = Vec::new();
+
+    let batch = Batch {
+        input_ids,
+        token_type_ids,
+        position_ids,
+        cumulative_seq_lengths,
+        max_length,
+        pooled_indices,
+        raw_indices,
+    };
+
+    // Backend::embed is synchronous and returns Result<Embeddings>
+    let embeddings_map = backend.embed(batch)?;
+
+    // Convert map to ordered vector
+    let mut results = vec![Vec::new(); encodings.len()];
+    for (idx, embedding) in embeddings_map {
+        if idx < results.len() {
+            match embedding {
+                Embedding::Pooled(vec) => results[idx] = vec,
+                Embedding::All(_) => {
+                    // We expect pooled embeddings
                 }
-                
-                Ok(results)
             }
         }
     }
+    Ok(results)
+}
+
+/// Query instruction recommended for the model family, if it uses one.
+fn default_query_template(model_id: &str) -> Option<String> {
+    let model = model_id.to_lowercase();
+    if model.contains("qwen3-embedding") {
+        Some("Instruct: Given a web search query, retrieve relevant passages that answer the query\nQuery: {text}".to_string())
+    } else if model.contains("e5-") {
+        Some("query: {text}".to_string())
+    } else if model.contains("bge-") && model.contains("-en") {
+        Some("Represent this sentence for searching relevant passages: {text}".to_string())
+    } else {
+        None
+    }
+}
+
+/// Document instruction recommended for the model family, if it uses one.
+fn default_document_template(model_id: &str) -> Option<String> {
+    model_id.to_lowercase().contains("e5-").then(|| "passage: {text}".to_string())
+}
+
+fn apply_template(template: Option<&str>, text: &str) -> String {
+    match template {
+        Some(template) if template.contains("{text}") => template.replace("{text}", text),
+        Some(template) => format!("{}{}", template, text),
+        None => text.to_string(),
+    }
+}
+
+/// Keep the first `dimensions` values of a Matryoshka embedding and scale
+/// the result back to unit length.
+fn shorten(vector: &mut Vec<f32>, dimensions: usize) -> Result<()> {
+    if vector.len() < dimensions {
+        anyhow::bail!(
+            "--embedding-dimensions {} exceeds the {} dimensions the model produces",
+            dimensions,
+            vector.len()
+        );
+    }
+    vector.truncate(dimensions);
+    normalize(vector);
+    Ok(())
+}
+
+/// Scale to unit L2 length; the zero vector is left as is.
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// Rough token count for API inputs, where no tokenizer is at hand.
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count() / CHARS_PER_TOKEN + 1
+}
+
+/// Keep at most `max` items, choosing which per `truncation`.
+fn truncate<T: Clone>(items: &[T], max: usize, truncation: Truncation) -> Vec<T> {
+    if items.len() <= max {
+        return items.to_vec();
+    }
+    match truncation {
+        Truncation::Head => items[..max].to_vec(),
+        Truncation::Tail => items[items.len() - max..].to_vec(),
+        Truncation::Middle => {
+            let head = max.div_ceil(2);
+            let mut kept = items[..head].to_vec();
+            kept.extend_from_slice(&items[items.len() - (max - head)..]);
+            kept
+        }
+    }
+}
+
+/// Least recently used query embeddings, keyed by model and query text.
+struct QueryCache {
+    capacity: usize,
+    state: std::sync::Mutex<QueryCacheState>,
+}
+
+#[derive(Default)]
+struct QueryCacheState {
+    entries: HashMap<(String, String), (u64, Vec<f32>)>,
+    /// Last use of each entry, oldest first
+    recency: BTreeMap<u64, (String, String)>,
+    clock: u64,
+}
+
+impl QueryCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: std::sync::Mutex::new(QueryCacheState::default()),
+        }
+    }
+
+    fn get(&self, model_id: &str, query: &str) -> Option<Vec<f32>> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        state.clock += 1;
+        let key = (model_id.to_string(), query.to_string());
+        let (used, vector) = state.entries.get_mut(&key)?;
+        state.recency.remove(used);
+        *used = state.clock;
+        state.recency.insert(state.clock, key);
+        Some(vector.clone())
+    }
+
+    fn insert(&self, model_id: &str, query: &str, vector: Vec<f32>) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        state.clock += 1;
+        let key = (model_id.to_string(), query.to_string());
+        if let Some((used, _)) = state.entries.insert(key.clone(), (state.clock, vector)) {
+            state.recency.remove(&used);
+        }
+        state.recency.insert(state.clock, key);
+        while state.entries.len() > self.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -198,4 +1873,47 @@ struct EmbeddingsResponse {
 #[derive(Deserialize)]
 struct EmbeddingData {
     embedding: Vec<f32>,
+}
+
+#[derive(Serialize)]
+struct CohereEmbedRequest<'a> {
+    model: &'a str,
+    texts: Vec<String>,
+    input_type: &'static str,
+    embedding_types: [&'static str; 1],
+    truncate: &'static str,
+}
+
+#[derive(Deserialize)]
+struct CohereEmbedResponse {
+    embeddings: CohereEmbeddings,
+}
+
+#[derive(Deserialize)]
+struct CohereEmbeddings {
+    float: Vec<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct TeiEmbedRequest {
+    inputs: Vec<String>,
+    truncate: bool,
+    truncation_direction: &'static str,
+    normalize: bool,
+}
+
+#[cfg(feature = "bedrock")]
+#[derive(Deserialize)]
+struct BedrockCohereResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use std::fmt;
+
+/// Machine-readable error categories, returned as `error` (name) and `code`
+/// (number) in failed responses so clients can decide whether to retry, fix
+/// their arguments or give up.
+///
+/// Lower layers tag an `anyhow::Error` with a category by attaching it as
+/// context, e.g. `.context(ErrorCode::EmbeddingFailed)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Anything not covered by a more specific code
+    Internal,
+    /// A parameter is missing, malformed or not allowed
+    InvalidArgument,
+    /// The named table does not exist
+    TableNotFound,
+    /// Another referenced resource (job, file) does not exist
+    NotFound,
+    /// Vectors do not match the dimension stored in the table, usually
+    /// because the embedding model changed
+    DimensionMismatch,
+    /// The embedding model or service failed
+    EmbeddingFailed,
+    /// A URL could not be fetched
+    FetchFailed,
+    /// No text could be extracted from a document
+    ExtractionFailed,
+    /// The client cancelled the request
+    Cancelled,
+    /// The table was embedded with a different model than the server uses
+    EmbeddingModelMismatch,
+    /// Too many calls in flight or per minute, for the session or the server
+    RateLimited,
+    /// The call ran longer than its tool's timeout
+    Timeout,
+    /// The server is draining for shutdown and takes no new calls
+    ShuttingDown,
+    /// The caller may not use the table or the session
+    PermissionDenied,
+}
+
+impl ErrorCode {
+    /// Numeric code reported in `ApiResponse::code`; 0 is success.
+    pub fn code(self) -> i32 {
+        match self {
+            ErrorCode::Internal => 1000,
+            ErrorCode::InvalidArgument => 1001,
+            ErrorCode::TableNotFound => 1002,
+            ErrorCode::NotFound => 1003,
+            ErrorCode::DimensionMismatch => 1004,
+            ErrorCode::EmbeddingFailed => 1005,
+            ErrorCode::FetchFailed => 1006,
+            ErrorCode::ExtractionFailed => 1007,
+            ErrorCode::Cancelled => 1008,
+            ErrorCode::EmbeddingModelMismatch => 1009,
+            ErrorCode::RateLimited => 1010,
+            ErrorCode::Timeout => 1011,
+            ErrorCode::ShuttingDown => 1012,
+            ErrorCode::PermissionDenied => 1013,
+        }
+    }
+
+    /// Whether the same call may succeed when retried unchanged.
+    pub fn retryable(self) -> bool {
+        matches!(self, ErrorCode::EmbeddingFailed | ErrorCode::FetchFailed | ErrorCode::RateLimited | ErrorCode::Timeout | ErrorCode::ShuttingDown)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ErrorCode::Internal => "Internal error",
+            ErrorCode::InvalidArgument => "Invalid argument",
+            ErrorCode::TableNotFound => "Table not found",
+            ErrorCode::NotFound => "Not found",
+            ErrorCode::DimensionMismatch => "Vector dimension mismatch",
+            ErrorCode::EmbeddingFailed => "Embedding failed",
+            ErrorCode::FetchFailed => "Fetch failed",
+            ErrorCode::ExtractionFailed => "Text extraction failed",
+            ErrorCode::Cancelled => "Request cancelled",
+            ErrorCode::EmbeddingModelMismatch => "Embedding model mismatch",
+            ErrorCode::RateLimited => "Rate limited",
+            ErrorCode::Timeout => "Timed out",
+            ErrorCode::ShuttingDown => "Shutting down",
+            ErrorCode::PermissionDenied => "Permission denied",
+        };
+        f.write_str(text)
+    }
+}
+
+/// A failed tool call: its category and a human-readable message.
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidArgument, message)
+    }
+
+    /// Categorise an error from a lower layer, using `fallback` when nothing
+    /// in its chain identifies a more specific category.
+    pub fn classify(err: anyhow::Error, fallback: ErrorCode) -> Self {
+        let code = err
+            .downcast_ref::<ErrorCode>()
+            .copied()
+            .or_else(|| err.chain().find_map(code_of_cause))
+            .unwrap_or(fallback);
+        Self::new(code, format!("{:#}", err))
+    }
+}
+
+fn code_of_cause(cause: &(dyn std::error::Error + 'static)) -> Option<ErrorCode> {
+    if let Some(err) = cause.downcast_ref::<lancedb::Error>() {
+        return match err {
+            lancedb::Error::TableNotFound { .. } => Some(ErrorCode::TableNotFound),
+            lancedb::Error::InvalidInput { .. } => Some(ErrorCode::InvalidArgument),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            return Some(ErrorCode::NotFound);
+        }
+    }
+    None
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::classify(err, ErrorCode::Internal)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
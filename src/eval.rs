@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::db::SearchResult;
+use crate::error::ErrorCode;
+
+/// Most cases one evaluation runs, each being a search.
+pub const MAX_EVAL_CASES: usize = 1000;
+
+/// One line of an evaluation set: a query and the documents that answer it,
+/// by name or chunk id.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalCase {
+    pub query: String,
+    pub expected: Vec<String>,
+}
+
+/// How one query did.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct CaseScore {
+    pub query: String,
+    pub recall: f64,
+    pub reciprocal_rank: f64,
+    pub ndcg: f64,
+    /// 1-based rank of the first expected result, absent when none was found
+    pub first_hit: Option<usize>,
+    /// Expected names and ids that were not in the top k
+    pub missed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct EvalReport {
+    pub cases: usize,
+    pub k: usize,
+    /// Mean share of each query's expected documents found in the top k
+    pub recall_at_k: f64,
+    /// Mean reciprocal rank of the first expected result, 0 when none is in the top k
+    pub mrr: f64,
+    /// Mean normalized discounted cumulative gain over the top k
+    pub ndcg_at_k: f64,
+    pub per_query: Vec<CaseScore>,
+}
+
+/// Parse an evaluation set, one JSON object per line.
+pub fn parse_cases(jsonl: &str) -> Result<Vec<EvalCase>> {
+    let mut cases = Vec::new();
+    for (i, line) in jsonl.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let case: EvalCase = serde_json::from_str(line)
+            .with_context(|| format!("Line {} is not {{\"query\": ..., \"expected\": [...]}}", i + 1))
+            .context(ErrorCode::InvalidArgument)?;
+        if case.expected.is_empty() {
+            return Err(anyhow::anyhow!("Line {} expects no documents", i + 1)).context(ErrorCode::InvalidArgument);
+        }
+        cases.push(case);
+    }
+    if cases.is_empty() || cases.len() > MAX_EVAL_CASES {
+        return Err(anyhow::anyhow!(
+            "Give between 1 and {} cases, got {}",
+            MAX_EVAL_CASES,
+            cases.len()
+        ))
+        .context(ErrorCode::InvalidArgument);
+    }
+    Ok(cases)
+}
+
+/// Score the top `k` `results` of `case`. A result matches an expected entry
+/// by its id or by the name of its document; each entry counts once, so the
+/// further chunks of an already found document are not credited again.
+pub fn score(case: &EvalCase, results: &[SearchResult], k: usize) -> CaseScore {
+    let expected: HashSet<&str> = case.expected.iter().map(String::as_str).collect();
+    let mut found: HashSet<&str> = HashSet::new();
+    let mut dcg = 0.0;
+    let mut first_hit = None;
+    for (i, result) in results.iter().take(k).enumerate() {
+        let matched = [result.id.as_str(), result.name.as_str()]
+            .into_iter()
+            .find(|key| expected.contains(key) && !found.contains(key));
+        if let Some(key) = matched {
+            found.insert(key);
+            dcg += 1.0 / (i as f64 + 2.0).log2();
+            first_hit.get_or_insert(i + 1);
+        }
+    }
+    let ideal: f64 = (0..expected.len().min(k)).map(|i| 1.0 / (i as f64 + 2.0).log2()).sum();
+    let mut missed: Vec<String> = expected.difference(&found).map(|s| s.to_string()).collect();
+    missed.sort();
+    CaseScore {
+        query: case.query.clone(),
+        recall: found.len() as f64 / expected.len() as f64,
+        reciprocal_rank: first_hit.map_or(0.0, |rank| 1.0 / rank as f64),
+        ndcg: if ideal > 0.0 { dcg / ideal } else { 0.0 },
+        first_hit,
+        missed,
+    }
+}
+
+pub fn report(k: usize, per_query: Vec<CaseScore>) -> EvalReport {
+    let n = per_query.len().max(1) as f64;
+    let mean = |metric: fn(&CaseScore) -> f64| per_query.iter().map(metric).sum::<f64>() / n;
+    EvalReport {
+        cases: per_query.len(),
+        k,
+        recall_at_k: mean(|c| c.recall),
+        mrr: mean(|c| c.reciprocal_rank),
+        ndcg_at_k: mean(|c| c.ndcg),
+        per_query,
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::dates;
+use crate::db::VectorDB;
+
+/// Metadata key holding the time a document's chunks are deleted.
+pub const EXPIRES_AT_KEY: &str = "expires_at";
+pub const DEFAULT_EXPIRY_SWEEP_SECS: u64 = 300;
+
+/// Resolve a user-given expiry such as `in 3 days` or an RFC 3339 time to
+/// the RFC 3339 UTC time stored in the metadata.
+pub fn resolve(expires_at: &str, now: DateTime<Utc>) -> Result<String> {
+    Ok(dates::parse(expires_at, now, None)?.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Periodically deletes the expired chunks of every table.
+pub struct ExpirySweeper {
+    db: Arc<VectorDB>,
+    interval: Duration,
+}
+
+impl ExpirySweeper {
+    pub fn new(db: Arc<VectorDB>, interval: Duration) -> Self {
+        Self { db, interval }
+    }
+
+    /// Run the sweeper in the background until the process exits.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                let tables = match self.db.list_tables().await {
+                    Ok(tables) => tables,
+                    Err(e) => {
+                        tracing::error!("Expiry sweep could not list tables: {}", e);
+                        continue;
+                    }
+                };
+                for table in tables {
+                    match self.db.delete_expired(&table, Utc::now()).await {
+                        Ok(0) => {}
+                        Ok(deleted) => tracing::info!(table, deleted, "Deleted expired chunks"),
+                        Err(e) => tracing::error!("Failed to delete expired chunks from '{}': {}", table, e),
+                    }
+                }
+            }
+        });
+    }
+}
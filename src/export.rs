@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use arrow::array::RecordBatch;
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio_util::io::ReaderStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::db::VectorDB;
+use crate::error::ErrorCode;
+
+/// How long an export made by the tool stays available for download.
+const EXPORT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Arrow columns as stored, metadata as a JSON string
+    Parquet,
+    /// One JSON object per row, metadata as a nested object
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "parquet" => Ok(Self::Parquet),
+            "jsonl" => Ok(Self::Jsonl),
+            other => Err(anyhow::anyhow!("Unknown export format '{}', expected 'parquet' or 'jsonl'", other))
+                .context(ErrorCode::InvalidArgument),
+        }
+    }
+
+    /// The format a file name asks for, `None` for other extensions.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "parquet" => Some(Self::Parquet),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// Write every row of `table_name` to `path`. The file appears only once it
+/// is complete, so a failed or cancelled export never leaves half a file
+/// behind. `progress` is told the number of rows written so far. Returns the
+/// number of rows.
+pub async fn export_table(
+    db: &VectorDB,
+    table_name: &str,
+    path: &Path,
+    format: ExportFormat,
+    with_vectors: bool,
+    cancel: &CancellationToken,
+    progress: impl Fn(usize),
+) -> Result<usize> {
+    let partial = path.with_extension(format!("{}.partial", format.extension()));
+    let result = write_rows(db, table_name, &partial, format, with_vectors, cancel, progress).await;
+    match result {
+        Ok(rows) => {
+            std::fs::rename(&partial, path).with_context(|| format!("Failed to move export into place at {}", path.display()))?;
+            Ok(rows)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+async fn write_rows(
+    db: &VectorDB,
+    table_name: &str,
+    path: &Path,
+    format: ExportFormat,
+    with_vectors: bool,
+    cancel: &CancellationToken,
+    progress: impl Fn(usize),
+) -> Result<usize> {
+    let mut stream = db.stream_rows(table_name, with_vectors).await?;
+    let file = std::fs::File::create(path).with_context(|| format!("Failed to create export file {}", path.display()))?;
+    let mut writer = match format {
+        ExportFormat::Parquet => {
+            let props = WriterProperties::builder()
+                .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                .build();
+            RowWriter::Parquet(ArrowWriter::try_new(file, stream.schema(), Some(props))?)
+        }
+        ExportFormat::Jsonl => RowWriter::Jsonl(BufWriter::new(file)),
+    };
+
+    let mut rows = 0;
+    while let Some(batch) = stream.try_next().await? {
+        if cancel.is_cancelled() {
+            return Err(anyhow::anyhow!("Export of '{}' cancelled", table_name)).context(ErrorCode::Cancelled);
+        }
+        writer.write(&batch)?;
+        rows += batch.num_rows();
+        progress(rows);
+    }
+    writer.finish()?;
+    Ok(rows)
+}
+
+enum RowWriter {
+    Parquet(ArrowWriter<std::fs::File>),
+    Jsonl(BufWriter<std::fs::File>),
+}
+
+impl RowWriter {
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            RowWriter::Parquet(writer) => writer.write(batch)?,
+            RowWriter::Jsonl(out) => {
+                let mut lines = arrow::json::LineDelimitedWriter::new(Vec::new());
+                lines.write(batch)?;
+                lines.finish()?;
+                for line in lines.into_inner().split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+                    let mut row: serde_json::Value = serde_json::from_slice(line)?;
+                    // Metadata is stored as a JSON string; export it as the object it is
+                    if let Some(metadata) = row.get_mut("metadata") {
+                        if let Some(parsed) = metadata.as_str().and_then(|m| serde_json::from_str(m).ok()) {
+                            *metadata = parsed;
+                        }
+                    }
+                    serde_json::to_writer(&mut *out, &row)?;
+                    out.write_all(b"\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            RowWriter::Parquet(writer) => {
+                writer.close()?;
+            }
+            RowWriter::Jsonl(mut out) => out.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Exports made by the `export_table` tool, kept in one directory and
+/// downloadable over HTTP by their file name until they expire.
+pub struct ExportDir {
+    dir: PathBuf,
+    /// Serializes sweeping with creating new exports
+    lock: Mutex<()>,
+}
+
+impl ExportDir {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, lock: Mutex::new(()) }
+    }
+
+    /// A fresh path for an export, after removing the expired ones.
+    pub fn allocate(&self, format: ExportFormat) -> Result<(String, PathBuf)> {
+        let _guard = self.lock.lock().unwrap();
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create export directory {}", self.dir.display()))?;
+        self.sweep();
+        let name = format!("{}.{}", uuid::Uuid::new_v4(), format.extension());
+        let path = self.dir.join(&name);
+        Ok((name, path))
+    }
+
+    /// The path of a finished export, `None` for names that are not one.
+    pub fn get(&self, name: &str) -> Option<PathBuf> {
+        if !is_export_name(name) {
+            return None;
+        }
+        let path = self.dir.join(name);
+        path.is_file().then_some(path)
+    }
+
+    /// Remove expired exports and the partial files of exports that never
+    /// finished. Anything else in the directory is left alone, as
+    /// `--export-dir` may point at a directory shared with other files.
+    fn sweep(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if !is_export_name(name.strip_suffix(".partial").unwrap_or(name)) || !entry.file_type().is_ok_and(|t| t.is_file()) {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > EXPORT_TTL);
+            if expired {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Whether `name` is one `ExportDir::allocate` hands out: a uuid followed by
+/// the extension of an export format.
+fn is_export_name(name: &str) -> bool {
+    let Some((id, _)) = name.rsplit_once('.') else {
+        return false;
+    };
+    uuid::Uuid::parse_str(id).is_ok() && ExportFormat::from_path(Path::new(name)).is_some()
+}
+
+/// Serve a finished export of the `export_table` tool.
+pub async fn download(State(exports): State<Arc<ExportDir>>, UrlPath(name): UrlPath<String>) -> Response {
+    let Some(path) = exports.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let content_type = match ExportFormat::from_path(&path) {
+        Some(ExportFormat::Parquet) => "application/vnd.apache.parquet",
+        _ => "application/x-ndjson",
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name)),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response()
+}
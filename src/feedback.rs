@@ -0,0 +1,161 @@
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, BooleanArray, Float32Array, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::db::{SearchResult, VectorDB};
+use crate::rows;
+
+/// Internal table `record_feedback` writes to.
+pub const FEEDBACK_TABLE: &str = "_mcp_feedback";
+/// Characters of a flagged chunk's text shown in the report.
+const PREVIEW_CHARS: usize = 200;
+/// Unhelpful queries listed per flagged chunk.
+const LISTED_QUERIES: usize = 5;
+
+/// A client's verdict on one search result.
+#[derive(Debug, Clone)]
+pub struct Feedback {
+    pub chunk_id: String,
+    pub helpful: bool,
+    /// 1-based position of the result in the search it came from
+    pub rank: Option<u32>,
+    pub score: Option<f32>,
+    pub comment: Option<String>,
+}
+
+/// A chunk that keeps getting marked unhelpful, with what is known about
+/// where it ranked.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct FlaggedChunk {
+    pub id: String,
+    /// Document the chunk belongs to, absent when it no longer exists
+    pub name: Option<String>,
+    /// Start of the chunk's text
+    pub preview: Option<String>,
+    pub helpful: usize,
+    pub unhelpful: usize,
+    /// Mean rank in the searches it was marked unhelpful for, over those that gave one
+    pub mean_rank: Option<f32>,
+    /// Some of the queries it was marked unhelpful for
+    pub queries: Vec<String>,
+}
+
+/// Store feedback on the results of a search for `query` in `table_name`.
+pub async fn record(db: &VectorDB, table_name: &str, query: &str, feedback: &[Feedback]) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("timestamp", DataType::Utf8, false),
+        Field::new("table_name", DataType::Utf8, false),
+        Field::new("query", DataType::Utf8, false),
+        Field::new("chunk_id", DataType::Utf8, false),
+        Field::new("helpful", DataType::Boolean, false),
+        Field::new("rank", DataType::Int32, true),
+        Field::new("score", DataType::Float32, true),
+        Field::new("comment", DataType::Utf8, true),
+    ]));
+    let n = feedback.len();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![chrono::Utc::now().to_rfc3339(); n])) as ArrayRef,
+            Arc::new(StringArray::from(vec![table_name; n])),
+            Arc::new(StringArray::from(vec![query; n])),
+            Arc::new(StringArray::from_iter_values(feedback.iter().map(|f| f.chunk_id.as_str()))),
+            Arc::new(BooleanArray::from_iter(feedback.iter().map(|f| Some(f.helpful)))),
+            Arc::new(Int32Array::from_iter(feedback.iter().map(|f| f.rank.map(|r| r as i32)))),
+            Arc::new(Float32Array::from_iter(feedback.iter().map(|f| f.score))),
+            Arc::new(StringArray::from_iter(feedback.iter().map(|f| f.comment.as_deref()))),
+        ],
+    )?;
+    db.append_rows(FEEDBACK_TABLE, batch).await
+}
+
+/// The chunks of `table_name` marked unhelpful at least `min_unhelpful`
+/// times and more often than helpful, whose mean rank when marked unhelpful
+/// is at most `max_rank` (or unknown). Worst first, at most `limit`.
+pub async fn flagged_chunks(
+    db: &VectorDB,
+    table_name: &str,
+    min_unhelpful: usize,
+    max_rank: f32,
+    limit: usize,
+) -> Result<Vec<FlaggedChunk>> {
+    let filter = format!("table_name = '{}'", table_name.replace('\'', "''"));
+    let mut tallies: HashMap<String, Tally> = HashMap::new();
+    if let Some(mut stream) = db.scan_rows(FEEDBACK_TABLE, Some(filter)).await? {
+        while let Some(batch) = stream.try_next().await? {
+            let ids = rows::required_strings(&batch, "chunk_id")?;
+            let queries = rows::required_strings(&batch, "query")?;
+            let helpful = rows::required_bools(&batch, "helpful")?;
+            let ranks = rows::floats(&batch, "rank")?;
+            for i in 0..batch.num_rows() {
+                let tally = tallies.entry(ids.value(i).to_string()).or_default();
+                if helpful.value(i) {
+                    tally.helpful += 1;
+                    continue;
+                }
+                tally.unhelpful += 1;
+                if let Some(rank) = ranks.as_ref().filter(|r| r.is_valid(i)) {
+                    tally.rank_sum += rank.value(i);
+                    tally.ranked += 1;
+                }
+                tally.queries.insert(queries.value(i).to_string());
+            }
+        }
+    }
+
+    let mut flagged: Vec<(String, Tally)> = tallies
+        .into_iter()
+        .filter(|(_, t)| t.unhelpful >= min_unhelpful && t.unhelpful > t.helpful)
+        .filter(|(_, t)| t.mean_rank().is_none_or(|rank| rank <= max_rank))
+        .collect();
+    flagged.sort_by(|(a_id, a), (b_id, b)| {
+        let net = |t: &Tally| t.unhelpful as i64 - t.helpful as i64;
+        net(b)
+            .cmp(&net(a))
+            .then_with(|| a.mean_rank().unwrap_or(f32::MAX).total_cmp(&b.mean_rank().unwrap_or(f32::MAX)))
+            .then_with(|| a_id.cmp(b_id))
+    });
+    flagged.truncate(limit);
+
+    let ids: Vec<String> = flagged.iter().map(|(id, _)| id.clone()).collect();
+    // Feedback outlives the table it was given on
+    let chunks: HashMap<String, SearchResult> = if db.table_exists(table_name).await? {
+        db.get_chunks(table_name, &ids).await?.into_iter().map(|c| (c.id.clone(), c)).collect()
+    } else {
+        HashMap::new()
+    };
+    Ok(flagged
+        .into_iter()
+        .map(|(id, tally)| {
+            let chunk = chunks.get(&id);
+            FlaggedChunk {
+                name: chunk.map(|c| c.name.clone()),
+                preview: chunk.map(|c| c.content.chars().take(PREVIEW_CHARS).collect()),
+                helpful: tally.helpful,
+                unhelpful: tally.unhelpful,
+                mean_rank: tally.mean_rank(),
+                queries: tally.queries.into_iter().take(LISTED_QUERIES).collect(),
+                id,
+            }
+        })
+        .collect())
+}
+
+#[derive(Default)]
+struct Tally {
+    helpful: usize,
+    unhelpful: usize,
+    rank_sum: f32,
+    ranked: usize,
+    queries: BTreeSet<String>,
+}
+
+impl Tally {
+    fn mean_rank(&self) -> Option<f32> {
+        (self.ranked > 0).then(|| self.rank_sum / self.ranked as f32)
+    }
+}
@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use scraper::{ElementRef, Html, Node, Selector};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::error::ErrorCode;
+use crate::http::HttpOptions;
+use crate::provenance;
+
+/// Elements whose contents are never part of the readable text of a page.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "iframe", "form", "button",
+    "nav", "header", "footer", "aside", "menu",
+];
+
+/// Elements that start a new line in the extracted text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "section", "article", "main", "br", "li", "ul", "ol", "table", "tr",
+    "h1", "h2", "h3", "h4", "h5", "h6", "pre", "blockquote", "dt", "dd",
+];
+
+/// Largest page body fetched; longer bodies fail the fetch instead of being
+/// read into memory.
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Host allowlist/denylist gating outbound fetches.
+///
+/// A pattern matches the host itself and all of its subdomains. The denylist
+/// wins over the allowlist; an empty allowlist allows every host. Unless
+/// `allow_private` is set, hosts must also resolve to public addresses only,
+/// so a client cannot reach the server's own network through add_url.
+pub struct UrlPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+    allow_private: bool,
+}
+
+impl UrlPolicy {
+    pub fn new(allow: Vec<String>, deny: Vec<String>, allow_private: bool) -> Self {
+        let normalize = |v: Vec<String>| {
+            v.into_iter()
+                .map(|p| p.trim().trim_start_matches("*.").trim_start_matches('.').to_ascii_lowercase())
+                .filter(|p| !p.is_empty())
+                .collect()
+        };
+        Self {
+            allow: normalize(allow),
+            deny: normalize(deny),
+            allow_private,
+        }
+    }
+
+    pub fn check(&self, url: &reqwest::Url) -> Result<()> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            anyhow::bail!("Unsupported URL scheme '{}'", url.scheme());
+        }
+        let host = url.host_str().context("URL has no host")?.to_ascii_lowercase();
+
+        let matches = |pattern: &String| host == *pattern || host.ends_with(&format!(".{}", pattern));
+        if self.deny.iter().any(matches) {
+            anyhow::bail!("Host '{}' is denied by the fetch denylist", host);
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(matches) {
+            anyhow::bail!("Host '{}' is not in the fetch allowlist", host);
+        }
+        // Host names are checked once resolved, by `PublicResolver`
+        match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => self.check_ip(ip),
+            Err(_) => Ok(()),
+        }
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> Result<()> {
+        if !self.allow_private && !is_public(ip) {
+            anyhow::bail!(
+                "Address {} is loopback, private or link-local; start the server with --fetch-allow-private to fetch from it",
+                ip
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Whether `ip` is reachable on the public internet, as opposed to the
+/// loopback, private, link-local (cloud metadata) or unspecified ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // "This network" and carrier-grade NAT
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+/// Resolves host names for the fetch client, refusing those with an address
+/// the [`UrlPolicy`] does not allow. Checking the addresses that are actually
+/// connected to also covers redirects and DNS names pointing at private ranges.
+struct PublicResolver {
+    policy: Arc<UrlPolicy>,
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            for addr in &addrs {
+                if let Err(e) = policy.check_ip(addr.ip()) {
+                    return Err(Box::new(DisallowedAddress(format!("Host '{}': {}", name.as_str(), e))) as _);
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct DisallowedAddress(String);
+
+impl std::fmt::Display for DisallowedAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DisallowedAddress {}
+
+pub struct FetchedPage {
+    pub url: String,
+    pub title: Option<String>,
+    pub text: String,
+    /// How the body was turned into `text`, recorded in chunk provenance
+    pub normalization: &'static str,
+}
+
+/// HTTP client for fetching web pages, enforcing the [`UrlPolicy`] on every
+/// request including redirects.
+pub struct UrlFetcher {
+    client: reqwest::Client,
+    policy: Arc<UrlPolicy>,
+}
+
+impl UrlFetcher {
+    pub fn new(policy: UrlPolicy, http: &HttpOptions) -> Result<Self> {
+        let policy = Arc::new(policy);
+        let redirect_policy = policy.clone();
+        let mut builder = http.client_builder()?;
+        if !policy.allow_private {
+            // A proxy resolves the host itself, past the resolver's address
+            // check, so fetches connect directly
+            if http.proxy.is_some() {
+                tracing::warn!("add_url does not use --proxy without --fetch-allow-private");
+            }
+            builder = builder
+                .no_proxy()
+                .dns_resolver(Arc::new(PublicResolver { policy: policy.clone() }));
+        }
+        let client = builder
+            .user_agent(concat!("lancedb-mcp-server/", env!("CARGO_PKG_VERSION")))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 10 {
+                    attempt.error("too many redirects")
+                } else if let Err(e) = redirect_policy.check(attempt.url()) {
+                    attempt.error(e.to_string())
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()?;
+
+        Ok(Self { client, policy })
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<FetchedPage> {
+        let url = reqwest::Url::parse(url).with_context(|| format!("Invalid URL '{}'", url))?;
+        self.policy.check(&url).context(ErrorCode::InvalidArgument)?;
+
+        let mut resp = match self.client.get(url).send().await {
+            Ok(resp) => resp.error_for_status()?,
+            Err(e) => match disallowed_address(&e) {
+                // A host resolving to a private address is the client's fault
+                Some(denied) => return Err(anyhow::anyhow!("{}", denied)).context(ErrorCode::InvalidArgument),
+                None => return Err(e.into()),
+            },
+        };
+        let final_url = resp.url().to_string();
+        let is_html = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.contains("html"))
+            .unwrap_or(true);
+        if resp.content_length().is_some_and(|len| len > MAX_BODY_BYTES as u64) {
+            anyhow::bail!("The page is larger than {} MB", MAX_BODY_BYTES / (1024 * 1024));
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            if body.len() + chunk.len() > MAX_BODY_BYTES {
+                anyhow::bail!("The page is larger than {} MB", MAX_BODY_BYTES / (1024 * 1024));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8_lossy(&body).into_owned();
+
+        if !is_html {
+            return Ok(FetchedPage {
+                url: final_url,
+                title: None,
+                text: body,
+                normalization: provenance::NORMALIZATION_NONE,
+            });
+        }
+
+        let (title, text) = extract_readable_text(&body);
+        Ok(FetchedPage {
+            url: final_url,
+            title,
+            text,
+            normalization: provenance::NORMALIZATION_HTML,
+        })
+    }
+}
+
+fn disallowed_address(e: &reqwest::Error) -> Option<&DisallowedAddress> {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if let Some(denied) = err.downcast_ref::<DisallowedAddress>() {
+            return Some(denied);
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Extract the page title and the readable body text from an HTML document,
+/// dropping scripts, navigation and other page chrome.
+pub fn extract_readable_text(html: &str) -> (Option<String>, String) {
+    let document = Html::parse_document(html);
+
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|sel| document.select(&sel).next())
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    // Prefer the main content container when the page marks one up
+    let root = ["article", "main", "body"]
+        .iter()
+        .filter_map(|tag| Selector::parse(tag).ok())
+        .find_map(|sel| document.select(&sel).next())
+        .unwrap_or_else(|| document.root_element());
+
+    let mut raw = String::new();
+    collect_text(root, &mut raw);
+
+    // Collapse runs of whitespace within lines and drop empty lines
+    let text = raw
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (title, text)
+}
+
+fn collect_text(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(text),
+            Node::Element(el) => {
+                let name = el.name();
+                if SKIPPED_ELEMENTS.contains(&name) {
+                    continue;
+                }
+                let is_block = BLOCK_ELEMENTS.contains(&name);
+                if is_block {
+                    out.push('\n');
+                }
+                if let Some(child_ref) = ElementRef::wrap(child) {
+                    collect_text(child_ref, out);
+                }
+                if is_block {
+                    out.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
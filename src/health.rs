@@ -0,0 +1,144 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::db::VectorDB;
+use crate::lazy_model::{HealthStatus, LazyModel, ModelState};
+
+/// Free space below which the database disk counts as unhealthy.
+const MIN_FREE_DISK_BYTES: u64 = 256 * 1024 * 1024;
+/// Longest a single check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long readiness probes reuse a passing report.
+const READY_CACHE_TTL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct HealthCheck {
+    /// database, embedding or disk
+    pub name: &'static str,
+    pub ok: bool,
+    /// What was found, or why the check failed
+    pub detail: String,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct HealthReport {
+    /// Whether every check passed, i.e. the server can serve every tool
+    pub ready: bool,
+    pub model: HealthStatus,
+    pub checks: Vec<HealthCheck>,
+}
+
+/// Check that the database answers, the embedding engine embeds a tiny text
+/// and the database disk has room left.
+pub async fn check(db: &VectorDB, model: &LazyModel) -> HealthReport {
+    let status = model.health();
+    let checks = vec![
+        timed("database", async {
+            let tables = db.list_tables().await.map_err(|e| format!("{:#}", e))?;
+            Ok(format!("{} tables", tables.len()))
+        })
+        .await,
+        timed("embedding", async {
+            match status.model_state {
+                ModelState::Loading => return Err("the model is still loading".to_string()),
+                ModelState::Failed => return Err(status.error.clone().unwrap_or_default()),
+                ModelState::Ready => {}
+            }
+            let model = model.get().await.map_err(|e| format!("{:#}", e))?;
+            let vectors = model.embed(vec!["health check".to_string()]).await.map_err(|e| format!("{:#}", e))?;
+            Ok(format!("dimension {}", vectors.first().map_or(0, Vec::len)))
+        })
+        .await,
+        timed("disk", async { disk_check(db.uri()) }).await,
+    ];
+
+    HealthReport {
+        ready: checks.iter().all(|c| c.ok),
+        model: status,
+        checks,
+    }
+}
+
+/// The last passing report of [`check`], reused by readiness probes for a
+/// few seconds so frequent probes do not each call a remote embedding API.
+/// Failing reports are not reused, so a recovered server turns ready on the
+/// next probe, and concurrent probes share one check.
+#[derive(Default)]
+pub struct ReadyCache(tokio::sync::Mutex<Option<(Instant, HealthReport)>>);
+
+impl ReadyCache {
+    pub async fn check(&self, db: &VectorDB, model: &LazyModel) -> HealthReport {
+        let mut last = self.0.lock().await;
+        if let Some((checked, report)) = &*last {
+            if checked.elapsed() < READY_CACHE_TTL {
+                return report.clone();
+            }
+        }
+        let report = check(db, model).await;
+        *last = report.ready.then(|| (Instant::now(), report.clone()));
+        report
+    }
+}
+
+async fn timed(name: &'static str, check: impl Future<Output = Result<String, String>>) -> HealthCheck {
+    let started = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())));
+    let (ok, detail) = match result {
+        Ok(detail) => (true, detail),
+        Err(detail) => (false, detail),
+    };
+    HealthCheck {
+        name,
+        ok,
+        detail,
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+fn disk_check(uri: &str) -> Result<String, String> {
+    // Object stores have no disk to fill up
+    let Some(path) = local_path(uri) else {
+        return Ok(format!("skipped, {} is not a local path", uri));
+    };
+    let Some(free) = free_bytes(std::path::Path::new(path)).map_err(|e| format!("{}: {}", path, e))? else {
+        return Ok("skipped, free space is only checked on unix".to_string());
+    };
+    let detail = format!("{} MiB free", free / (1024 * 1024));
+    if free < MIN_FREE_DISK_BYTES {
+        return Err(format!("{}, below {} MiB", detail, MIN_FREE_DISK_BYTES / (1024 * 1024)));
+    }
+    Ok(detail)
+}
+
+fn local_path(uri: &str) -> Option<&str> {
+    match uri.split_once("://") {
+        None => Some(uri),
+        Some(("file", path)) => Some(path),
+        Some(_) => None,
+    }
+}
+
+#[cfg(unix)]
+fn free_bytes(path: &std::path::Path) -> std::io::Result<Option<u64>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: statvfs only writes to the struct it is given and reads the
+    // NUL-terminated path.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The field widths differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    Ok(Some(free))
+}
+
+#[cfg(not(unix))]
+fn free_bytes(_path: &std::path::Path) -> std::io::Result<Option<u64>> {
+    Ok(None)
+}
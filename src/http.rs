@@ -0,0 +1,49 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest an outbound connection may take to establish.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest an outbound request may take, body included, so a stalled server
+/// cannot hold a tool call forever.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Proxy and TLS settings shared by every outbound HTTP client: the
+/// embedding API and URL ingestion.
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    /// Proxy for all requests, replacing `HTTP(S)_PROXY`; `NO_PROXY` is still
+    /// honored
+    pub proxy: Option<String>,
+    /// PEM files of extra trusted root certificates, e.g. the CA of a TLS
+    /// intercepting proxy
+    pub ca_certs: Vec<PathBuf>,
+    /// Accept any server certificate
+    pub insecure: bool,
+}
+
+impl HttpOptions {
+    /// A client builder with these settings and the timeouts applied.
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder().connect_timeout(CONNECT_TIMEOUT).timeout(REQUEST_TIMEOUT);
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .with_context(|| format!("Invalid --proxy '{}'", proxy))?
+                .no_proxy(reqwest::NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+        for path in &self.ca_certs {
+            let pem = std::fs::read(path).with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+            let certs = reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid PEM certificate file {}", path.display()))?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if self.insecure {
+            tracing::warn!("TLS certificate verification is disabled for outbound requests");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+
+use crate::error::ErrorCode;
+
+/// Largest image `add_images` accepts, after decoding.
+pub const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// An image to embed, with the format told from its leading bytes.
+pub struct Image {
+    pub bytes: Vec<u8>,
+    pub format: &'static str,
+}
+
+/// Load an image from a file on the server or from base64 `data`, which
+/// may be a `data:` URL. Exactly one of them must be given.
+pub async fn load(path: Option<&str>, data: Option<&str>) -> Result<Image> {
+    let bytes = match (path, data) {
+        (Some(path), None) => read(std::path::Path::new(path)).await?,
+        (None, Some(data)) => decode(data)?,
+        _ => {
+            return Err(anyhow::anyhow!("Give every image either a path or base64 data")).context(ErrorCode::InvalidArgument);
+        }
+    };
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(anyhow::anyhow!(
+            "Image of {} bytes is over the limit of {} bytes",
+            bytes.len(),
+            MAX_IMAGE_BYTES
+        ))
+        .context(ErrorCode::InvalidArgument);
+    }
+    let Some(format) = format(&bytes) else {
+        return Err(anyhow::anyhow!("Not a PNG, JPEG, GIF, WebP or BMP image")).context(ErrorCode::InvalidArgument);
+    };
+    Ok(Image { bytes, format })
+}
+
+/// Builds without the `local-files` feature never read the server's filesystem.
+#[cfg(not(feature = "local-files"))]
+async fn read(path: &std::path::Path) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!("Cannot read {}: this build has no local-files feature, pass the image as data", path.display()))
+        .context(ErrorCode::InvalidArgument)
+}
+
+#[cfg(feature = "local-files")]
+async fn read(path: &std::path::Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn decode(data: &str) -> Result<Vec<u8>> {
+    let data = match data.strip_prefix("data:") {
+        Some(url) => url.split_once(',').map_or(url, |(_, data)| data),
+        None => data,
+    };
+    base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| anyhow::anyhow!("Image data is not valid base64: {}", e))
+        .context(ErrorCode::InvalidArgument)
+}
+
+/// Image format of `bytes`, from their magic number.
+fn format(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else if bytes.starts_with(b"BM") {
+        Some("bmp")
+    } else {
+        None
+    }
+}
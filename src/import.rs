@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::error::ErrorCode;
+use crate::provenance::{self, Provenance, SourceType};
+
+/// Rows are read into memory before they are written; larger files should be
+/// split first.
+#[cfg_attr(not(feature = "local-files"), allow(dead_code))]
+const MAX_IMPORT_ROWS: usize = 1_000_000;
+
+type Row = serde_json::Map<String, serde_json::Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Parquet,
+    /// With a header row
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "parquet" => Ok(Self::Parquet),
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            other => Err(anyhow::anyhow!("Unknown import format '{}', expected 'parquet', 'csv' or 'jsonl'", other))
+                .context(ErrorCode::InvalidArgument),
+        }
+    }
+
+    /// The format of a file, from its extension.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        Self::parse(&extension).with_context(|| format!("Cannot tell the format of {}, pass it explicitly", path.display()))
+    }
+}
+
+/// Which columns of the file hold what.
+pub struct Columns {
+    /// The text to store and, without vectors, embed
+    pub text: String,
+    /// Document name; rows without one are named after the file
+    pub name: Option<String>,
+    /// Precomputed embeddings. `None` uses a `vector` column when the file
+    /// has one.
+    pub vector: Option<String>,
+    /// Embed the text even when the file has vectors
+    pub reembed: bool,
+    /// Columns kept as metadata, every other column when `None`
+    pub metadata: Option<Vec<String>>,
+}
+
+/// Rows ready for `VectorDB::add_texts` or `VectorDB::add_vectors`.
+pub struct ImportedRows {
+    pub texts: Vec<String>,
+    pub metadatas: Vec<serde_json::Value>,
+    /// Present when the file brought its own embeddings
+    pub vectors: Option<Vec<Vec<f32>>>,
+    /// Rows left out because their text was missing or blank
+    pub skipped: usize,
+}
+
+/// Builds without the `local-files` feature never read the server's filesystem.
+#[cfg(not(feature = "local-files"))]
+pub async fn read_rows(path: &Path, _format: ImportFormat) -> Result<Vec<Row>> {
+    Err(anyhow::anyhow!("Cannot read {}: this build has no local-files feature", path.display()))
+        .context(ErrorCode::InvalidArgument)
+}
+
+/// Read every row of a local file as a JSON object keyed by column name.
+#[cfg(feature = "local-files")]
+pub async fn read_rows(path: &Path, format: ImportFormat) -> Result<Vec<Row>> {
+    let owned = path.to_path_buf();
+    // Decoding is CPU bound, keep it off the async workers
+    tokio::task::spawn_blocking(move || read_rows_blocking(&owned, format))
+        .await?
+        .with_context(|| format!("Failed to read {}", path.display()))
+        .context(ErrorCode::InvalidArgument)
+}
+
+#[cfg(feature = "local-files")]
+fn read_rows_blocking(path: &Path, format: ImportFormat) -> Result<Vec<Row>> {
+    use std::io::{BufRead, Seek};
+
+    let mut file = std::fs::File::open(path)?;
+    let mut rows = Vec::new();
+    match format {
+        ImportFormat::Parquet => {
+            let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+            for batch in reader {
+                append_batch(&mut rows, &batch?)?;
+            }
+        }
+        ImportFormat::Csv => {
+            let csv = arrow::csv::reader::Format::default().with_header(true);
+            let (schema, _) = csv.infer_schema(&mut file, Some(1000))?;
+            file.rewind()?;
+            let reader = arrow::csv::ReaderBuilder::new(std::sync::Arc::new(schema))
+                .with_format(csv)
+                .build(file)?;
+            for batch in reader {
+                append_batch(&mut rows, &batch?)?;
+            }
+        }
+        ImportFormat::Jsonl => {
+            for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line).with_context(|| format!("Invalid JSON on line {}", i + 1))? {
+                    serde_json::Value::Object(row) => rows.push(row),
+                    _ => anyhow::bail!("Line {} is not a JSON object", i + 1),
+                }
+                check_row_count(rows.len())?;
+            }
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(feature = "local-files")]
+fn append_batch(rows: &mut Vec<Row>, batch: &arrow::array::RecordBatch) -> Result<()> {
+    let mut lines = arrow::json::LineDelimitedWriter::new(Vec::new());
+    lines.write(batch)?;
+    lines.finish()?;
+    for line in lines.into_inner().split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        rows.push(serde_json::from_slice(line)?);
+    }
+    check_row_count(rows.len())
+}
+
+#[cfg(feature = "local-files")]
+fn check_row_count(rows: usize) -> Result<()> {
+    if rows > MAX_IMPORT_ROWS {
+        anyhow::bail!("More than {} rows; split the file and import the parts", MAX_IMPORT_ROWS);
+    }
+    Ok(())
+}
+
+/// Map the rows of `source` onto texts, metadata and optional vectors.
+/// `dimension` is the embedding model's; precomputed vectors must match it
+/// so the table stays searchable with the server's model.
+pub fn map_rows(rows: Vec<Row>, columns: &Columns, source: &str, dimension: usize) -> Result<ImportedRows> {
+    let vector_column = columns.vector.as_deref().unwrap_or("vector");
+    let with_vectors = !columns.reembed && (columns.vector.is_some() || rows.iter().any(|row| row.contains_key(vector_column)));
+    if !rows.is_empty() && !rows.iter().any(|row| row.contains_key(&columns.text)) {
+        return Err(anyhow::anyhow!("The file has no '{}' column; pass text_column", columns.text))
+            .context(ErrorCode::InvalidArgument);
+    }
+
+    let file_name = Path::new(source)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.to_string());
+    let provenance = Provenance::new(
+        SourceType::File,
+        Some(source.to_string()),
+        provenance::CLIENT_CHUNKER,
+        provenance::NORMALIZATION_NONE,
+    );
+
+    let mut imported = ImportedRows {
+        texts: Vec::with_capacity(rows.len()),
+        metadatas: Vec::with_capacity(rows.len()),
+        vectors: with_vectors.then(|| Vec::with_capacity(rows.len())),
+        skipped: 0,
+    };
+    for (i, mut row) in rows.into_iter().enumerate() {
+        let text = match row.remove(&columns.text) {
+            Some(serde_json::Value::String(text)) if !text.trim().is_empty() => text,
+            _ => {
+                imported.skipped += 1;
+                continue;
+            }
+        };
+        // Never kept as metadata, even when the text is re-embedded
+        let vector = row.remove(vector_column);
+        if let Some(vectors) = imported.vectors.as_mut() {
+            vectors.push(parse_vector(vector, vector_column, i + 1, dimension)?);
+        }
+        // Ids are assigned on write
+        row.remove("id");
+
+        let name = columns
+            .name
+            .as_ref()
+            .and_then(|column| row.remove(column))
+            .map(|value| match value {
+                serde_json::Value::String(name) => name,
+                other => other.to_string(),
+            });
+        let mut metadata = serde_json::Map::new();
+        for (key, value) in row {
+            if columns.metadata.as_ref().is_some_and(|keep| !keep.contains(&key)) {
+                continue;
+            }
+            // An exported table keeps its metadata in one column, as an
+            // object or a JSON string; bring its fields back to the top
+            if key == "metadata" {
+                let nested = match value {
+                    serde_json::Value::String(s) => serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s)),
+                    other => other,
+                };
+                match nested {
+                    serde_json::Value::Object(fields) => metadata.extend(fields),
+                    other => {
+                        metadata.insert(key, other);
+                    }
+                }
+                continue;
+            }
+            metadata.insert(key, value);
+        }
+        if let Some(name) = name {
+            metadata.insert("name".to_string(), serde_json::Value::String(name));
+        }
+        metadata
+            .entry("name".to_string())
+            .or_insert_with(|| serde_json::Value::String(file_name.clone()));
+
+        let mut metadata = serde_json::Value::Object(metadata);
+        if metadata.get(provenance::PROVENANCE_KEY).is_none() {
+            provenance.attach(&mut metadata);
+        }
+        imported.texts.push(text);
+        imported.metadatas.push(metadata);
+    }
+    Ok(imported)
+}
+
+/// A vector cell as written by Parquet/JSONL (an array) or CSV (a JSON
+/// array in a string).
+fn parse_vector(value: Option<serde_json::Value>, column: &str, row: usize, dimension: usize) -> Result<Vec<f32>> {
+    let value = match value {
+        Some(serde_json::Value::String(s)) => serde_json::from_str(&s).ok(),
+        other => other,
+    };
+    let vector: Option<Vec<f32>> = value.and_then(|v| serde_json::from_value(v).ok());
+    let vector = vector
+        .with_context(|| format!("Row {} has no list of numbers in column '{}'", row, column))
+        .context(ErrorCode::InvalidArgument)?;
+    if vector.len() != dimension {
+        return Err(anyhow::anyhow!(
+            "Row {} has a {}-dimensional vector but the embedding model produces {} dimensions; \
+             pass reembed to embed the text instead",
+            row,
+            vector.len(),
+            dimension
+        ))
+        .context(ErrorCode::DimensionMismatch);
+    }
+    Ok(vector)
+}
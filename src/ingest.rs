@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+#[cfg(feature = "local-files")]
+use crate::chunking;
+use crate::provenance;
+
+/// A chunk of text extracted from a file, with the page it came from when the
+/// format has pages.
+pub struct FileChunk {
+    pub text: String,
+    pub page: Option<usize>,
+}
+
+/// The text normalization `chunk_file` applies to `path`, recorded in chunk provenance.
+pub fn normalization(path: &Path) -> &'static str {
+    match extension(path).as_str() {
+        "pdf" => provenance::NORMALIZATION_PDF,
+        "html" | "htm" => provenance::NORMALIZATION_HTML,
+        _ => provenance::NORMALIZATION_NONE,
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Builds without the `local-files` feature never read the server's filesystem.
+#[cfg(not(feature = "local-files"))]
+pub async fn chunk_file(path: &Path, _chunk_size: usize, _overlap: usize) -> Result<Vec<FileChunk>> {
+    Err(anyhow::anyhow!("Cannot read {}: this build has no local-files feature", path.display()))
+        .context(crate::error::ErrorCode::InvalidArgument)
+}
+
+/// Read a local file and split it into chunks.
+///
+/// PDFs are extracted page by page so every chunk knows its (1-based) page
+/// number; HTML is reduced to its readable text; anything else is read as UTF-8.
+#[cfg(feature = "local-files")]
+pub async fn chunk_file(path: &Path, chunk_size: usize, overlap: usize) -> Result<Vec<FileChunk>> {
+    let sections: Vec<(Option<usize>, String)> = match extension(path).as_str() {
+        "pdf" => {
+            let bytes = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            // PDF parsing is CPU bound, keep it off the async workers
+            let pages = tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem_by_pages(&bytes))
+                .await?
+                .map_err(|e| anyhow::anyhow!("Failed to extract text from {}: {}", path.display(), e))?;
+            pages
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| (Some(i + 1), text))
+                .collect()
+        }
+        "html" | "htm" => {
+            let html = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let (_, text) = crate::fetch::extract_readable_text(&html);
+            vec![(None, text)]
+        }
+        _ => {
+            let text = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read {} as UTF-8 text", path.display()))?;
+            vec![(None, text)]
+        }
+    };
+
+    Ok(sections
+        .into_iter()
+        .flat_map(|(page, text)| {
+            chunking::chunk_text(&text, chunk_size, overlap)
+                .into_iter()
+                .map(move |text| FileChunk { text, page })
+        })
+        .collect())
+}
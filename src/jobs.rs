@@ -0,0 +1,129 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::db::IngestProgress;
+
+/// Finished jobs kept around for `get_job_status` / `list_jobs`.
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobState {
+    fn is_finished(self) -> bool {
+        matches!(self, JobState::Completed | JobState::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct JobStatus {
+    pub id: String,
+    pub tool: String,
+    pub table_name: String,
+    pub state: JobState,
+    pub total_chunks: usize,
+    pub chunks_embedded: usize,
+    pub rows_written: usize,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+/// Background ingestion jobs. Jobs run one at a time in submission order;
+/// their status is kept in memory only.
+pub struct JobQueue {
+    jobs: Mutex<Vec<JobStatus>>,
+    worker: Semaphore,
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            worker: Semaphore::new(1),
+        }
+    }
+
+    /// Register a new queued job and return its id.
+    pub fn create(&self, tool: &str, table_name: &str, total_chunks: usize) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut jobs = self.jobs.lock().unwrap();
+
+        // Drop the oldest finished jobs beyond the retention limit
+        let finished = jobs.iter().filter(|j| j.state.is_finished()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS - 1);
+        jobs.retain(|j| {
+            if excess > 0 && j.state.is_finished() {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+
+        jobs.push(JobStatus {
+            id: id.clone(),
+            tool: tool.to_string(),
+            table_name: table_name.to_string(),
+            state: JobState::Queued,
+            total_chunks,
+            chunks_embedded: 0,
+            rows_written: 0,
+            error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            started_at: None,
+            finished_at: None,
+        });
+        id
+    }
+
+    /// Wait until no other job is running, then mark `id` as running. The job
+    /// owns the worker until the returned permit is dropped.
+    pub async fn start(&self, id: &str) -> Option<SemaphorePermit<'_>> {
+        let permit = self.worker.acquire().await.ok()?;
+        self.update(id, |job| {
+            job.state = JobState::Running;
+            job.started_at = Some(chrono::Utc::now().to_rfc3339());
+        });
+        Some(permit)
+    }
+
+    pub fn progress(&self, id: &str, progress: IngestProgress) {
+        self.update(id, |job| {
+            job.total_chunks = progress.total;
+            job.chunks_embedded = progress.embedded;
+            job.rows_written = progress.written;
+        });
+    }
+
+    pub fn finish(&self, id: &str, error: Option<String>) {
+        self.update(id, |job| {
+            job.state = if error.is_some() { JobState::Failed } else { JobState::Completed };
+            job.error = error;
+            job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobStatus> {
+        self.jobs.lock().unwrap().iter().find(|j| j.id == id).cloned()
+    }
+
+    /// All known jobs, newest first.
+    pub fn list(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut JobStatus)) {
+        if let Some(job) = self.jobs.lock().unwrap().iter_mut().find(|j| j.id == id) {
+            f(job);
+        }
+    }
+}
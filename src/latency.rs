@@ -0,0 +1,230 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::tenant::Tenant;
+
+pub const DEFAULT_LATENCY_WINDOW_SECS: u64 = 300;
+/// Samples kept per table and pipeline, so a burst of searches cannot grow
+/// the window without bound.
+const MAX_SAMPLES: usize = 10_000;
+
+/// Label used for searches that ran without a configured pipeline.
+pub fn mode_label(mode: &str) -> String {
+    format!("mode:{}", mode)
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct LatencyStats {
+    /// Table name, or "*" when aggregated over all tables
+    pub table: String,
+    /// Pipeline name (`mode:vector` / `mode:hybrid` without one), or "*" when
+    /// aggregated over all pipelines
+    pub pipeline: String,
+    pub count: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    /// Whether p95 is within the requested budget, only present when one was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub within_budget: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct PerformanceReport {
+    pub window_secs: u64,
+    /// One entry per table and pipeline
+    pub by_table_pipeline: Vec<LatencyStats>,
+    /// Per pipeline, over all tables
+    pub by_pipeline: Vec<LatencyStats>,
+    /// Per table, over all pipelines
+    pub by_table: Vec<LatencyStats>,
+}
+
+/// The searches of one tenant, or of sessions without one, over all its
+/// tables and pipelines.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct TenantLatency {
+    /// Tenant id, absent for sessions without a tenant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(flatten)]
+    pub stats: LatencyStats,
+}
+
+type SeriesKey = (Option<Tenant>, String, String);
+
+/// Search latencies over a sliding time window, keyed by tenant, table and
+/// pipeline.
+pub struct LatencyTracker {
+    window: Duration,
+    samples: Mutex<HashMap<SeriesKey, VecDeque<(Instant, Duration)>>>,
+}
+
+impl LatencyTracker {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn record(&self, tenant: Option<&Tenant>, table: &str, pipeline: &str, elapsed: Duration) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        let series = samples.entry((tenant.cloned(), table.to_string(), pipeline.to_string())).or_default();
+        series.push_back((now, elapsed));
+        if series.len() > MAX_SAMPLES {
+            series.pop_front();
+        }
+        self.expire(series, now);
+    }
+
+    /// Latency percentiles of the searches inside the window. With a
+    /// `budget`, every entry reports whether its p95 stays within it.
+    /// `visible` gives the name to report a table under, `None` to leave it out.
+    pub fn report(&self, budget: Option<Duration>, visible: impl Fn(&str) -> Option<String>) -> PerformanceReport {
+        let samples = self.live_samples();
+        let mut by_table_pipeline: HashMap<(String, String), Vec<Duration>> = HashMap::new();
+        for ((_, table, pipeline), series) in samples.iter() {
+            let Some(table) = visible(table) else {
+                continue;
+            };
+            let durations = series.iter().map(|(_, d)| *d);
+            by_table_pipeline.entry((table.clone(), pipeline.clone())).or_default().extend(durations.clone());
+            by_table_pipeline.entry(("*".to_string(), pipeline.clone())).or_default().extend(durations.clone());
+            by_table_pipeline.entry((table.clone(), "*".to_string())).or_default().extend(durations);
+        }
+
+        let mut stats: Vec<LatencyStats> = by_table_pipeline
+            .into_iter()
+            .map(|((table, pipeline), durations)| summarize(table, pipeline, durations, budget))
+            .collect();
+        stats.sort_by(|a, b| (&a.table, &a.pipeline).cmp(&(&b.table, &b.pipeline)));
+
+        let (by_pipeline, rest): (Vec<_>, Vec<_>) = stats.into_iter().partition(|s| s.table == "*");
+        let (by_table, by_table_pipeline): (Vec<_>, Vec<_>) = rest.into_iter().partition(|s| s.pipeline == "*");
+        PerformanceReport {
+            window_secs: self.window.as_secs(),
+            by_table_pipeline,
+            by_pipeline,
+            by_table,
+        }
+    }
+
+    /// Latency percentiles of each tenant's searches inside the window,
+    /// over all its tables and pipelines.
+    pub fn by_tenant(&self) -> Vec<TenantLatency> {
+        let samples = self.live_samples();
+        let mut by_tenant: HashMap<Option<String>, Vec<Duration>> = HashMap::new();
+        for ((tenant, _, _), series) in samples.iter() {
+            let tenant = tenant.as_ref().map(|t| t.id().to_string());
+            by_tenant.entry(tenant).or_default().extend(series.iter().map(|(_, d)| *d));
+        }
+        let mut stats: Vec<TenantLatency> = by_tenant
+            .into_iter()
+            .map(|(tenant, durations)| TenantLatency {
+                tenant,
+                stats: summarize("*".to_string(), "*".to_string(), durations, None),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        stats
+    }
+
+    /// The per tenant, table and pipeline percentiles in the Prometheus text
+    /// format, only `tenant`'s with `Some`. Tables of a tenant are labelled
+    /// with the name the tenant knows them by.
+    pub fn prometheus(&self, tenant: Option<&Tenant>) -> String {
+        let samples = self.live_samples();
+        let mut series: Vec<(String, LatencyStats)> = samples
+            .iter()
+            .filter(|((owner, _, _), _)| tenant.is_none() || owner.as_ref() == tenant)
+            .map(|((owner, table, pipeline), series)| {
+                let (owner, table) = match owner {
+                    Some(owner) => (owner.id(), owner.strip(table).unwrap_or(table)),
+                    None => ("", table.as_str()),
+                };
+                let durations = series.iter().map(|(_, d)| *d).collect();
+                (owner.to_string(), summarize(table.to_string(), pipeline.clone(), durations, None))
+            })
+            .collect();
+        drop(samples);
+        series.sort_by(|a, b| (&a.0, &a.1.table, &a.1.pipeline).cmp(&(&b.0, &b.1.table, &b.1.pipeline)));
+
+        let mut out = String::new();
+        out.push_str("# HELP lancedb_mcp_search_latency_seconds Search latency over the sliding window.\n");
+        out.push_str("# TYPE lancedb_mcp_search_latency_seconds summary\n");
+        for (owner, s) in &series {
+            let mut labels = format!("table=\"{}\",pipeline=\"{}\"", escape(&s.table), escape(&s.pipeline));
+            if !owner.is_empty() {
+                labels = format!("tenant=\"{}\",{}", escape(owner), labels);
+            }
+            for (quantile, ms) in [("0.5", s.p50_ms), ("0.9", s.p90_ms), ("0.95", s.p95_ms), ("0.99", s.p99_ms)] {
+                let _ = writeln!(
+                    out,
+                    "lancedb_mcp_search_latency_seconds{{{},quantile=\"{}\"}} {}",
+                    labels,
+                    quantile,
+                    ms / 1000.0
+                );
+            }
+            let _ = writeln!(out, "lancedb_mcp_search_latency_seconds_count{{{}}} {}", labels, s.count);
+        }
+        out
+    }
+
+    /// The samples, with those that fell out of the window dropped.
+    fn live_samples(&self) -> std::sync::MutexGuard<'_, HashMap<SeriesKey, VecDeque<(Instant, Duration)>>> {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        samples.retain(|_, series| {
+            self.expire(series, now);
+            !series.is_empty()
+        });
+        samples
+    }
+
+    fn expire(&self, series: &mut VecDeque<(Instant, Duration)>, now: Instant) {
+        while let Some((at, _)) = series.front() {
+            if now.duration_since(*at) <= self.window {
+                break;
+            }
+            series.pop_front();
+        }
+    }
+}
+
+fn summarize(table: String, pipeline: String, mut durations: Vec<Duration>, budget: Option<Duration>) -> LatencyStats {
+    durations.sort();
+    let percentile = |p: f64| {
+        // Nearest-rank percentile
+        let rank = ((p * durations.len() as f64).ceil() as usize).clamp(1, durations.len());
+        durations[rank - 1]
+    };
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let p95 = percentile(0.95);
+    LatencyStats {
+        table,
+        pipeline,
+        count: durations.len(),
+        p50_ms: ms(percentile(0.5)),
+        p90_ms: ms(percentile(0.9)),
+        p95_ms: ms(p95),
+        p99_ms: ms(percentile(0.99)),
+        max_ms: ms(*durations.last().expect("series are never empty")),
+        within_budget: budget.map(|budget| p95 <= budget),
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
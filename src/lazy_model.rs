@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::embeddings::EmbeddingModel;
+use crate::error::ErrorCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelState {
+    Loading,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct HealthStatus {
+    /// Whether tools that embed text can run without waiting
+    pub ready: bool,
+    pub model_state: ModelState,
+    /// Embedding model id, once loaded
+    pub model: Option<String>,
+    /// Why loading the model failed
+    pub error: Option<String>,
+    /// Seconds spent loading so far, or in total once loaded
+    pub load_seconds: f64,
+}
+
+/// Load state, with how long loading took once it finished.
+#[derive(Clone)]
+enum Slot {
+    Loading,
+    Ready(Arc<EmbeddingModel>, Duration),
+    Failed(String, Duration),
+}
+
+/// The embedding model, possibly still loading in the background. Callers
+/// that need it wait until it is loaded; the server itself answers right away.
+pub struct LazyModel {
+    slot: watch::Receiver<Slot>,
+    started: Instant,
+}
+
+impl LazyModel {
+    pub fn ready(model: EmbeddingModel) -> Self {
+        Self {
+            slot: watch::channel(Slot::Ready(Arc::new(model), Duration::ZERO)).1,
+            started: Instant::now(),
+        }
+    }
+
+    /// Load the model with `load` on a background task.
+    pub fn spawn(load: impl Future<Output = Result<EmbeddingModel>> + Send + 'static) -> Self {
+        let (slot_tx, slot) = watch::channel(Slot::Loading);
+        let started = Instant::now();
+        tokio::spawn(async move {
+            let result = load.await;
+            let took = started.elapsed();
+            let _ = slot_tx.send(match result {
+                Ok(model) => {
+                    tracing::info!("Embedding model '{}' loaded in {:.1}s", model.model_id(), took.as_secs_f64());
+                    Slot::Ready(Arc::new(model), took)
+                }
+                Err(e) => {
+                    tracing::error!("Failed to load the embedding model: {:#}", e);
+                    Slot::Failed(format!("{:#}", e), took)
+                }
+            });
+        });
+        Self { slot, started }
+    }
+
+    /// The model, waiting for it while it loads.
+    pub async fn get(&self) -> Result<Arc<EmbeddingModel>> {
+        let mut slot = self.slot.clone();
+        let state = match &*slot.borrow() {
+            Slot::Loading => None,
+            state => Some(state.clone()),
+        };
+        let state = match state {
+            Some(state) => state,
+            None => slot
+                .wait_for(|s| !matches!(s, Slot::Loading))
+                .await
+                .map(|s| s.clone())
+                .map_err(|_| anyhow::anyhow!("Loading the embedding model was aborted"))
+                .context(ErrorCode::Internal)?,
+        };
+        match state {
+            Slot::Ready(model, _) => Ok(model),
+            Slot::Failed(e, _) => Err(anyhow::anyhow!("The embedding model failed to load: {}", e)).context(ErrorCode::Internal),
+            Slot::Loading => unreachable!("waited until loaded"),
+        }
+    }
+
+    pub fn health(&self) -> HealthStatus {
+        let (model_state, model, error, took) = match &*self.slot.borrow() {
+            Slot::Loading => (ModelState::Loading, None, None, self.started.elapsed()),
+            Slot::Ready(model, took) => (ModelState::Ready, Some(model.model_id().to_string()), None, *took),
+            Slot::Failed(e, took) => (ModelState::Failed, None, Some(e.clone()), *took),
+        };
+        HealthStatus {
+            ready: model_state == ModelState::Ready,
+            model_state,
+            model,
+            error,
+            load_seconds: took.as_secs_f64(),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Caps on tool calls, applied once across the server, once per tenant and
+/// once per session.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Most tool calls running at once
+    pub max_in_flight: Option<usize>,
+    /// Most tool calls started per minute
+    pub per_minute: Option<u32>,
+}
+
+/// Tool call admission for one scope, the whole server, one tenant or one
+/// session.
+/// Calls over a limit are rejected right away rather than queued, so one
+/// client hammering the server cannot build a backlog everyone else waits
+/// behind.
+pub struct CallBudget {
+    scope: &'static str,
+    in_flight: Option<Arc<Semaphore>>,
+    rate: Option<CallRate>,
+}
+
+/// Token bucket refilled at `per_minute` per minute, holding at most a
+/// minute's worth of calls.
+struct CallRate {
+    per_minute: u32,
+    /// Available calls and when they were last refilled
+    state: Mutex<(f64, Instant)>,
+}
+
+/// Held for the duration of an admitted call.
+pub struct CallPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+/// The budget of every tenant, each capped by the same limits and created
+/// on its first call, so the sessions of one tenant share a single quota.
+pub struct TenantBudgets {
+    limits: Limits,
+    budgets: Mutex<HashMap<String, Arc<CallBudget>>>,
+}
+
+impl CallBudget {
+    pub fn new(scope: &'static str, limits: Limits) -> Arc<Self> {
+        Arc::new(Self {
+            scope,
+            in_flight: limits.max_in_flight.map(|n| Arc::new(Semaphore::new(n))),
+            rate: limits.per_minute.map(|per_minute| CallRate {
+                per_minute,
+                state: Mutex::new((per_minute as f64, Instant::now())),
+            }),
+        })
+    }
+
+    fn try_acquire(&self) -> Result<Option<OwnedSemaphorePermit>, ApiError> {
+        let permit = match &self.in_flight {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().map_err(|_| {
+                ApiError::new(
+                    ErrorCode::RateLimited,
+                    format!("Too many tool calls in flight for this {}, retry when one finishes", self.scope),
+                )
+            })?),
+            None => None,
+        };
+        if let Some(rate) = &self.rate {
+            if let Some(retry_after) = rate.take() {
+                return Err(ApiError::new(
+                    ErrorCode::RateLimited,
+                    format!(
+                        "More than {} tool calls per minute for this {}, retry in {:.1}s",
+                        rate.per_minute, self.scope, retry_after
+                    ),
+                ));
+            }
+        }
+        Ok(permit)
+    }
+
+    /// Give back the rate token of a call admitted here that did not run.
+    fn refund(&self) {
+        if let Some(rate) = &self.rate {
+            rate.put_back();
+        }
+    }
+}
+
+impl TenantBudgets {
+    pub fn new(limits: Limits) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            budgets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The budget of `tenant`, `None` if tenants are not limited.
+    pub fn get(&self, tenant: &str) -> Option<Arc<CallBudget>> {
+        if self.limits.max_in_flight.is_none() && self.limits.per_minute.is_none() {
+            return None;
+        }
+        let mut budgets = self.budgets.lock().unwrap();
+        let budget = budgets.entry(tenant.to_string()).or_insert_with(|| CallBudget::new("tenant", self.limits));
+        Some(budget.clone())
+    }
+}
+
+impl CallRate {
+    /// Take one call, or return the seconds until one is available.
+    fn take(&self) -> Option<f64> {
+        let capacity = self.per_minute as f64;
+        let refill_per_sec = capacity / 60.0;
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let tokens = (state.0 + now.duration_since(state.1).as_secs_f64() * refill_per_sec).min(capacity);
+        *state = (tokens, now);
+        if tokens < 1.0 {
+            return Some((1.0 - tokens) / refill_per_sec);
+        }
+        state.0 -= 1.0;
+        None
+    }
+
+    fn put_back(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.0 = (state.0 + 1.0).min(self.per_minute as f64);
+    }
+}
+
+/// Admit a call against each of `budgets`, narrowest scope first. A call
+/// rejected by any budget uses up none of them.
+pub fn admit(budgets: &[&CallBudget]) -> Result<CallPermit, ApiError> {
+    let mut permits = Vec::with_capacity(budgets.len());
+    for (i, budget) in budgets.iter().enumerate() {
+        match budget.try_acquire() {
+            Ok(permit) => permits.extend(permit),
+            Err(e) => {
+                // The in-flight permits taken so far are released as they drop
+                for admitted in &budgets[..i] {
+                    admitted.refund();
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(CallPermit { _permits: permits })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn rate(per_minute: u32) -> CallRate {
+        CallRate {
+            per_minute,
+            state: Mutex::new((per_minute as f64, Instant::now())),
+        }
+    }
+
+    fn tokens(budget: &CallBudget) -> f64 {
+        budget.rate.as_ref().unwrap().state.lock().unwrap().0
+    }
+
+    #[test]
+    fn bucket_allows_a_minutes_burst_then_waits() {
+        let rate = rate(60);
+        for _ in 0..60 {
+            assert_eq!(rate.take(), None);
+        }
+        let retry_after = rate.take().unwrap();
+        assert!(retry_after > 0.9 && retry_after <= 1.0, "{}", retry_after);
+    }
+
+    #[test]
+    fn bucket_refills_at_the_per_minute_rate() {
+        let rate = rate(60);
+        *rate.state.lock().unwrap() = (0.0, Instant::now() - Duration::from_secs(2));
+        assert_eq!(rate.take(), None);
+        assert_eq!(rate.take(), None);
+        assert!(rate.take().is_some());
+    }
+
+    #[test]
+    fn bucket_holds_at_most_a_minute_of_calls() {
+        let rate = rate(6);
+        *rate.state.lock().unwrap() = (0.0, Instant::now() - Duration::from_secs(3600));
+        for _ in 0..6 {
+            assert_eq!(rate.take(), None);
+        }
+        assert!(rate.take().is_some());
+
+        rate.put_back();
+        rate.put_back();
+        rate.put_back();
+        assert!(rate.state.lock().unwrap().0 <= 6.0);
+    }
+
+    #[test]
+    fn rejected_calls_use_up_no_budget() {
+        let session = CallBudget::new("session", Limits { max_in_flight: Some(1), per_minute: Some(2) });
+        let global = CallBudget::new("server", Limits { max_in_flight: None, per_minute: Some(1) });
+        let permit = admit(&[&session, &global]).unwrap();
+        drop(permit);
+
+        let Err(e) = admit(&[&session, &global]) else {
+            panic!("the server budget should reject the second call");
+        };
+        assert_eq!(e.code, ErrorCode::RateLimited);
+        // The session's rate token and in-flight permit both came back
+        assert!(tokens(&session) >= 1.0);
+        assert_eq!(session.in_flight.as_ref().unwrap().available_permits(), 1);
+    }
+
+    #[test]
+    fn in_flight_permits_are_held_until_the_call_ends() {
+        let session = CallBudget::new("session", Limits { max_in_flight: Some(1), per_minute: None });
+        let permit = admit(&[&session]).unwrap();
+        assert!(admit(&[&session]).is_err());
+        drop(permit);
+        assert!(admit(&[&session]).is_ok());
+    }
+
+    #[test]
+    fn tenants_share_one_budget_each() {
+        assert!(TenantBudgets::new(Limits::default()).get("team-a").is_none());
+
+        let budgets = TenantBudgets::new(Limits { max_in_flight: Some(1), per_minute: None });
+        let a = budgets.get("team-a").unwrap();
+        assert!(Arc::ptr_eq(&a, &budgets.get("team-a").unwrap()));
+        assert!(!Arc::ptr_eq(&a, &budgets.get("team-b").unwrap()));
+    }
+}
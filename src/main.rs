@@ -2,36 +2,162 @@ use anyhow::Result;
 use clap::Parser;
 use rmcp::{
     ServerHandler,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{ServerCapabilities, ServerInfo},
-    tool, tool_handler, tool_router,
+    handler::server::{router::{prompt::PromptRouter, tool::ToolRouter}, tool::ToolCallContext, wrapper::{Json, Parameters}},
+    model::{
+        CallToolRequestParam, CallToolResult, CompleteRequestParam, CompleteResult, Content, InitializeRequestParam, InitializeResult,
+        IntoContents, ListToolsResult, PaginatedRequestParam, ServerCapabilities, ServerInfo,
+    },
+    service::RequestContext,
+    prompt_handler, tool, tool_router, RoleServer,
     ServiceExt, transport::{
         stdio,
         streamable_http_server::{
-            StreamableHttpService,
+            StreamableHttpServerConfig, StreamableHttpService,
             session::local::LocalSessionManager,
         },
     },
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
 use axum::{
-    Router,
+    Extension, Router,
 };
 use tower_http::trace::TraceLayer;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
+mod acl;
+mod audit;
+mod auth;
+mod backup;
+mod chunking;
+mod columns;
+mod compare;
+mod config;
+mod context;
+mod cors;
+mod daemon;
+mod dates;
 mod db;
 mod embeddings;
+mod error;
+mod eval;
+mod expiry;
+mod feedback;
+mod export;
+mod fetch;
+mod health;
+mod http;
+mod images;
+mod import;
+mod ingest;
+mod jobs;
+mod latency;
+mod lazy_model;
+mod limits;
+mod optimize;
+mod paths;
+mod pipeline;
+mod progress;
+mod prompts;
+mod provenance;
+mod query_log;
+mod repl;
+mod replay;
+mod router;
+mod rows;
+mod scratch;
+mod shutdown;
+mod slow_queries;
+mod sse;
+mod storage;
+mod table_config;
+mod tenant;
+mod timeouts;
+mod uds;
+mod usage;
+#[cfg(feature = "local-files")]
+mod watch;
 
-use db::VectorDB;
-use embeddings::EmbeddingModel;
+use acl::{Access, Acl, Permissions};
+use audit::{AuditLog, CallRecord, Outcome};
+use auth::{BearerAuth, TokenFingerprint};
+use columns::ColumnType;
+use compare::ComparedResult;
+use config::ConfigFile;
+use backup::Manifest;
+use dates::DateFilter;
+use db::{CompactionReport, Diagnostics, ModelMismatchPolicy, QueryPlan, ScalarIndexKind, SearchDiagnostics, SearchResult, SearchTarget, TableVersion, VectorDB};
+use embeddings::{
+    ApiOptions, ApiProvider, DType, DEFAULT_AZURE_API_VERSION, Device, EmbeddingModel, LocalBackend, LocalOptions, ModelInfo, Pooling, RetryPolicy, SparseEncoder, Truncation, DEFAULT_EMBEDDING_BATCH_SIZE, DEFAULT_EMBEDDING_BATCH_TOKENS, DEFAULT_EMBEDDING_WORKERS, DEFAULT_EMBEDDING_RETRIES, DEFAULT_EMBEDDING_RETRY_BASE_MS, DEFAULT_QUERY_CACHE_SIZE,
+};
+use error::{ApiError, ErrorCode};
+use eval::EvalReport;
+use expiry::{ExpirySweeper, DEFAULT_EXPIRY_SWEEP_SECS};
+use export::{ExportDir, ExportFormat};
+use feedback::{Feedback, FlaggedChunk};
+use fetch::{UrlFetcher, UrlPolicy};
+use http::HttpOptions;
+use health::HealthReport;
+use import::ImportFormat;
+use lazy_model::LazyModel;
+use limits::{CallBudget, Limits, TenantBudgets};
+use jobs::{JobQueue, JobStatus};
+use latency::{LatencyTracker, PerformanceReport, DEFAULT_LATENCY_WINDOW_SECS};
+use optimize::{IndexOptimizer, MaintenanceScheduler, DEFAULT_OPTIMIZE_INTERVAL_SECS, DEFAULT_OPTIMIZE_ROWS, DEFAULT_VERSION_RETENTION_DAYS};
+use paths::PathRoot;
+use pipeline::Pipelines;
+use context::CallContext;
+use provenance::{Provenance, SourceType};
+use query_log::{QueryLog, QueryStats};
+use router::TableRouter;
+use shutdown::{Drain, DEFAULT_SHUTDOWN_TIMEOUT_SECS};
+use slow_queries::{SlowQuery, SlowQueryLog};
+use storage::{CloudOptions, ConnectOptions, Consistency};
+use scratch::{ScratchTableInfo, ScratchTables, SessionScope, DEFAULT_SCRATCH_TTL_SECS, SCRATCH_TABLE_PREFIX};
+use table_config::{FusionWeights, Quantization, TableConfig, VectorColumn};
+use tenant::{Tenant, TenantPolicy};
+use timeouts::{Run, ToolTimeouts, DEFAULT_TOOL_TIMEOUT_SECS};
+use usage::{AdminReport, UsageTracker};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(long, default_value = "./lancedb_data")]
+    /// TOML file with settings for any of these flags, plus per-table defaults; flags given here win
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Local directory, or s3://, s3+ddb://, gs://, az:// or LanceDB Cloud db:// URI of the database
+    #[arg(long, visible_alias = "db-uri", default_value = "./lancedb_data")]
     db_path: String,
 
+    /// API key for a LanceDB Cloud db:// database
+    #[arg(long, env = "LANCEDB_API_KEY")]
+    lancedb_api_key: Option<String>,
+
+    /// Region of the LanceDB Cloud database
+    #[arg(long, default_value = storage::DEFAULT_CLOUD_REGION)]
+    lancedb_region: String,
+
+    /// LanceDB Cloud endpoint to use instead of the region's, e.g. for a dedicated deployment
+    #[arg(long)]
+    lancedb_host_override: Option<String>,
+
+    /// Object store option for the database as key=value, e.g. aws_access_key_id=..., repeatable
+    #[arg(long)]
+    storage_option: Vec<String>,
+
+    /// Region of the S3 bucket in --db-path
+    #[arg(long)]
+    s3_region: Option<String>,
+
+    /// S3-compatible endpoint, e.g. http://minio:9000 for MinIO
+    #[arg(long)]
+    s3_endpoint: Option<String>,
+
+    /// How soon reads see writes of other replicas: strong (check on every read), eventual, or an interval like 5s
+    #[arg(long, default_value = "strong")]
+    consistency: String,
+
     #[arg(long)]
     embedding_endpoint: Option<String>,
 
@@ -41,33 +167,565 @@ struct Cli {
     #[arg(long, env = "OPENAI_API_KEY")]
     api_key: Option<String>,
 
+    #[arg(long, default_value = "openai")]
+    embedding_provider: String, // openai, ollama, tei, cohere, bedrock, azure
+
+    /// Retries of an embedding API request after a rate limit, server error or failed connection
+    #[arg(long, default_value_t = DEFAULT_EMBEDDING_RETRIES)]
+    embedding_retries: u32,
+
+    /// Wait before the first retry in milliseconds, doubled (with jitter) for each further one
+    #[arg(long, default_value_t = DEFAULT_EMBEDDING_RETRY_BASE_MS)]
+    embedding_retry_base_ms: u64,
+
+    /// Most embedding API requests per second, retries included (default: unlimited)
+    #[arg(long)]
+    embedding_rps: Option<f64>,
+
+    /// Most embedding API requests in flight at once (default: unlimited)
+    #[arg(long)]
+    embedding_concurrency: Option<usize>,
+
+    /// Extra header for embedding API requests as 'Name: value', repeatable
+    #[arg(long)]
+    embedding_header: Vec<String>,
+
+    /// JSON object file of extra embedding API headers, overridden by --embedding-header
+    #[arg(long)]
+    embedding_headers_file: Option<String>,
+
+    /// Path of the embedding endpoint below --embedding-endpoint (default: the provider's, e.g. /v1/embeddings)
+    #[arg(long)]
+    embedding_path: Option<String>,
+
+    /// Azure OpenAI deployment to call (default: the embedding model)
+    #[arg(long)]
+    azure_deployment: Option<String>,
+
+    /// Azure OpenAI api-version query parameter
+    #[arg(long, default_value = DEFAULT_AZURE_API_VERSION)]
+    azure_api_version: String,
+
+    #[arg(long, default_value = "reject")]
+    on_model_mismatch: String, // reject, warn
+
     #[arg(long, default_value = "stdio")]
-    transport: String, // stdio, streamable-http
+    transport: String, // stdio, streamable-http, sse, unix:<socket path>, repl
+
+    /// Interface the streamable-http transport listens on, 0.0.0.0 for all
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
 
     #[arg(long, default_value = "3000")]
     port: u16,
+
+    /// Bearer token HTTP clients must send, repeatable to accept several (default: no authentication)
+    #[arg(long, env = "MCP_AUTH_TOKEN")]
+    auth_token: Vec<String>,
+
+    /// File of accepted bearer tokens, one per line as '<token>' or '<tenant> <token>', repeatable
+    #[arg(long)]
+    auth_token_file: Vec<String>,
+
+    /// Keep each tenant's tables, search metrics, audit log and usage apart, taking the tenant from the bearer token or --tenant-header (HTTP transports only)
+    #[arg(long)]
+    multi_tenant: bool,
+
+    /// Request header naming the tenant of requests whose bearer token names none, with --multi-tenant
+    #[arg(long)]
+    tenant_header: Option<String>,
+
+    /// Tenant that may see every tenant's metrics and usage through admin_report and /metrics, repeatable
+    #[arg(long)]
+    operator_tenant: Vec<String>,
+
+    /// Directory export_table writes its files to (default: lancedb-mcp-exports in the system temp directory)
+    #[arg(long)]
+    export_dir: Option<String>,
+
+    /// TOML file granting tenants and tokens read, write or admin access to tables; anything not granted is denied
+    #[arg(long)]
+    acl: Option<String>,
+
+    /// Origin browser clients may connect from, repeatable; '*' allows any (default: no cross-origin access)
+    #[arg(long)]
+    cors_origin: Vec<String>,
+
+    /// Request header cross-origin clients may send, repeatable (default: content-type, authorization and the MCP headers)
+    #[arg(long)]
+    cors_header: Vec<String>,
+
+    /// Method cross-origin clients may use, repeatable (default: GET, POST, DELETE)
+    #[arg(long)]
+    cors_method: Vec<String>,
+
+    /// Detach from the terminal and run in the background (streamable-http only, requires --log-file)
+    #[arg(long)]
+    daemon: bool,
+
+    /// Write the process id to this file, removed again on exit
+    #[arg(long)]
+    pid_file: Option<String>,
+
+    /// Write logs to this file instead of stderr
+    #[arg(long)]
+    log_file: Option<String>,
+
+    #[arg(long, default_value = "daily")]
+    log_rotation: String, // daily, hourly, never
+
+    /// Log as human-readable text or as one JSON object per line for log shippers
+    #[arg(long, default_value = "text")]
+    log_format: String, // text, json
+
+    /// Log filter, a level or per-module directives such as 'info,lancedb=warn'
+    #[arg(long, env = "RUST_LOG", default_value = "info")]
+    log_level: String,
+
+    /// Hosts add_url may fetch from (comma separated, subdomains included). Empty allows all
+    #[arg(long, value_delimiter = ',')]
+    fetch_allow: Vec<String>,
+
+    /// Hosts add_url must never fetch from (comma separated, subdomains included)
+    #[arg(long, value_delimiter = ',')]
+    fetch_deny: Vec<String>,
+
+    /// Let add_url fetch from loopback, private and link-local addresses, which are refused by default
+    #[arg(long)]
+    fetch_allow_private: bool,
+
+    /// Load the local model from this directory (or .gguf file) instead of the Hugging Face Hub
+    #[arg(long)]
+    model_path: Option<String>,
+
+    /// Never download models, only use the Hugging Face cache or --model-path
+    #[arg(long)]
+    offline: bool,
+
+    /// Hugging Face cache directory for downloaded models (default: $HF_HOME/hub)
+    #[arg(long)]
+    hf_cache_dir: Option<String>,
+
+    /// Hugging Face Hub or mirror to download models from, e.g. https://hf-mirror.com
+    #[arg(long, env = "HF_ENDPOINT")]
+    hf_endpoint: Option<String>,
+
+    /// Start serving right away and load the embedding model in the background; see the health_check tool
+    #[arg(long)]
+    lazy_model: bool,
+
+    /// Proxy for outbound HTTP (embedding API and add_url), e.g. http://proxy:3128 (default: HTTP(S)_PROXY)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// PEM file of extra trusted CA certificates for outbound HTTPS, repeatable
+    #[arg(long)]
+    ca_cert: Vec<String>,
+
+    /// Skip TLS certificate verification of outbound HTTPS. Insecure, for testing only
+    #[arg(long)]
+    insecure_skip_tls_verify: bool,
+
+    /// Only register tools that read, hiding and refusing everything that adds, changes or drops data
+    #[arg(long)]
+    read_only: bool,
+
+    /// Default maximum chunk length in characters of add_url, add_file and --watch-dir
+    #[arg(long, default_value_t = chunking::DEFAULT_CHUNK_SIZE)]
+    chunk_size: usize,
+
+    /// Default characters of overlap between consecutive chunks
+    #[arg(long, default_value_t = chunking::DEFAULT_CHUNK_OVERLAP)]
+    chunk_overlap: usize,
+
+    /// Register only these tools (comma separated, default: all)
+    #[arg(long, value_delimiter = ',')]
+    enable_tools: Vec<String>,
+
+    /// Never register these tools (comma separated)
+    #[arg(long, value_delimiter = ',')]
+    disable_tools: Vec<String>,
+
+    /// Keep the files in this directory indexed, re-ingesting changes and removing deleted files
+    #[arg(long)]
+    watch_dir: Option<String>,
+
+    /// Directory add_file, import_table and add_images may read from (default: --watch-dir). Without either, they cannot read server files
+    #[arg(long)]
+    ingest_root: Option<String>,
+
+    /// Directory backup_database and restore_database may use. Without it, those tools are refused
+    #[arg(long)]
+    backup_dir: Option<String>,
+
+    /// Table that --watch-dir ingests into
+    #[arg(long, default_value = DEFAULT_TABLE_NAME)]
+    #[cfg_attr(not(feature = "local-files"), allow(dead_code))]
+    watch_table: String,
+
+    /// Route searches without a table_name to the tables whose description best matches the query
+    #[arg(long)]
+    auto_route: bool,
+
+    /// Most tool calls running at once across all sessions (default: unlimited)
+    #[arg(long)]
+    max_concurrent_calls: Option<usize>,
+
+    /// Most tool calls started per minute across all sessions (default: unlimited)
+    #[arg(long)]
+    max_calls_per_minute: Option<u32>,
+
+    /// Most tool calls the sessions of one tenant may have running at once together (default: unlimited)
+    #[arg(long)]
+    tenant_max_concurrent_calls: Option<usize>,
+
+    /// Most tool calls the sessions of one tenant may start per minute together (default: unlimited)
+    #[arg(long)]
+    tenant_max_calls_per_minute: Option<u32>,
+
+    /// Most tool calls one session may have running at once (default: unlimited)
+    #[arg(long)]
+    session_max_concurrent_calls: Option<usize>,
+
+    /// Most tool calls one session may start per minute (default: unlimited)
+    #[arg(long)]
+    session_max_calls_per_minute: Option<u32>,
+
+    /// Seconds to wait on shutdown for running tool calls and ingest jobs before exiting anyway
+    #[arg(long, default_value_t = DEFAULT_SHUTDOWN_TIMEOUT_SECS)]
+    shutdown_timeout_secs: u64,
+
+    /// Longest a tool call may run, in seconds (0 disables the timeout)
+    #[arg(long, default_value_t = DEFAULT_TOOL_TIMEOUT_SECS)]
+    tool_timeout_secs: u64,
+
+    /// Timeout of one tool as 'tool=seconds', repeatable, e.g. 'search=10'
+    #[arg(long)]
+    tool_timeout: Vec<String>,
+
+    /// Largest HTTP request body accepted, in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_REQUEST_BYTES)]
+    max_request_bytes: usize,
+
+    /// Append every tool call, and the full arguments of successful mutating calls, to this JSONL file
+    #[arg(long)]
+    audit_log: Option<String>,
+
+    /// Log tool calls that take at least this many milliseconds, with a timing breakdown for searches
+    #[arg(long)]
+    slow_query_ms: Option<u64>,
+
+    /// Also store the slow calls in the internal _mcp_slow_queries table (requires --slow-query-ms)
+    #[arg(long, requires = "slow_query_ms")]
+    slow_query_table: bool,
+
+    /// Record every search, with its query text, top results and latency, in the internal _mcp_query_log table for query_stats
+    #[arg(long)]
+    query_log: bool,
+
+    /// JSON file defining named retrieval pipelines selectable with the search `pipeline` parameter
+    #[arg(long)]
+    pipelines: Option<String>,
+
+    /// Add new rows to a table's indices once this many were written to it (0 disables index optimization)
+    #[arg(long, default_value_t = DEFAULT_OPTIMIZE_ROWS)]
+    index_optimize_rows: usize,
+
+    /// Also add any new rows to the indices every this many seconds (0 disables the schedule)
+    #[arg(long, default_value_t = DEFAULT_OPTIMIZE_INTERVAL_SECS)]
+    index_optimize_interval_secs: u64,
+
+    /// Compact, clean up old versions of and reindex every table on this schedule, e.g. 30m, 6h or 1d (off by default)
+    #[arg(long)]
+    maintenance_interval: Option<String>,
+
+    /// UTC time of day (HH:MM) scheduled maintenance is aligned to, e.g. 03:00 with a 1d interval
+    #[arg(long)]
+    maintenance_at: Option<String>,
+
+    /// Days of old table versions scheduled maintenance keeps for time travel
+    #[arg(long, default_value_t = DEFAULT_VERSION_RETENTION_DAYS)]
+    maintenance_retain_days: f64,
+
+    /// Rows per fragment scheduled compaction aims for (LanceDB's default when unset)
+    #[arg(long)]
+    maintenance_target_rows: Option<usize>,
+
+    /// Delete chunks past their expires_at every this many seconds (0 disables the sweep; purge_expired still works)
+    #[arg(long, default_value_t = DEFAULT_EXPIRY_SWEEP_SECS)]
+    expiry_sweep_secs: u64,
+
+    /// Sliding window, in seconds, over which search latency percentiles are reported
+    #[arg(long, default_value_t = DEFAULT_LATENCY_WINDOW_SECS)]
+    latency_window_secs: u64,
+
+    /// Number of local embedding model instances, so concurrent requests don't wait for each other
+    #[arg(long, default_value_t = DEFAULT_EMBEDDING_WORKERS)]
+    embedding_workers: usize,
+
+    /// Local engine: candle, onnx, llama-cpp or fastembed (default: candle, or fastembed in builds without candle)
+    #[arg(long)]
+    backend: Option<String>,
+
+    /// Device of the local model: cpu, cuda or metal (default: the GPU this build supports, if present)
+    #[arg(long)]
+    device: Option<String>,
+
+    /// Precision of the local model: float32, float16 or bfloat16
+    #[arg(long, default_value = "float32")]
+    dtype: String,
+
+    /// Pooling of the local model: mean, cls or last-token (default: the model family's own)
+    #[arg(long)]
+    pooling: Option<String>,
+
+    /// Maximum number of texts embedded in one batch
+    #[arg(long, default_value_t = DEFAULT_EMBEDDING_BATCH_SIZE)]
+    embedding_batch_size: usize,
+
+    /// Maximum number of tokens embedded in one batch (estimated for embedding APIs)
+    #[arg(long, default_value_t = DEFAULT_EMBEDDING_BATCH_TOKENS)]
+    embedding_batch_tokens: usize,
+
+    /// Cut texts longer than this many tokens before embedding them (default: no limit)
+    #[arg(long)]
+    max_tokens: Option<usize>,
+
+    #[arg(long, default_value = "head")]
+    truncation: String, // head, tail, middle
+
+    /// Keep only the first N dimensions of every embedding, renormalized (Matryoshka models)
+    #[arg(long)]
+    embedding_dimensions: Option<usize>,
+
+    /// Scale every embedding, from an API or a local model, to unit length
+    #[arg(long)]
+    normalize_embeddings: bool,
+
+    /// Instruction template for search queries, `{text}` marking the query (default: the model family's own, "" for none)
+    #[arg(long)]
+    query_instruction: Option<String>,
+
+    /// Instruction template for stored documents, `{text}` marking the document (default: the model family's own, "" for none)
+    #[arg(long)]
+    document_instruction: Option<String>,
+
+    /// Number of recent query embeddings to cache (0 disables the cache)
+    #[arg(long, default_value_t = DEFAULT_QUERY_CACHE_SIZE)]
+    query_cache_size: usize,
+
+    /// Sparse (SPLADE) model whose vectors are stored with every write and
+    /// used by hybrid search, e.g. prithivida/Splade_PP_en_v1 (requires the fastembed feature)
+    #[arg(long)]
+    sparse_model: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Re-execute the mutating operations of an audit log against --db-path
+    Replay {
+        /// Path of the audit log to replay
+        log: String,
+
+        /// Only list the operations that would be applied
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write a consistent, checksummed backup of every table in --db-path to a new directory
+    Backup {
+        /// Directory to create
+        output: String,
+    },
+    /// Restore the tables of a backup directory into --db-path
+    Restore {
+        /// Directory written by `backup` or the backup_database tool
+        input: String,
+
+        /// Replace tables that already exist
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Score searches of a table in --db-path against a JSONL file of queries and the documents they should find
+    Evaluate {
+        /// JSONL file with one {"query": ..., "expected": [names or ids]} per line
+        cases: String,
+
+        /// Table to search
+        #[arg(long, default_value = DEFAULT_TABLE_NAME)]
+        table: String,
+
+        /// Number of results scored per query
+        #[arg(long, default_value_t = DEFAULT_EVAL_K)]
+        k: usize,
+
+        /// Search mode, 'vector' or 'hybrid' (default: as search decides)
+        #[arg(long)]
+        mode: Option<String>,
+
+        /// Retrieval pipeline from --pipelines to evaluate
+        #[arg(long)]
+        pipeline: Option<String>,
+    },
+    /// Write the rows of a table in --db-path to a Parquet or JSONL file
+    Export {
+        /// Table to export
+        table: String,
+
+        /// Output file; a .parquet or .jsonl extension picks the format
+        output: String,
+
+        /// 'parquet' or 'jsonl', when the output file's extension does not say
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Include the embedding vectors
+        #[arg(long)]
+        with_vectors: bool,
+    },
 }
 
 const DEFAULT_TABLE_NAME: &str = "knowledge_base";
+/// Weight of chunk importance in search scores when neither the request nor the table sets one.
+const DEFAULT_IMPORTANCE_WEIGHT: f32 = 0.1;
+/// Candidates fetched per requested result when a date filter drops results
+/// after the search, and how much more each retry fetches.
+const DATE_FILTER_CANDIDATE_FACTOR: usize = 4;
+/// Most candidates a date-filtered search reads before returning fewer results
+/// than asked for.
+const MAX_DATE_FILTER_CANDIDATES: usize = 10_000;
+/// Most results one `search` call returns; larger result sets are paged
+/// with narrower queries or filters.
+const MAX_SEARCH_LIMIT: usize = 1000;
+/// Results scored per query by `evaluate_retrieval` unless it says otherwise.
+const DEFAULT_EVAL_K: usize = 10;
+/// Most texts one `embed` call accepts, keeping responses a sane size.
+const MAX_EMBED_TEXTS: usize = 64;
+/// Largest HTTP request body accepted unless --max-request-bytes says otherwise.
+const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+/// The tools --read-only keeps. Listed rather than taken from the read-only
+/// annotations, which also cover tools that write files on the server
+/// (export_table, backup_database) without touching the tables.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "search",
+    "compare_search",
+    "explain_query",
+    "evaluate_retrieval",
+    "embed",
+    "list_tables",
+    "describe_table",
+    "get_table_config",
+    "list_table_versions",
+    "list_scratch_tables",
+    "get_job_status",
+    "list_jobs",
+    "model_info",
+    "health_check",
+    "performance_report",
+    "admin_report",
+    "query_stats",
+    "feedback_report",
+];
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
 struct ApiResponse<T> {
     code: i32,
     message: String,
     data: Option<T>,
+    /// Error category, only present on failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorCode>,
+    /// Whether retrying the same call may succeed, only present on failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retryable: Option<bool>,
+    /// Timings and index use of a search, only present when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<SearchDiagnostics>,
 }
 
 impl<T> ApiResponse<T> {
     fn success(data: T) -> Self {
+        Self::success_with_message(data, "success".to_string())
+    }
+
+    fn success_with_message(data: T, message: String) -> Self {
         Self {
             code: 0,
-            message: "success".to_string(),
+            message,
             data: Some(data),
+            error: None,
+            retryable: None,
+            diagnostics: None,
+        }
+    }
+
+    fn with_diagnostics(self, diagnostics: Option<SearchDiagnostics>) -> Self {
+        Self { diagnostics, ..self }
+    }
+
+    fn error(err: ApiError) -> Self {
+        Self {
+            code: err.code.code(),
+            message: err.message,
+            data: None,
+            error: Some(err.code),
+            retryable: Some(err.code.retryable()),
+            diagnostics: None,
+        }
+    }
+}
+
+/// Failed tool calls are returned as an error result whose text is the
+/// serialized `ApiResponse::error`.
+impl IntoContents for ApiError {
+    fn into_contents(self) -> Vec<Content> {
+        let resp = ApiResponse::<()>::error(self);
+        let text = serde_json::to_string_pretty(&resp).unwrap_or_else(|_| resp.message.clone());
+        vec![Content::text(text)]
+    }
+}
+
+/// Result of `add_documents`: a summary when the documents were added
+/// directly, or the queued job when `async` was set.
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
+enum AddDocumentsResult {
+    Added(String),
+    Queued(QueuedJob),
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct QueuedJob {
+    job_id: String,
+    documents: usize,
+    chunks: usize,
+}
+
+/// Build the metadata stored with every chunk of a document: the caller's
+/// custom metadata plus the document name, optional description and optional
+/// importance (which `add_texts` moves into its own column).
+fn document_metadata(
+    name: &str,
+    description: Option<&str>,
+    importance: Option<f32>,
+    metadata: Option<serde_json::Value>,
+) -> serde_json::Value {
+    let mut metadata = metadata.unwrap_or_else(|| serde_json::json!({}));
+    if let serde_json::Value::Object(ref mut map) = metadata {
+        map.insert("name".to_string(), serde_json::Value::String(name.to_string()));
+        if let Some(desc) = description {
+            map.insert("description".to_string(), serde_json::Value::String(desc.to_string()));
+        }
+        if let Some(importance) = importance {
+            map.insert("importance".to_string(), serde_json::json!(importance));
         }
     }
+    metadata
 }
 
-#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 struct DocumentInput {
     #[schemars(description = "The name of the document")]
     name: String,
@@ -77,141 +735,3081 @@ struct DocumentInput {
     chunks: Vec<String>,
     #[schemars(description = "Additional custom metadata shared by all chunks in this document")]
     metadata: Option<serde_json::Value>,
+    #[schemars(description = "Importance of the document, typically 0.0-1.0 (default: 0). Boosts its chunks in search ranking, e.g. for curated canonical docs")]
+    importance: Option<f32>,
+    #[schemars(description = "When the document's chunks are deleted, e.g. 'in 3 days' or an RFC 3339 time (default: never)")]
+    expires_at: Option<String>,
 }
 
-#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 struct AddDocumentsRequest {
     #[schemars(description = "The name of the table to add documents to (default: knowledge_base)")]
     table_name: Option<String>,
     #[schemars(description = "List of documents to add")]
     documents: Vec<DocumentInput>,
+    #[schemars(description = "Vector column to store the embeddings in, added to an existing table on first use, e.g. 'title_vector' (default: vector)")]
+    vector_column: Option<String>,
+    #[serde(rename = "async")]
+    #[schemars(description = "Run the ingest in the background and return a job id immediately (default: false). Poll progress with get_job_status")]
+    run_async: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct ImageInput {
+    #[schemars(description = "Path of the image file on the server, relative to its ingest root. Give either path or data")]
+    path: Option<String>,
+    #[schemars(description = "The image encoded as base64, or a data: URL. Give either path or data")]
+    data: Option<String>,
+    #[schemars(description = "Name of the image (default: the file name; required with data)")]
+    name: Option<String>,
+    #[schemars(description = "What the image shows. Returned in place of chunk text and matched by keyword search")]
+    description: Option<String>,
+    #[schemars(description = "Additional custom metadata of the image")]
+    metadata: Option<serde_json::Value>,
+    #[schemars(description = "Importance of the image, typically 0.0-1.0 (default: 0). Boosts it in search ranking")]
+    importance: Option<f32>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct AddImagesRequest {
+    #[schemars(description = "The name of the table to add the images to (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "PNG, JPEG, GIF, WebP or BMP images to add")]
+    images: Vec<ImageInput>,
+    #[schemars(description = "Vector column to store the embeddings in, as for add_documents (default: vector)")]
+    vector_column: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct GetJobStatusRequest {
+    #[schemars(description = "The job id returned by an async add_documents call")]
+    job_id: String,
 }
 
-#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 struct SearchRequest {
-    #[schemars(description = "The name of the table to search in (default: knowledge_base)")]
+    #[schemars(description = "The name of the table to search in (default: knowledge_base, or the best matching tables when the server routes queries)")]
     table_name: Option<String>,
     #[schemars(description = "The query text")]
     query: String,
-    #[schemars(description = "Number of results to return")]
+    #[schemars(description = "Number of results to return (default: 5, at most 1000)")]
     limit: Option<usize>,
+    #[schemars(description = "Search mode: 'vector' or 'hybrid' (vector + full-text). Defaults to 'hybrid' when the table has fusion settings, otherwise 'vector'")]
+    mode: Option<String>,
+    #[schemars(description = "Override the table's hybrid fusion preset for this query: 'code', 'prose' or 'balanced'")]
+    fusion_preset: Option<String>,
+    #[schemars(description = "Override the weight of the vector score in hybrid search (use together with keyword_weight)")]
+    vector_weight: Option<f32>,
+    #[schemars(description = "Override the weight of the keyword (BM25) score in hybrid search (use together with vector_weight)")]
+    keyword_weight: Option<f32>,
+    #[schemars(description = "Override how strongly chunk importance is added to the relevance score (default: table setting or 0.1; 0 disables)")]
+    importance_weight: Option<f32>,
+    #[schemars(description = "Override the weight of the sparse (SPLADE) score in hybrid search (default: table setting or 0.3; 0 disables). Only used when the server runs a --sparse-model")]
+    sparse_weight: Option<f32>,
+    #[schemars(description = "Name of a retrieval pipeline configured on the server, e.g. 'precise'. Cannot be combined with mode")]
+    pipeline: Option<String>,
+    #[schemars(description = "Only return chunks dated on or after this: ISO 8601 / RFC 3339, epoch seconds or milliseconds, DD/MM/YYYY or MM/DD/YYYY, or relative ('yesterday', '3 days ago', 'last tuesday')")]
+    after: Option<String>,
+    #[schemars(description = "Only return chunks dated before this, same formats as after")]
+    before: Option<String>,
+    #[schemars(description = "Metadata field holding the date that after/before apply to (default: ingested_at, the ingestion time)")]
+    date_field: Option<String>,
+    #[schemars(description = "Locale deciding whether ambiguous numeric dates are day or month first, e.g. 'en-US' or 'en-GB'")]
+    date_locale: Option<String>,
+    #[schemars(description = "Search the table as it was at this version (from list_table_versions) rather than now, for reproducible results")]
+    version: Option<u64>,
+    #[schemars(description = "Search the table as it was at this time, same formats as after. Cannot be combined with version")]
+    as_of: Option<String>,
+    #[schemars(description = "Vector column to search, e.g. one filled by another model with reembed_table (default: vector)")]
+    vector_column: Option<String>,
+    #[schemars(description = "Only return rows embedded from 'text' or from an 'image' (default: both). With a CLIP model, a text query finds matching images")]
+    modality: Option<String>,
+    #[schemars(description = "On a quantized table, rescore the top candidates with the full-precision vectors (default: table setting or true)")]
+    rescore: Option<bool>,
+    #[schemars(description = "Add a diagnostics object to the response: time spent embedding, in the database and reranking, the distance type and whether an ANN index was used (default: false)")]
+    diagnostics: Option<bool>,
 }
 
-#[derive(Clone)]
-struct LanceDBServer {
-    db: Arc<VectorDB>,
-    model: Arc<Mutex<EmbeddingModel>>,
-    tool_router: ToolRouter<Self>,
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct CompareSearchRequest {
+    #[schemars(description = "The first table to search (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "The second table to search, e.g. the same documents embedded with another model or chunked differently")]
+    compare_table: String,
+    #[schemars(description = "The query text")]
+    query: String,
+    #[schemars(description = "Number of results to take from each table (default: 5, at most 1000)")]
+    limit: Option<usize>,
+    #[schemars(description = "Search mode for both tables: 'vector' or 'hybrid' (default: as in search, per table)")]
+    mode: Option<String>,
+    #[schemars(description = "Name of a retrieval pipeline configured on the server to use for both tables. Cannot be combined with mode")]
+    pipeline: Option<String>,
 }
 
-#[tool_router]
-impl LanceDBServer {
-    fn new(db: Arc<VectorDB>, model: Arc<Mutex<EmbeddingModel>>) -> Self {
-        Self {
-            db,
-            model,
-            tool_router: Self::tool_router(),
-        }
-    }
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct CompareSearchResult {
+    table_a: String,
+    table_b: String,
+    /// Results found by both searches
+    overlap: usize,
+    /// The results of both tables, taken from each in turn
+    results: Vec<ComparedResult>,
+}
 
-    #[tool(description = "Add documents to a LanceDB table. Supports batching multiple documents, where each document can have multiple chunks sharing the same metadata.")]
-    async fn add_documents(&self, Parameters(req): Parameters<AddDocumentsRequest>) -> Result<String, String> {
-        let table_name = req.table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME);
-        
-        let mut all_texts = Vec::new();
-        let mut all_metadatas = Vec::new();
-        let mut total_chunks = 0;
-        let total_docs = req.documents.len();
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct EvaluateRetrievalRequest {
+    #[schemars(description = "The table to search (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "JSONL, one {\"query\": \"...\", \"expected\": [\"document name or chunk id\", ...]} per line (at most 1000 lines)")]
+    cases: String,
+    #[schemars(description = "Number of results scored per query (default: 10, at most 1000)")]
+    k: Option<usize>,
+    #[schemars(description = "Search mode: 'vector' or 'hybrid' (default: as search decides)")]
+    mode: Option<String>,
+    #[schemars(description = "Name of a retrieval pipeline configured on the server to evaluate. Cannot be combined with mode")]
+    pipeline: Option<String>,
+    #[schemars(description = "Vector column to search (default: vector)")]
+    vector_column: Option<String>,
+    #[schemars(description = "Evaluate the table as it was at this version (from list_table_versions)")]
+    version: Option<u64>,
+}
 
-        for doc in req.documents {
-            // Prepare base metadata
-            let mut base_metadata = doc.metadata.unwrap_or_else(|| serde_json::json!({}));
-            
-            // Inject name and description into metadata
-            if let serde_json::Value::Object(ref mut map) = base_metadata {
-                map.insert("name".to_string(), serde_json::Value::String(doc.name.clone()));
-                if let Some(desc) = &doc.description {
-                    map.insert("description".to_string(), serde_json::Value::String(desc.clone()));
-                }
-            }
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct ResultFeedback {
+    #[schemars(description = "The id of the search result")]
+    id: String,
+    #[schemars(description = "Whether the result helped answer the query")]
+    helpful: bool,
+    #[schemars(description = "1-based position of the result in the search results")]
+    rank: Option<u32>,
+    #[schemars(description = "The score the search gave the result")]
+    score: Option<f32>,
+    #[schemars(description = "Why the result did or did not help")]
+    comment: Option<String>,
+}
 
-            for chunk in doc.chunks {
-                all_texts.push(chunk);
-                all_metadatas.push(base_metadata.clone());
-                total_chunks += 1;
-            }
-        }
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct RecordFeedbackRequest {
+    #[schemars(description = "The table that was searched (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "The query text of the search")]
+    query: String,
+    #[schemars(description = "Verdicts on results of the search (at most 100)")]
+    feedback: Vec<ResultFeedback>,
+}
 
-        let model = self.model.lock().await;
-        
-        self.db.add_texts(table_name, all_texts, all_metadatas, &*model)
-            .await
-            .map_err(|e| e.to_string())?;
-            
-        let msg = format!("Successfully added {} documents ({} chunks) to table '{}'", total_docs, total_chunks, table_name);
-        let resp = ApiResponse::success(msg);
-        
-        serde_json::to_string_pretty(&resp).map_err(|e| e.to_string())
-    }
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct FeedbackReportRequest {
+    #[schemars(description = "The table to report on (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Report chunks marked unhelpful at least this many times (default: 2)")]
+    min_unhelpful: Option<usize>,
+    #[schemars(description = "Report chunks whose mean rank when marked unhelpful is at most this, i.e. that ranked high (default: 3)")]
+    max_rank: Option<f32>,
+    #[schemars(description = "Number of chunks to report (default: 20, at most 100)")]
+    limit: Option<usize>,
+}
 
-    #[tool(description = "Search for similar documents in a LanceDB table using semantic vector search.")]
-    async fn search(&self, Parameters(req): Parameters<SearchRequest>) -> Result<String, String> {
-        let table_name = req.table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME);
-        let model = self.model.lock().await;
-        let limit = req.limit.unwrap_or(5);
-        
-        let results = self.db.search(table_name, &req.query, limit, &*model)
-            .await
-            .map_err(|e| e.to_string())?;
-            
-        let resp = ApiResponse::success(results);
-        serde_json::to_string_pretty(&resp).map_err(|e| e.to_string())
-    }
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct QueryStatsRequest {
+    #[schemars(description = "Only summarize searches of this table (default: all tables)")]
+    table_name: Option<String>,
+    #[schemars(description = "Only summarize searches made since this time: ISO 8601 / RFC 3339, epoch seconds or milliseconds, or relative ('7 days ago')")]
+    since: Option<String>,
+    #[schemars(description = "Number of queries to list as top and zero-result queries (default: 10, at most 100)")]
+    top: Option<usize>,
+}
 
-    #[tool(description = "List all tables in the LanceDB database.")]
-    async fn list_tables(&self) -> Result<String, String> {
-        let tables = self.db.list_tables()
-            .await
-            .map_err(|e| e.to_string())?;
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct ExplainQueryRequest {
+    #[schemars(description = "The table to plan the search on (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "The query text. Without one, the plan is for a plain scan with the filter")]
+    query: Option<String>,
+    #[schemars(description = "SQL condition on the table's columns, e.g. \"source = 'wiki' AND page > 3\", joined with the search's own filters")]
+    filter: Option<String>,
+    #[schemars(description = "Number of results the search would return (default: 5, at most 1000)")]
+    limit: Option<usize>,
+    #[schemars(description = "'vector' (default) or 'hybrid', which also plans the full-text search")]
+    mode: Option<String>,
+    #[schemars(description = "Vector column to search (default: vector)")]
+    vector_column: Option<String>,
+    #[schemars(description = "Only 'text' or 'image' rows (default: both)")]
+    modality: Option<String>,
+    #[schemars(description = "Include more detail, such as the columns each step reads (default: false)")]
+    verbose: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct TableNameRequest {
+    #[schemars(description = "The name of the table (default: knowledge_base)")]
+    table_name: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct SwapTablesRequest {
+    #[schemars(description = "The live table whose contents are replaced")]
+    table_name: String,
+    #[schemars(description = "A fully built table that takes the live table's place. It no longer exists afterwards")]
+    replacement_table: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct EmbedRequest {
+    #[schemars(description = "Texts to embed (at most 64)")]
+    texts: Vec<String>,
+    #[schemars(description = "Embed the texts as 'query' (default) or 'document'; models with instructions embed search queries and stored chunks differently")]
+    input_type: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct EmbedResult {
+    model: String,
+    dimension: usize,
+    /// One vector per input text, in order
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct ExportTableRequest {
+    #[schemars(description = "The table to export (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "'jsonl' (default), one JSON object per row with metadata as an object, or 'parquet'")]
+    format: Option<String>,
+    #[schemars(description = "Include the embedding vectors (default: false)")]
+    include_vectors: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct ExportResult {
+    table_name: String,
+    format: String,
+    rows: usize,
+    /// Where the file was written on the server
+    path: String,
+    /// URL path to download the file from over the HTTP transports, for 24 hours
+    download: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct ImportTableRequest {
+    #[schemars(description = "The table to import into (default: knowledge_base); created if it does not exist")]
+    table_name: Option<String>,
+    #[schemars(description = "Path of the Parquet, CSV (with a header row) or JSONL file on the server, relative to its ingest root")]
+    path: String,
+    #[schemars(description = "'parquet', 'csv' or 'jsonl' (default: from the file extension)")]
+    format: Option<String>,
+    #[schemars(description = "Column holding the text of each row (default: text). Rows without text are skipped")]
+    text_column: Option<String>,
+    #[schemars(description = "Column holding the document name (default: the file name)")]
+    name_column: Option<String>,
+    #[schemars(description = "Column holding precomputed embeddings from the server's embedding model (default: vector, if the file has it). Without one the text is embedded")]
+    vector_column: Option<String>,
+    #[schemars(description = "Embed the text even if the file has a vector column (default: false)")]
+    reembed: Option<bool>,
+    #[schemars(description = "Columns to keep as metadata (default: all other columns). A 'metadata' column holding an object, as written by export_table, is merged in")]
+    metadata_columns: Option<Vec<String>>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct ImportResult {
+    table_name: String,
+    /// Rows added to the table
+    rows: usize,
+    /// Rows left out because their text was missing or blank
+    skipped: usize,
+    /// Whether the text was embedded, rather than the file's vectors used
+    embedded: bool,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct ReembedTableRequest {
+    #[schemars(description = "The table to re-embed (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "New table to write the re-embedded rows to, leaving table_name as it is. Without it the rows are built into a hidden table that then replaces table_name's contents in one step")]
+    target_table: Option<String>,
+    #[schemars(description = "Fill this vector column of table_name in place instead, embedding only the rows without a vector in it, e.g. to compare the server's model with the one behind 'vector'. The column is added on first use. Cannot be combined with target_table")]
+    vector_column: Option<String>,
+    #[serde(rename = "async")]
+    #[schemars(description = "Run in the background and return a job id immediately (default: false). Poll progress with get_job_status")]
+    run_async: Option<bool>,
+}
+
+/// The outcome of `reembed_table`: the rows re-embedded, or the queued job
+/// when `async` was set.
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
+enum ReembedTableResult {
+    Done(ReembedSummary),
+    Queued { job_id: String },
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct ReembedSummary {
+    /// The table now holding the re-embedded rows
+    table_name: String,
+    rows: usize,
+    embedding_model: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct OptimizeTableRequest {
+    #[schemars(description = "The table to optimize (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Rows per data fragment to compact towards (default: LanceDB's, 1048576)")]
+    target_rows_per_fragment: Option<usize>,
+    #[schemars(description = "Days of old versions to keep for list_table_versions, time-travel search and restore_table_version (default: 7; 0 keeps only the current version)")]
+    retain_versions_days: Option<f64>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct DeleteDocumentRequest {
+    #[schemars(description = "The table holding the document (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Name of the document whose chunks to delete")]
+    document_name: String,
+    #[schemars(description = "Delete the chunks outright instead of tombstoning them; they cannot be undeleted (default: false)")]
+    hard: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct UndeleteRequest {
+    #[schemars(description = "The table holding the document (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Name of the deleted document to restore")]
+    document_name: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct PurgeRequest {
+    #[schemars(description = "The table to purge (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Only purge this deleted document (default: every deleted chunk of the table)")]
+    document_name: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct DocumentChunksResult {
+    table_name: String,
+    document_name: String,
+    /// Chunks whose state changed
+    chunks: usize,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct PurgeResult {
+    table_name: String,
+    /// Chunks deleted for good
+    deleted: usize,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct AddColumnRequest {
+    #[schemars(description = "The table to add the column to (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Name of the new metadata field: lowercase letters, digits and underscores")]
+    column: String,
+    #[schemars(description = "Value of the field for existing rows without it and for rows written later without it; a string, number or boolean (default: null)")]
+    default: Option<serde_json::Value>,
+    #[schemars(description = "'string', 'integer', 'float' or 'boolean' (default: the type of the default value; required when it is null)")]
+    column_type: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct AddColumnResult {
+    table_name: String,
+    column: String,
+    column_type: String,
+    /// Rows in the table
+    rows: usize,
+    /// Rows whose metadata already had the field and kept its value instead
+    /// of the default
+    from_metadata: usize,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct CreateScalarIndexRequest {
+    #[schemars(description = "The table to index (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "A metadata key such as 'name', 'source' or 'created_at', or the 'id' or 'importance' column")]
+    column: String,
+    #[schemars(description = "'btree' for keys with many distinct values and range filters such as dates, 'bitmap' for keys with few distinct values such as a source or category (default: btree)")]
+    index_type: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct ScalarIndexResult {
+    table_name: String,
+    column: String,
+    index_type: String,
+    /// Rows whose metadata key was copied into the indexed column; 0 when
+    /// the column already existed
+    filled_rows: usize,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct RestoreTableVersionRequest {
+    #[schemars(description = "The table to roll back (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "The version to restore, from list_table_versions")]
+    version: u64,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+struct TableDescription {
+    table_name: String,
+    rows: usize,
+    /// Current version and when it was written
+    version: TableVersion,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedding_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedding_dim: Option<usize>,
+    /// Vector columns besides `vector` and the model behind each
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vector_columns: Option<BTreeMap<String, VectorColumn>>,
+    /// Sparse model behind the table's sparse vectors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sparse_model: Option<String>,
+    /// How the vectors are quantized for search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quantization: Option<Quantization>,
+    /// Whether quantized searches rescore with the full-precision vectors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rescore: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct BackupDatabaseRequest {
+    #[schemars(description = "Directory to write the backup to, relative to the server's backup directory; must not exist yet")]
+    path: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct RestoreDatabaseRequest {
+    #[schemars(description = "Directory holding a backup written by backup_database, relative to the server's backup directory")]
+    path: String,
+    #[schemars(description = "Replace tables that already exist (default: false, fail without restoring anything)")]
+    overwrite: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct PerformanceReportRequest {
+    #[schemars(description = "Latency budget in milliseconds; every entry then reports whether its p95 stays within it")]
+    budget_ms: Option<f64>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct CreateScratchTableRequest {
+    #[schemars(description = "Seconds until the table is dropped even if the session is still open (default: 3600)")]
+    ttl_seconds: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct SetTableConfigRequest {
+    #[schemars(description = "The name of the table to configure (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Hybrid fusion preset: 'code' (keywords weigh more), 'prose' (vectors weigh more) or 'balanced'")]
+    fusion_preset: Option<String>,
+    #[schemars(description = "Explicit vector score weight, overrides the preset (use together with keyword_weight)")]
+    vector_weight: Option<f32>,
+    #[schemars(description = "Explicit keyword score weight, overrides the preset (use together with vector_weight)")]
+    keyword_weight: Option<f32>,
+    #[schemars(description = "Language used by the full-text index for stemming and stop words, e.g. 'English', 'German', 'French'")]
+    fts_language: Option<String>,
+    #[schemars(description = "Whether the full-text index stems tokens (default: true)")]
+    fts_stem: Option<bool>,
+    #[schemars(description = "What the table contains. Used to route searches that do not name a table")]
+    description: Option<String>,
+    #[schemars(description = "How strongly chunk importance is added to the relevance score in searches on this table (default: 0.1)")]
+    importance_weight: Option<f32>,
+    #[schemars(description = "Weight of the sparse (SPLADE) score in hybrid searches on this table, next to vector_weight and keyword_weight (default: 0.3). Only used when the server runs a --sparse-model")]
+    sparse_weight: Option<f32>,
+    #[schemars(description = "Quantization of the table's vectors: 'int8' (scalar-quantized index, built once the table has 256 rows), 'binary' (one bit per dimension, searched by Hamming distance) or 'none'. Set it before the first add to create the table quantized")]
+    quantization: Option<String>,
+    #[schemars(description = "Whether quantized searches rescore their top candidates with the full-precision vectors (default: true)")]
+    rescore: Option<bool>,
+}
+
+/// Resolve explicit weights or a preset name into fusion weights. `Ok(None)`
+/// when neither is given.
+fn resolve_fusion(
+    preset: Option<&str>,
+    vector_weight: Option<f32>,
+    keyword_weight: Option<f32>,
+) -> Result<Option<FusionWeights>, ApiError> {
+    match (vector_weight, keyword_weight) {
+        (Some(vector), Some(keyword)) => {
+            if vector < 0.0 || keyword < 0.0 || vector + keyword <= 0.0 {
+                return Err(ApiError::invalid_argument("Fusion weights must be non-negative and not both zero"));
+            }
+            return Ok(Some(FusionWeights { vector, keyword, sparse: None }));
+        }
+        (None, None) => {}
+        _ => return Err(ApiError::invalid_argument("vector_weight and keyword_weight must be given together")),
+    }
+    match preset {
+        Some(name) => FusionWeights::preset(name).map(Some).ok_or_else(|| {
+            ApiError::invalid_argument(format!("Unknown fusion preset '{}', expected one of {:?}", name, FusionWeights::PRESETS))
+        }),
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct AddUrlRequest {
+    #[schemars(description = "The name of the table to add the page to (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "The http(s) URL of the page to fetch")]
+    url: String,
+    #[schemars(description = "Optional document name (default: the page title, or the URL)")]
+    name: Option<String>,
+    #[schemars(description = "Optional description of the document")]
+    description: Option<String>,
+    #[schemars(description = "Maximum chunk length in characters (default: 1000)")]
+    chunk_size: Option<usize>,
+    #[schemars(description = "Characters of overlap between consecutive chunks (default: 100)")]
+    chunk_overlap: Option<usize>,
+    #[schemars(description = "Additional custom metadata shared by all chunks of the page")]
+    metadata: Option<serde_json::Value>,
+    #[schemars(description = "Importance of the document, typically 0.0-1.0 (default: 0). Boosts its chunks in search ranking")]
+    importance: Option<f32>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct AddFileRequest {
+    #[schemars(description = "The name of the table to add the file to (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Path of the file on the server, relative to its ingest root. PDF, HTML and plain text/markdown files are supported")]
+    path: String,
+    #[schemars(description = "Optional document name (default: the file name)")]
+    name: Option<String>,
+    #[schemars(description = "Optional description of the document")]
+    description: Option<String>,
+    #[schemars(description = "Maximum chunk length in characters (default: 1000)")]
+    chunk_size: Option<usize>,
+    #[schemars(description = "Characters of overlap between consecutive chunks (default: 100)")]
+    chunk_overlap: Option<usize>,
+    #[schemars(description = "Additional custom metadata shared by all chunks of the file")]
+    metadata: Option<serde_json::Value>,
+    #[schemars(description = "Importance of the document, typically 0.0-1.0 (default: 0). Boosts its chunks in search ranking")]
+    importance: Option<f32>,
+}
+
+#[derive(Clone)]
+struct LanceDBServer {
+    db: Arc<VectorDB>,
+    model: Arc<LazyModel>,
+    fetcher: Arc<UrlFetcher>,
+    audit: Option<Arc<AuditLog>>,
+    jobs: Arc<JobQueue>,
+    router: Option<Arc<TableRouter>>,
+    pipelines: Arc<Pipelines>,
+    latency: Arc<LatencyTracker>,
+    scratch: Arc<ScratchTables>,
+    session: Arc<SessionScope>,
+    usage: Arc<UsageTracker>,
+    /// Whether every session must belong to a tenant
+    multi_tenant: bool,
+    /// The tenant of this session, set when it initializes
+    tenant: Arc<OnceLock<Tenant>>,
+    /// Tenants that see every tenant's metrics and usage
+    operator_tenants: Arc<Vec<Tenant>>,
+    /// Tool call limits shared by every session
+    call_budget: Arc<CallBudget>,
+    /// Tool call limits shared by the sessions of each tenant
+    tenant_budgets: Arc<TenantBudgets>,
+    /// Limits each new session starts with
+    session_limits: Limits,
+    session_budget: Arc<CallBudget>,
+    timeouts: Arc<ToolTimeouts>,
+    /// Chunk size and overlap used when a request does not set them
+    chunking: (usize, usize),
+    drain: Drain,
+    acl: Option<Arc<Acl>>,
+    /// What this session may do under the ACL, set when it initializes
+    permissions: Arc<OnceLock<Permissions>>,
+    exports: Arc<ExportDir>,
+    /// The directory add_file, import_table and add_images may read from
+    ingest_root: Option<Arc<PathRoot>>,
+    /// The directory backup_database writes to and restore_database reads from
+    backup_root: Option<Arc<PathRoot>>,
+    slow_queries: Option<Arc<SlowQueryLog>>,
+    query_log: Option<Arc<QueryLog>>,
+    tool_router: ToolRouter<Self>,
+    prompt_router: PromptRouter<Self>,
+}
+
+impl LanceDBServer {
+    /// The tenant a request of this session came from, with --multi-tenant.
+    fn request_tenant(context: &RequestContext<RoleServer>) -> Option<&Tenant> {
+        context
+            .extensions
+            .get::<axum::http::request::Parts>()
+            .and_then(|parts| parts.extensions.get::<Tenant>())
+    }
+
+    /// The stored name of the table a request names, or of the default table
+    /// when it names none.
+    fn table_name(&self, name: Option<&str>) -> String {
+        let name = name.unwrap_or(DEFAULT_TABLE_NAME);
+        // Scratch tables are already private to their session and keep their name
+        if self.owns_scratch(name) {
+            return name.to_string();
+        }
+        match self.tenant.get() {
+            Some(tenant) => tenant.table(name),
+            None => name.to_string(),
+        }
+    }
+
+    /// The name this session knows a stored table by, `None` if the table
+    /// belongs to another tenant or the ACL does not let the session read it.
+    fn shown_table_name<'a>(&self, stored: &'a str) -> Option<&'a str> {
+        if stored.starts_with(SCRATCH_TABLE_PREFIX) {
+            return self.owns_scratch(stored).then_some(stored);
+        }
+        let shown = match self.tenant.get() {
+            Some(tenant) => tenant.strip(stored)?,
+            None => stored,
+        };
+        self.may(shown, Access::Read).then_some(shown)
+    }
+
+    /// Whether the ACL, if any, gives this session `access` to a table it
+    /// calls `table_name`. The session's own scratch tables are always
+    /// allowed, other internal tables never.
+    fn may(&self, table_name: &str, access: Access) -> bool {
+        if table_name.starts_with(db::INTERNAL_TABLE_PREFIX) {
+            return self.owns_scratch(table_name);
+        }
+        if self.acl.is_none() {
+            return true;
+        }
+        self.permissions.get().is_some_and(|p| p.allows(table_name, access))
+    }
+
+    /// Whether `table_name` is a scratch table created by this session.
+    fn owns_scratch(&self, table_name: &str) -> bool {
+        table_name.starts_with(SCRATCH_TABLE_PREFIX) && self.scratch.owns(&self.session, table_name)
+    }
+
+    fn check_access(&self, table_name: &str, access: Access) -> Result<(), ApiError> {
+        if self.may(table_name, access) {
+            return Ok(());
+        }
+        Err(ApiError::new(
+            ErrorCode::PermissionDenied,
+            format!("The session has no {} access to table '{}'", access.name(), table_name),
+        ))
+    }
+
+    /// Reject a call whose HTTP request comes from another tenant or token
+    /// than the one the session was opened with, e.g. a session id used with
+    /// someone else's token.
+    fn check_caller(&self, context: &RequestContext<RoleServer>) -> Result<(), ApiError> {
+        let parts = context.extensions.get::<axum::http::request::Parts>();
+        if self.multi_tenant {
+            let caller = Self::request_tenant(context);
+            if caller.is_none() || caller != self.tenant.get() {
+                return Err(ApiError::new(ErrorCode::PermissionDenied, "The session belongs to another tenant"));
+            }
+        }
+        if let Some(permissions) = self.permissions.get() {
+            let token = parts.and_then(|parts| parts.extensions.get::<TokenFingerprint>());
+            if token.map(|t| t.0.as_str()) != permissions.token() {
+                return Err(ApiError::new(ErrorCode::PermissionDenied, "The session was opened with another token"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the ACL for the tables a tool call names, before the tool runs.
+    fn check_tool_access(&self, tool: &str, arguments: Option<&serde_json::Map<String, serde_json::Value>>) -> Result<(), ApiError> {
+        let argument = |name: &str| arguments.and_then(|args| args.get(name)).and_then(|v| v.as_str());
+        // Clients only ever name internal tables that are their own scratch tables
+        for name in ["table_name", "replacement_table", "target_table", "compare_table"] {
+            if let Some(table_name) = argument(name) {
+                if table_name.starts_with(db::INTERNAL_TABLE_PREFIX) && !self.owns_scratch(table_name) {
+                    return Err(ApiError::new(
+                        ErrorCode::PermissionDenied,
+                        format!("Table '{}' is internal, or a scratch table of another session", table_name),
+                    ));
+                }
+            }
+        }
+        let Some(access) = self.acl.as_ref().and_then(|acl| acl.required(tool)) else {
+            return Ok(());
+        };
+        match argument("table_name") {
+            Some(table_name) => self.check_access(table_name, access)?,
+            // Routed searches only ever pick tables the session may read
+            None if tool == "search" && self.router.is_some() => {}
+            None => self.check_access(DEFAULT_TABLE_NAME, access)?,
+        }
+        for other in ["replacement_table", "target_table", "compare_table"] {
+            if let Some(table_name) = argument(other) {
+                self.check_access(table_name, access)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A job as this session sees it, `None` if it belongs to another tenant.
+    fn visible_job(&self, mut job: JobStatus) -> Option<JobStatus> {
+        job.table_name = self.shown_table_name(&job.table_name)?.to_string();
+        Some(job)
+    }
+
+    /// Record a successful mutating call in the audit log, if one is configured.
+    /// Failing to write the log never fails the call that was already applied.
+    async fn record_mutation(&self, tool: &str, mut arguments: serde_json::Value) {
+        // Scratch tables do not outlive their session, so replaying writes to them is meaningless
+        let table_name = arguments.get("table_name").and_then(|t| t.as_str()).unwrap_or_default();
+        if table_name.starts_with(SCRATCH_TABLE_PREFIX) {
+            return;
+        }
+        // Replay knows no tenants, so record the stored table names
+        if let (Some(_), serde_json::Value::Object(map)) = (self.tenant.get(), &mut arguments) {
+            let table_name = self.table_name(map.get("table_name").and_then(|t| t.as_str()));
+            map.insert("table_name".to_string(), serde_json::Value::String(table_name));
+            for other in ["replacement_table", "target_table"] {
+                if let Some(table_name) = map.get(other).and_then(|t| t.as_str()) {
+                    let table_name = self.table_name(Some(table_name));
+                    map.insert(other.to_string(), serde_json::Value::String(table_name));
+                }
+            }
+        }
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.record(self.tenant.get(), tool, arguments).await {
+                tracing::error!("Failed to write audit log entry for {}: {}", tool, e);
+            }
+        }
+    }
+
+    async fn add_documents_impl(&self, mut req: AddDocumentsRequest, call: &CallContext) -> Result<ApiResponse<AddDocumentsResult>, ApiError> {
+        let shown_name = req.table_name.clone().unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string());
+        let table_name = &self.table_name(req.table_name.as_deref());
+        // Record absolute times, so a replay expires documents when the original did
+        let now = chrono::Utc::now();
+        for doc in &mut req.documents {
+            if let Some(expires_at) = &doc.expires_at {
+                doc.expires_at = Some(expiry::resolve(expires_at, now)?);
+            }
+        }
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        
+        let mut all_texts = Vec::new();
+        let mut all_metadatas = Vec::new();
+        let mut total_chunks = 0;
+        let total_docs = req.documents.len();
+        let vector_column = req.vector_column.unwrap_or_else(|| columns::VECTOR_COLUMN.to_string());
+
+        for doc in req.documents {
+            // Prepare base metadata with name and description injected
+            let mut base_metadata = document_metadata(&doc.name, doc.description.as_deref(), doc.importance, doc.metadata);
+            if let (Some(expires_at), serde_json::Value::Object(map)) = (doc.expires_at, &mut base_metadata) {
+                map.insert(expiry::EXPIRES_AT_KEY.to_string(), serde_json::Value::String(expires_at));
+            }
+            Provenance::new(SourceType::Inline, None, provenance::CLIENT_CHUNKER, provenance::NORMALIZATION_NONE)
+                .attach(&mut base_metadata);
+
+            for chunk in doc.chunks {
+                all_texts.push(chunk);
+                all_metadatas.push(base_metadata.clone());
+                total_chunks += 1;
+            }
+        }
+
+        if req.run_async.unwrap_or(false) {
+            let job_id = self.jobs.create("add_documents", table_name, total_chunks);
+            let server = self.clone();
+            let table_name = table_name.to_string();
+            let id = job_id.clone();
+
+            self.drain.spawn(async move {
+                let _permit = server.jobs.start(&id).await;
+                let jobs = server.jobs.clone();
+                let progress_id = id.clone();
+                let progress = move |p: db::IngestProgress| jobs.progress(&progress_id, p);
+
+                let result = match server.model.get().await {
+                    Ok(model) => {
+                        server
+                            .db
+                            .add_texts_into(&table_name, &vector_column, all_texts, all_metadatas, &model, Some(&progress), None)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+
+                match result {
+                    Ok(()) => {
+                        server.jobs.finish(&id, None);
+                        server.record_mutation("add_documents", arguments).await;
+                    }
+                    Err(e) => {
+                        tracing::error!("Ingest job {} failed: {}", id, e);
+                        server.jobs.finish(&id, Some(e.to_string()));
+                    }
+                }
+            });
+
+            return Ok(ApiResponse::success(AddDocumentsResult::Queued(QueuedJob {
+                job_id,
+                documents: total_docs,
+                chunks: total_chunks,
+            })));
+        }
+
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
         
-        let resp = ApiResponse::success(tables);
-        serde_json::to_string_pretty(&resp).map_err(|e| e.to_string())
+        self.db
+            .add_texts_into(table_name, &vector_column, all_texts, all_metadatas, &*self.model.get().await?, Some(&report), Some(&call.cancel))
+            .await?;
+        self.record_mutation("add_documents", arguments).await;
+            
+        let msg = format!("Successfully added {} documents ({} chunks) to table '{}'", total_docs, total_chunks, shown_name);
+        Ok(ApiResponse::success(AddDocumentsResult::Added(msg)))
+    }
+
+    async fn add_images_impl(&self, req: AddImagesRequest, call: &CallContext) -> Result<ApiResponse<String>, ApiError> {
+        let shown_name = req.table_name.clone().unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string());
+        let table_name = &self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let model = self.model.get().await?;
+        // Fail before reading anything
+        model.check_images()?;
+
+        let total = req.images.len();
+        let mut images = Vec::with_capacity(total);
+        let mut texts = Vec::with_capacity(total);
+        let mut metadatas = Vec::with_capacity(total);
+        for (i, input) in req.images.into_iter().enumerate() {
+            call.report(i as f64, Some(total as f64), format!("reading image {} of {}", i + 1, total));
+            let path = input.path.as_deref().map(|path| self.ingest_path(path)).transpose()?;
+            let image = images::load(path.as_deref(), input.data.as_deref()).await?;
+            let name = match (input.name, input.path.as_deref()) {
+                (Some(name), _) => name,
+                (None, Some(path)) => std::path::Path::new(path)
+                    .file_name()
+                    .map_or_else(|| path.to_string(), |n| n.to_string_lossy().into_owned()),
+                (None, None) => return Err(ApiError::invalid_argument("Name every image given as data")),
+            };
+            let mut metadata = document_metadata(&name, input.description.as_deref(), input.importance, input.metadata);
+            if let serde_json::Value::Object(map) = &mut metadata {
+                map.insert(columns::MODALITY_COLUMN.to_string(), serde_json::json!(columns::MODALITY_IMAGE));
+                map.insert("format".to_string(), serde_json::json!(image.format));
+                if let Some(path) = &input.path {
+                    map.insert("source_path".to_string(), serde_json::json!(path));
+                }
+            }
+            let source_type = if input.path.is_some() { SourceType::File } else { SourceType::Inline };
+            Provenance::new(source_type, input.path, provenance::IMAGE_CHUNKER, provenance::NORMALIZATION_NONE)
+                .attach(&mut metadata);
+            texts.push(input.description.unwrap_or(name));
+            metadatas.push(metadata);
+            images.push(image.bytes);
+        }
+
+        call.report(0.0, Some(total as f64), "embedding images");
+        let vectors = call
+            .cancellable(async { model.embed_images(images).await.map_err(|e| ApiError::classify(e, ErrorCode::EmbeddingFailed)) })
+            .await?;
+        let vector_column = req.vector_column.as_deref().unwrap_or(columns::VECTOR_COLUMN);
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
+        self.db
+            .add_images(table_name, vector_column, texts, vectors, metadatas, &model, Some(&report), Some(&call.cancel))
+            .await?;
+        self.record_mutation("add_images", arguments).await;
+
+        Ok(ApiResponse::success(format!("Successfully added {} images to table '{}'", total, shown_name)))
+    }
+
+    async fn add_url_impl(&self, req: AddUrlRequest, call: &CallContext) -> Result<ApiResponse<String>, ApiError> {
+        let shown_name = req.table_name.clone().unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string());
+        let table_name = &self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+
+        call.report(0.0, None, format!("fetching {}", req.url));
+        let page = self.fetcher.fetch(&req.url)
+            .await
+            .map_err(|e| ApiError::classify(e.context(format!("Failed to fetch {}", req.url)), ErrorCode::FetchFailed))?;
+
+        let chunks = chunking::chunk_text(
+            &page.text,
+            req.chunk_size.unwrap_or(self.chunking.0),
+            req.chunk_overlap.unwrap_or(self.chunking.1),
+        );
+        if chunks.is_empty() {
+            return Err(ApiError::new(ErrorCode::ExtractionFailed, format!("No readable text found at {}", page.url)));
+        }
+
+        let name = req.name.or(page.title).unwrap_or_else(|| page.url.clone());
+        let mut metadata = document_metadata(&name, req.description.as_deref(), req.importance, req.metadata);
+        if let serde_json::Value::Object(ref mut map) = metadata {
+            map.insert("url".to_string(), serde_json::Value::String(page.url.clone()));
+            map.insert("fetched_at".to_string(), serde_json::Value::String(chrono::Utc::now().to_rfc3339()));
+        }
+        Provenance::new(SourceType::Url, Some(page.url.clone()), provenance::CHUNKER_VERSION, page.normalization)
+            .attach(&mut metadata);
+
+        let total_chunks = chunks.len();
+        let metadatas = vec![metadata; total_chunks];
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
+
+        self.db.add_texts(table_name, chunks, metadatas, &*self.model.get().await?, Some(&report), Some(&call.cancel))
+            .await?;
+        self.record_mutation("add_url", arguments).await;
+
+        let msg = format!("Successfully added {} ({} chunks) to table '{}'", page.url, total_chunks, shown_name);
+        Ok(ApiResponse::success(msg))
+    }
+
+    async fn add_file_impl(&self, req: AddFileRequest, call: &CallContext) -> Result<ApiResponse<String>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let req = AddFileRequest {
+            path: self.ingest_path(&req.path)?,
+            ..req
+        };
+        let total_chunks = self.ingest_file(&table_name, &req, call).await?;
+        self.record_mutation("add_file", arguments).await;
+
+        let shown_name = req.table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME);
+        let msg = format!("Successfully added {} ({} chunks) to table '{}'", req.path, total_chunks, shown_name);
+        Ok(ApiResponse::success(msg))
+    }
+
+    async fn set_table_config_impl(&self, req: SetTableConfigRequest, call: &CallContext) -> Result<ApiResponse<TableConfig>, ApiError> {
+        let table_name = &self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        // Validate before storing anything
+        resolve_fusion(req.fusion_preset.as_deref(), req.vector_weight, req.keyword_weight)?;
+        let quantization = req.quantization.as_deref().map(Quantization::parse).transpose()?;
+
+        let mut config: TableConfig = self.db.stored_table_config(table_name)
+            .await?;
+        if req.fusion_preset.is_some() {
+            config.fusion_preset = req.fusion_preset.clone();
+            config.fusion_weights = None;
+        }
+        if let (Some(vector), Some(keyword)) = (req.vector_weight, req.keyword_weight) {
+            config.fusion_weights = Some(FusionWeights { vector, keyword, sparse: None });
+        }
+        let fts_changed = req.fts_language.is_some() || req.fts_stem.is_some();
+        if req.fts_language.is_some() {
+            config.fts_language = req.fts_language.clone();
+        }
+        if req.fts_stem.is_some() {
+            config.fts_stem = req.fts_stem;
+        }
+        if req.description.is_some() {
+            config.description = req.description.clone();
+        }
+        if req.importance_weight.is_some() {
+            config.importance_weight = req.importance_weight;
+        }
+        if let Some(weight) = req.sparse_weight {
+            if weight < 0.0 || !weight.is_finite() {
+                return Err(ApiError::invalid_argument("sparse_weight must be non-negative"));
+            }
+            config.sparse_weight = Some(weight);
+        }
+        if let Some(quantization) = quantization {
+            config.quantization = quantization;
+        }
+        if req.rescore.is_some() {
+            config.rescore = req.rescore;
+        }
+
+        self.db.set_table_config(table_name, &config)
+            .await?;
+        let config = self.db.get_table_config(table_name).await?;
+        if fts_changed && self.db.table_exists(table_name).await? {
+            call.report(0.0, Some(1.0), "building full-text index");
+            call.cancellable(async {
+                self.db.create_fts_index(table_name, &config)
+                    .await
+                    .map_err(ApiError::from)
+            })
+            .await?;
+            call.report(1.0, Some(1.0), "full-text index built");
+        }
+        if quantization.is_some() && self.db.table_exists(table_name).await? {
+            call.report(0.0, Some(1.0), "quantizing vectors");
+            let rows = self.db.quantize(table_name, config.quantization, Some(&call.cancel)).await?;
+            call.report(1.0, Some(1.0), format!("quantized {} rows", rows));
+        }
+        self.record_mutation("set_table_config", arguments).await;
+
+        Ok(ApiResponse::success(config))
+    }
+
+    async fn delete_document_impl(&self, req: DeleteDocumentRequest) -> Result<ApiResponse<DocumentChunksResult>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let chunks = if req.hard.unwrap_or(false) {
+            self.db.delete_document(&table_name, &req.document_name).await?
+        } else {
+            self.db.set_document_deleted(&table_name, &req.document_name, true).await?
+        };
+        self.record_mutation("delete_document", arguments).await;
+        Ok(ApiResponse::success(DocumentChunksResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            document_name: req.document_name,
+            chunks,
+        }))
+    }
+
+    async fn undelete_impl(&self, req: UndeleteRequest) -> Result<ApiResponse<DocumentChunksResult>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let chunks = self.db.set_document_deleted(&table_name, &req.document_name, false).await?;
+        self.record_mutation("undelete", arguments).await;
+        Ok(ApiResponse::success(DocumentChunksResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            document_name: req.document_name,
+            chunks,
+        }))
+    }
+
+    async fn purge_impl(&self, req: PurgeRequest) -> Result<ApiResponse<PurgeResult>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let deleted = self.db.purge_deleted(&table_name, req.document_name.as_deref()).await?;
+        self.record_mutation("purge", arguments).await;
+        Ok(ApiResponse::success(PurgeResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            deleted,
+        }))
+    }
+
+    /// Delete the chunks of a table that expired at or before `now`. The
+    /// cut-off is recorded, so a replay deletes exactly the same chunks.
+    async fn purge_expired_impl(&self, req: TableNameRequest, now: chrono::DateTime<chrono::Utc>) -> Result<ApiResponse<PurgeResult>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let mut arguments = serde_json::to_value(&req).unwrap_or_default();
+        let deleted = self.db.delete_expired(&table_name, now).await?;
+        tracing::info!(table = table_name, deleted, "Purged expired chunks");
+        if let serde_json::Value::Object(map) = &mut arguments {
+            map.insert("expired_before".to_string(), serde_json::json!(now.to_rfc3339()));
+        }
+        self.record_mutation("purge_expired", arguments).await;
+        Ok(ApiResponse::success(PurgeResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            deleted,
+        }))
+    }
+
+    async fn add_column_impl(&self, req: AddColumnRequest, call: &CallContext) -> Result<ApiResponse<AddColumnResult>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let default = req.default.unwrap_or(serde_json::Value::Null);
+        let column_type = req.column_type.as_deref().map(ColumnType::parse).transpose()?;
+
+        call.report(0.0, Some(1.0), format!("adding column '{}'", req.column));
+        let (rows, from_metadata) = call
+            .cancellable(async {
+                self.db
+                    .add_column(&table_name, &req.column, &default, column_type)
+                    .await
+                    .map_err(ApiError::from)
+            })
+            .await?;
+        call.report(1.0, Some(1.0), "column added");
+        self.record_mutation("add_column", arguments).await;
+
+        let column_type = column_type.or(ColumnType::of(&default)?).map_or("string", ColumnType::name);
+        Ok(ApiResponse::success(AddColumnResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            column: req.column,
+            column_type: column_type.to_string(),
+            rows,
+            from_metadata,
+        }))
+    }
+
+    async fn export_table_impl(&self, req: ExportTableRequest, call: &CallContext) -> Result<ApiResponse<ExportResult>, ApiError> {
+        let format = ExportFormat::parse(req.format.as_deref().unwrap_or("jsonl"))?;
+        let table_name = self.table_name(req.table_name.as_deref());
+        let (name, path) = self.exports.allocate(format)?;
+
+        let report = |rows: usize| call.report(rows as f64, None, format!("{} rows exported", rows));
+        let rows = export::export_table(
+            &self.db,
+            &table_name,
+            &path,
+            format,
+            req.include_vectors.unwrap_or(false),
+            &call.cancel,
+            report,
+        )
+        .await?;
+
+        Ok(ApiResponse::success(ExportResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            format: format.extension().to_string(),
+            rows,
+            path: path.display().to_string(),
+            download: format!("/exports/{}", name),
+        }))
+    }
+
+    async fn import_table_impl(&self, req: ImportTableRequest, call: &CallContext) -> Result<ApiResponse<ImportResult>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let path = &self.ingest_path(&req.path)?;
+        let path = std::path::Path::new(path);
+        let format = match req.format.as_deref() {
+            Some(format) => ImportFormat::parse(format)?,
+            None => ImportFormat::from_path(path)?,
+        };
+        let columns = import::Columns {
+            text: req.text_column.clone().unwrap_or_else(|| "text".to_string()),
+            name: req.name_column.clone(),
+            vector: req.vector_column.clone(),
+            reembed: req.reembed.unwrap_or(false),
+            metadata: req.metadata_columns.clone(),
+        };
+
+        call.report(0.0, None, format!("reading {}", req.path));
+        let rows = call.cancellable(async { import::read_rows(path, format).await.map_err(ApiError::from) }).await?;
+        let model = self.model.get().await?;
+        let dimension = model.dimension().await.map_err(|e| ApiError::classify(e, ErrorCode::EmbeddingFailed))?;
+        let imported = import::map_rows(rows, &columns, &req.path, dimension)?;
+        if imported.texts.is_empty() {
+            return Err(ApiError::invalid_argument(format!("{} has no rows with text to import", req.path)));
+        }
+
+        let total = imported.texts.len();
+        let embedded = imported.vectors.is_none();
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
+        match imported.vectors {
+            Some(vectors) => {
+                self.db
+                    .add_vectors(&table_name, imported.texts, vectors, imported.metadatas, &model, Some(&report), Some(&call.cancel))
+                    .await?
+            }
+            None => {
+                self.db
+                    .add_texts(&table_name, imported.texts, imported.metadatas, &model, Some(&report), Some(&call.cancel))
+                    .await?
+            }
+        }
+        self.record_mutation("import_table", arguments).await;
+
+        Ok(ApiResponse::success(ImportResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            rows: total,
+            skipped: imported.skipped,
+            embedded,
+        }))
+    }
+
+    async fn reembed_table_impl(&self, req: ReembedTableRequest, call: &CallContext) -> Result<ApiResponse<ReembedTableResult>, ApiError> {
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let source = self.table_name(req.table_name.as_deref());
+        if !self.db.table_exists(&source).await? {
+            return Err(ApiError::new(ErrorCode::TableNotFound, format!("Table '{}' not found", req.table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME))));
+        }
+        if let Some(column) = req.vector_column.clone() {
+            if req.target_table.is_some() {
+                return Err(ApiError::invalid_argument("Give either target_table or vector_column, not both"));
+            }
+            return self.fill_vector_column(source, column, req, arguments, call).await;
+        }
+        let target = match req.target_table.as_deref() {
+            Some(target_table) => {
+                let target = self.table_name(Some(target_table));
+                if self.db.table_exists(&target).await? {
+                    return Err(ApiError::invalid_argument(format!("Target table '{}' already exists", target_table)));
+                }
+                target
+            }
+            None if self.db.is_local() && cfg!(feature = "local-files") => {
+                format!("{}reembed_{}", db::INTERNAL_TABLE_PREFIX, uuid::Uuid::new_v4().simple())
+            }
+            None => return Err(ApiError::invalid_argument("This database cannot replace a table in place; pass target_table")),
+        };
+        let shown_target = req.target_table.clone().or(req.table_name.clone()).unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string());
+
+        if req.run_async.unwrap_or(false) {
+            let job_id = self.jobs.create("reembed_table", &source, 0);
+            let server = self.clone();
+            let id = job_id.clone();
+            self.drain.spawn(async move {
+                let _permit = server.jobs.start(&id).await;
+                let jobs = server.jobs.clone();
+                let progress_id = id.clone();
+                let progress = move |p: db::IngestProgress| jobs.progress(&progress_id, p);
+                match server.reembed(&source, &target, req.target_table.is_none(), &progress, None).await {
+                    Ok(_) => {
+                        server.jobs.finish(&id, None);
+                        server.record_mutation("reembed_table", arguments).await;
+                    }
+                    Err(e) => {
+                        tracing::error!("Re-embedding job {} failed: {}", id, e);
+                        server.jobs.finish(&id, Some(e.to_string()));
+                    }
+                }
+            });
+            return Ok(ApiResponse::success(ReembedTableResult::Queued { job_id }));
+        }
+
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
+        let rows = self.reembed(&source, &target, req.target_table.is_none(), &report, Some(&call.cancel)).await?;
+        self.record_mutation("reembed_table", arguments).await;
+        Ok(ApiResponse::success(ReembedTableResult::Done(ReembedSummary {
+            table_name: shown_target,
+            rows,
+            embedding_model: self.model.get().await?.model_id().to_string(),
+        })))
+    }
+
+    /// `reembed_table` with a `vector_column`: fill that column of `source`
+    /// with the server's model.
+    async fn fill_vector_column(
+        &self,
+        source: String,
+        column: String,
+        req: ReembedTableRequest,
+        arguments: serde_json::Value,
+        call: &CallContext,
+    ) -> Result<ApiResponse<ReembedTableResult>, ApiError> {
+        if req.run_async.unwrap_or(false) {
+            let job_id = self.jobs.create("reembed_table", &source, 0);
+            let server = self.clone();
+            let id = job_id.clone();
+            self.drain.spawn(async move {
+                let _permit = server.jobs.start(&id).await;
+                let jobs = server.jobs.clone();
+                let progress_id = id.clone();
+                let progress = move |p: db::IngestProgress| jobs.progress(&progress_id, p);
+                let result = match server.model.get().await {
+                    Ok(model) => server.db.embed_into_column(&source, &column, &model, Some(&progress), None).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(_) => {
+                        server.jobs.finish(&id, None);
+                        server.record_mutation("reembed_table", arguments).await;
+                    }
+                    Err(e) => {
+                        tracing::error!("Re-embedding job {} failed: {}", id, e);
+                        server.jobs.finish(&id, Some(e.to_string()));
+                    }
+                }
+            });
+            return Ok(ApiResponse::success(ReembedTableResult::Queued { job_id }));
+        }
+
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
+        let model = self.model.get().await?;
+        let rows = self.db.embed_into_column(&source, &column, &model, Some(&report), Some(&call.cancel)).await?;
+        self.record_mutation("reembed_table", arguments).await;
+        Ok(ApiResponse::success(ReembedTableResult::Done(ReembedSummary {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            rows,
+            embedding_model: model.model_id().to_string(),
+        })))
+    }
+
+    /// Re-embed `source` into `target`, then swap `target` into `source`'s
+    /// place when `in_place`. `target` is dropped again if anything fails.
+    async fn reembed(
+        &self,
+        source: &str,
+        target: &str,
+        in_place: bool,
+        progress: db::ProgressFn<'_>,
+        cancel: Option<&tokio_util::sync::CancellationToken>,
+    ) -> anyhow::Result<usize> {
+        let result = async {
+            let model = self.model.get().await?;
+            let rows = self.db.reembed(source, target, &model, Some(progress), cancel).await?;
+            if in_place {
+                self.db.swap_tables(source, target).await?;
+            }
+            Ok(rows)
+        }
+        .await;
+        if result.is_err() {
+            if let Err(e) = self.db.drop_table(target).await {
+                tracing::warn!("Failed to drop partly re-embedded table '{}': {}", target, e);
+            }
+        }
+        result
+    }
+
+    async fn backup_database_impl(&self, req: BackupDatabaseRequest, call: &CallContext) -> Result<ApiResponse<Manifest>, ApiError> {
+        // Only the tables this session can see, under the names it knows them by
+        let tables: Vec<(String, String)> = self
+            .db
+            .list_tables()
+            .await?
+            .into_iter()
+            .filter_map(|stored| {
+                let shown = self.shown_table_name(&stored)?.to_string();
+                Some((stored, shown))
+            })
+            .collect();
+
+        let dir = paths::required(self.backup_root.as_deref(), "--backup-dir")?.resolve_new(&req.path)?;
+        let total = tables.len() as f64;
+        let report = |i: usize, name: &str| call.report(i as f64, Some(total), format!("backing up '{}'", name));
+        let manifest = backup::backup(&self.db, &tables, &dir, &call.cancel, report).await?;
+        Ok(ApiResponse::success(manifest))
+    }
+
+    async fn restore_database_impl(&self, req: RestoreDatabaseRequest, call: &CallContext) -> Result<ApiResponse<Manifest>, ApiError> {
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let dir = &paths::required(self.backup_root.as_deref(), "--backup-dir")?.resolve(&req.path)?;
+        call.report(0.0, None, "verifying backup checksums");
+        let manifest = backup::verify(dir).await?;
+        for table in &manifest.tables {
+            if table.name.starts_with(db::INTERNAL_TABLE_PREFIX) {
+                return Err(ApiError::invalid_argument(format!("Backup holds the internal table '{}'", table.name)));
+            }
+            self.check_access(&table.name, Access::Admin)?;
+        }
+
+        let total = manifest.tables.len() as f64;
+        let report = |i: usize, name: &str| call.report(i as f64, Some(total), format!("restoring '{}'", name));
+        backup::restore(
+            &self.db,
+            dir,
+            &manifest,
+            |name| self.table_name(Some(name)),
+            req.overwrite.unwrap_or(false),
+            &call.cancel,
+            report,
+        )
+        .await?;
+        self.record_mutation("restore_database", arguments).await;
+        Ok(ApiResponse::success(manifest))
+    }
+
+    async fn swap_tables_impl(&self, req: SwapTablesRequest) -> Result<ApiResponse<String>, ApiError> {
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        for name in [&req.table_name, &req.replacement_table] {
+            if name.starts_with(db::INTERNAL_TABLE_PREFIX) && !name.starts_with(SCRATCH_TABLE_PREFIX) {
+                return Err(ApiError::invalid_argument(format!("Table '{}' is internal and cannot be swapped", name)));
+            }
+        }
+
+        self.db.swap_tables(&self.table_name(Some(&req.table_name)), &self.table_name(Some(&req.replacement_table)))
+            .await?;
+        self.record_mutation("swap_tables", arguments).await;
+
+        let msg = format!("Replaced table '{}' with '{}'", req.table_name, req.replacement_table);
+        Ok(ApiResponse::success(msg))
+    }
+
+    /// Run a search. `breakdown`, when given, records where the time went
+    /// whether or not the request asked for diagnostics.
+    async fn search_impl(
+        &self,
+        req: SearchRequest,
+        breakdown: Option<&Diagnostics>,
+    ) -> Result<ApiResponse<Vec<SearchResult>>, ApiError> {
+        let limit = req.limit.unwrap_or(5);
+        if limit > MAX_SEARCH_LIMIT {
+            return Err(ApiError::invalid_argument(format!("limit must be at most {}", MAX_SEARCH_LIMIT)));
+        }
+        let requested = req.diagnostics.unwrap_or(false);
+        let own_breakdown = (requested && breakdown.is_none()).then(Diagnostics::default);
+        let diagnostics = breakdown.or(own_breakdown.as_ref());
+        let reported = || diagnostics.filter(|_| requested).map(Diagnostics::snapshot);
+
+        if let (None, Some(router)) = (&req.table_name, &self.router) {
+            if req.version.is_some() {
+                return Err(ApiError::invalid_argument("A version belongs to one table; pass table_name with it"));
+            }
+            let visible = |table: &str| self.shown_table_name(table).is_some();
+            let routes = router.route(&self.db, &*self.model.get().await?, &req.query, visible).await?;
+
+            if !routes.is_empty() {
+                let mut results = Vec::new();
+                for route in &routes {
+                    let shown_name = self.shown_table_name(&route.table).unwrap_or(route.table.as_str());
+                    for mut result in self.search_table(&route.table, &req, limit, diagnostics).await? {
+                        result.table = Some(shown_name.to_string());
+                        results.push(result);
+                    }
+                }
+                results.sort_by(SearchResult::ranking_order);
+                results.truncate(limit);
+
+                let decision = routes
+                    .iter()
+                    .map(|r| format!("{} ({:.3})", self.shown_table_name(&r.table).unwrap_or(r.table.as_str()), r.similarity))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Ok(ApiResponse::success_with_message(results, format!("success; routed to table(s): {}", decision))
+                    .with_diagnostics(reported()));
+            }
+        }
+
+        let table_name = self.table_name(req.table_name.as_deref());
+        let results = self.search_table(&table_name, &req, limit, diagnostics).await?;
+
+        Ok(ApiResponse::success(results).with_diagnostics(reported()))
+    }
+
+    /// Run every case of an evaluation set as a search and score the results.
+    async fn evaluate_retrieval_impl(&self, req: EvaluateRetrievalRequest, call: &CallContext) -> Result<EvalReport, ApiError> {
+        let cases = eval::parse_cases(&req.cases)?;
+        let k = req.k.unwrap_or(DEFAULT_EVAL_K);
+        if k == 0 || k > MAX_SEARCH_LIMIT {
+            return Err(ApiError::invalid_argument(format!("k must be between 1 and {}", MAX_SEARCH_LIMIT)));
+        }
+        let total = cases.len();
+        let mut scores = Vec::with_capacity(total);
+        for (i, case) in cases.iter().enumerate() {
+            call.report(i as f64, Some(total as f64), format!("searching {}/{}", i + 1, total));
+            let search = SearchRequest {
+                table_name: req.table_name.clone(),
+                query: case.query.clone(),
+                limit: Some(k),
+                mode: req.mode.clone(),
+                fusion_preset: None,
+                vector_weight: None,
+                keyword_weight: None,
+                importance_weight: None,
+                sparse_weight: None,
+                pipeline: req.pipeline.clone(),
+                after: None,
+                before: None,
+                date_field: None,
+                date_locale: None,
+                version: req.version,
+                as_of: None,
+                vector_column: req.vector_column.clone(),
+                modality: None,
+                rescore: None,
+                diagnostics: None,
+            };
+            let results = call.cancellable(self.search_impl(search, None)).await?.data.unwrap_or_default();
+            scores.push(eval::score(case, &results, k));
+        }
+        Ok(eval::report(k, scores))
+    }
+
+    /// Run a search request against one table and record it in the query
+    /// log, if there is one.
+    async fn search_table(
+        &self,
+        table_name: &str,
+        req: &SearchRequest,
+        limit: usize,
+        diagnostics: Option<&Diagnostics>,
+    ) -> Result<Vec<SearchResult>, ApiError> {
+        let started = std::time::Instant::now();
+        let results = self.search_table_dated(table_name, req, limit, diagnostics).await?;
+        if let Some(log) = &self.query_log {
+            log.record(table_name, &req.query, &results, started.elapsed());
+        }
+        Ok(results)
+    }
+
+    /// Run a search request against one table, keeping only results within
+    /// the request's date bounds. The filter runs after the search, so the
+    /// search is repeated with more candidates until enough fall within the
+    /// bounds, the table has no more, or `MAX_DATE_FILTER_CANDIDATES` are read.
+    async fn search_table_dated(
+        &self,
+        table_name: &str,
+        req: &SearchRequest,
+        limit: usize,
+        diagnostics: Option<&Diagnostics>,
+    ) -> Result<Vec<SearchResult>, ApiError> {
+        let filter = DateFilter::new(
+            req.date_field.as_deref(),
+            req.after.as_deref(),
+            req.before.as_deref(),
+            req.date_locale.as_deref(),
+        )?;
+        let Some(filter) = filter else {
+            return self.search_table_unfiltered(table_name, req, limit, diagnostics).await;
+        };
+
+        let mut candidates = (limit * DATE_FILTER_CANDIDATE_FACTOR).min(MAX_DATE_FILTER_CANDIDATES);
+        loop {
+            let found = self.search_table_unfiltered(table_name, req, candidates, diagnostics).await?;
+            let exhausted = found.len() < candidates;
+            let read = found.len();
+            let results: Vec<SearchResult> = found.into_iter().filter(|r| filter.matches(r)).take(limit).collect();
+            if results.len() == limit || exhausted || candidates == MAX_DATE_FILTER_CANDIDATES {
+                let shortfall = (!exhausted && results.len() < limit).then(|| limit - results.len());
+                if let Some(shortfall) = shortfall {
+                    tracing::debug!(table = table_name, read, shortfall, "Date filter matched too few candidates");
+                }
+                if let Some(diagnostics) = diagnostics {
+                    diagnostics.record(|d| {
+                        d.date_filter_candidates = Some(read);
+                        d.date_filter_shortfall = shortfall;
+                    });
+                }
+                return Ok(results);
+            }
+            candidates = (candidates * DATE_FILTER_CANDIDATE_FACTOR).min(MAX_DATE_FILTER_CANDIDATES);
+        }
+    }
+
+    /// Run a search request against one table, applying the table's fusion
+    /// settings unless the request overrides them.
+    async fn search_table_unfiltered(
+        &self,
+        table_name: &str,
+        req: &SearchRequest,
+        limit: usize,
+        diagnostics: Option<&Diagnostics>,
+    ) -> Result<Vec<SearchResult>, ApiError> {
+        let config = self.db.get_table_config(table_name).await?;
+        let version = match (req.version, req.as_of.as_deref()) {
+            (Some(_), Some(_)) => return Err(ApiError::invalid_argument("Give either version or as_of, not both")),
+            (Some(version), None) => Some(version),
+            (None, Some(as_of)) => {
+                let at = dates::parse(as_of, chrono::Utc::now(), req.date_locale.as_deref())?;
+                Some(self.db.version_as_of(table_name, at).await?)
+            }
+            (None, None) => None,
+        };
+        let target = SearchTarget {
+            version,
+            vector_column: req.vector_column.as_deref(),
+            modality: req.modality.as_deref(),
+            rescore: req.rescore.or(config.rescore).unwrap_or(true),
+            diagnostics,
+        };
+
+        // Request-level settings win over the table's stored ones
+        let request_fusion = resolve_fusion(req.fusion_preset.as_deref(), req.vector_weight, req.keyword_weight)?;
+        let fusion = request_fusion.or_else(|| config.fusion());
+        let importance_weight = req.importance_weight
+            .or(config.importance_weight)
+            .unwrap_or(DEFAULT_IMPORTANCE_WEIGHT);
+        let sparse_weight = req.sparse_weight.or(config.sparse_weight);
+        if sparse_weight.is_some_and(|w| w < 0.0 || !w.is_finite()) {
+            return Err(ApiError::invalid_argument("sparse_weight must be non-negative"));
+        }
+
+        let pipeline = match (req.pipeline.as_deref(), req.mode.as_deref()) {
+            (Some(_), Some(_)) => return Err(ApiError::invalid_argument("Give either pipeline or mode, not both")),
+            (Some(name), None) => Some((name, self.pipelines.get(name).ok_or_else(|| {
+                ApiError::invalid_argument(format!(
+                    "Unknown pipeline '{}', configured pipelines: {:?}",
+                    name,
+                    self.pipelines.names()
+                ))
+            })?)),
+            (None, Some(_)) => None,
+            (None, None) => self.pipelines.get(pipeline::DEFAULT_PIPELINE).map(|p| (pipeline::DEFAULT_PIPELINE, p)),
+        };
+        // Waiting for a lazily loaded model is not search latency
+        let model = self.model.get().await?;
+        if let Some((name, pipeline)) = pipeline {
+            let params = pipeline::SearchParams {
+                table_name,
+                target,
+                query: &req.query,
+                limit,
+                importance_weight,
+                request_fusion,
+                table_fusion: config.fusion(),
+                sparse_weight,
+            };
+            let started = std::time::Instant::now();
+            let results = pipeline::execute(pipeline, &self.db, &model, &params).await?;
+            self.latency.record(self.tenant.get(), table_name, name, started.elapsed());
+            tracing::debug!(table = table_name, pipeline = name, results = results.len(), "Searched");
+            return Ok(results);
+        }
+
+        let mode = req.mode.as_deref().unwrap_or(if fusion.is_some() { "hybrid" } else { "vector" });
+        let started = std::time::Instant::now();
+        let results = match mode {
+            "vector" => self.db.search(table_name, target, &req.query, limit, &model, importance_weight).await,
+            "hybrid" => {
+                let weights = fusion.or_else(|| FusionWeights::preset("balanced")).unwrap();
+                let weights = FusionWeights { sparse: sparse_weight.or(weights.sparse), ..weights };
+                self.db.hybrid_search(table_name, target, &req.query, limit, &model, weights, importance_weight).await
+            }
+            other => {
+                return Err(ApiError::invalid_argument(format!(
+                    "Unknown search mode '{}', expected 'vector' or 'hybrid'",
+                    other
+                )));
+            }
+        }?;
+        self.latency.record(self.tenant.get(), table_name, &latency::mode_label(mode), started.elapsed());
+        tracing::debug!(table = table_name, mode, results = results.len(), "Searched");
+        Ok(results)
+    }
+
+    /// The file on the server a client means by `path`, which must be inside
+    /// the ingest root.
+    fn ingest_path(&self, path: &str) -> Result<String, ApiError> {
+        let root = paths::required(self.ingest_root.as_deref(), "--ingest-root")?;
+        Ok(root.resolve(path)?.to_string_lossy().into_owned())
+    }
+
+    /// Extract, chunk and store a local file. Shared by the `add_file` tool and
+    /// the directory watcher. Returns the number of chunks added.
+    async fn ingest_file(
+        &self,
+        table_name: &str,
+        req: &AddFileRequest,
+        call: &CallContext,
+    ) -> Result<usize, ApiError> {
+        let path = std::path::Path::new(&req.path);
+        call.report(0.0, None, format!("extracting text from {}", req.path));
+
+        let chunks = ingest::chunk_file(
+            path,
+            req.chunk_size.unwrap_or(self.chunking.0),
+            req.chunk_overlap.unwrap_or(self.chunking.1),
+        )
+        .await
+        .map_err(|e| ApiError::classify(e, ErrorCode::ExtractionFailed))?;
+        if chunks.is_empty() {
+            return Err(ApiError::new(ErrorCode::ExtractionFailed, format!("No text could be extracted from {}", req.path)));
+        }
+
+        let name = req.name.clone().unwrap_or_else(|| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| req.path.clone())
+        });
+        let mut base_metadata = document_metadata(&name, req.description.as_deref(), req.importance, req.metadata.clone());
+        if let serde_json::Value::Object(ref mut map) = base_metadata {
+            map.insert("source_path".to_string(), serde_json::Value::String(req.path.clone()));
+        }
+        Provenance::new(
+            SourceType::File,
+            Some(req.path.clone()),
+            provenance::CHUNKER_VERSION,
+            ingest::normalization(path),
+        )
+        .attach(&mut base_metadata);
+
+        let total_chunks = chunks.len();
+        let mut texts = Vec::with_capacity(total_chunks);
+        let mut metadatas = Vec::with_capacity(total_chunks);
+        for chunk in chunks {
+            let mut metadata = base_metadata.clone();
+            if let (Some(page), serde_json::Value::Object(map)) = (chunk.page, &mut metadata) {
+                map.insert("page".to_string(), serde_json::json!(page));
+            }
+            texts.push(chunk.text);
+            metadatas.push(metadata);
+        }
+
+        let report = |p: db::IngestProgress| call.ingest_progress(p);
+
+        self.db.add_texts(table_name, texts, metadatas, &*self.model.get().await?, Some(&report), Some(&call.cancel))
+            .await?;
+
+        Ok(total_chunks)
+    }
+}
+
+#[tool_router]
+impl LanceDBServer {
+    fn new(
+        db: Arc<VectorDB>,
+        model: Arc<LazyModel>,
+        fetcher: Arc<UrlFetcher>,
+        audit: Option<Arc<AuditLog>>,
+        router: Option<Arc<TableRouter>>,
+        pipelines: Arc<Pipelines>,
+        latency: Arc<LatencyTracker>,
+    ) -> Self {
+        let scratch = ScratchTables::new(db.clone());
+        #[allow(unused_mut)]
+        let mut tool_router = Self::tool_router();
+        // Remote-only builds cannot read files on the server
+        #[cfg(not(feature = "local-files"))]
+        tool_router.remove_route("add_file");
+        #[cfg(not(feature = "local-files"))]
+        tool_router.remove_route("import_table");
+        #[cfg(not(feature = "local-files"))]
+        tool_router.remove_route("backup_database");
+        #[cfg(not(feature = "local-files"))]
+        tool_router.remove_route("restore_database");
+        Self {
+            db,
+            model,
+            fetcher,
+            audit,
+            jobs: Arc::new(JobQueue::new()),
+            router,
+            pipelines,
+            latency,
+            session: scratch.session(),
+            scratch,
+            usage: Arc::new(UsageTracker::default()),
+            multi_tenant: false,
+            tenant: Arc::new(OnceLock::new()),
+            operator_tenants: Arc::new(Vec::new()),
+            call_budget: CallBudget::new("server", Limits::default()),
+            tenant_budgets: TenantBudgets::new(Limits::default()),
+            session_limits: Limits::default(),
+            session_budget: CallBudget::new("session", Limits::default()),
+            timeouts: Arc::new(ToolTimeouts::default()),
+            drain: Drain::default(),
+            chunking: (chunking::DEFAULT_CHUNK_SIZE, chunking::DEFAULT_CHUNK_OVERLAP),
+            acl: None,
+            permissions: Arc::new(OnceLock::new()),
+            exports: Arc::new(ExportDir::new(std::env::temp_dir().join("lancedb-mcp-exports"))),
+            ingest_root: None,
+            backup_root: None,
+            slow_queries: None,
+            query_log: None,
+            tool_router,
+            prompt_router: Self::prompt_router(),
+        }
+    }
+
+    /// Require every session to belong to a tenant, and scope its tables,
+    /// metrics, audit log and usage to it.
+    fn multi_tenant(self) -> Self {
+        Self {
+            multi_tenant: true,
+            ..self
+        }
+    }
+
+    /// Let these tenants see every tenant's metrics and usage.
+    fn with_operator_tenants(self, operator_tenants: Vec<Tenant>) -> Self {
+        Self {
+            operator_tenants: Arc::new(operator_tenants),
+            ..self
+        }
+    }
+
+    /// Limit tool calls across all sessions, across the sessions of each
+    /// tenant and within each session.
+    fn with_call_limits(self, global: Limits, per_tenant: Limits, per_session: Limits) -> Self {
+        Self {
+            call_budget: CallBudget::new("server", global),
+            tenant_budgets: TenantBudgets::new(per_tenant),
+            session_limits: per_session,
+            session_budget: CallBudget::new("session", per_session),
+            ..self
+        }
+    }
+
+    /// Unregister every tool but the `READ_ONLY_TOOLS`, so clients can
+    /// neither see nor call anything that writes to the database or the
+    /// server's filesystem.
+    fn read_only(mut self) -> Self {
+        for tool in self.tool_router.list_all() {
+            if !READ_ONLY_TOOLS.contains(&&*tool.name) {
+                self.tool_router.remove_route(&tool.name);
+            }
+        }
+        self
+    }
+
+    /// Register only the `enable`d tools (all when empty), minus the
+    /// `disable`d ones. Naming a tool this build does not have is an error.
+    fn select_tools(mut self, enable: &[String], disable: &[String]) -> Result<Self> {
+        let available: Vec<String> = self.tool_router.list_all().into_iter().map(|t| t.name.to_string()).collect();
+        for name in enable.iter().chain(disable) {
+            if !available.contains(name) {
+                anyhow::bail!("Unknown tool '{}', expected one of {:?}", name, available);
+            }
+        }
+        for name in &available {
+            if (!enable.is_empty() && !enable.contains(name)) || disable.contains(name) {
+                self.tool_router.remove_route(name);
+            }
+        }
+        Ok(self)
+    }
+
+    fn with_chunking(self, chunk_size: usize, chunk_overlap: usize) -> Self {
+        Self {
+            chunking: (chunk_size, chunk_overlap),
+            ..self
+        }
+    }
+
+    /// Keep the files of `export_table` in `dir`.
+    fn with_export_dir(self, dir: &str) -> Self {
+        Self {
+            exports: Arc::new(ExportDir::new(dir.into())),
+            ..self
+        }
+    }
+
+    /// Let clients name files in `root` to add_file, import_table and add_images.
+    fn with_ingest_root(self, root: PathRoot) -> Self {
+        Self {
+            ingest_root: Some(Arc::new(root)),
+            ..self
+        }
+    }
+
+    /// Let clients back up to and restore from directories in `root`.
+    fn with_backup_root(self, root: PathRoot) -> Self {
+        Self {
+            backup_root: Some(Arc::new(root)),
+            ..self
+        }
+    }
+
+    /// Log the tool calls slower than the log's threshold.
+    fn with_slow_query_log(self, log: SlowQueryLog) -> Self {
+        Self {
+            slow_queries: Some(Arc::new(log)),
+            ..self
+        }
+    }
+
+    /// Record every search in the query log.
+    fn with_query_log(self, log: QueryLog) -> Self {
+        Self {
+            query_log: Some(Arc::new(log)),
+            ..self
+        }
+    }
+
+    /// Stop tool calls that run longer than their tool's timeout.
+    fn with_tool_timeouts(self, timeouts: ToolTimeouts) -> Self {
+        Self {
+            timeouts: Arc::new(timeouts),
+            ..self
+        }
+    }
+
+    /// Enforce a table access policy on every session.
+    fn with_acl(self, acl: Acl) -> Self {
+        Self {
+            acl: Some(Arc::new(acl)),
+            ..self
+        }
+    }
+
+    /// A clone of the server for a new MCP session, with its own scratch
+    /// tables, call limits, tenant and permissions.
+    fn for_new_session(&self) -> Self {
+        Self {
+            session: self.scratch.session(),
+            session_budget: CallBudget::new("session", self.session_limits),
+            tenant: Arc::new(OnceLock::new()),
+            permissions: Arc::new(OnceLock::new()),
+            ..self.clone()
+        }
+    }
+
+    #[tool(
+        description = "Add documents to a LanceDB table. Supports batching multiple documents, where each document can have multiple chunks sharing the same metadata. Set `async` to queue large ingests as a background job.",
+        annotations(title = "Add documents", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn add_documents(
+        &self,
+        Parameters(req): Parameters<AddDocumentsRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<AddDocumentsResult>>, ApiError> {
+        self.add_documents_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Fetch a web page, extract its readable text, split it into chunks and add them to a LanceDB table. Chunks carry `url` and `fetched_at` metadata.",
+        annotations(title = "Add web page", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = true)
+    )]
+    async fn add_url(
+        &self,
+        Parameters(req): Parameters<AddUrlRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<String>>, ApiError> {
+        self.add_url_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Add images (PNG, JPEG, GIF, WebP or BMP) from files on the server or base64 data to a LanceDB table. Requires a CLIP-style embedding model, which puts images and texts in one space so a text search finds images; search with modality 'image' to only get images. Rows record modality 'image' and the image format.",
+        annotations(title = "Add images", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn add_images(
+        &self,
+        Parameters(req): Parameters<AddImagesRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<String>>, ApiError> {
+        self.add_images_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Read a file from the server's filesystem, extract its text, split it into chunks and add them to a LanceDB table. PDFs are extracted per page and each chunk records its `page` number; all chunks record the `source_path`.",
+        annotations(title = "Add file", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn add_file(
+        &self,
+        Parameters(req): Parameters<AddFileRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<String>>, ApiError> {
+        self.add_file_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Search for similar documents in a LanceDB table using semantic vector search.",
+        annotations(title = "Search", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn search(
+        &self,
+        Parameters(req): Parameters<SearchRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<Vec<SearchResult>>>, ApiError> {
+        let call = CallContext::from_request(&ctx);
+        let breakdown = ctx.extensions.get::<Arc<Diagnostics>>().cloned();
+        call.cancellable(self.search_impl(req, breakdown.as_deref())).await.map(Json)
+    }
+
+    #[tool(
+        description = "Get the status and progress of a background ingest job.",
+        annotations(title = "Get job status", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn get_job_status(&self, Parameters(req): Parameters<GetJobStatusRequest>) -> Result<Json<ApiResponse<JobStatus>>, ApiError> {
+        let job = self.jobs.get(&req.job_id)
+            .and_then(|job| self.visible_job(job))
+            .ok_or_else(|| ApiError::new(ErrorCode::NotFound, format!("Job '{}' not found", req.job_id)))?;
+
+        Ok(Json(ApiResponse::success(job)))
+    }
+
+    #[tool(
+        description = "List background ingest jobs, newest first.",
+        annotations(title = "List jobs", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn list_jobs(&self) -> Result<Json<ApiResponse<Vec<JobStatus>>>, ApiError> {
+        let jobs: Vec<JobStatus> = self.jobs.list().into_iter().filter_map(|job| self.visible_job(job)).collect();
+        Ok(Json(ApiResponse::success(jobs)))
+    }
+
+    #[tool(
+        description = "Get the stored configuration of a table (hybrid fusion preset/weights, full-text language and stemming).",
+        annotations(title = "Get table config", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn get_table_config(&self, Parameters(req): Parameters<TableNameRequest>) -> Result<Json<ApiResponse<TableConfig>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let config = self.db.get_table_config(&table_name)
+            .await?;
+
+        Ok(Json(ApiResponse::success(config)))
+    }
+
+    #[tool(
+        description = "Update the configuration of a table. The hybrid fusion preset or weights are applied automatically to searches on the table; changing the full-text language or stemming rebuilds the full-text index.",
+        annotations(title = "Set table config", read_only_hint = false, destructive_hint = true, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn set_table_config(
+        &self,
+        Parameters(req): Parameters<SetTableConfigRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<TableConfig>>, ApiError> {
+        self.set_table_config_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Atomically replace the contents of a live table with another, fully built table (e.g. after re-embedding or an import), so searches never see a half-built or empty table. The replaced data is dropped; the table's config is kept.",
+        annotations(title = "Swap tables", read_only_hint = false, destructive_hint = true, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn swap_tables(&self, Parameters(req): Parameters<SwapTablesRequest>) -> Result<Json<ApiResponse<String>>, ApiError> {
+        self.swap_tables_impl(req).await.map(Json)
+    }
+
+    #[tool(
+        description = "Export every row of a table (text, metadata and optionally vectors) to a JSONL or Parquet file on the server, for backups or analytics. Returns the file's path and a URL path to download it from over HTTP for 24 hours.",
+        annotations(title = "Export table", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn export_table(
+        &self,
+        Parameters(req): Parameters<ExportTableRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<ExportResult>>, ApiError> {
+        self.export_table_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Bulk-load rows from a Parquet, CSV or JSONL file on the server into a LanceDB table. Each row's text column becomes a chunk and its other columns its metadata; rows are embedded unless the file has a vector column from the server's embedding model. Files written by export_table import as they were exported.",
+        annotations(title = "Import table", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn import_table(
+        &self,
+        Parameters(req): Parameters<ImportTableRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<ImportResult>>, ApiError> {
+        self.import_table_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Embed the text of every row of a table again with the server's current embedding model, e.g. after switching models. Rows keep their ids and metadata. Without target_table the re-embedded rows replace the table's contents in one step once complete; rows written meanwhile are carried over. Extra vector columns are not carried over. With vector_column the table is kept instead and that vector column filled in place. Reports progress; pass async to run it as a background job.",
+        annotations(title = "Re-embed table", read_only_hint = false, destructive_hint = true, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn reembed_table(
+        &self,
+        Parameters(req): Parameters<ReembedTableRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<ReembedTableResult>>, ApiError> {
+        self.reembed_table_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Write a consistent backup of every table (rows, vectors and table config, including the embedding model) to a new directory on the server, with a SHA-256 checksum of every file. Tables are read as of one moment, so writes during the backup cannot leave it inconsistent.",
+        annotations(title = "Back up database", read_only_hint = false, destructive_hint = true, open_world_hint = false)
+    )]
+    async fn backup_database(
+        &self,
+        Parameters(req): Parameters<BackupDatabaseRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<Manifest>>, ApiError> {
+        self.backup_database_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Restore the tables of a backup written by backup_database, after checking every file against its checksum. Existing tables are only replaced with overwrite.",
+        annotations(title = "Restore database", read_only_hint = false, destructive_hint = true, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn restore_database(
+        &self,
+        Parameters(req): Parameters<RestoreDatabaseRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<Manifest>>, ApiError> {
+        self.restore_database_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Report search latency percentiles (p50/p90/p95/p99) over the recent sliding window, per table and pipeline, per pipeline and per table. Pass budget_ms to check each entry's p95 against a latency budget.",
+        annotations(title = "Performance report", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn performance_report(
+        &self,
+        Parameters(req): Parameters<PerformanceReportRequest>,
+    ) -> Result<Json<ApiResponse<PerformanceReport>>, ApiError> {
+        let budget = match req.budget_ms {
+            Some(ms) if ms.is_finite() && ms > 0.0 => Some(std::time::Duration::from_secs_f64(ms / 1000.0)),
+            Some(_) => return Err(ApiError::invalid_argument("budget_ms must be a positive number")),
+            None => None,
+        };
+
+        let report = self.latency.report(budget, |table| self.shown_table_name(table).map(str::to_string));
+        Ok(Json(ApiResponse::success(report)))
+    }
+
+    #[tool(
+        description = "Report tool call counts, failures, rate limited calls and time spent, and search latency percentiles, per tenant. Operator tenants see every tenant, other sessions only their own.",
+        annotations(title = "Admin report", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn admin_report(&self) -> Result<Json<ApiResponse<AdminReport>>, ApiError> {
+        let scope = tenant::scope(self.tenant.get(), &self.operator_tenants).map(|t| t.id().to_string());
+        let visible = |tenant: &Option<String>| scope.is_none() || *tenant == scope;
+        let report = AdminReport {
+            usage: self.usage.report().into_iter().filter(|u| visible(&u.tenant)).collect(),
+            latency_window_secs: self.latency.window().as_secs(),
+            search_latency: self.latency.by_tenant().into_iter().filter(|l| visible(&l.tenant)).collect(),
+        };
+        Ok(Json(ApiResponse::success(report)))
+    }
+
+    #[tool(
+        description = "Run the same query against two tables, e.g. embedded with different models or chunkers, and return their results interleaved with each table's rank and score, for side-by-side quality reviews. Scores from different tables are not directly comparable.",
+        annotations(title = "Compare search", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn compare_search(
+        &self,
+        Parameters(req): Parameters<CompareSearchRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<CompareSearchResult>>, ApiError> {
+        let table_a = req.table_name.clone().unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string());
+        if table_a == req.compare_table {
+            return Err(ApiError::invalid_argument("Give two different tables to compare"));
+        }
+        let search = |table_name: &str| SearchRequest {
+            table_name: Some(table_name.to_string()),
+            query: req.query.clone(),
+            limit: req.limit,
+            mode: req.mode.clone(),
+            fusion_preset: None,
+            vector_weight: None,
+            keyword_weight: None,
+            importance_weight: None,
+            sparse_weight: None,
+            pipeline: req.pipeline.clone(),
+            after: None,
+            before: None,
+            date_field: None,
+            date_locale: None,
+            version: None,
+            as_of: None,
+            vector_column: None,
+            modality: None,
+            rescore: None,
+            diagnostics: None,
+        };
+        let call = CallContext::from_request(&ctx);
+        let (a, b) = call
+            .cancellable(async {
+                tokio::try_join!(
+                    self.search_impl(search(&table_a), None),
+                    self.search_impl(search(&req.compare_table), None)
+                )
+            })
+            .await?;
+        let (a, b) = (a.data.unwrap_or_default(), b.data.unwrap_or_default());
+        let results = compare::interleave(&a, &b);
+        let overlap = results.iter().filter(|r| r.found_in == compare::Side::Both).count();
+        Ok(Json(ApiResponse::success(CompareSearchResult {
+            table_a,
+            table_b: req.compare_table,
+            overlap,
+            results,
+        })))
+    }
+
+    #[tool(
+        description = "Measure retrieval quality: run each query of a JSONL evaluation set against a table with the current model, index and search settings, and report recall@k, MRR and nDCG@k, overall and per query. Reports progress.",
+        annotations(title = "Evaluate retrieval", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn evaluate_retrieval(
+        &self,
+        Parameters(req): Parameters<EvaluateRetrievalRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<EvalReport>>, ApiError> {
+        let report = self.evaluate_retrieval_impl(req, &CallContext::from_request(&ctx)).await?;
+        Ok(Json(ApiResponse::success(report)))
+    }
+
+    #[tool(
+        description = "Mark results of a search as helpful or unhelpful for its query. The feedback is stored apart from the table and surfaced by feedback_report.",
+        annotations(title = "Record feedback", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn record_feedback(&self, Parameters(req): Parameters<RecordFeedbackRequest>) -> Result<Json<ApiResponse<String>>, ApiError> {
+        if req.feedback.is_empty() || req.feedback.len() > 100 {
+            return Err(ApiError::invalid_argument("Give between 1 and 100 feedback entries"));
+        }
+        if req.feedback.iter().any(|f| f.rank == Some(0)) {
+            return Err(ApiError::invalid_argument("rank starts at 1"));
+        }
+        let table_name = self.table_name(req.table_name.as_deref());
+        let feedback: Vec<Feedback> = req
+            .feedback
+            .into_iter()
+            .map(|f| Feedback {
+                chunk_id: f.id,
+                helpful: f.helpful,
+                rank: f.rank,
+                score: f.score,
+                comment: f.comment,
+            })
+            .collect();
+        feedback::record(&self.db, &table_name, &req.query, &feedback).await?;
+        Ok(Json(ApiResponse::success(format!("Recorded feedback on {} results", feedback.len()))))
+    }
+
+    #[tool(
+        description = "List the chunks of a table that rank high in searches but keep getting marked unhelpful with record_feedback, worst first, with the queries they failed.",
+        annotations(title = "Feedback report", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn feedback_report(&self, Parameters(req): Parameters<FeedbackReportRequest>) -> Result<Json<ApiResponse<Vec<FlaggedChunk>>>, ApiError> {
+        let limit = req.limit.unwrap_or(20);
+        if !(1..=100).contains(&limit) {
+            return Err(ApiError::invalid_argument("limit must be between 1 and 100"));
+        }
+        let min_unhelpful = req.min_unhelpful.unwrap_or(2).max(1);
+        let max_rank = req.max_rank.unwrap_or(3.0);
+        let table_name = self.table_name(req.table_name.as_deref());
+        let chunks = feedback::flagged_chunks(&self.db, &table_name, min_unhelpful, max_rank, limit).await?;
+        Ok(Json(ApiResponse::success(chunks)))
+    }
+
+    #[tool(
+        description = "Summarize the searches recorded with --query-log: the most frequent queries, the queries that returned nothing, and how the scores of the results are distributed.",
+        annotations(title = "Query stats", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn query_stats(&self, Parameters(req): Parameters<QueryStatsRequest>) -> Result<Json<ApiResponse<QueryStats>>, ApiError> {
+        let Some(log) = &self.query_log else {
+            return Err(ApiError::invalid_argument("Searches are not being recorded; start the server with --query-log"));
+        };
+        let top = req.top.unwrap_or(10);
+        if !(1..=100).contains(&top) {
+            return Err(ApiError::invalid_argument("top must be between 1 and 100"));
+        }
+        let since = req
+            .since
+            .as_deref()
+            .map(|since| dates::parse(since, chrono::Utc::now(), None))
+            .transpose()?;
+        let table_name = req.table_name.as_deref().map(|name| self.table_name(Some(name)));
+        let stats = log
+            .stats(table_name.as_deref(), since, top, |table| self.shown_table_name(table).is_some())
+            .await?;
+        Ok(Json(ApiResponse::success(stats)))
+    }
+
+    #[tool(
+        description = "Create a temporary table for throwaway work such as comparisons. It can be used with every tool that takes a table_name, is hidden from list_tables, and is dropped automatically when this session ends or its TTL expires.",
+        annotations(title = "Create scratch table", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn create_scratch_table(
+        &self,
+        Parameters(req): Parameters<CreateScratchTableRequest>,
+    ) -> Result<Json<ApiResponse<ScratchTableInfo>>, ApiError> {
+        let ttl = std::time::Duration::from_secs(req.ttl_seconds.unwrap_or(DEFAULT_SCRATCH_TTL_SECS));
+        let info = self.scratch.create(&self.session, ttl);
+
+        Ok(Json(ApiResponse::success(info)))
+    }
+
+    #[tool(
+        description = "List the scratch tables of this session and the seconds until each one expires.",
+        annotations(title = "List scratch tables", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn list_scratch_tables(&self) -> Result<Json<ApiResponse<Vec<ScratchTableInfo>>>, ApiError> {
+        Ok(Json(ApiResponse::success(self.scratch.list(&self.session))))
+    }
+
+    #[tool(
+        description = "Return the embedding vectors of the given texts, with the model id and dimension. Useful to debug retrieval quality or to do your own vector math; queries and documents are embedded exactly as search and ingestion do.",
+        annotations(title = "Embed text", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn embed(&self, Parameters(req): Parameters<EmbedRequest>) -> Result<Json<ApiResponse<EmbedResult>>, ApiError> {
+        if req.texts.is_empty() || req.texts.len() > MAX_EMBED_TEXTS {
+            return Err(ApiError::invalid_argument(format!("texts must hold 1 to {} texts", MAX_EMBED_TEXTS)));
+        }
+        let model = self.model.get().await?;
+        let embeddings = match req.input_type.as_deref().unwrap_or("query") {
+            "query" => {
+                let mut embeddings = Vec::with_capacity(req.texts.len());
+                for text in &req.texts {
+                    embeddings.push(model.embed_query(text).await.map_err(|e| ApiError::classify(e, ErrorCode::EmbeddingFailed))?);
+                }
+                embeddings
+            }
+            "document" => model.embed_documents(req.texts).await.map_err(|e| ApiError::classify(e, ErrorCode::EmbeddingFailed))?.0,
+            other => {
+                return Err(ApiError::invalid_argument(format!(
+                    "Unknown input_type '{}', expected 'query' or 'document'",
+                    other
+                )));
+            }
+        };
+
+        Ok(Json(ApiResponse::success(EmbedResult {
+            model: model.model_id().to_string(),
+            dimension: embeddings.first().map_or(0, Vec::len),
+            embeddings,
+        })))
+    }
+
+    #[tool(
+        description = "Report the active embedding configuration: engine (local or api), backend, model id, dimension, pooling, device, max tokens and truncation, dimension truncation, normalization and instructions.",
+        annotations(title = "Model info", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn model_info(&self) -> Result<Json<ApiResponse<ModelInfo>>, ApiError> {
+        let model = self.model.get().await?;
+        let info = model.info().await.map_err(|e| ApiError::classify(e, ErrorCode::EmbeddingFailed))?;
+
+        Ok(Json(ApiResponse::success(info)))
+    }
+
+    #[tool(
+        description = "Check whether the server is ready: the database answers, the embedding model is loaded (it loads in the background with --lazy-model) and embeds a tiny text, and the database disk has free space.",
+        annotations(title = "Health check", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn health_check(&self) -> Result<Json<ApiResponse<HealthReport>>, ApiError> {
+        Ok(Json(ApiResponse::success(health::check(&self.db, &self.model).await)))
+    }
+
+    #[tool(
+        description = "Describe a table: its row count, current version and when it was written, and the embedding model its vectors came from, per vector column.",
+        annotations(title = "Describe table", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn describe_table(&self, Parameters(req): Parameters<TableNameRequest>) -> Result<Json<ApiResponse<TableDescription>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let (rows, version) = self.db.describe(&table_name).await?;
+        let config = self.db.stored_table_config(&table_name).await?;
+
+        Ok(Json(ApiResponse::success(TableDescription {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            rows,
+            version,
+            embedding_model: config.embedding_model,
+            embedding_dim: config.embedding_dim,
+            vector_columns: config.vector_columns,
+            sparse_model: config.sparse_model,
+            quantization: config.quantization,
+            rescore: config.quantization.map(|_| config.rescore.unwrap_or(true)),
+        })))
+    }
+
+    #[tool(
+        description = "Show LanceDB's query plan for a search without running it: whether the filter is pushed down to a scalar index and whether the vector search goes through an ANN index or scans every row.",
+        annotations(title = "Explain query", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn explain_query(&self, Parameters(req): Parameters<ExplainQueryRequest>) -> Result<Json<ApiResponse<QueryPlan>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let limit = req.limit.unwrap_or(5);
+        if limit > MAX_SEARCH_LIMIT {
+            return Err(ApiError::invalid_argument(format!("limit must be at most {}", MAX_SEARCH_LIMIT)));
+        }
+        let hybrid = match req.mode.as_deref() {
+            None | Some("vector") => false,
+            Some("hybrid") => true,
+            Some(other) => {
+                return Err(ApiError::invalid_argument(format!(
+                    "Unknown search mode '{}', expected 'vector' or 'hybrid'",
+                    other
+                )));
+            }
+        };
+        let config = self.db.get_table_config(&table_name).await?;
+        let target = SearchTarget {
+            vector_column: req.vector_column.as_deref(),
+            modality: req.modality.as_deref(),
+            rescore: config.rescore.unwrap_or(true),
+            ..SearchTarget::default()
+        };
+        let plan = self
+            .db
+            .explain_search(
+                &table_name,
+                target,
+                req.query.as_deref(),
+                req.filter.as_deref(),
+                limit,
+                hybrid,
+                &*self.model.get().await?,
+                req.verbose.unwrap_or(false),
+            )
+            .await?;
+        Ok(Json(ApiResponse::success(plan)))
+    }
+
+    #[tool(
+        description = "List the versions of a table still kept on disk, oldest first. Every write creates a version; use restore_table_version to roll back to one.",
+        annotations(title = "List table versions", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn list_table_versions(&self, Parameters(req): Parameters<TableNameRequest>) -> Result<Json<ApiResponse<Vec<TableVersion>>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let versions = self.db.list_versions(&table_name).await?;
+        Ok(Json(ApiResponse::success(versions)))
+    }
+
+    #[tool(
+        description = "Roll a table back to an earlier version, e.g. after an accidental delete or a bad ingest. The restore is written as a new version, so the versions after it are kept and it can be undone the same way. Returns the new version.",
+        annotations(title = "Restore table version", read_only_hint = false, destructive_hint = true, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn restore_table_version(
+        &self,
+        Parameters(req): Parameters<RestoreTableVersionRequest>,
+    ) -> Result<Json<ApiResponse<TableVersion>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let arguments = serde_json::to_value(&req).unwrap_or_default();
+        let version = self.db.restore_version(&table_name, req.version).await?;
+        // Recorded so the log shows the rollback; replay refuses to skip over it
+        self.record_mutation("restore_table_version", arguments).await;
+        Ok(Json(ApiResponse::success(version)))
+    }
+
+    #[tool(
+        description = "Compact a table's small data fragments into larger ones, update its indices and delete old versions. Heavily ingested tables accumulate many small fragments that slow searches down. Versions deleted here can no longer be searched or restored.",
+        annotations(title = "Optimize table", read_only_hint = false, destructive_hint = true, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn optimize_table(
+        &self,
+        Parameters(req): Parameters<OptimizeTableRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<CompactionReport>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let days = req.retain_versions_days.unwrap_or(DEFAULT_VERSION_RETENTION_DAYS);
+        if !days.is_finite() || days < 0.0 {
+            return Err(ApiError::invalid_argument("retain_versions_days must be a non-negative number"));
+        }
+        if req.target_rows_per_fragment == Some(0) {
+            return Err(ApiError::invalid_argument("target_rows_per_fragment must be positive"));
+        }
+        let retain = chrono::Duration::seconds((days * 86_400.0) as i64);
+
+        let call = CallContext::from_request(&ctx);
+        call.report(0.0, None, format!("compacting '{}'", req.table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME)));
+        let report = call
+            .cancellable(async {
+                self.db
+                    .compact_and_cleanup(&table_name, req.target_rows_per_fragment, retain)
+                    .await
+                    .map_err(ApiError::from)
+            })
+            .await?;
+        tracing::info!(
+            table = table_name,
+            fragments_removed = report.fragments_removed,
+            versions_removed = report.versions_removed,
+            "Optimized table"
+        );
+        Ok(Json(ApiResponse::success(report)))
+    }
+
+    #[tool(
+        description = "Delete a document's chunks. By default they are only tombstoned: left out of searches and document listings, but kept so undelete can bring them back until purge removes them. Pass hard to delete them outright.",
+        annotations(title = "Delete document", read_only_hint = false, destructive_hint = true, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn delete_document(&self, Parameters(req): Parameters<DeleteDocumentRequest>) -> Result<Json<ApiResponse<DocumentChunksResult>>, ApiError> {
+        self.delete_document_impl(req).await.map(Json)
+    }
+
+    #[tool(
+        description = "Restore a document deleted with delete_document, as long as it was not purged or hard deleted.",
+        annotations(title = "Undelete document", read_only_hint = false, destructive_hint = false, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn undelete(&self, Parameters(req): Parameters<UndeleteRequest>) -> Result<Json<ApiResponse<DocumentChunksResult>>, ApiError> {
+        self.undelete_impl(req).await.map(Json)
+    }
+
+    #[tool(
+        description = "Permanently remove tombstoned chunks left by delete_document, of one document or the whole table. Purged chunks cannot be undeleted.",
+        annotations(title = "Purge deleted", read_only_hint = false, destructive_hint = true, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn purge(&self, Parameters(req): Parameters<PurgeRequest>) -> Result<Json<ApiResponse<PurgeResult>>, ApiError> {
+        self.purge_impl(req).await.map(Json)
+    }
+
+    #[tool(
+        description = "Delete the chunks of a table whose expires_at has passed now, rather than waiting for the background sweep. Documents get an expiry through the expires_at field of add_documents or an 'expires_at' metadata key.",
+        annotations(title = "Purge expired", read_only_hint = false, destructive_hint = true, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn purge_expired(&self, Parameters(req): Parameters<TableNameRequest>) -> Result<Json<ApiResponse<PurgeResult>>, ApiError> {
+        self.purge_expired_impl(req, chrono::Utc::now()).await.map(Json)
+    }
+
+    #[tool(
+        description = "Add a metadata field to every row of a table as a typed column, without re-ingesting. Existing rows without the field get the default value, as do rows written later without it; rows that already have the field keep their value. The field shows up in search results' metadata and can be indexed with create_scalar_index.",
+        annotations(title = "Add column", read_only_hint = false, destructive_hint = false, idempotent_hint = false, open_world_hint = false)
+    )]
+    async fn add_column(
+        &self,
+        Parameters(req): Parameters<AddColumnRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<AddColumnResult>>, ApiError> {
+        self.add_column_impl(req, &CallContext::from_request(&ctx)).await.map(Json)
+    }
+
+    #[tool(
+        description = "Build a scalar index (btree or bitmap) on a metadata key such as 'name', 'source' or 'created_at' so that looking up and deleting rows by it no longer scans the whole table. The key is copied into a column of its own, which later writes keep filled. Replaces any index the column has.",
+        annotations(title = "Create scalar index", read_only_hint = false, destructive_hint = false, idempotent_hint = true, open_world_hint = false)
+    )]
+    async fn create_scalar_index(
+        &self,
+        Parameters(req): Parameters<CreateScalarIndexRequest>,
+        ctx: RequestContext<RoleServer>,
+    ) -> Result<Json<ApiResponse<ScalarIndexResult>>, ApiError> {
+        let table_name = self.table_name(req.table_name.as_deref());
+        let index_type = req.index_type.unwrap_or_else(|| "btree".to_string());
+        let kind = ScalarIndexKind::parse(&index_type)?;
+
+        let call = CallContext::from_request(&ctx);
+        call.report(0.0, None, format!("indexing '{}'", req.column));
+        let filled_rows = call
+            .cancellable(async {
+                self.db
+                    .create_scalar_index(&table_name, &req.column, kind)
+                    .await
+                    .map_err(ApiError::from)
+            })
+            .await?;
+        tracing::info!(table = table_name, column = req.column, index_type, filled_rows, "Created scalar index");
+        Ok(Json(ApiResponse::success(ScalarIndexResult {
+            table_name: req.table_name.unwrap_or_else(|| DEFAULT_TABLE_NAME.to_string()),
+            column: req.column,
+            index_type,
+            filled_rows,
+        })))
+    }
+
+    #[tool(
+        description = "List all tables in the LanceDB database.",
+        annotations(title = "List tables", read_only_hint = true, open_world_hint = false)
+    )]
+    async fn list_tables(&self) -> Result<Json<ApiResponse<Vec<String>>>, ApiError> {
+        let tables: Vec<String> = self.db.list_tables()
+            .await?
+            .iter()
+            .filter_map(|table| self.shown_table_name(table).map(str::to_string))
+            .collect();
+
+        Ok(Json(ApiResponse::success(tables)))
     }
 }
 
-#[tool_handler]
+#[prompt_handler]
     impl ServerHandler for LanceDBServer {
         fn get_info(&self) -> ServerInfo {
             ServerInfo {
                 instructions: Some("A generic LanceDB MCP server with local embedding support (Qwen 0.5B default).".into()),
-                capabilities: ServerCapabilities::builder().enable_tools().build(),
+                capabilities: ServerCapabilities::builder().enable_tools().enable_prompts().enable_completions().build(),
                 ..Default::default()
             }
         }
+
+        /// Pin the session to the tenant of the request that opened it, and
+        /// to what its token may do under the ACL.
+        async fn initialize(
+            &self,
+            request: InitializeRequestParam,
+            context: RequestContext<RoleServer>,
+        ) -> Result<InitializeResult, rmcp::ErrorData> {
+            if self.multi_tenant {
+                let tenant = Self::request_tenant(&context)
+                    .ok_or_else(|| rmcp::ErrorData::invalid_request("The session names no tenant", None))?;
+                let _ = self.tenant.set(tenant.clone());
+            }
+            if let Some(acl) = &self.acl {
+                let extensions = context.extensions.get::<axum::http::request::Parts>().map(|parts| &parts.extensions);
+                let tenant = extensions.and_then(|e| e.get::<Tenant>());
+                let token = extensions.and_then(|e| e.get::<TokenFingerprint>()).map(|t| t.0.as_str());
+                let _ = self.permissions.set(acl.permissions(tenant, token));
+            }
+            if context.peer.peer_info().is_none() {
+                context.peer.set_peer_info(request);
+            }
+            Ok(self.get_info())
+        }
+
+        async fn list_tools(
+            &self,
+            _request: Option<PaginatedRequestParam>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListToolsResult, rmcp::ErrorData> {
+            Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+        }
+
+        /// Dispatch to the tool router, logging every call with its tool,
+        /// table, outcome and duration, recording it in the audit log and
+        /// counting it towards the session's tenant.
+        async fn call_tool(
+            &self,
+            request: CallToolRequestParam,
+            mut context: RequestContext<RoleServer>,
+        ) -> Result<CallToolResult, rmcp::ErrorData> {
+            let tool = request.name.to_string();
+            let tenant = self.tenant.get().map(Tenant::id);
+            let table = request
+                .arguments
+                .as_ref()
+                .and_then(|args| args.get("table_name"))
+                .and_then(|t| t.as_str())
+                .map(str::to_string);
+            let arguments_sha256 = CallRecord::hash_arguments(request.arguments.as_ref());
+            let client = context.peer.peer_info().map(|info| info.client_info.name.clone());
+            let query_sha256 = self.slow_queries.as_ref().and_then(|_| SlowQuery::hash_query(request.arguments.as_ref()));
+            // Searches record their timing breakdown here for the slow-query log
+            let breakdown = self.slow_queries.as_ref().map(|_| Arc::new(Diagnostics::default()));
+            if let Some(breakdown) = &breakdown {
+                context.extensions.insert(breakdown.clone());
+            }
+            let started = std::time::Instant::now();
+
+            let timeout = self.timeouts.get(&tool);
+            let cancel = context.ct.clone();
+            let admitted = self
+                .drain
+                .enter()
+                .ok_or_else(|| ApiError::new(ErrorCode::ShuttingDown, "The server is shutting down"))
+                .and_then(|draining| {
+                    self.check_caller(&context)?;
+                    self.check_tool_access(&tool, request.arguments.as_ref())?;
+                    let tenant_budget = self.tenant.get().and_then(|t| self.tenant_budgets.get(t.id()));
+                    let mut budgets = vec![&*self.session_budget];
+                    budgets.extend(tenant_budget.as_deref());
+                    budgets.push(&*self.call_budget);
+                    Ok((draining, limits::admit(&budgets)?))
+                });
+            let result = match admitted {
+                Ok(admitted) => {
+                    // Spawned so a call outliving its timeout is detached rather than
+                    // dropped midway through a write, which could leave a document half
+                    // ingested. It holds on to its drain and call permits until it ends,
+                    // so shutdown waits for it and it still counts against the limits.
+                    let server = self.clone();
+                    let call = tokio::spawn(async move {
+                        let _admitted = admitted;
+                        server.tool_router.call(ToolCallContext::new(&server, request, context)).await
+                    });
+                    let joined = |result: Result<Result<CallToolResult, rmcp::ErrorData>, tokio::task::JoinError>| {
+                        result.unwrap_or_else(|e| Err(rmcp::ErrorData::internal_error(format!("{} failed: {}", tool, e), None)))
+                    };
+                    match timeouts::run(call, timeout, &cancel).await {
+                        Run::Finished(result) => joined(result),
+                        // It came back after all, and whatever it wrote is applied; a
+                        // timeout error would make the client retry those writes
+                        Run::TimedOut(Some(result)) => joined(result),
+                        Run::TimedOut(None) => {
+                            let secs = timeout.map_or(0, |t| t.as_secs());
+                            let err = ApiError::new(ErrorCode::Timeout, format!("{} did not finish within {}s", tool, secs));
+                            Ok(CallToolResult::error(err.into_contents()))
+                        }
+                    }
+                }
+                Err(e) => Ok(CallToolResult::error(e.into_contents())),
+            };
+
+            let elapsed = started.elapsed();
+            let duration_ms = elapsed.as_secs_f64() * 1000.0;
+            let code = result
+                .as_ref()
+                .ok()
+                .and_then(|r| r.structured_content.as_ref())
+                .and_then(|c| c.get("code"))
+                .and_then(|c| c.as_i64());
+            let outcome = match &result {
+                Ok(r) if r.is_error != Some(true) => Outcome::Ok,
+                Ok(_) => Outcome::Error,
+                Err(_) => Outcome::Rejected,
+            };
+            self.usage.record(tenant, outcome, code, elapsed);
+            if let Some(audit) = &self.audit {
+                let call = CallRecord {
+                    session: self.session.id(),
+                    tenant: tenant.map(str::to_string),
+                    client,
+                    table: table.clone(),
+                    arguments_sha256,
+                    duration_ms,
+                    outcome,
+                    code,
+                };
+                if let Err(e) = audit.record_call(self.tenant.get(), &tool, call).await {
+                    tracing::error!("Failed to write audit log entry for {}: {}", tool, e);
+                }
+            }
+            if let Some(slow_queries) = self.slow_queries.as_ref().filter(|log| log.is_slow(elapsed)) {
+                slow_queries.record(SlowQuery {
+                    tool: tool.clone(),
+                    tenant: tenant.map(str::to_string),
+                    table: table.clone(),
+                    query_sha256,
+                    duration_ms,
+                    breakdown: breakdown.filter(|_| tool == "search").map(|b| b.snapshot()),
+                });
+            }
+            match &result {
+                Ok(r) if r.is_error != Some(true) => {
+                    tracing::info!(tool = %tool, tenant, table = table.as_deref(), duration_ms, code, "Tool call succeeded")
+                }
+                Ok(_) => tracing::warn!(tool = %tool, tenant, table = table.as_deref(), duration_ms, code, "Tool call failed"),
+                Err(e) => tracing::warn!(tool = %tool, tenant, table = table.as_deref(), duration_ms, error = %e.message, "Tool call rejected"),
+            }
+            result
+        }
+
+        async fn complete(
+            &self,
+            request: CompleteRequestParam,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<CompleteResult, rmcp::ErrorData> {
+            self.complete_argument(request).await
+        }
+    }
+
+fn main() -> Result<()> {
+    let (args, config_file) = config::parse::<Cli>()?;
+
+    if args.daemon && args.command.is_none() {
+        if args.transport == "stdio" || args.transport == "repl" {
+            anyhow::bail!("--daemon requires a network transport, {} cannot be detached", args.transport);
+        }
+        if args.log_file.is_none() {
+            // A daemon's stderr is /dev/null
+            anyhow::bail!("--daemon requires --log-file, otherwise every log line is lost");
+        }
+        daemon::daemonize()?;
     }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_writer(std::io::stderr)
-        .with_env_filter("info")
-        .init();
+    // Initialize logging; the guard flushes the file writer on exit
+    let _log_guard = init_logging(&args)?;
+
+    if let Some(path) = &args.config {
+        tracing::info!("Loaded settings from {}", path);
+    }
+
+    let _pid_file = match &args.pid_file {
+        Some(path) => Some(daemon::PidFile::create(std::path::Path::new(path))?),
+        None => None,
+    };
+
+    let device = Device::select(args.device.as_deref())?;
+    // SAFETY: the runtime is not started yet and the log writer thread does
+    // not touch the environment
+    unsafe { device.pin() };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(args, config_file, device))
+}
+
+fn init_logging(args: &Cli) -> Result<Option<tracing_appender::non_blocking::WorkerGuard>> {
+    let filter = tracing_subscriber::EnvFilter::try_new(&args.log_level)
+        .map_err(|e| anyhow::anyhow!("Invalid --log-level '{}': {}", args.log_level, e))?;
+
+    let (writer, guard) = match &args.log_file {
+        None => (BoxMakeWriter::new(std::io::stderr), None),
+        Some(log_file) => {
+            let path = std::path::Path::new(log_file);
+            let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+            let prefix = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "lancedb-mcp-server.log".to_string());
 
-    let args = Cli::parse();
+            let appender = match args.log_rotation.as_str() {
+                "daily" => tracing_appender::rolling::daily(dir, prefix),
+                "hourly" => tracing_appender::rolling::hourly(dir, prefix),
+                "never" => tracing_appender::rolling::never(dir, prefix),
+                other => anyhow::bail!("Unknown log rotation: {}", other),
+            };
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+    };
+
+    let logger = tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(args.log_file.is_none())
+        .with_env_filter(filter);
+    match args.log_format.as_str() {
+        "text" => logger.init(),
+        // Fields at the top level of each line, as Loki and Elasticsearch expect
+        "json" => logger.json().flatten_event(true).init(),
+        other => anyhow::bail!("Unknown --log-format '{}', expected 'text' or 'json'", other),
+    }
 
+    Ok(guard)
+}
+
+async fn run(args: Cli, config_file: ConfigFile, device: Device) -> Result<()> {
     tracing::info!("Initializing LanceDB at {}", args.db_path);
-    let db = Arc::new(VectorDB::new(&args.db_path).await?);
+    let maintenance_tables = config_file.maintenance_tables();
+    let model_mismatch = match args.on_model_mismatch.as_str() {
+        "reject" => ModelMismatchPolicy::Reject,
+        "warn" => ModelMismatchPolicy::Warn,
+        other => anyhow::bail!("Unknown --on-model-mismatch '{}', expected 'reject' or 'warn'", other),
+    };
+    storage::check_uri(&args.db_path)?;
+    let connect_options = ConnectOptions {
+        storage: storage::options(&args.storage_option, args.s3_region.as_deref(), args.s3_endpoint.as_deref())?,
+        cloud: CloudOptions::for_uri(
+            &args.db_path,
+            args.lancedb_api_key.clone(),
+            args.lancedb_region.clone(),
+            args.lancedb_host_override.clone(),
+        )?,
+        consistency: Consistency::parse(&args.consistency)?,
+    };
+    let sparse = args.sparse_model.clone().map(SparseEncoder::new).transpose()?;
+    let db = Arc::new(
+        VectorDB::new(&args.db_path, connect_options, model_mismatch)
+            .await?
+            .with_table_defaults(config_file.tables)
+            .with_sparse_encoder(sparse),
+    );
+
+    // Exporting, backing up and restoring need no embedding model, so don't wait for one to load
+    if let Some(Command::Export { table, output, format, with_vectors }) = &args.command {
+        let path = std::path::Path::new(output);
+        let format = match format {
+            Some(format) => ExportFormat::parse(format)?,
+            None => ExportFormat::from_path(path)
+                .ok_or_else(|| anyhow::anyhow!("Cannot tell the format of {}, name it .parquet or .jsonl or pass --format", output))?,
+        };
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let rows = export::export_table(&db, table, path, format, *with_vectors, &cancel, |_| {}).await?;
+        tracing::info!("Exported {} rows of '{}' to {}", rows, table, output);
+        return Ok(());
+    }
+    if let Some(Command::Backup { output }) = &args.command {
+        let tables: Vec<(String, String)> = db.list_tables().await?.into_iter().map(|t| (t.clone(), t)).collect();
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let manifest = backup::backup(&db, &tables, std::path::Path::new(output), &cancel, |_, name| {
+            tracing::info!("Backing up '{}'", name)
+        })
+        .await?;
+        tracing::info!("Backed up {} tables to {}", manifest.tables.len(), output);
+        return Ok(());
+    }
+    if let Some(Command::Restore { input, overwrite }) = &args.command {
+        let dir = std::path::Path::new(input);
+        let manifest = backup::verify(dir).await?;
+        let cancel = tokio_util::sync::CancellationToken::new();
+        backup::restore(&db, dir, &manifest, str::to_string, *overwrite, &cancel, |_, name| {
+            tracing::info!("Restoring '{}'", name)
+        })
+        .await?;
+        tracing::info!("Restored {} tables from {}", manifest.tables.len(), input);
+        return Ok(());
+    }
+
+    let truncation = match args.truncation.as_str() {
+        "head" => Truncation::Head,
+        "tail" => Truncation::Tail,
+        "middle" => Truncation::Middle,
+        other => anyhow::bail!("Unknown --truncation '{}', expected 'head', 'tail' or 'middle'", other),
+    };
+
+    let default_backend = if cfg!(feature = "local-embeddings") || !cfg!(feature = "fastembed") {
+        "candle"
+    } else {
+        "fastembed"
+    };
+    let backend = match args.backend.as_deref().unwrap_or(default_backend) {
+        "candle" => LocalBackend::Candle,
+        "onnx" => LocalBackend::Onnx,
+        "llama-cpp" => LocalBackend::LlamaCpp,
+        "fastembed" => LocalBackend::FastEmbed,
+        other => anyhow::bail!("Unknown --backend '{}', expected 'candle', 'onnx', 'llama-cpp' or 'fastembed'", other),
+    };
+    let pooling = match args.pooling.as_deref() {
+        None => None,
+        Some("mean") => Some(Pooling::Mean),
+        Some("cls") => Some(Pooling::Cls),
+        Some("last-token") => Some(Pooling::LastToken),
+        Some(other) => anyhow::bail!("Unknown --pooling '{}', expected 'mean', 'cls' or 'last-token'", other),
+    };
 
+    let provider = match args.embedding_provider.as_str() {
+        "openai" => ApiProvider::OpenAi,
+        "ollama" => ApiProvider::Ollama,
+        "tei" => ApiProvider::Tei,
+        "cohere" => ApiProvider::Cohere,
+        "bedrock" => ApiProvider::Bedrock,
+        "azure" => ApiProvider::Azure {
+            deployment: args.azure_deployment,
+            api_version: args.azure_api_version,
+        },
+        other => anyhow::bail!("Unknown --embedding-provider '{}', expected 'openai', 'ollama', 'tei', 'cohere', 'bedrock' or 'azure'", other),
+    };
+    let http = HttpOptions {
+        proxy: args.proxy,
+        ca_certs: args.ca_cert.into_iter().map(Into::into).collect(),
+        insecure: args.insecure_skip_tls_verify,
+    };
+    let embedding_headers = embeddings::extra_headers(
+        &args.embedding_header,
+        args.embedding_headers_file.as_deref().map(std::path::Path::new),
+    )?;
+    // Bedrock finds its endpoint through the AWS configuration
+    let api = (args.embedding_endpoint.is_some() || provider == ApiProvider::Bedrock).then(|| ApiOptions {
+        endpoint: args.embedding_endpoint,
+        api_key: args.api_key,
+        provider,
+        retry: RetryPolicy {
+            max_retries: args.embedding_retries,
+            base_delay: std::time::Duration::from_millis(args.embedding_retry_base_ms),
+        },
+        requests_per_second: args.embedding_rps,
+        concurrency: args.embedding_concurrency,
+        headers: embedding_headers,
+        path: args.embedding_path,
+        http: http.clone(),
+    });
+
+    let local = LocalOptions {
+        workers: args.embedding_workers,
+        pooling,
+        backend,
+        device,
+        dtype: DType::select(&args.dtype, device)?,
+        model_path: args.model_path.map(Into::into),
+        offline: args.offline,
+        hf_cache_dir: args.hf_cache_dir.map(Into::into),
+        hf_endpoint: args.hf_endpoint,
+    };
+    let configure = move |model: EmbeddingModel| {
+        model
+            .with_batch_limits(args.embedding_batch_size, args.embedding_batch_tokens)
+            .with_max_tokens(args.max_tokens, truncation)
+            .with_instructions(args.query_instruction, args.document_instruction)
+            .with_dimensions(args.embedding_dimensions)
+            .with_normalization(args.normalize_embeddings)
+            .with_query_cache(args.query_cache_size)
+    };
+    let load_model = EmbeddingModel::new(args.embedding_model, api, local);
+    let load_model = async move { Ok::<_, anyhow::Error>(configure(load_model.await?)) };
     tracing::info!("Loading embedding model...");
-    let model = Arc::new(Mutex::new(EmbeddingModel::new(
-        args.embedding_endpoint,
-        args.embedding_model,
-        args.api_key
-    ).await?));
+    let model = Arc::new(if args.lazy_model {
+        LazyModel::spawn(load_model)
+    } else {
+        LazyModel::ready(load_model.await?)
+    });
+
+    let fetcher = Arc::new(UrlFetcher::new(UrlPolicy::new(args.fetch_allow, args.fetch_deny, args.fetch_allow_private), &http)?);
 
-    let server = LanceDBServer::new(db, model);
+    let audit = match &args.audit_log {
+        Some(path) => Some(Arc::new(AuditLog::open(std::path::Path::new(path)).await?)),
+        None => None,
+    };
 
+    let router = args.auto_route.then(|| Arc::new(TableRouter::new()));
+
+    let pipelines = match &args.pipelines {
+        Some(path) => Arc::new(Pipelines::load(std::path::Path::new(path))?),
+        None => Arc::new(Pipelines::default()),
+    };
+
+    let latency = Arc::new(LatencyTracker::new(std::time::Duration::from_secs(args.latency_window_secs)));
+
+    let operator_tenants = args.operator_tenant.iter().map(|id| Tenant::new(id)).collect::<Result<Vec<_>>>()?;
+    if !operator_tenants.is_empty() && !args.multi_tenant {
+        anyhow::bail!("--operator-tenant needs --multi-tenant");
+    }
+    for (flag, value) in [
+        ("--max-concurrent-calls", args.max_concurrent_calls.map(|n| n as u64)),
+        ("--max-calls-per-minute", args.max_calls_per_minute.map(u64::from)),
+        ("--tenant-max-concurrent-calls", args.tenant_max_concurrent_calls.map(|n| n as u64)),
+        ("--tenant-max-calls-per-minute", args.tenant_max_calls_per_minute.map(u64::from)),
+        ("--session-max-concurrent-calls", args.session_max_concurrent_calls.map(|n| n as u64)),
+        ("--session-max-calls-per-minute", args.session_max_calls_per_minute.map(u64::from)),
+    ] {
+        if value == Some(0) {
+            anyhow::bail!("{} must be at least 1", flag);
+        }
+    }
+    let server = LanceDBServer::new(db.clone(), model.clone(), fetcher, audit, router, pipelines, latency.clone())
+        .with_operator_tenants(operator_tenants)
+        .with_call_limits(
+            Limits {
+                max_in_flight: args.max_concurrent_calls,
+                per_minute: args.max_calls_per_minute,
+            },
+            Limits {
+                max_in_flight: args.tenant_max_concurrent_calls,
+                per_minute: args.tenant_max_calls_per_minute,
+            },
+            Limits {
+                max_in_flight: args.session_max_concurrent_calls,
+                per_minute: args.session_max_calls_per_minute,
+            },
+        )
+        .with_tool_timeouts(ToolTimeouts::new(args.tool_timeout_secs, &args.tool_timeout)?)
+        .with_chunking(args.chunk_size, args.chunk_overlap);
+    let server = match args.slow_query_ms {
+        Some(ms) => {
+            let store = args.slow_query_table.then(|| db.clone());
+            server.with_slow_query_log(SlowQueryLog::new(std::time::Duration::from_millis(ms), store))
+        }
+        None => server,
+    };
+    let server = if args.query_log { server.with_query_log(QueryLog::new(db.clone())) } else { server };
+    let server = match &args.export_dir {
+        Some(dir) => server.with_export_dir(dir),
+        None => server,
+    };
+    let server = match (&args.ingest_root, &args.watch_dir) {
+        (Some(dir), _) => server.with_ingest_root(PathRoot::new(dir, "--ingest-root")?),
+        (None, Some(dir)) => server.with_ingest_root(PathRoot::new(dir, "--watch-dir")?),
+        (None, None) => server,
+    };
+    let server = match &args.backup_dir {
+        Some(dir) => server.with_backup_root(PathRoot::new(dir, "--backup-dir")?),
+        None => server,
+    };
+    // Before any tool is unregistered, so prompts still see what search needs
+    let server = match &args.acl {
+        Some(path) => {
+            let acl = Acl::load(std::path::Path::new(path), &server.tool_router.list_all())?;
+            server.with_acl(acl)
+        }
+        None => server,
+    };
+    let server = server.select_tools(&args.enable_tools, &args.disable_tools)?;
+    let server = if args.read_only { server.read_only() } else { server };
+    let server = if args.multi_tenant { server.multi_tenant() } else { server };
+
+    if let Some(Command::Replay { log, dry_run }) = &args.command {
+        if args.audit_log.as_deref() == Some(log.as_str()) {
+            anyhow::bail!("Refusing to replay {} while also appending to it as --audit-log", log);
+        }
+        if args.read_only && !*dry_run {
+            anyhow::bail!("Replaying writes to the database and cannot be combined with --read-only");
+        }
+        return replay::run(&server, std::path::Path::new(log), *dry_run).await;
+    }
+
+    if let Some(Command::Evaluate { cases, table, k, mode, pipeline }) = &args.command {
+        let jsonl = tokio::fs::read_to_string(cases)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", cases, e))?;
+        let req = EvaluateRetrievalRequest {
+            table_name: Some(table.clone()),
+            cases: jsonl,
+            k: Some(*k),
+            mode: mode.clone(),
+            pipeline: pipeline.clone(),
+            vector_column: None,
+            version: None,
+        };
+        let report = server
+            .evaluate_retrieval_impl(req, &CallContext::default())
+            .await
+            .map_err(|e| anyhow::anyhow!("Evaluation failed: {}", e))?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    // Scratch tables never survive a restart
+    let scratch = server.scratch.clone();
+    scratch.drop_everything().await;
+    scratch.spawn_sweeper();
+    if args.expiry_sweep_secs > 0 {
+        ExpirySweeper::new(server.db.clone(), std::time::Duration::from_secs(args.expiry_sweep_secs)).spawn();
+    }
+
+    // LanceDB Cloud keeps its indexes up to date itself
+    if args.index_optimize_rows > 0 && !args.db_path.starts_with("db://") {
+        let interval = (args.index_optimize_interval_secs > 0)
+            .then(|| std::time::Duration::from_secs(args.index_optimize_interval_secs));
+        IndexOptimizer::new(server.db.clone(), args.index_optimize_rows, interval).spawn();
+    }
+    if !args.db_path.starts_with("db://") {
+        let maintenance = MaintenanceScheduler::new(
+            server.db.clone(),
+            args.maintenance_interval.as_deref(),
+            args.maintenance_at.as_deref(),
+            args.maintenance_retain_days,
+            args.maintenance_target_rows,
+            maintenance_tables,
+        )?;
+        if maintenance.is_active() {
+            maintenance.spawn();
+        }
+    }
+
+    if args.read_only && args.watch_dir.is_some() {
+        anyhow::bail!("--watch-dir writes to the database and cannot be combined with --read-only");
+    }
+    #[cfg(not(feature = "local-files"))]
+    if args.watch_dir.is_some() {
+        anyhow::bail!("--watch-dir is not available: this build has no local-files feature");
+    }
+    #[cfg(feature = "local-files")]
+    if let Some(dir) = &args.watch_dir {
+        let watcher = watch::run(server.clone(), dir.into(), args.watch_table.clone());
+        tokio::spawn(async move {
+            if let Err(e) = watcher.await {
+                tracing::error!("Directory watcher stopped: {}", e);
+            }
+        });
+    }
+
+    if args.acl.is_some() && args.transport == "repl" {
+        anyhow::bail!("--acl applies to MCP clients and cannot be combined with the repl transport");
+    }
+    if args.multi_tenant && (args.transport == "stdio" || args.transport == "repl") {
+        anyhow::bail!("--multi-tenant takes tenants from HTTP requests and needs an HTTP transport, not {}", args.transport);
+    }
+
+    let drain = server.drain.clone();
+    let shutdown_timeout = std::time::Duration::from_secs(args.shutdown_timeout_secs);
+    // Ends every MCP session once in-flight calls have drained
+    let sessions = tokio_util::sync::CancellationToken::new();
     match args.transport.as_str() {
         "stdio" => {
             tracing::info!("Starting MCP server on stdio...");
@@ -222,25 +3820,188 @@ async fn main() -> Result<()> {
                     return Err(e.into());
                 }
             };
+            daemon::notify_ready();
             service.waiting().await?;
+            finish_draining(&drain, shutdown_timeout).await;
+            scratch.drop_everything().await;
         }
-        "streamable-http" => {
-            tracing::info!("Starting MCP server on Streamable HTTP transport at http://0.0.0.0:{}", args.port);
-            let service = StreamableHttpService::new(
-                move || Ok(server.clone()),
-                LocalSessionManager::default().into(),
-                Default::default()
-            );
-
-            let app = Router::new()
-                .fallback_service(service)
-                .layer(TraceLayer::new_for_http());
-
-            let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", args.port)).await?;
-            axum::serve(listener, app).await?;
+        flavor @ ("streamable-http" | "sse") => {
+            let sse = flavor == "sse";
+            let app = http_app(server, db, model, latency, sse, sessions.clone(), &args)?;
+            let listener = tokio::net::TcpListener::bind((args.host.as_str(), args.port)).await?;
+            if sse {
+                tracing::info!("Starting MCP server on SSE transport at http://{}/sse", listener.local_addr()?);
+            } else {
+                tracing::info!("Starting MCP server on Streamable HTTP transport at http://{}", listener.local_addr()?);
+            }
+            daemon::notify_ready();
+            let stop = tokio_util::sync::CancellationToken::new();
+            let serving = axum::serve(listener, app).with_graceful_shutdown(stop.clone().cancelled_owned());
+            serve_until_shutdown(async move { Ok(serving.await?) }, stop, sessions, &drain, shutdown_timeout).await?;
+            scratch.drop_everything().await;
+        }
+        transport if transport.starts_with("unix:") => {
+            let path = std::path::Path::new(&transport["unix:".len()..]);
+            if path.as_os_str().is_empty() {
+                anyhow::bail!("The unix transport needs a socket path, e.g. unix:/run/lancedb-mcp.sock");
+            }
+            let app = http_app(server, db, model, latency, false, sessions.clone(), &args)?;
+            tracing::info!("Starting MCP server on Streamable HTTP transport at unix:{}", path.display());
+            let stop = tokio_util::sync::CancellationToken::new();
+            let serving = uds::serve(path.to_path_buf(), app, stop.clone().cancelled_owned());
+            serve_until_shutdown(serving, stop, sessions, &drain, shutdown_timeout).await?;
+            scratch.drop_everything().await;
+        }
+        "repl" => {
+            repl::run(server).await?;
         }
         _ => anyhow::bail!("Unknown transport: {}", args.transport),
     }
 
     Ok(())
 }
+
+/// The MCP endpoint, streamable HTTP or legacy SSE, plus metrics and probes,
+/// served over TCP or a unix socket.
+fn http_app(
+    server: LanceDBServer,
+    db: Arc<VectorDB>,
+    model: Arc<LazyModel>,
+    latency: Arc<LatencyTracker>,
+    sse: bool,
+    sessions: tokio_util::sync::CancellationToken,
+    args: &Cli,
+) -> Result<Router> {
+    let operator_tenants = server.operator_tenants.clone();
+    let exports = server.exports.clone();
+    // Operators and single-tenant servers see every series, other tenants only their own
+    let mcp = Router::new()
+        .route("/metrics", axum::routing::get(move |tenant: Option<Extension<Tenant>>| {
+            let latency = latency.clone();
+            let operator_tenants = operator_tenants.clone();
+            async move { latency.prometheus(tenant::scope(tenant.as_deref(), &operator_tenants)) }
+        }));
+    let mut mcp = if sse {
+        mcp.merge(sse::router(move || server.for_new_session(), sessions))
+    } else {
+        mcp.fallback_service(StreamableHttpService::new(
+            move || Ok(server.for_new_session()),
+            LocalSessionManager::default().into(),
+            StreamableHttpServerConfig {
+                cancellation_token: sessions,
+                ..Default::default()
+            },
+        ))
+    };
+    let auth = BearerAuth::new(&args.auth_token, &args.auth_token_file)?;
+    if args.multi_tenant {
+        let header = args
+            .tenant_header
+            .as_deref()
+            .map(axum::http::HeaderName::try_from)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid --tenant-header: {}", e))?;
+        if header.is_none() && !auth.as_ref().is_some_and(BearerAuth::has_tenants) {
+            anyhow::bail!("--multi-tenant needs tenants in --auth-token-file or a --tenant-header");
+        }
+        // Inside authentication, which tags requests with their token's tenant
+        mcp = mcp.layer(axum::middleware::from_fn_with_state(Arc::new(TenantPolicy { header }), tenant::identify));
+    }
+    // Export names are unguessable and belong to no tenant
+    mcp = mcp.route("/exports/:name", axum::routing::get(export::download).with_state(exports));
+    match auth {
+        Some(auth) => {
+            mcp = mcp.layer(axum::middleware::from_fn_with_state(Arc::new(auth), auth::require_bearer));
+        }
+        None => tracing::warn!("No --auth-token configured, the HTTP transport accepts unauthenticated requests"),
+    }
+
+    let ready = Arc::new(health::ReadyCache::default());
+    // Probes stay unauthenticated and name no tenant so orchestrators can reach them
+    let mut app = Router::new()
+        // Liveness: the process serves HTTP
+        .route("/healthz", axum::routing::get(|| async { "ok" }))
+        // Readiness: every tool can be served
+        .route("/readyz", axum::routing::get(move || {
+            let (db, model, ready) = (db.clone(), model.clone(), ready.clone());
+            async move {
+                let report = ready.check(&db, &model).await;
+                let status = if report.ready { axum::http::StatusCode::OK } else { axum::http::StatusCode::SERVICE_UNAVAILABLE };
+                (status, axum::Json(report))
+            }
+        }))
+        .merge(mcp);
+    // Outside authentication, so preflight requests without a token succeed
+    if let Some(cors) = cors::layer(&args.cors_origin, &args.cors_header, &args.cors_method)? {
+        app = app.layer(cors);
+    }
+    Ok(app
+        .layer(tower_http::limit::RequestBodyLimitLayer::new(args.max_request_bytes))
+        .layer(TraceLayer::new_for_http()))
+}
+
+/// Serve until a shutdown signal, then drain: stop accepting connections, let
+/// in-flight tool calls and ingest jobs finish (rejecting new calls), and only
+/// then end the remaining MCP sessions so the server can exit.
+async fn serve_until_shutdown(
+    serving: impl Future<Output = Result<()>> + Send + 'static,
+    stop: tokio_util::sync::CancellationToken,
+    sessions: tokio_util::sync::CancellationToken,
+    drain: &Drain,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    let mut serving = tokio::spawn(serving);
+    tokio::select! {
+        result = &mut serving => return result?,
+        _ = shutdown_signal() => {}
+    }
+    daemon::notify_stopping();
+    stop.cancel();
+    finish_draining(drain, timeout).await;
+    sessions.cancel();
+    // Connections close once their sessions are gone; don't wait on stragglers
+    match tokio::time::timeout(std::time::Duration::from_secs(5), &mut serving).await {
+        Ok(result) => result?,
+        Err(_) => {
+            tracing::warn!("Closing HTTP connections that are still open");
+            serving.abort();
+            Ok(())
+        }
+    }
+}
+
+async fn finish_draining(drain: &Drain, timeout: std::time::Duration) {
+    if drain.wait(timeout).await {
+        tracing::info!("All tool calls and ingest jobs finished");
+    } else {
+        tracing::warn!("Tool calls or ingest jobs still running after {}s, exiting anyway", timeout.as_secs());
+    }
+}
+
+/// Resolves on Ctrl-C or SIGTERM (what systemd sends on stop).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received");
+}
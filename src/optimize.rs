@@ -0,0 +1,244 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::VectorDB;
+use crate::scratch::SCRATCH_TABLE_PREFIX;
+
+pub const DEFAULT_OPTIMIZE_ROWS: usize = 10_000;
+pub const DEFAULT_OPTIMIZE_INTERVAL_SECS: u64 = 600;
+/// Days of old table versions `optimize_table` and scheduled maintenance
+/// keep for time travel.
+pub const DEFAULT_VERSION_RETENTION_DAYS: f64 = 7.0;
+/// How often the maintenance scheduler looks for tables that are due.
+const MAINTENANCE_POLL: Duration = Duration::from_secs(60);
+
+/// Keeps table indices current after ingests by adding new rows to them
+/// incrementally instead of rebuilding them. A table is optimized as soon as
+/// `threshold` rows were written to it, and every `interval` if any were.
+pub struct IndexOptimizer {
+    db: Arc<VectorDB>,
+    threshold: usize,
+    interval: Option<Duration>,
+}
+
+impl IndexOptimizer {
+    pub fn new(db: Arc<VectorDB>, threshold: usize, interval: Option<Duration>) -> Self {
+        Self { db, threshold, interval }
+    }
+
+    /// Run the optimizer in the background until the process exits.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = self.interval.map(tokio::time::interval);
+            if let Some(interval) = &mut interval {
+                // The first tick completes immediately
+                interval.tick().await;
+            }
+            loop {
+                let scheduled = tokio::select! {
+                    _ = self.db.rows_written() => false,
+                    _ = async {
+                        match &mut interval {
+                            Some(interval) => interval.tick().await,
+                            None => std::future::pending().await,
+                        }
+                    } => true,
+                };
+                let min_rows = if scheduled { 1 } else { self.threshold };
+                for (table, rows) in self.db.take_unindexed(min_rows) {
+                    self.optimize(&table, rows).await;
+                }
+            }
+        });
+    }
+
+    async fn optimize(&self, table: &str, rows: usize) {
+        match self.db.optimize_indices(table).await {
+            Ok(true) => tracing::info!("Added {} new rows to the indices of '{}'", rows, table),
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to optimize the indices of '{}': {}", table, e);
+                // Retry on the next scheduled run, unless the table is gone
+                if self.db.table_exists(table).await.unwrap_or(false) {
+                    self.db.requeue_unindexed(table, rows);
+                }
+            }
+        }
+    }
+}
+
+/// A `[maintenance.tables.<name>]` section of the config file: how one table's
+/// maintenance differs from the `--maintenance-*` flags.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleOverride {
+    /// `false` leaves the table alone
+    pub enabled: Option<bool>,
+    pub interval: Option<String>,
+    pub retain_versions_days: Option<f64>,
+    pub target_rows_per_fragment: Option<usize>,
+}
+
+/// When and how one table is maintained.
+#[derive(Debug, Clone)]
+struct Schedule {
+    interval: chrono::Duration,
+    retain: chrono::Duration,
+    target_rows_per_fragment: Option<usize>,
+}
+
+/// Compacts, cleans up and reindexes tables on a schedule with
+/// `compact_and_cleanup`, like the `optimize_table` tool. Runs fall on the
+/// slots `at + k * interval`, so `--maintenance-interval 1d
+/// --maintenance-at 03:00` runs every night at 03:00 UTC; without an `at`
+/// the slots count from startup.
+pub struct MaintenanceScheduler {
+    db: Arc<VectorDB>,
+    default: Option<Schedule>,
+    tables: HashMap<String, Option<Schedule>>,
+    anchor: DateTime<Utc>,
+}
+
+impl MaintenanceScheduler {
+    /// `interval` of `None` maintains only the tables whose override sets one.
+    pub fn new(
+        db: Arc<VectorDB>,
+        interval: Option<&str>,
+        at: Option<&str>,
+        retain_days: f64,
+        target_rows_per_fragment: Option<usize>,
+        overrides: HashMap<String, ScheduleOverride>,
+    ) -> Result<Self> {
+        let now = Utc::now();
+        let anchor = match at {
+            Some(at) => {
+                let time = NaiveTime::parse_from_str(at, "%H:%M")
+                    .with_context(|| format!("Invalid --maintenance-at '{}', expected HH:MM", at))?;
+                now.date_naive().and_time(time).and_utc()
+            }
+            None => now,
+        };
+        let retain = retention(retain_days)?;
+        let default = interval
+            .map(|interval| -> Result<Schedule> {
+                Ok(Schedule {
+                    interval: parse_interval(interval)?,
+                    retain,
+                    target_rows_per_fragment,
+                })
+            })
+            .transpose()?;
+
+        let mut tables = HashMap::new();
+        for (name, table) in overrides {
+            let schedule = if table.enabled == Some(false) {
+                None
+            } else {
+                let interval = match (&table.interval, &default) {
+                    (Some(interval), _) => parse_interval(interval)?,
+                    (None, Some(default)) => default.interval,
+                    (None, None) => anyhow::bail!("[maintenance.tables.{}] needs an interval without --maintenance-interval", name),
+                };
+                Some(Schedule {
+                    interval,
+                    retain: table.retain_versions_days.map(retention).transpose()?.unwrap_or(retain),
+                    target_rows_per_fragment: table.target_rows_per_fragment.or(target_rows_per_fragment),
+                })
+            };
+            tables.insert(name, schedule);
+        }
+        Ok(Self { db, default, tables, anchor })
+    }
+
+    /// Whether any table is maintained at all.
+    pub fn is_active(&self) -> bool {
+        self.default.is_some() || self.tables.values().any(Option::is_some)
+    }
+
+    /// Run the scheduler in the background until the process exits.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut next_runs: HashMap<String, DateTime<Utc>> = HashMap::new();
+            let mut tick = tokio::time::interval(MAINTENANCE_POLL);
+            loop {
+                tick.tick().await;
+                let names = match self.db.table_names_with_prefix("").await {
+                    Ok(names) => names,
+                    Err(e) => {
+                        tracing::error!("Maintenance could not list tables: {}", e);
+                        continue;
+                    }
+                };
+                next_runs.retain(|name, _| names.contains(name));
+                for name in names {
+                    // Scratch tables are dropped long before they need it
+                    if name.starts_with(SCRATCH_TABLE_PREFIX) {
+                        continue;
+                    }
+                    let Some(schedule) = self.tables.get(&name).cloned().unwrap_or_else(|| self.default.clone()) else {
+                        continue;
+                    };
+                    let now = Utc::now();
+                    let next = *next_runs.entry(name.clone()).or_insert_with(|| self.next_slot(now, schedule.interval));
+                    if now < next {
+                        continue;
+                    }
+                    self.maintain(&name, &schedule).await;
+                    next_runs.insert(name, self.next_slot(Utc::now(), schedule.interval));
+                }
+            }
+        });
+    }
+
+    async fn maintain(&self, table: &str, schedule: &Schedule) {
+        match self.db.compact_and_cleanup(table, schedule.target_rows_per_fragment, schedule.retain).await {
+            Ok(report) => tracing::info!(
+                table,
+                fragments_removed = report.fragments_removed,
+                fragments_added = report.fragments_added,
+                versions_removed = report.versions_removed,
+                bytes_removed = report.bytes_removed,
+                "Scheduled maintenance done"
+            ),
+            Err(e) => tracing::error!("Scheduled maintenance of '{}' failed: {}", table, e),
+        }
+    }
+
+    /// The first slot after `now`.
+    fn next_slot(&self, now: DateTime<Utc>, interval: chrono::Duration) -> DateTime<Utc> {
+        let elapsed = (now - self.anchor).num_seconds();
+        let step = interval.num_seconds().max(1);
+        self.anchor + chrono::Duration::seconds((elapsed.div_euclid(step) + 1) * step)
+    }
+}
+
+/// A duration like `90s`, `30m`, `6h` or `1d`; a bare number is seconds.
+fn parse_interval(text: &str) -> Result<chrono::Duration> {
+    let text = text.trim();
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => anyhow::bail!("Invalid maintenance interval '{}', expected e.g. 30m, 6h or 1d", text),
+    };
+    number
+        .parse::<i64>()
+        .ok()
+        .filter(|n| *n > 0)
+        .and_then(|n| n.checked_mul(seconds))
+        .and_then(chrono::Duration::try_seconds)
+        .with_context(|| format!("Invalid maintenance interval '{}', expected e.g. 30m, 6h or 1d", text))
+}
+
+fn retention(days: f64) -> Result<chrono::Duration> {
+    Some(days)
+        .filter(|days| days.is_finite() && *days >= 0.0)
+        .and_then(|days| chrono::Duration::try_seconds((days * 86_400.0) as i64))
+        .with_context(|| format!("Invalid version retention of {} days", days))
+}
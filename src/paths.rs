@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::ErrorCode;
+
+/// A directory clients may name files in. Their paths are taken relative to
+/// it and, once symlinks and `..` are resolved, must stay inside it, so a
+/// client cannot read or write anywhere else on the server.
+#[derive(Debug, Clone)]
+pub struct PathRoot {
+    root: PathBuf,
+    /// The flag that configures the root, named in errors
+    flag: &'static str,
+}
+
+impl PathRoot {
+    pub fn new(dir: &str, flag: &'static str) -> Result<Self> {
+        let root = Path::new(dir)
+            .canonicalize()
+            .with_context(|| format!("{} {} does not exist", flag, dir))?;
+        if !root.is_dir() {
+            anyhow::bail!("{} {} is not a directory", flag, dir);
+        }
+        Ok(Self { root, flag })
+    }
+
+    /// The existing file a client means by `path`.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let resolved = self
+            .root
+            .join(path)
+            .canonicalize()
+            .with_context(|| format!("{} does not exist under {}", path, self.flag))
+            .context(ErrorCode::NotFound)?;
+        self.confine(path, resolved)
+    }
+
+    /// A new entry a client wants created at `path`. Its parent directory
+    /// must exist, the entry itself need not.
+    pub fn resolve_new(&self, path: &str) -> Result<PathBuf> {
+        let joined = self.root.join(path);
+        let name = match joined.components().next_back() {
+            Some(Component::Normal(name)) => name.to_owned(),
+            _ => {
+                return Err(anyhow::anyhow!("{} does not name a new file or directory", path))
+                    .context(ErrorCode::InvalidArgument);
+            }
+        };
+        let parent = joined
+            .parent()
+            .unwrap_or(&self.root)
+            .canonicalize()
+            .with_context(|| format!("The parent directory of {} does not exist under {}", path, self.flag))
+            .context(ErrorCode::NotFound)?;
+        self.confine(path, parent.join(name))
+    }
+
+    fn confine(&self, path: &str, resolved: PathBuf) -> Result<PathBuf> {
+        if !resolved.starts_with(&self.root) {
+            return Err(anyhow::anyhow!("{} is outside the directory set by {}", path, self.flag))
+                .context(ErrorCode::PermissionDenied);
+        }
+        Ok(resolved)
+    }
+}
+
+/// The root of `flag`, or an error telling the operator to configure it.
+pub fn required<'a>(root: Option<&'a PathRoot>, flag: &str) -> Result<&'a PathRoot> {
+    root.with_context(|| format!("Clients may not name files on this server, start it with {} to allow it", flag))
+        .context(ErrorCode::PermissionDenied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> (tempfile::TempDir, PathRoot) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs/a.txt"), "a").unwrap();
+        let root = PathRoot::new(dir.path().join("docs").to_str().unwrap(), "--ingest-root").unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn resolves_relative_and_absolute_paths_inside() {
+        let (dir, root) = root();
+        let expected = dir.path().join("docs/a.txt").canonicalize().unwrap();
+        assert_eq!(root.resolve("a.txt").unwrap(), expected);
+        assert_eq!(root.resolve(expected.to_str().unwrap()).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_paths_escaping_the_root() {
+        let (dir, root) = root();
+        std::fs::write(dir.path().join("secret"), "s").unwrap();
+        assert!(root.resolve("../secret").is_err());
+        assert!(root.resolve(dir.path().join("secret").to_str().unwrap()).is_err());
+        assert!(root.resolve("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn resolves_new_entries_inside() {
+        let (dir, root) = root();
+        let docs = dir.path().join("docs").canonicalize().unwrap();
+        assert_eq!(root.resolve_new("backup-1").unwrap(), docs.join("backup-1"));
+        assert!(root.resolve_new("../backup-1").is_err());
+        assert!(root.resolve_new("missing/backup-1").is_err());
+        assert!(root.resolve_new("backup-1/..").is_err());
+        assert!(root.resolve_new("/tmp/backup-1").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_root() {
+        let (dir, root) = root();
+        std::fs::write(dir.path().join("secret"), "s").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret"), dir.path().join("docs/link")).unwrap();
+        assert!(root.resolve("link").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::db::{SearchResult, SearchTarget, VectorDB};
+use crate::embeddings::EmbeddingModel;
+use crate::table_config::FusionWeights;
+
+/// Pipeline used by searches that name neither a pipeline nor a mode, when
+/// the config defines one under this name.
+pub const DEFAULT_PIPELINE: &str = "default";
+
+/// Candidates fetched per requested result when later stages re-rank or drop
+/// results and the retrieval stage does not say how many to fetch.
+const CANDIDATE_FACTOR: usize = 4;
+
+/// One step of a retrieval pipeline. A pipeline starts with exactly one
+/// retrieval stage (`vector` or `hybrid`) followed by any number of
+/// post-processing stages.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Stage {
+    /// Embed the query and run a vector search
+    Vector {
+        candidates: Option<usize>,
+    },
+    /// Fuse vector and full-text search
+    Hybrid {
+        fusion_preset: Option<String>,
+        vector_weight: Option<f32>,
+        keyword_weight: Option<f32>,
+        candidates: Option<usize>,
+    },
+    /// Drop results scoring below `min`
+    MinScore {
+        min: f32,
+    },
+    /// Maximal marginal relevance: re-order for diversity, penalizing results
+    /// whose text overlaps with ones already chosen. `lambda` = 1 is pure
+    /// relevance.
+    Mmr {
+        #[serde(default = "default_mmr_lambda")]
+        lambda: f32,
+    },
+}
+
+fn default_mmr_lambda() -> f32 {
+    0.5
+}
+
+impl Stage {
+    fn is_retrieval(&self) -> bool {
+        matches!(self, Stage::Vector { .. } | Stage::Hybrid { .. })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    fn validate(&self) -> Result<()> {
+        match self.stages.first() {
+            Some(stage) if stage.is_retrieval() => {}
+            Some(_) => anyhow::bail!("the first stage must be 'vector' or 'hybrid'"),
+            None => anyhow::bail!("a pipeline needs at least one stage"),
+        }
+        if self.stages[1..].iter().any(Stage::is_retrieval) {
+            anyhow::bail!("only the first stage may be a retrieval stage");
+        }
+        for stage in &self.stages {
+            match stage {
+                Stage::Hybrid { fusion_preset: Some(name), .. } if FusionWeights::preset(name).is_none() => {
+                    anyhow::bail!("unknown fusion preset '{}', expected one of {:?}", name, FusionWeights::PRESETS)
+                }
+                Stage::Hybrid { vector_weight, keyword_weight, .. }
+                    if vector_weight.is_some() != keyword_weight.is_some() =>
+                {
+                    anyhow::bail!("vector_weight and keyword_weight must be given together")
+                }
+                Stage::Mmr { lambda } if !(0.0..=1.0).contains(lambda) => {
+                    anyhow::bail!("mmr lambda must be between 0 and 1")
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Named pipelines loaded from the `--pipelines` file.
+#[derive(Debug, Default)]
+pub struct Pipelines {
+    pipelines: HashMap<String, Pipeline>,
+}
+
+impl Pipelines {
+    /// Load pipelines from a JSON object mapping names to stage lists.
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pipeline config {}", path.display()))?;
+        let pipelines: HashMap<String, Pipeline> = serde_json::from_str(&raw)
+            .with_context(|| format!("Invalid pipeline config {}", path.display()))?;
+        for (name, pipeline) in &pipelines {
+            pipeline
+                .validate()
+                .with_context(|| format!("Invalid pipeline '{}' in {}", name, path.display()))?;
+        }
+        Ok(Self { pipelines })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Pipeline> {
+        self.pipelines.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.pipelines.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+}
+
+/// Inputs of a pipeline run that come from the request and the table config.
+pub struct SearchParams<'a> {
+    pub table_name: &'a str,
+    /// Table version and vector column to search
+    pub target: SearchTarget<'a>,
+    pub query: &'a str,
+    pub limit: usize,
+    pub importance_weight: f32,
+    /// Fusion weights given on the request; they win over a stage's own
+    pub request_fusion: Option<FusionWeights>,
+    /// Fusion weights stored for the table, used when a hybrid stage sets none
+    pub table_fusion: Option<FusionWeights>,
+    /// Weight of the sparse score, from the request or the table
+    pub sparse_weight: Option<f32>,
+}
+
+/// Run `pipeline` and return at most `params.limit` results.
+pub async fn execute(
+    pipeline: &Pipeline,
+    db: &VectorDB,
+    model: &EmbeddingModel,
+    params: &SearchParams<'_>,
+) -> Result<Vec<SearchResult>> {
+    let (retrieval, rest) = pipeline.stages.split_first().context("empty pipeline")?;
+    let default_candidates = if rest.is_empty() {
+        params.limit
+    } else {
+        params.limit * CANDIDATE_FACTOR
+    };
+
+    let mut results = match retrieval {
+        Stage::Vector { candidates } => {
+            let candidates = candidates.unwrap_or(default_candidates).max(params.limit);
+            db.search(params.table_name, params.target, params.query, candidates, model, params.importance_weight)
+                .await?
+        }
+        Stage::Hybrid {
+            fusion_preset,
+            vector_weight,
+            keyword_weight,
+            candidates,
+        } => {
+            let stage_fusion = match (vector_weight, keyword_weight) {
+                (Some(vector), Some(keyword)) => Some(FusionWeights {
+                    vector: *vector,
+                    keyword: *keyword,
+                    sparse: None,
+                }),
+                _ => fusion_preset.as_deref().and_then(FusionWeights::preset),
+            };
+            let weights = params
+                .request_fusion
+                .or(stage_fusion)
+                .or(params.table_fusion)
+                .or_else(|| FusionWeights::preset("balanced"))
+                .unwrap();
+            let weights = FusionWeights { sparse: params.sparse_weight.or(weights.sparse), ..weights };
+            let candidates = candidates.unwrap_or(default_candidates).max(params.limit);
+            db.hybrid_search(
+                params.table_name,
+                params.target,
+                params.query,
+                candidates,
+                model,
+                weights,
+                params.importance_weight,
+            )
+            .await?
+        }
+        _ => anyhow::bail!("the first stage must be 'vector' or 'hybrid'"),
+    };
+
+    let started = std::time::Instant::now();
+    for stage in rest {
+        results = match stage {
+            Stage::MinScore { min } => results.into_iter().filter(|r| r.score >= *min).collect(),
+            Stage::Mmr { lambda } => mmr(results, *lambda, params.limit),
+            Stage::Vector { .. } | Stage::Hybrid { .. } => {
+                anyhow::bail!("only the first stage may be a retrieval stage")
+            }
+        };
+    }
+
+    results.truncate(params.limit);
+    params.target.record(|d| d.rerank_ms += started.elapsed().as_secs_f64() * 1000.0);
+    Ok(results)
+}
+
+/// Greedy maximal marginal relevance over word-set (Jaccard) similarity of
+/// the result texts.
+fn mmr(results: Vec<SearchResult>, lambda: f32, limit: usize) -> Vec<SearchResult> {
+    let max_score = results.iter().map(|r| r.score).fold(f32::MIN, f32::max);
+    let min_score = results.iter().map(|r| r.score).fold(f32::MAX, f32::min);
+    let range = (max_score - min_score).max(f32::EPSILON);
+    let words: Vec<HashSet<String>> = results.iter().map(|r| word_set(&r.content)).collect();
+
+    let mut remaining: Vec<usize> = (0..results.len()).collect();
+    let mut selected: Vec<usize> = Vec::new();
+    while selected.len() < limit && !remaining.is_empty() {
+        let (pos, _) = remaining
+            .iter()
+            .enumerate()
+            .map(|(pos, &i)| {
+                let relevance = (results[i].score - min_score) / range;
+                let redundancy = selected
+                    .iter()
+                    .map(|&j| jaccard(&words[i], &words[j]))
+                    .fold(0.0, f32::max);
+                (pos, lambda * relevance - (1.0 - lambda) * redundancy)
+            })
+            // On equal values keep the higher ranked candidate
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+            .expect("remaining is not empty");
+        selected.push(remaining.remove(pos));
+    }
+
+    let mut results: Vec<Option<SearchResult>> = results.into_iter().map(Some).collect();
+    selected.into_iter().filter_map(|i| results[i].take()).collect()
+}
+
+fn word_set(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
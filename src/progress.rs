@@ -0,0 +1,59 @@
+use rmcp::model::{ProgressNotificationParam, ProgressToken};
+use rmcp::service::RequestContext;
+use rmcp::RoleServer;
+use tokio::sync::mpsc;
+
+use crate::db::IngestProgress;
+
+/// Sends MCP progress notifications for a request that asked for them by
+/// including a progress token.
+///
+/// Notifications are queued and forwarded in order by a background task, so
+/// reporting never blocks the work being reported on.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    token: ProgressToken,
+    tx: mpsc::UnboundedSender<ProgressNotificationParam>,
+}
+
+impl ProgressReporter {
+    /// `None` when the client did not request progress for this call.
+    pub fn from_context(ctx: &RequestContext<RoleServer>) -> Option<Self> {
+        let token = ctx.meta.get_progress_token()?;
+        let peer = ctx.peer.clone();
+        let (tx, mut rx) = mpsc::unbounded_channel::<ProgressNotificationParam>();
+
+        tokio::spawn(async move {
+            while let Some(param) = rx.recv().await {
+                if let Err(e) = peer.notify_progress(param).await {
+                    tracing::debug!("Failed to send progress notification: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Some(Self { token, tx })
+    }
+
+    pub fn report(&self, progress: f64, total: Option<f64>, message: impl Into<String>) {
+        let _ = self.tx.send(ProgressNotificationParam {
+            progress_token: self.token.clone(),
+            progress,
+            total,
+            message: Some(message.into()),
+        });
+    }
+
+    /// Report an `add_texts` step. Embedding and writing each count for half
+    /// of a chunk so progress increases on every step.
+    pub fn ingest(&self, progress: IngestProgress) {
+        self.report(
+            (progress.embedded + progress.written) as f64 / 2.0,
+            Some(progress.total as f64),
+            format!(
+                "embedded {}/{} chunks, written {}/{}",
+                progress.embedded, progress.total, progress.written, progress.total
+            ),
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use rmcp::{
+    ErrorData,
+    handler::server::wrapper::Parameters,
+    model::{CompleteRequestParam, CompleteResult, CompletionInfo, GetPromptResult, PromptMessage, PromptMessageRole, Reference},
+    prompt, prompt_router,
+};
+
+use crate::acl::Access;
+use crate::db::SearchResult;
+use crate::error::{ApiError, ErrorCode};
+use crate::{LanceDBServer, SearchRequest, DEFAULT_TABLE_NAME};
+
+/// Chunks retrieved when a prompt does not say how many.
+const DEFAULT_CONTEXT_CHUNKS: usize = 5;
+
+// Prompt arguments are always passed as strings, so `k` is parsed by hand.
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct AnswerWithContextArgs {
+    #[schemars(description = "The table to retrieve context from (default: knowledge_base, or the best matching tables when the server routes queries)")]
+    table_name: Option<String>,
+    #[schemars(description = "The question to answer")]
+    question: String,
+    #[schemars(description = "Number of chunks to retrieve (default: 5)")]
+    k: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SummarizeTopicArgs {
+    #[schemars(description = "The table to retrieve context from (default: knowledge_base, or the best matching tables when the server routes queries)")]
+    table_name: Option<String>,
+    #[schemars(description = "The topic to summarize")]
+    topic: String,
+    #[schemars(description = "Number of chunks to retrieve (default: 5)")]
+    k: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SummarizeDocumentArgs {
+    #[schemars(description = "The table the document is stored in (default: knowledge_base)")]
+    table_name: Option<String>,
+    #[schemars(description = "Name of the document to summarize")]
+    document_name: String,
+}
+
+#[prompt_router(vis = "pub(crate)")]
+impl LanceDBServer {
+    #[prompt(
+        name = "answer_with_context",
+        description = "Search a table for the question and return a prompt that answers it from the retrieved chunks, citing them as [1], [2], ..."
+    )]
+    async fn answer_with_context(&self, Parameters(args): Parameters<AnswerWithContextArgs>) -> Result<GetPromptResult, ErrorData> {
+        let results = self.retrieve(args.table_name, &args.question, args.k.as_deref()).await?;
+
+        let text = format!(
+            "Answer the question using only the numbered context below. Cite the chunks you rely on with their markers, e.g. [1] or [2][3]. \
+             If the context does not contain the answer, say so instead of guessing.\n\n\
+             Context:\n{}\n\nQuestion: {}",
+            format_context(&results),
+            args.question
+        );
+        Ok(GetPromptResult {
+            description: Some(format!("Answer with {} retrieved chunk(s)", results.len())),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+
+    #[prompt(
+        name = "summarize_topic",
+        description = "Search a table for a topic and return a prompt that summarizes what the retrieved chunks say about it, citing them as [1], [2], ..."
+    )]
+    async fn summarize_topic(&self, Parameters(args): Parameters<SummarizeTopicArgs>) -> Result<GetPromptResult, ErrorData> {
+        let results = self.retrieve(args.table_name, &args.topic, args.k.as_deref()).await?;
+
+        let text = format!(
+            "Summarize what the numbered context below says about \"{}\". Cite every statement with the markers of the chunks it comes from, \
+             e.g. [1] or [2][3], and point out where the chunks disagree.\n\n\
+             Context:\n{}",
+            args.topic,
+            format_context(&results)
+        );
+        Ok(GetPromptResult {
+            description: Some(format!("Summary of {} retrieved chunk(s)", results.len())),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+
+    #[prompt(
+        name = "summarize_document",
+        description = "Return a prompt that summarizes one stored document from all of its chunks"
+    )]
+    async fn summarize_document(&self, Parameters(args): Parameters<SummarizeDocumentArgs>) -> Result<GetPromptResult, ErrorData> {
+        let table_name = args.table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME);
+        self.check_access(table_name, Access::Read).map_err(to_error_data)?;
+        let texts = self
+            .db
+            .document_texts(&self.table_name(Some(table_name)), &args.document_name)
+            .await
+            .map_err(|e| to_error_data(ApiError::from(e)))?;
+        if texts.is_empty() {
+            return Err(ErrorData::invalid_params(
+                format!("Document '{}' not found in table '{}'", args.document_name, table_name),
+                None,
+            ));
+        }
+
+        let text = format!(
+            "Summarize the document \"{}\" below. Keep its structure and key facts; do not add information that is not in it.\n\n{}",
+            args.document_name,
+            texts.join("\n\n")
+        );
+        Ok(GetPromptResult {
+            description: Some(format!("Summary of '{}' ({} chunk(s))", args.document_name, texts.len())),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+}
+
+impl LanceDBServer {
+    /// Complete prompt arguments that name a table (`table_name`) or a
+    /// document (`document_name`, looked up in the `table_name` already
+    /// given, or the default table).
+    pub(crate) async fn complete_argument(&self, request: CompleteRequestParam) -> Result<CompleteResult, ErrorData> {
+        if !matches!(request.r#ref, Reference::Prompt(_)) {
+            return Ok(CompleteResult::default());
+        }
+        let candidates = match request.argument.name.as_str() {
+            "table_name" => self.db.cached_table_names().await.map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| self.shown_table_name(name).map(str::to_string))
+                    .collect()
+            }),
+            "document_name" => {
+                let table_name = request
+                    .context
+                    .as_ref()
+                    .and_then(|c| c.arguments.as_ref())
+                    .and_then(|args| args.get("table_name"))
+                    .filter(|t| !t.is_empty())
+                    .map(String::as_str);
+                if !self.may(table_name.unwrap_or(DEFAULT_TABLE_NAME), Access::Read) {
+                    return Ok(CompleteResult::default());
+                }
+                let table_name = self.table_name(table_name);
+                match self.db.table_exists(&table_name).await {
+                    Ok(true) => self.db.cached_document_names(&table_name).await,
+                    Ok(false) => Ok(Vec::new()),
+                    Err(e) => Err(e),
+                }
+            }
+            _ => return Ok(CompleteResult::default()),
+        }
+        .map_err(|e| to_error_data(ApiError::from(e)))?;
+
+        let prefix = request.argument.value.to_lowercase();
+        let mut values: Vec<String> = candidates
+            .into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .collect();
+        let total = values.len();
+        values.truncate(CompletionInfo::MAX_VALUES);
+        let completion = CompletionInfo::with_pagination(values, Some(total as u32), total > CompletionInfo::MAX_VALUES)
+            .map_err(|e| ErrorData::internal_error(e, None))?;
+        Ok(CompleteResult { completion })
+    }
+
+    /// Run the search behind a prompt with the table's default settings.
+    async fn retrieve(&self, table_name: Option<String>, query: &str, k: Option<&str>) -> Result<Vec<SearchResult>, ErrorData> {
+        let limit = match k {
+            Some(k) => k
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|k| *k > 0)
+                .ok_or_else(|| ErrorData::invalid_params(format!("k must be a positive integer, got '{}'", k), None))?,
+            None => DEFAULT_CONTEXT_CHUNKS,
+        };
+        // The same access check as calling the search tool
+        let arguments = serde_json::json!({ "table_name": table_name });
+        self.check_tool_access("search", arguments.as_object()).map_err(to_error_data)?;
+
+        let resp = self
+            .search_impl(SearchRequest {
+                table_name,
+                query: query.to_string(),
+                limit: Some(limit),
+                mode: None,
+                fusion_preset: None,
+                vector_weight: None,
+                keyword_weight: None,
+                importance_weight: None,
+                sparse_weight: None,
+                pipeline: None,
+                after: None,
+                before: None,
+                date_field: None,
+                date_locale: None,
+                version: None,
+                as_of: None,
+                vector_column: None,
+                modality: None,
+                rescore: None,
+                diagnostics: None,
+            }, None)
+            .await
+            .map_err(to_error_data)?;
+        Ok(resp.data.unwrap_or_default())
+    }
+}
+
+/// Number the chunks so the model can cite them, each with the document name
+/// and source it came from.
+fn format_context(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return "(no matching chunks found)".to_string();
+    }
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let mut header = format!("[{}] {}", i + 1, r.name);
+            if let Some(source) = &r.provenance.source {
+                header.push_str(&format!(" ({})", source));
+            }
+            if let Some(table) = &r.table {
+                header.push_str(&format!(" [table: {}]", table));
+            }
+            format!("{}\n{}", header, r.content.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Prompts have no structured error result, so the category goes into the
+/// message and decides between an invalid-params and an internal error.
+fn to_error_data(err: ApiError) -> ErrorData {
+    let message = format!("{}: {}", err.code, err.message);
+    match err.code {
+        ErrorCode::InvalidArgument | ErrorCode::TableNotFound | ErrorCode::NotFound | ErrorCode::PermissionDenied => {
+            ErrorData::invalid_params(message, None)
+        }
+        _ => ErrorData::internal_error(message, None),
+    }
+}
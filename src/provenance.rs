@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+/// Metadata key under which a chunk's provenance is stored.
+pub const PROVENANCE_KEY: &str = "provenance";
+
+/// Version of `chunking::chunk_text`; bump when its boundaries change.
+pub const CHUNKER_VERSION: &str = "chunk_text/v1";
+/// Chunks that were split by the client before calling `add_documents`.
+pub const CLIENT_CHUNKER: &str = "client";
+/// Images, embedded whole by `add_images`.
+pub const IMAGE_CHUNKER: &str = "image";
+
+/// Text stored as received.
+pub const NORMALIZATION_NONE: &str = "none";
+/// Readable text extracted from HTML by `fetch::extract_readable_text`.
+pub const NORMALIZATION_HTML: &str = "html_readable/v1";
+/// Per-page text extracted from PDFs.
+pub const NORMALIZATION_PDF: &str = "pdf_text/v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceType {
+    /// Text passed directly to `add_documents`
+    Inline,
+    /// A file on the server, from `add_file` or the directory watcher
+    File,
+    /// A web page fetched by `add_url`
+    Url,
+}
+
+/// Where a chunk came from and how it was processed, so consumers can audit
+/// retrieved context.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Provenance {
+    pub source_type: SourceType,
+    /// Original file path or URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// RFC 3339 time the chunk was written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingested_at: Option<String>,
+    /// Chunking strategy and version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunker: Option<String>,
+    /// Embedding model that produced the vector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// Text extraction / normalization applied before chunking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<String>,
+}
+
+impl Provenance {
+    pub fn new(source_type: SourceType, source: Option<String>, chunker: &str, normalization: &str) -> Self {
+        Self {
+            source_type,
+            source,
+            ingested_at: None,
+            chunker: Some(chunker.to_string()),
+            embedding_model: None,
+            normalization: Some(normalization.to_string()),
+        }
+    }
+
+    /// Store the provenance in a chunk's metadata.
+    pub fn attach(&self, metadata: &mut serde_json::Value) {
+        if let serde_json::Value::Object(map) = metadata {
+            map.insert(PROVENANCE_KEY.to_string(), serde_json::to_value(self).unwrap_or_default());
+        }
+    }
+
+    /// Take the provenance out of a chunk's metadata. Rows ingested before
+    /// provenance was recorded get what can be inferred from their metadata.
+    pub fn take(metadata: &mut serde_json::Value) -> Self {
+        let serde_json::Value::Object(map) = metadata else {
+            return Self::unknown(SourceType::Inline, None);
+        };
+        if let Some(provenance) = map
+            .remove(PROVENANCE_KEY)
+            .and_then(|v| serde_json::from_value(v).ok())
+        {
+            return provenance;
+        }
+
+        let string = |key: &str| map.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        if let Some(url) = string("url") {
+            let mut provenance = Self::unknown(SourceType::Url, Some(url));
+            provenance.ingested_at = string("fetched_at");
+            provenance
+        } else if let Some(path) = string("source_path") {
+            Self::unknown(SourceType::File, Some(path))
+        } else {
+            Self::unknown(SourceType::Inline, None)
+        }
+    }
+
+    fn unknown(source_type: SourceType, source: Option<String>) -> Self {
+        Self {
+            source_type,
+            source,
+            ingested_at: None,
+            chunker: None,
+            embedding_model: None,
+            normalization: None,
+        }
+    }
+}
+
+/// Record the ingestion time and embedding model in a chunk's provenance,
+/// creating the provenance entry if the caller did not supply one. A chunk
+/// that is re-embedded or re-imported keeps the time it was first ingested.
+pub fn stamp(metadata: &mut serde_json::Value, ingested_at: &str, embedding_model: &str) {
+    let serde_json::Value::Object(map) = metadata else {
+        return;
+    };
+    let entry = map
+        .entry(PROVENANCE_KEY.to_string())
+        .or_insert_with(|| serde_json::json!({ "source_type": SourceType::Inline }));
+    if let serde_json::Value::Object(provenance) = entry {
+        provenance
+            .entry("ingested_at".to_string())
+            .or_insert_with(|| serde_json::json!(ingested_at));
+        provenance.insert("embedding_model".to_string(), serde_json::json!(embedding_model));
+    }
+}
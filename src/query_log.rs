@@ -0,0 +1,227 @@
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray, Float32Builder, Float64Array, ListBuilder, RecordBatch, StringArray, StringBuilder};
+use arrow::datatypes::{DataType, Field, Float32Type, Schema};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::{SearchResult, VectorDB};
+use crate::rows;
+
+/// Internal table searches are recorded in with --query-log.
+pub const QUERY_LOG_TABLE: &str = "_mcp_query_log";
+/// Results kept per logged search, best first.
+const LOGGED_RESULTS: usize = 10;
+
+/// How often one query was searched for, grouped case-insensitively.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct QueryFrequency {
+    pub query: String,
+    pub count: usize,
+    /// Searches for it that returned nothing
+    pub zero_results: usize,
+    /// Mean score of the best result, over the searches that had one
+    pub mean_top_score: Option<f32>,
+    pub mean_latency_ms: f64,
+}
+
+/// Spread of a set of scores.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ScoreDistribution {
+    pub count: usize,
+    pub min: f32,
+    pub p10: f32,
+    pub p25: f32,
+    pub p50: f32,
+    pub p75: f32,
+    pub p90: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct QueryStats {
+    pub searches: usize,
+    pub zero_result_searches: usize,
+    /// Most frequent queries, most frequent first
+    pub top_queries: Vec<QueryFrequency>,
+    /// Most frequent queries that returned nothing at least once
+    pub zero_result_queries: Vec<QueryFrequency>,
+    /// Score of the best result of each search that had one
+    pub top_scores: Option<ScoreDistribution>,
+    /// Scores of every logged result
+    pub all_scores: Option<ScoreDistribution>,
+}
+
+/// Records every search in `QUERY_LOG_TABLE`: the query text, the table,
+/// the ids and scores of the top results and how long it took.
+pub struct QueryLog {
+    db: Arc<VectorDB>,
+}
+
+impl QueryLog {
+    pub fn new(db: Arc<VectorDB>) -> Self {
+        Self { db }
+    }
+
+    /// Store the search in the background, so it is not slowed down.
+    pub fn record(&self, table_name: &str, query: &str, results: &[SearchResult], latency: Duration) {
+        let db = self.db.clone();
+        let batch = entry(table_name, query, results, latency);
+        tokio::spawn(async move {
+            let stored = match batch {
+                Ok(batch) => db.append_rows(QUERY_LOG_TABLE, batch).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = stored {
+                tracing::error!("Failed to log search: {:#}", e);
+            }
+        });
+    }
+
+    /// Summarize the logged searches of `table_name`, or of every table, made
+    /// since `since`, leaving out the tables that are not `visible`.
+    pub async fn stats(
+        &self,
+        table_name: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        top: usize,
+        visible: impl Fn(&str) -> bool,
+    ) -> Result<QueryStats> {
+        let mut conditions = Vec::new();
+        if let Some(table_name) = table_name {
+            conditions.push(format!("table_name = '{}'", table_name.replace('\'', "''")));
+        }
+        if let Some(since) = since {
+            // RFC 3339 times in UTC sort as strings
+            conditions.push(format!("timestamp >= '{}'", since.to_rfc3339()));
+        }
+        let filter = (!conditions.is_empty()).then(|| conditions.join(" AND "));
+
+        let mut queries: HashMap<String, Tally> = HashMap::new();
+        let mut top_scores = Vec::new();
+        let mut all_scores = Vec::new();
+        let (mut searches, mut zero_result_searches) = (0, 0);
+        if let Some(mut stream) = self.db.scan_rows(QUERY_LOG_TABLE, filter).await? {
+            while let Some(batch) = stream.try_next().await? {
+                let tables = rows::required_strings(&batch, "table_name")?;
+                let texts = rows::required_strings(&batch, "query")?;
+                let latencies = rows::floats(&batch, "latency_ms")?;
+                let scores = rows::lists(&batch, "scores")?;
+                for i in 0..batch.num_rows() {
+                    if !visible(tables.value(i)) {
+                        continue;
+                    }
+                    let row_scores = scores.value(i);
+                    let row_scores = row_scores.as_primitive::<Float32Type>().values();
+                    let best = row_scores.first().copied();
+                    searches += 1;
+                    zero_result_searches += usize::from(best.is_none());
+                    top_scores.extend(best);
+                    all_scores.extend_from_slice(row_scores);
+
+                    let tally = queries.entry(texts.value(i).trim().to_lowercase()).or_default();
+                    tally.count += 1;
+                    tally.zero_results += usize::from(best.is_none());
+                    if let Some(best) = best {
+                        tally.top_score_sum += best;
+                        tally.with_results += 1;
+                    }
+                    tally.latency_sum += latencies.as_ref().filter(|l| l.is_valid(i)).map_or(0.0, |l| l.value(i) as f64);
+                }
+            }
+        }
+
+        let mut frequencies: Vec<QueryFrequency> = queries.into_iter().map(|(query, tally)| tally.frequency(query)).collect();
+        // Ties go to the alphabetically first query, so reports are stable
+        frequencies.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.query.cmp(&b.query)));
+        let zero_result_queries = frequencies.iter().filter(|f| f.zero_results > 0).take(top).cloned().collect();
+        frequencies.truncate(top);
+        Ok(QueryStats {
+            searches,
+            zero_result_searches,
+            top_queries: frequencies,
+            zero_result_queries,
+            top_scores: distribution(top_scores),
+            all_scores: distribution(all_scores),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    count: usize,
+    zero_results: usize,
+    with_results: usize,
+    top_score_sum: f32,
+    latency_sum: f64,
+}
+
+impl Tally {
+    fn frequency(self, query: String) -> QueryFrequency {
+        QueryFrequency {
+            query,
+            count: self.count,
+            zero_results: self.zero_results,
+            mean_top_score: (self.with_results > 0).then(|| self.top_score_sum / self.with_results as f32),
+            mean_latency_ms: self.latency_sum / self.count as f64,
+        }
+    }
+}
+
+fn distribution(mut scores: Vec<f32>) -> Option<ScoreDistribution> {
+    if scores.is_empty() {
+        return None;
+    }
+    scores.sort_by(f32::total_cmp);
+    let percentile = |p: f64| {
+        // Nearest-rank percentile
+        let rank = ((p * scores.len() as f64).ceil() as usize).clamp(1, scores.len());
+        scores[rank - 1]
+    };
+    Some(ScoreDistribution {
+        count: scores.len(),
+        min: scores[0],
+        p10: percentile(0.1),
+        p25: percentile(0.25),
+        p50: percentile(0.5),
+        p75: percentile(0.75),
+        p90: percentile(0.9),
+        max: scores[scores.len() - 1],
+        mean: scores.iter().sum::<f32>() / scores.len() as f32,
+    })
+}
+
+fn entry(table_name: &str, query: &str, results: &[SearchResult], latency: Duration) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("timestamp", DataType::Utf8, false),
+        Field::new("table_name", DataType::Utf8, false),
+        Field::new("query", DataType::Utf8, false),
+        Field::new("result_ids", DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))), false),
+        Field::new("scores", DataType::List(Arc::new(Field::new("item", DataType::Float32, true))), false),
+        Field::new("latency_ms", DataType::Float64, false),
+    ]));
+    let top = &results[..results.len().min(LOGGED_RESULTS)];
+    let mut ids = ListBuilder::new(StringBuilder::new());
+    let mut scores = ListBuilder::new(Float32Builder::new());
+    for result in top {
+        ids.values().append_value(&result.id);
+        scores.values().append_value(result.score);
+    }
+    ids.append(true);
+    scores.append(true);
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![Utc::now().to_rfc3339()])) as ArrayRef,
+            Arc::new(StringArray::from(vec![table_name.to_string()])),
+            Arc::new(StringArray::from(vec![query.to_string()])),
+            Arc::new(ids.finish()),
+            Arc::new(scores.finish()),
+            Arc::new(Float64Array::from(vec![latency.as_secs_f64() * 1000.0])),
+        ],
+    )?)
+}
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use rmcp::handler::server::wrapper::Json;
+use serde::Serialize;
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::context::CallContext;
+use crate::db::SearchResult;
+use crate::{AddDocumentsRequest, AddFileRequest, AddUrlRequest, ApiResponse, DocumentInput, LanceDBServer, SearchRequest};
+
+const HELP: &str = "\
+Commands:
+  list_tables
+  health_check
+  search <table> <query> [limit]
+  add <table> <name> <text>
+  add_url <table> <url>
+  add_file <table> <path>
+  help
+  quit
+
+Arguments containing spaces can be wrapped in \"double\" or 'single' quotes.";
+
+/// Run an interactive prompt on stdin/stdout that invokes the server's tools
+/// directly, without an MCP client in between.
+pub async fn run(server: LanceDBServer) -> Result<()> {
+    println!("LanceDB MCP server REPL. Type `help` for commands.");
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        let args = match split_args(&line) {
+            Ok(args) => args,
+            Err(e) => {
+                println!("error: {}", e);
+                continue;
+            }
+        };
+        let Some((command, rest)) = args.split_first() else {
+            continue;
+        };
+
+        let result = match (command.as_str(), rest) {
+            ("quit" | "exit", _) => break,
+            ("help", _) => {
+                println!("{}", HELP);
+                continue;
+            }
+            ("list_tables", []) => server.list_tables().await.map(|Json(resp)| to_json(&resp)),
+            ("health_check", []) => server.health_check().await.map(|Json(resp)| to_json(&resp)),
+            ("search", [table, query, rest @ ..]) if rest.len() <= 1 => {
+                let limit = match rest.first().map(|l| l.parse::<usize>()).transpose() {
+                    Ok(limit) => limit,
+                    Err(e) => {
+                        println!("error: invalid limit: {}", e);
+                        continue;
+                    }
+                };
+                server
+                    .search_impl(SearchRequest {
+                        table_name: Some(table.clone()),
+                        query: query.clone(),
+                        limit,
+                        mode: None,
+                        fusion_preset: None,
+                        vector_weight: None,
+                        keyword_weight: None,
+                        importance_weight: None,
+                        sparse_weight: None,
+                        pipeline: None,
+                        after: None,
+                        before: None,
+                        date_field: None,
+                        date_locale: None,
+                        version: None,
+                        as_of: None,
+                        vector_column: None,
+                        modality: None,
+                        rescore: None,
+                        diagnostics: None,
+                    }, None)
+                    .await
+                    .map(|resp| format_search_results(&resp))
+            }
+            ("add", [table, name, text]) => {
+                server
+                    .add_documents_impl(
+                        AddDocumentsRequest {
+                            table_name: Some(table.clone()),
+                            documents: vec![DocumentInput {
+                                name: name.clone(),
+                                description: None,
+                                chunks: vec![text.clone()],
+                                metadata: None,
+                                importance: None,
+                                expires_at: None,
+                            }],
+                            vector_column: None,
+                            run_async: None,
+                        },
+                        &CallContext::default(),
+                    )
+                    .await
+                    .map(|resp| to_json(&resp))
+            }
+            ("add_url", [table, url]) => {
+                server
+                    .add_url_impl(
+                        AddUrlRequest {
+                            table_name: Some(table.clone()),
+                            url: url.clone(),
+                            name: None,
+                            description: None,
+                            chunk_size: None,
+                            chunk_overlap: None,
+                            metadata: None,
+                            importance: None,
+                        },
+                        &CallContext::default(),
+                    )
+                    .await
+                    .map(|resp| to_json(&resp))
+            }
+            ("add_file", [table, path]) => {
+                server
+                    .add_file_impl(
+                        AddFileRequest {
+                            table_name: Some(table.clone()),
+                            path: path.clone(),
+                            name: None,
+                            description: None,
+                            chunk_size: None,
+                            chunk_overlap: None,
+                            metadata: None,
+                            importance: None,
+                        },
+                        &CallContext::default(),
+                    )
+                    .await
+                    .map(|resp| to_json(&resp))
+            }
+            _ => {
+                println!("error: unknown command or wrong arguments, type `help` for usage");
+                continue;
+            }
+        };
+
+        match result {
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|e| format!("<unserializable result: {}>", e))
+}
+
+/// Render the results of the search tool as a readable listing.
+fn format_search_results(resp: &ApiResponse<Vec<SearchResult>>) -> String {
+    let results = resp.data.as_deref().unwrap_or_default();
+    if results.is_empty() {
+        return "(no results)".to_string();
+    }
+
+    let mut out = String::new();
+    for (i, result) in results.iter().enumerate() {
+        out.push_str(&format!("{}. [{:.4}] {}\n", i + 1, result.score, result.name));
+        for line in result.content.lines() {
+            out.push_str("   ");
+            out.push_str(line);
+            out.push('\n');
+        }
+        if let Some(meta) = result.metadata.as_object().filter(|m| !m.is_empty()) {
+            out.push_str(&format!("   metadata: {}\n", serde_json::Value::Object(meta.clone())));
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Split a command line into arguments, honouring single and double quotes.
+fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        anyhow::bail!("unterminated quote");
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::context::CallContext;
+use crate::{AddDocumentsRequest, LanceDBServer, ReembedTableRequest, TableNameRequest};
+use crate::audit::AuditEntry;
+use crate::error::ApiError;
+
+/// Re-execute the mutating operations recorded in an audit log, in order.
+///
+/// Call records and entries for read-only tools are skipped. Replay stops at the first failing
+/// operation so the target database is never left with a gap in the history, and refuses
+/// logs with a rollback it cannot reproduce before applying anything.
+pub async fn run(server: &LanceDBServer, log_path: &Path, dry_run: bool) -> Result<()> {
+    check_replayable(log_path).await?;
+    let mut lines = open(log_path).await?;
+
+    let mut line_no = 0;
+    let mut applied = 0;
+    let mut skipped = 0;
+
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: AuditEntry = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid audit entry on line {}", line_no))?;
+
+        if entry.call.is_some() || !is_mutating(&entry.tool) {
+            skipped += 1;
+            continue;
+        }
+
+        tracing::info!("Replaying line {}: {} (recorded {})", line_no, entry.tool, entry.timestamp);
+        if dry_run {
+            applied += 1;
+            continue;
+        }
+
+        apply(server, &entry)
+            .await
+            .map_err(|e| anyhow::anyhow!("Replay failed on line {} ({}): {}", line_no, entry.tool, e))?;
+        applied += 1;
+    }
+
+    tracing::info!(
+        "Replay finished: {} operations {}, {} call records and read-only entries skipped",
+        applied,
+        if dry_run { "would be applied" } else { "applied" },
+        skipped
+    );
+    Ok(())
+}
+
+async fn open(log_path: &Path) -> Result<tokio::io::Lines<BufReader<tokio::fs::File>>> {
+    let file = tokio::fs::File::open(log_path)
+        .await
+        .with_context(|| format!("Failed to open audit log {}", log_path.display()))?;
+    Ok(BufReader::new(file).lines())
+}
+
+/// Fail if the log rolls tables back to a version of the original database
+/// or to the contents of a backup. Neither exists in the database replayed
+/// into, and skipping them would leave it with rows the original dropped.
+async fn check_replayable(log_path: &Path) -> Result<()> {
+    let mut lines = open(log_path).await?;
+    let mut line_no = 0;
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: AuditEntry = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid audit entry on line {}", line_no))?;
+        if entry.call.is_none() && matches!(entry.tool.as_str(), "restore_table_version" | "restore_database") {
+            anyhow::bail!(
+                "Line {} ({}) rolls tables back to a state replay cannot reproduce; restore a backup taken after it \
+                 ({}) and replay only the entries that follow",
+                line_no,
+                entry.tool,
+                entry.timestamp
+            );
+        }
+    }
+    Ok(())
+}
+
+fn is_mutating(tool: &str) -> bool {
+    matches!(
+        tool,
+        "add_documents"
+            | "add_images"
+            | "add_url"
+            | "add_file"
+            | "import_table"
+            | "reembed_table"
+            | "set_table_config"
+            | "add_column"
+            | "delete_document"
+            | "undelete"
+            | "purge"
+            | "purge_expired"
+            | "swap_tables"
+            | "restore_table_version"
+            | "restore_database"
+    )
+}
+
+async fn apply(server: &LanceDBServer, entry: &AuditEntry) -> Result<(), ApiError> {
+    let args = entry.arguments.clone();
+    match entry.tool.as_str() {
+        "add_documents" => {
+            let mut req: AddDocumentsRequest = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            // Replay must apply operations in order, never as background jobs
+            req.run_async = None;
+            server.add_documents_impl(req, &CallContext::default()).await?;
+        }
+        "add_images" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.add_images_impl(req, &CallContext::default()).await?;
+        }
+        "add_url" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.add_url_impl(req, &CallContext::default()).await?;
+        }
+        "add_file" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.add_file_impl(req, &CallContext::default()).await?;
+        }
+        "import_table" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.import_table_impl(req, &CallContext::default()).await?;
+        }
+        "reembed_table" => {
+            let mut req: ReembedTableRequest = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            req.run_async = None;
+            server.reembed_table_impl(req, &CallContext::default()).await?;
+        }
+        "set_table_config" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.set_table_config_impl(req, &CallContext::default()).await?;
+        }
+        "add_column" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.add_column_impl(req, &CallContext::default()).await?;
+        }
+        "delete_document" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.delete_document_impl(req).await?;
+        }
+        "undelete" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.undelete_impl(req).await?;
+        }
+        "purge" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.purge_impl(req).await?;
+        }
+        "purge_expired" => {
+            // The same cut-off as the original call, not the time of the replay
+            let now = args
+                .get("expired_before")
+                .and_then(|t| t.as_str())
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&chrono::Utc))
+                .ok_or_else(|| ApiError::invalid_argument("purge_expired entry has no valid expired_before"))?;
+            let req: TableNameRequest = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.purge_expired_impl(req, now).await?;
+        }
+        "swap_tables" => {
+            let req = serde_json::from_value(args).map_err(|e| ApiError::invalid_argument(e.to_string()))?;
+            server.swap_tables_impl(req).await?;
+        }
+        other => return Err(ApiError::invalid_argument(format!("Unknown tool '{}'", other))),
+    }
+    Ok(())
+}
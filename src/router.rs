@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::db::VectorDB;
+use crate::embeddings::EmbeddingModel;
+use crate::error::ErrorCode;
+
+/// Tables whose similarity is within this margin of the best match are
+/// searched as well, so near-ties do not hide relevant results.
+const ROUTE_MARGIN: f32 = 0.05;
+const MAX_ROUTED_TABLES: usize = 3;
+
+/// Picks the tables to search when a query does not name one, by comparing
+/// the query embedding with the embeddings of the table descriptions stored
+/// in the table config.
+pub struct TableRouter {
+    /// Description text -> embedding, so descriptions are embedded once
+    cache: Mutex<HashMap<String, Vec<f32>>>,
+}
+
+/// A table chosen by the router and how similar its description is to the query.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RouteDecision {
+    pub table: String,
+    pub similarity: f32,
+}
+
+impl TableRouter {
+    pub fn new() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Rank the described tables the caller can see for `query`. Returns an
+    /// empty list when no such table has a description.
+    pub async fn route(
+        &self,
+        db: &VectorDB,
+        model: &EmbeddingModel,
+        query: &str,
+        visible: impl Fn(&str) -> bool,
+    ) -> Result<Vec<RouteDecision>> {
+        let tables = db.list_tables().await?;
+        let configs = db.list_table_configs().await?;
+        let described: Vec<(String, String)> = tables
+            .into_iter()
+            .filter(|table| visible(table))
+            .filter_map(|table| {
+                let description = configs.get(&table)?.description.clone()?;
+                Some((table, description))
+            })
+            .collect();
+        if described.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Embed the query together with any descriptions not seen before
+        let missing: Vec<String> = {
+            let cache = self.cache.lock().unwrap();
+            described
+                .iter()
+                .map(|(_, d)| d.clone())
+                .filter(|d| !cache.contains_key(d))
+                .collect()
+        };
+        let query_vec = model.embed_query(query).await.context(ErrorCode::EmbeddingFailed)?;
+        let vectors = if missing.is_empty() {
+            Vec::new()
+        } else {
+            model.embed_documents(missing.clone()).await.context(ErrorCode::EmbeddingFailed)?.0
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        for (description, vector) in missing.into_iter().zip(vectors) {
+            cache.insert(description, vector);
+        }
+
+        let mut ranked: Vec<RouteDecision> = described
+            .into_iter()
+            .filter_map(|(table, description)| {
+                let vector = cache.get(&description)?;
+                Some(RouteDecision {
+                    table,
+                    similarity: cosine_similarity(&query_vec, vector),
+                })
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then_with(|| a.table.cmp(&b.table)));
+
+        let best = ranked.first().map(|d| d.similarity).unwrap_or_default();
+        ranked.retain(|d| d.similarity >= best - ROUTE_MARGIN);
+        ranked.truncate(MAX_ROUTED_TABLES);
+        Ok(ranked)
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
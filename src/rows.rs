@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use arrow::array::{Array, ArrayRef, AsArray, BooleanArray, FixedSizeListArray, Float32Array, ListArray, RecordBatch, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float32Type};
+
+/// Column `name` of `batch` as strings, `None` when there is no such
+/// column. Large and view strings, numbers and the like are converted, so
+/// tables written by other tools read too.
+pub fn strings(batch: &RecordBatch, name: &str) -> Result<Option<StringArray>> {
+    Ok(convert(batch, name, &DataType::Utf8)?.map(|column| column.as_string::<i32>().clone()))
+}
+
+/// Column `name` of `batch` as 32-bit floats, `None` when there is no such
+/// column. Other numeric types are converted.
+pub fn floats(batch: &RecordBatch, name: &str) -> Result<Option<Float32Array>> {
+    Ok(convert(batch, name, &DataType::Float32)?.map(|column| column.as_primitive::<Float32Type>().clone()))
+}
+
+/// Like `strings`, failing when the column is missing.
+pub fn required_strings(batch: &RecordBatch, name: &str) -> Result<StringArray> {
+    strings(batch, name)?.with_context(|| missing(batch, name))
+}
+
+/// Like `floats`, failing when the column is missing.
+pub fn required_floats(batch: &RecordBatch, name: &str) -> Result<Float32Array> {
+    floats(batch, name)?.with_context(|| missing(batch, name))
+}
+
+/// Column `name` of `batch` as booleans, failing when it is missing.
+pub fn required_bools(batch: &RecordBatch, name: &str) -> Result<BooleanArray> {
+    let column = convert(batch, name, &DataType::Boolean)?.with_context(|| missing(batch, name))?;
+    Ok(column.as_boolean().clone())
+}
+
+/// Vector column `name` of `batch`.
+pub fn vectors<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a FixedSizeListArray> {
+    let column = batch.column_by_name(name).with_context(|| missing(batch, name))?;
+    column
+        .as_fixed_size_list_opt()
+        .with_context(|| format!("Column '{}' holds {} values, not vectors", name, column.data_type()))
+}
+
+/// List column `name` of `batch`, such as the sparse vector columns.
+pub fn lists<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ListArray> {
+    let column = batch.column_by_name(name).with_context(|| missing(batch, name))?;
+    column
+        .as_list_opt::<i32>()
+        .with_context(|| format!("Column '{}' holds {} values, not lists", name, column.data_type()))
+}
+
+fn convert(batch: &RecordBatch, name: &str, to: &DataType) -> Result<Option<ArrayRef>> {
+    let Some(column) = batch.column_by_name(name) else {
+        return Ok(None);
+    };
+    if column.data_type() == to {
+        return Ok(Some(column.clone()));
+    }
+    cast(column, to)
+        .map(Some)
+        .with_context(|| format!("Column '{}' holds {} values, which cannot be read as {}", name, column.data_type(), to))
+}
+
+fn missing(batch: &RecordBatch, name: &str) -> String {
+    let schema = batch.schema();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    format!("Results have no column '{}' (columns: {})", name, names.join(", "))
+}
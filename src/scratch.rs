@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::db::VectorDB;
+
+/// Scratch tables are internal tables, so they never show up in `list_tables`.
+pub const SCRATCH_TABLE_PREFIX: &str = "_mcp_scratch_";
+pub const DEFAULT_SCRATCH_TTL_SECS: u64 = 3600;
+/// How often expired scratch tables are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct ScratchTable {
+    session: u64,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ScratchTableInfo {
+    pub table_name: String,
+    pub expires_in_secs: u64,
+}
+
+/// Temporary tables owned by an MCP session. They are dropped when the owning
+/// session ends or when their TTL expires, whichever comes first.
+pub struct ScratchTables {
+    db: Arc<VectorDB>,
+    tables: Mutex<HashMap<String, ScratchTable>>,
+    next_session: AtomicU64,
+}
+
+impl ScratchTables {
+    pub fn new(db: Arc<VectorDB>) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            tables: Mutex::new(HashMap::new()),
+            next_session: AtomicU64::new(0),
+        })
+    }
+
+    /// Start a new session scope. Scratch tables created through it are
+    /// dropped once the last clone of the returned handle is dropped.
+    pub fn session(self: &Arc<Self>) -> Arc<SessionScope> {
+        Arc::new(SessionScope {
+            id: self.next_session.fetch_add(1, Ordering::Relaxed),
+            scratch: self.clone(),
+        })
+    }
+
+    /// Reserve a scratch table name for `session`. The table itself is created
+    /// by the first write to it.
+    pub fn create(&self, session: &SessionScope, ttl: Duration) -> ScratchTableInfo {
+        let table_name = format!("{}{}", SCRATCH_TABLE_PREFIX, uuid::Uuid::new_v4().simple());
+        self.tables.lock().unwrap().insert(
+            table_name.clone(),
+            ScratchTable {
+                session: session.id,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        ScratchTableInfo {
+            table_name,
+            expires_in_secs: ttl.as_secs(),
+        }
+    }
+
+    /// Whether `table_name` is a live scratch table issued to `session`.
+    pub fn owns(&self, session: &SessionScope, table_name: &str) -> bool {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(table_name)
+            .is_some_and(|t| t.session == session.id && t.expires_at > Instant::now())
+    }
+
+    /// Scratch tables of `session` that are still alive.
+    pub fn list(&self, session: &SessionScope) -> Vec<ScratchTableInfo> {
+        let now = Instant::now();
+        let mut tables: Vec<ScratchTableInfo> = self
+            .tables
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, t)| t.session == session.id)
+            .map(|(name, t)| ScratchTableInfo {
+                table_name: name.clone(),
+                expires_in_secs: t.expires_at.saturating_duration_since(now).as_secs(),
+            })
+            .collect();
+        tables.sort_by(|a, b| a.table_name.cmp(&b.table_name));
+        tables
+    }
+
+    /// Drop every scratch table owned by `session`.
+    async fn release_session(&self, session: u64) {
+        let names = self.take(|t| t.session == session);
+        self.drop_all(names).await;
+    }
+
+    /// Drop every scratch table whose TTL has passed.
+    pub async fn sweep_expired(&self) {
+        let now = Instant::now();
+        let names = self.take(|t| t.expires_at <= now);
+        self.drop_all(names).await;
+    }
+
+    /// Drop all scratch tables, including ones left behind by a previous run
+    /// that did not shut down cleanly. A database on an object store may be
+    /// shared with other servers, so there only this server's are dropped.
+    pub async fn drop_everything(&self) {
+        let mut names = self.take(|_| true);
+        if self.db.is_local() {
+            match self.db.table_names_with_prefix(SCRATCH_TABLE_PREFIX).await {
+                Ok(existing) => names.extend(existing),
+                Err(e) => tracing::error!("Failed to list scratch tables: {}", e),
+            }
+        }
+        names.sort();
+        names.dedup();
+        self.drop_all(names).await;
+    }
+
+    /// Periodically drop expired scratch tables until the process exits.
+    pub fn spawn_sweeper(self: &Arc<Self>) {
+        let scratch = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                scratch.sweep_expired().await;
+            }
+        });
+    }
+
+    fn take(&self, mut pred: impl FnMut(&ScratchTable) -> bool) -> Vec<String> {
+        let mut tables = self.tables.lock().unwrap();
+        let names: Vec<String> = tables.iter().filter(|(_, t)| pred(t)).map(|(n, _)| n.clone()).collect();
+        for name in &names {
+            tables.remove(name);
+        }
+        names
+    }
+
+    async fn drop_all(&self, names: Vec<String>) {
+        for name in names {
+            match self.db.drop_table(&name).await {
+                Ok(()) => tracing::info!("Dropped scratch table '{}'", name),
+                Err(e) => tracing::error!("Failed to drop scratch table '{}': {}", name, e),
+            }
+        }
+    }
+}
+
+/// Identifies one MCP session. Every session gets its own server clone
+/// holding one of these, so it is dropped when the session's service ends.
+pub struct SessionScope {
+    id: u64,
+    scratch: Arc<ScratchTables>,
+}
+
+impl SessionScope {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for SessionScope {
+    fn drop(&mut self) {
+        if !self.scratch.tables.lock().unwrap().values().any(|t| t.session == self.id) {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let scratch = self.scratch.clone();
+        let session = self.id;
+        runtime.spawn(async move {
+            scratch.release_session(session).await;
+        });
+    }
+}
@@ -0,0 +1,36 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::task::{TaskTracker, TaskTrackerToken};
+
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Tool calls and background ingest jobs that shutdown waits for, so a
+/// rollout does not cut off a write halfway.
+#[derive(Clone, Default)]
+pub struct Drain {
+    tasks: TaskTracker,
+}
+
+impl Drain {
+    /// Register a tool call for as long as the token is held. `None` once
+    /// shutdown has begun and no new calls are taken.
+    pub fn enter(&self) -> Option<TaskTrackerToken> {
+        (!self.tasks.is_closed()).then(|| self.tasks.token())
+    }
+
+    /// Run a background job that shutdown waits for.
+    pub fn spawn(&self, job: impl Future<Output = ()> + Send + 'static) {
+        self.tasks.spawn(job);
+    }
+
+    /// Stop taking new calls and wait up to `timeout` for the running calls
+    /// and jobs to finish. Whether they all did.
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        if self.tasks.is_empty() {
+            return true;
+        }
+        tracing::info!("Waiting for {} tool calls and ingest jobs to finish", self.tasks.len());
+        tokio::time::timeout(timeout, self.tasks.wait()).await.is_ok()
+    }
+}
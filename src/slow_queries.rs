@@ -0,0 +1,109 @@
+use anyhow::Result;
+use arrow::array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::{SearchDiagnostics, VectorDB};
+
+/// Internal table slow calls are stored in with --slow-query-table.
+pub const SLOW_QUERY_TABLE: &str = "_mcp_slow_queries";
+
+/// A tool call that took longer than the threshold. Only a hash of the query
+/// is kept, like the audit log keeps only a hash of the arguments.
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    pub tool: String,
+    pub tenant: Option<String>,
+    pub table: Option<String>,
+    /// SHA-256 of the `query` argument, for calls that have one
+    pub query_sha256: Option<String>,
+    pub duration_ms: f64,
+    /// Where a search's time went
+    pub breakdown: Option<SearchDiagnostics>,
+}
+
+impl SlowQuery {
+    pub fn hash_query(arguments: Option<&serde_json::Map<String, serde_json::Value>>) -> Option<String> {
+        let query = arguments?.get("query")?.as_str()?;
+        Some(format!("{:x}", Sha256::digest(query.as_bytes())))
+    }
+}
+
+/// Logs the tool calls that exceed a latency threshold, and optionally
+/// stores them in `SLOW_QUERY_TABLE`.
+pub struct SlowQueryLog {
+    threshold: Duration,
+    store: Option<Arc<VectorDB>>,
+}
+
+impl SlowQueryLog {
+    pub fn new(threshold: Duration, store: Option<Arc<VectorDB>>) -> Self {
+        Self { threshold, store }
+    }
+
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        elapsed >= self.threshold
+    }
+
+    /// Log `query` and store it in the background, so the call it describes
+    /// is not slowed down further.
+    pub fn record(&self, query: SlowQuery) {
+        let breakdown = query.breakdown.as_ref();
+        tracing::warn!(
+            tool = %query.tool,
+            tenant = query.tenant.as_deref(),
+            table = query.table.as_deref(),
+            query_sha256 = query.query_sha256.as_deref(),
+            duration_ms = query.duration_ms,
+            embed_ms = breakdown.map(|b| b.embed_ms),
+            db_ms = breakdown.map(|b| b.db_ms),
+            rerank_ms = breakdown.map(|b| b.rerank_ms),
+            ann_index = breakdown.map(|b| b.ann_index),
+            threshold_ms = self.threshold.as_millis() as u64,
+            "Slow tool call"
+        );
+        if let Some(db) = &self.store {
+            let db = db.clone();
+            tokio::spawn(async move {
+                if let Err(e) = store(&db, &query).await {
+                    tracing::error!("Failed to store slow {} call: {:#}", query.tool, e);
+                }
+            });
+        }
+    }
+}
+
+async fn store(db: &VectorDB, query: &SlowQuery) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("timestamp", DataType::Utf8, false),
+        Field::new("tool", DataType::Utf8, false),
+        Field::new("tenant", DataType::Utf8, true),
+        Field::new("table_name", DataType::Utf8, true),
+        Field::new("query_sha256", DataType::Utf8, true),
+        Field::new("duration_ms", DataType::Float64, false),
+        Field::new("embed_ms", DataType::Float64, true),
+        Field::new("db_ms", DataType::Float64, true),
+        Field::new("rerank_ms", DataType::Float64, true),
+        Field::new("index_name", DataType::Utf8, true),
+    ]));
+    let breakdown = query.breakdown.as_ref();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from(vec![chrono::Utc::now().to_rfc3339()])) as ArrayRef,
+            Arc::new(StringArray::from(vec![query.tool.clone()])),
+            Arc::new(StringArray::from(vec![query.tenant.clone()])),
+            Arc::new(StringArray::from(vec![query.table.clone()])),
+            Arc::new(StringArray::from(vec![query.query_sha256.clone()])),
+            Arc::new(Float64Array::from(vec![query.duration_ms])),
+            Arc::new(Float64Array::from(vec![breakdown.map(|b| b.embed_ms)])),
+            Arc::new(Float64Array::from(vec![breakdown.map(|b| b.db_ms)])),
+            Arc::new(Float64Array::from(vec![breakdown.map(|b| b.rerank_ms)])),
+            // Empty when the vector search scanned every row
+            Arc::new(StringArray::from(vec![breakdown.map(|b| b.index_name.clone().unwrap_or_default())])),
+        ],
+    )?;
+    db.append_rows(SLOW_QUERY_TABLE, batch).await
+}
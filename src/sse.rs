@@ -0,0 +1,133 @@
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{StatusCode, request::Parts},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{SinkExt, Stream, StreamExt, channel::mpsc};
+use rmcp::{
+    ServerHandler, ServiceExt,
+    model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Messages buffered per session in either direction before senders wait.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// The legacy HTTP+SSE transport (MCP 2024-11-05) for clients that do not
+/// speak streamable HTTP yet: `GET /sse` opens a session whose first event
+/// names the URL to `POST` messages to, and every server message arrives as
+/// a `message` event on that stream.
+struct SseSessions<F> {
+    new_session: F,
+    /// Where to deliver client messages, by session id
+    sessions: Mutex<HashMap<String, mpsc::Sender<ClientJsonRpcMessage>>>,
+}
+
+#[derive(serde::Deserialize)]
+struct MessageQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+/// Routes serving the SSE transport, each session handled by a fresh
+/// handler from `new_session`. Cancelling `shutdown` ends every session.
+pub fn router<S, F>(new_session: F, shutdown: CancellationToken) -> Router
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + 'static,
+{
+    let state = Arc::new(SseSessions {
+        new_session,
+        sessions: Mutex::new(HashMap::new()),
+    });
+    let closing = state.clone();
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        // Without a way to send, each service sees its input end and stops
+        closing.sessions.lock().unwrap().clear();
+    });
+    Router::new()
+        .route("/sse", axum::routing::get(open::<S, F>))
+        .route("/message", axum::routing::post(post::<S, F>))
+        .with_state(state)
+}
+
+async fn open<S, F>(State(state): State<Arc<SseSessions<F>>>) -> Sse<impl Stream<Item = Result<Event, Infallible>>>
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + 'static,
+{
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let (to_server, from_client) = mpsc::channel(CHANNEL_CAPACITY);
+    let (to_client, from_server) = mpsc::channel::<ServerJsonRpcMessage>(CHANNEL_CAPACITY);
+    state.sessions.lock().unwrap().insert(id.clone(), to_server);
+
+    let handler = (state.new_session)();
+    let session_id = id.clone();
+    tokio::spawn(async move {
+        match handler.serve((to_client, from_client)).await {
+            Ok(service) => {
+                let _ = service.waiting().await;
+            }
+            Err(e) => tracing::warn!(session = %session_id, "SSE session failed to initialize: {}", e),
+        }
+    });
+
+    // Dropping the stream when the client disconnects forgets the session,
+    // which closes its message channel and ends the service
+    let guard = SessionGuard { state: state.clone(), id: id.clone() };
+    let endpoint = Event::default().event("endpoint").data(format!("/message?sessionId={}", id));
+    let messages = from_server.map(move |message| {
+        let _session = &guard;
+        Ok(Event::default()
+            .event("message")
+            .json_data(&message)
+            .unwrap_or_else(|e| Event::default().event("error").data(e.to_string())))
+    });
+    Sse::new(futures::stream::once(async { Ok(endpoint) }).chain(messages)).keep_alive(KeepAlive::default())
+}
+
+async fn post<S, F>(
+    State(state): State<Arc<SseSessions<F>>>,
+    Query(query): Query<MessageQuery>,
+    parts: Parts,
+    Json(mut message): Json<ClientJsonRpcMessage>,
+) -> StatusCode
+where
+    S: ServerHandler,
+    F: Fn() -> S + Send + Sync + 'static,
+{
+    // Like the streamable HTTP transport, hand the HTTP request to the handler
+    match &mut message {
+        ClientJsonRpcMessage::Request(req) => {
+            req.request.extensions_mut().insert(parts);
+        }
+        ClientJsonRpcMessage::Notification(not) => {
+            not.notification.extensions_mut().insert(parts);
+        }
+        _ => {}
+    }
+    let sender = state.sessions.lock().unwrap().get(&query.session_id).cloned();
+    match sender {
+        Some(mut sender) => match sender.send(message).await {
+            Ok(()) => StatusCode::ACCEPTED,
+            Err(_) => StatusCode::GONE,
+        },
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+struct SessionGuard<F> {
+    state: Arc<SseSessions<F>>,
+    id: String,
+}
+
+impl<F> Drop for SessionGuard<F> {
+    fn drop(&mut self) {
+        self.state.sessions.lock().unwrap().remove(&self.id);
+    }
+}
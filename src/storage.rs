@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+pub const DEFAULT_CLOUD_REGION: &str = "us-east-1";
+
+/// Remote schemes `--db-path` may use, the build feature each needs and
+/// whether this build has it.
+const REMOTE_SCHEMES: [(&str, &str, bool); 5] = [
+    ("db://", "cloud", cfg!(feature = "cloud")),
+    ("s3://", "s3", cfg!(feature = "s3")),
+    ("s3+ddb://", "dynamodb", cfg!(feature = "dynamodb")),
+    ("gs://", "gcs", cfg!(feature = "gcs")),
+    ("az://", "azure", cfg!(feature = "azure")),
+];
+
+/// How to reach the database besides its URI.
+#[derive(Default)]
+pub struct ConnectOptions {
+    /// Object store options: region, endpoint, credentials
+    pub storage: HashMap<String, String>,
+    /// LanceDB Cloud settings, for `db://` URIs
+    pub cloud: Option<CloudOptions>,
+    /// How soon reads see writes made through other connections
+    pub consistency: Consistency,
+}
+
+/// How soon reads see versions of a table written by other processes,
+/// e.g. replicas sharing object storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Consistency {
+    /// Every read checks for a newer version first
+    #[default]
+    Strong,
+    /// Reads check for a newer version at most this often
+    Interval(std::time::Duration),
+    /// Reads use the version the table was opened at, and do not wait for
+    /// this process's own writes to be listed
+    Eventual,
+}
+
+impl Consistency {
+    /// Parse `strong`, `eventual`, or an interval like `500ms`, `5s` or `1m`
+    /// (a bare number is seconds).
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        match text {
+            "strong" => return Ok(Self::Strong),
+            "eventual" => return Ok(Self::Eventual),
+            _ => {}
+        }
+        let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+        let millis = match unit.trim() {
+            "ms" => 1,
+            "" | "s" => 1000,
+            "m" => 60 * 1000,
+            _ => anyhow::bail!("Invalid --consistency '{}', expected strong, eventual or an interval like 5s", text),
+        };
+        let interval = number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(millis))
+            .with_context(|| format!("Invalid --consistency '{}', expected strong, eventual or an interval like 5s", text))?;
+        Ok(if interval == 0 { Self::Strong } else { Self::Interval(std::time::Duration::from_millis(interval)) })
+    }
+
+    /// LanceDB's read consistency interval: zero checks on every read,
+    /// `None` never checks.
+    pub fn read_interval(self) -> Option<std::time::Duration> {
+        match self {
+            Self::Strong => Some(std::time::Duration::ZERO),
+            Self::Interval(interval) => Some(interval),
+            Self::Eventual => None,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "cloud"), allow(dead_code))]
+pub struct CloudOptions {
+    pub api_key: String,
+    pub region: String,
+    /// Endpoint to use instead of the one derived from the region, e.g. for
+    /// a dedicated deployment
+    pub host_override: Option<String>,
+}
+
+impl CloudOptions {
+    /// The settings a `db://` URI needs, `None` for any other database.
+    pub fn for_uri(uri: &str, api_key: Option<String>, region: String, host_override: Option<String>) -> Result<Option<Self>> {
+        if !uri.starts_with("db://") {
+            return Ok(None);
+        }
+        let api_key = api_key
+            .filter(|key| !key.trim().is_empty())
+            .with_context(|| format!("--lancedb-api-key is required to connect to {}", uri))?;
+        Ok(Some(Self {
+            api_key,
+            region,
+            host_override,
+        }))
+    }
+}
+
+/// Fail early, with the feature to build with, when `--db-path` names a
+/// remote database this build cannot reach.
+pub fn check_uri(uri: &str) -> Result<()> {
+    for (scheme, feature, available) in REMOTE_SCHEMES {
+        if uri.starts_with(scheme) && !available {
+            anyhow::bail!("--db-path {} needs a build with the {} feature", uri, feature);
+        }
+    }
+    Ok(())
+}
+
+/// Object store options for the database connection: the `key=value`
+/// entries of `--storage-option`, after the region and endpoint flags so
+/// explicit entries win. Credentials not given here are taken from the
+/// environment (e.g. `AWS_ACCESS_KEY_ID`) by the object store itself.
+pub fn options(entries: &[String], s3_region: Option<&str>, s3_endpoint: Option<&str>) -> Result<HashMap<String, String>> {
+    let mut options = HashMap::new();
+    if let Some(region) = s3_region {
+        options.insert("aws_region".to_string(), region.to_string());
+    }
+    if let Some(endpoint) = s3_endpoint {
+        options.insert("aws_endpoint".to_string(), endpoint.to_string());
+        // MinIO and other self-hosted stores usually serve plain HTTP
+        if endpoint.starts_with("http://") {
+            options.insert("allow_http".to_string(), "true".to_string());
+        }
+    }
+    for entry in entries {
+        let (key, value) = entry
+            .split_once('=')
+            .with_context(|| format!("Invalid --storage-option '{}', expected key=value", entry))?;
+        options.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+    Ok(options)
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::ErrorCode;
+
+/// Per-table settings, persisted alongside the data so they apply to every
+/// client and survive restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TableConfig {
+    /// What the table contains, used to route queries that name no table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Named fusion preset used by hybrid search (`code`, `prose`, `balanced`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fusion_preset: Option<String>,
+    /// Explicit fusion weights, taking precedence over the preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fusion_weights: Option<FusionWeights>,
+    /// Stemming / stop-word language of the full-text index (e.g. `English`, `German`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fts_language: Option<String>,
+    /// Whether the full-text index stems tokens (default: true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fts_stem: Option<bool>,
+    /// How strongly chunk importance is blended into search scores
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub importance_weight: Option<f32>,
+    /// Weight of the sparse score in hybrid search, on tables with sparse vectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse_weight: Option<f32>,
+    /// Embedding model the table's vectors were produced with, recorded on
+    /// first use and checked on every add and search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// Dimension of the table's vectors, recorded with `embedding_model`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_dim: Option<usize>,
+    /// Defaults of the metadata columns added with `add_column`, stored for
+    /// rows written without the key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column_defaults: Option<BTreeMap<String, serde_json::Value>>,
+    /// Sparse model whose vectors the table stores, recorded on first write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse_model: Option<String>,
+    /// Vector columns besides `vector`, with the model that filled each
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector_columns: Option<BTreeMap<String, VectorColumn>>,
+    /// How the `vector` column is quantized for search (default: not at all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<Quantization>,
+    /// Whether quantized searches rescore their top candidates with the
+    /// full-precision vectors (default: true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rescore: Option<bool>,
+}
+
+/// Compressed form of a table's vectors that searches run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    /// An IVF-HNSW index of 8-bit scalar-quantized vectors
+    Int8,
+    /// One sign bit per dimension, compared by Hamming distance
+    Binary,
+}
+
+impl Quantization {
+    /// Parse a quantization name; `none` turns quantization off.
+    pub fn parse(name: &str) -> Result<Option<Self>> {
+        match name {
+            "none" => Ok(None),
+            "int8" => Ok(Some(Self::Int8)),
+            "binary" => Ok(Some(Self::Binary)),
+            other => Err(anyhow::anyhow!("Unknown quantization '{}', expected 'none', 'int8' or 'binary'", other))
+                .context(ErrorCode::InvalidArgument),
+        }
+    }
+}
+
+/// Embedding model and dimension recorded for an extra vector column.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct VectorColumn {
+    pub embedding_model: String,
+    pub embedding_dim: usize,
+}
+
+impl TableConfig {
+    /// Fill the settings left unset here from `defaults`. The recorded
+    /// embedding model is never defaulted.
+    pub fn with_defaults(self, defaults: &TableConfig) -> Self {
+        // A preset and weights together make one fusion setting
+        let (fusion_preset, fusion_weights) = if self.fusion_preset.is_none() && self.fusion_weights.is_none() {
+            (defaults.fusion_preset.clone(), defaults.fusion_weights)
+        } else {
+            (self.fusion_preset.clone(), self.fusion_weights)
+        };
+        Self {
+            description: self.description.or_else(|| defaults.description.clone()),
+            fusion_preset,
+            fusion_weights,
+            fts_language: self.fts_language.or_else(|| defaults.fts_language.clone()),
+            fts_stem: self.fts_stem.or(defaults.fts_stem),
+            importance_weight: self.importance_weight.or(defaults.importance_weight),
+            sparse_weight: self.sparse_weight.or(defaults.sparse_weight),
+            quantization: self.quantization.or(defaults.quantization),
+            rescore: self.rescore.or(defaults.rescore),
+            ..self
+        }
+    }
+
+    /// Fusion weights configured for this table, if hybrid search is set up for it.
+    pub fn fusion(&self) -> Option<FusionWeights> {
+        self.fusion_weights
+            .or_else(|| self.fusion_preset.as_deref().and_then(FusionWeights::preset))
+    }
+}
+
+/// Weight of the sparse score in hybrid search when none is set.
+pub const DEFAULT_SPARSE_WEIGHT: f32 = 0.3;
+
+/// Relative weight of the vector and keyword scores in hybrid search, and
+/// of the sparse score on tables with sparse vectors.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FusionWeights {
+    pub vector: f32,
+    pub keyword: f32,
+    /// Weight of the sparse (SPLADE) score, `DEFAULT_SPARSE_WEIGHT` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<f32>,
+}
+
+impl FusionWeights {
+    pub const PRESETS: &'static [&'static str] = &["code", "prose", "balanced"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let (vector, keyword) = match name {
+            // Identifiers and exact tokens matter more than paraphrase in code
+            "code" => (0.3, 0.7),
+            "prose" => (0.8, 0.2),
+            "balanced" => (0.5, 0.5),
+            _ => return None,
+        };
+        Some(Self { vector, keyword, sparse: None })
+    }
+
+    /// Scale the weights so they sum to 1, counting the sparse weight only
+    /// `with_sparse`; otherwise it is 0.
+    pub fn normalized(self, with_sparse: bool) -> Self {
+        let sparse = if with_sparse { self.sparse.unwrap_or(DEFAULT_SPARSE_WEIGHT) } else { 0.0 };
+        let sum = self.vector + self.keyword + sparse;
+        if sum <= 0.0 {
+            return Self { vector: 0.5, keyword: 0.5, sparse: Some(0.0) };
+        }
+        Self {
+            vector: self.vector / sum,
+            keyword: self.keyword / sum,
+            sparse: Some(sparse / sum),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Between the tenant id and the table name in stored table names.
+const SEPARATOR: &str = "__";
+const MAX_TENANT_LEN: usize = 64;
+
+/// One tenant of a shared server. Every table it names is stored as
+/// `<tenant>__<name>`, so tenants only ever see and touch their own tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tenant(String);
+
+impl Tenant {
+    /// Tenant ids are letters, digits and dashes, so the separator can never
+    /// appear inside one.
+    pub fn new(id: &str) -> Result<Self> {
+        if id.is_empty() || id.len() > MAX_TENANT_LEN || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            anyhow::bail!(
+                "Invalid tenant id '{}', expected 1-{} letters, digits or dashes",
+                id,
+                MAX_TENANT_LEN
+            );
+        }
+        Ok(Self(id.to_string()))
+    }
+
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+
+    /// The stored name of a table this tenant calls `name`.
+    pub fn table(&self, name: &str) -> String {
+        format!("{}{}{}", self.0, SEPARATOR, name)
+    }
+
+    /// The name this tenant knows a stored table by, `None` if the table
+    /// belongs to someone else.
+    pub fn strip<'a>(&self, stored: &'a str) -> Option<&'a str> {
+        stored.strip_prefix(self.0.as_str())?.strip_prefix(SEPARATOR)
+    }
+}
+
+/// The tenant whose data a caller may see, `None` for every tenant's:
+/// operators and callers without a tenant see everything.
+pub fn scope<'a>(tenant: Option<&'a Tenant>, operators: &[Tenant]) -> Option<&'a Tenant> {
+    tenant.filter(|tenant| !operators.contains(tenant))
+}
+
+/// Where the HTTP transports take the tenant of a request from when the
+/// bearer token does not name one.
+pub struct TenantPolicy {
+    pub header: Option<HeaderName>,
+}
+
+/// Tag each request with its tenant: the one its bearer token belongs to,
+/// else the one named in the tenant header. Requests with neither are
+/// rejected.
+pub async fn identify(State(policy): State<Arc<TenantPolicy>>, mut request: Request, next: Next) -> Response {
+    if request.extensions().get::<Tenant>().is_some() {
+        return next.run(request).await;
+    }
+    let header = policy
+        .header
+        .as_ref()
+        .and_then(|name| request.headers().get(name))
+        .map(|value| value.to_str().map_err(|e| anyhow::anyhow!(e)).and_then(Tenant::new));
+    match header {
+        Some(Ok(tenant)) => {
+            request.extensions_mut().insert(tenant);
+            next.run(request).await
+        }
+        Some(Err(e)) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        None => (StatusCode::FORBIDDEN, "request names no tenant").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_tenant_ids() {
+        assert!(Tenant::new("team-a").is_ok());
+        assert!(Tenant::new(&"a".repeat(MAX_TENANT_LEN)).is_ok());
+        for id in ["", "team_a", "team__a", "team a", "team/a", "a".repeat(MAX_TENANT_LEN + 1).as_str()] {
+            assert!(Tenant::new(id).is_err(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn table_and_strip_round_trip() {
+        let tenant = Tenant::new("team-a").unwrap();
+        for name in ["docs", "knowledge_base", "with__separator", ""] {
+            let stored = tenant.table(name);
+            assert_eq!(stored, format!("team-a__{}", name));
+            assert_eq!(tenant.strip(&stored), Some(name));
+        }
+    }
+
+    #[test]
+    fn strip_ignores_other_tenants_tables() {
+        let tenant = Tenant::new("team").unwrap();
+        let other = Tenant::new("team-b").unwrap();
+        assert_eq!(tenant.strip(&other.table("docs")), None);
+        assert_eq!(other.strip(&tenant.table("docs")), None);
+        assert_eq!(tenant.strip("docs"), None);
+        assert_eq!(tenant.strip("teamdocs"), None);
+    }
+
+    #[test]
+    fn operators_and_callers_without_a_tenant_see_everything() {
+        let team = Tenant::new("team-a").unwrap();
+        let ops = Tenant::new("ops").unwrap();
+        let operators = [ops.clone()];
+        assert_eq!(scope(Some(&team), &operators), Some(&team));
+        assert_eq!(scope(Some(&ops), &operators), None);
+        assert_eq!(scope(None, &operators), None);
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+pub const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 300;
+/// Time a timed out call gets to stop at its next safe point, e.g. between
+/// ingest batches, before its caller stops waiting for it.
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// How long each tool may run. Background ingest jobs are not tool calls and
+/// are never timed out.
+#[derive(Debug, Clone, Default)]
+pub struct ToolTimeouts {
+    default: Option<Duration>,
+    per_tool: HashMap<String, Option<Duration>>,
+}
+
+impl ToolTimeouts {
+    /// `default_secs` applies to every tool, overridden by `tool=secs`
+    /// entries; 0 means no timeout.
+    pub fn new(default_secs: u64, overrides: &[String]) -> Result<Self> {
+        let mut per_tool = HashMap::new();
+        for entry in overrides {
+            let (tool, secs) = entry
+                .split_once('=')
+                .with_context(|| format!("Invalid --tool-timeout '{}', expected tool=seconds", entry))?;
+            let secs: u64 = secs
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid --tool-timeout '{}', expected tool=seconds", entry))?;
+            per_tool.insert(tool.trim().to_string(), seconds(secs));
+        }
+        Ok(Self {
+            default: seconds(default_secs),
+            per_tool,
+        })
+    }
+
+    pub fn get(&self, tool: &str) -> Option<Duration> {
+        self.per_tool.get(tool).copied().unwrap_or(self.default)
+    }
+}
+
+fn seconds(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// How a call run under a timeout ended.
+pub enum Run<T> {
+    Finished(T),
+    /// The limit passed. Holds what the call returned if it still came back
+    /// within the grace period, having finished or stopped at a safe point.
+    TimedOut(Option<T>),
+}
+
+/// Run `fut` for at most `limit`. When the limit passes, `cancel` is
+/// triggered so the call can stop cleanly, and after a short grace period
+/// `fut` is dropped. Pass the handle of a spawned task to detach the call
+/// instead, letting it finish in the background.
+pub async fn run<T>(fut: impl Future<Output = T>, limit: Option<Duration>, cancel: &CancellationToken) -> Run<T> {
+    let Some(limit) = limit else {
+        return Run::Finished(fut.await);
+    };
+    tokio::pin!(fut);
+    if let Ok(result) = tokio::time::timeout(limit, &mut fut).await {
+        return Run::Finished(result);
+    }
+    cancel.cancel();
+    Run::TimedOut(tokio::time::timeout(CANCEL_GRACE, fut).await.ok())
+}
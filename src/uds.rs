@@ -0,0 +1,62 @@
+use anyhow::Result;
+use axum::Router;
+use std::path::PathBuf;
+
+/// Serve the HTTP app on a unix domain socket until `shutdown` resolves, for
+/// sidecars on the same host that should not reach it over the network.
+///
+/// A socket file left behind by a previous run is replaced, and the socket is
+/// removed again on shutdown. A path that is not a socket, or a socket another
+/// server still accepts connections on, is left alone and fails the start.
+#[cfg(unix)]
+pub async fn serve(path: PathBuf, app: Router, shutdown: impl Future<Output = ()>) -> Result<()> {
+    use anyhow::Context;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto;
+    use hyper_util::service::TowerToHyperService;
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a unix socket", path.display());
+        }
+        if tokio::net::UnixStream::connect(&path).await.is_ok() {
+            anyhow::bail!("Another server is already listening on {}", path.display());
+        }
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .with_context(|| format!("Failed to bind unix socket {}", path.display()))?;
+    crate::daemon::notify_ready();
+
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Failed to accept unix socket connection: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Unix socket connection closed with error: {}", e);
+            }
+        });
+    }
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn serve(_path: PathBuf, _app: Router, _shutdown: impl Future<Output = ()>) -> Result<()> {
+    anyhow::bail!("The unix transport is only supported on unix platforms")
+}
@@ -0,0 +1,68 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::audit::Outcome;
+use crate::error::ErrorCode;
+use crate::latency::TenantLatency;
+
+/// Tool calls of one tenant, or of sessions without one, since the server
+/// started.
+#[derive(Debug, Clone, Default, Serialize, schemars::JsonSchema)]
+pub struct TenantUsage {
+    /// Tenant id, absent for sessions without a tenant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub calls: u64,
+    /// Calls that returned an error, rate limited ones included
+    pub failed: u64,
+    /// Calls turned away by a call limit
+    pub rate_limited: u64,
+    /// Time spent in all calls together
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct AdminReport {
+    /// One entry per tenant that made a call
+    pub usage: Vec<TenantUsage>,
+    /// Search latency window in seconds
+    pub latency_window_secs: u64,
+    /// One entry per tenant that searched inside the window
+    pub search_latency: Vec<TenantLatency>,
+}
+
+/// Counts tool calls per tenant for `admin_report`.
+#[derive(Default)]
+pub struct UsageTracker {
+    usage: Mutex<HashMap<Option<String>, TenantUsage>>,
+}
+
+impl UsageTracker {
+    pub fn record(&self, tenant: Option<&str>, outcome: Outcome, code: Option<i64>, elapsed: Duration) {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(tenant.map(str::to_string)).or_default();
+        entry.calls += 1;
+        if !matches!(outcome, Outcome::Ok) {
+            entry.failed += 1;
+        }
+        if code == Some(ErrorCode::RateLimited.code() as i64) {
+            entry.rate_limited += 1;
+        }
+        entry.total_ms += elapsed.as_secs_f64() * 1000.0;
+    }
+
+    pub fn report(&self) -> Vec<TenantUsage> {
+        let usage = self.usage.lock().unwrap();
+        let mut report: Vec<TenantUsage> = usage
+            .iter()
+            .map(|(tenant, usage)| TenantUsage {
+                tenant: tenant.clone(),
+                ..usage.clone()
+            })
+            .collect();
+        report.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        report
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::context::CallContext;
+use crate::{AddFileRequest, LanceDBServer};
+
+/// File types picked up by the watcher; everything else in the directory is ignored.
+const SUPPORTED_EXTENSIONS: &[&str] = &["pdf", "html", "htm", "txt", "md", "markdown", "rst"];
+
+/// How long to wait for a burst of filesystem events to settle before syncing.
+const DEBOUNCE: Duration = Duration::from_millis(1000);
+
+/// Keep `table_name` in sync with the files under `dir`.
+///
+/// On startup every file whose modification time differs from the stored
+/// `source_mtime` is re-ingested and chunks of files that disappeared are
+/// deleted; afterwards filesystem events are applied as they arrive.
+pub async fn run(server: LanceDBServer, dir: PathBuf, table_name: String) -> Result<()> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Watch directory {} does not exist", dir.display()))?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let _ = tx.send(res);
+    })?;
+    watcher.watch(&dir, RecursiveMode::Recursive)?;
+    tracing::info!("Watching {} for changes (table '{}')", dir.display(), table_name);
+
+    if let Err(e) = initial_sync(&server, &dir, &table_name).await {
+        tracing::error!("Initial sync of {} failed: {}", dir.display(), e);
+    }
+
+    while let Some(event) = rx.recv().await {
+        let mut dirty = HashSet::new();
+        collect_paths(event, &mut dirty);
+
+        // Coalesce the burst of events a single save usually produces
+        let deadline = tokio::time::sleep(DEBOUNCE);
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                event = rx.recv() => match event {
+                    Some(event) => collect_paths(event, &mut dirty),
+                    None => break,
+                },
+            }
+        }
+
+        for path in dirty {
+            sync_path(&server, &table_name, &path).await;
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_paths(event: notify::Result<notify::Event>, dirty: &mut HashSet<PathBuf>) {
+    match event {
+        Ok(event) if matches!(event.kind, notify::EventKind::Access(_)) => {}
+        Ok(event) => dirty.extend(event.paths),
+        Err(e) => tracing::warn!("File watcher error: {}", e),
+    }
+}
+
+fn is_supported(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .map(|n| n.to_string_lossy().starts_with('.'))
+        .unwrap_or(true);
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    !hidden && SUPPORTED_EXTENSIONS.contains(&extension.as_str())
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn walk_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            walk_files(&path, out);
+        } else if is_supported(&path) {
+            out.push(path);
+        }
+    }
+}
+
+async fn initial_sync(server: &LanceDBServer, dir: &Path, table_name: &str) -> Result<()> {
+    let mut stored: HashMap<String, Option<u64>> = HashMap::new();
+    for (_, meta) in server.db.scan_metadata(table_name, Some("\"source_path\"")).await? {
+        if let Some(source) = meta.get("source_path").and_then(|v| v.as_str()) {
+            if Path::new(source).starts_with(dir) {
+                stored.insert(source.to_string(), meta.get("source_mtime").and_then(|v| v.as_u64()));
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    walk_files(dir, &mut files);
+
+    for path in &files {
+        let key = path.to_string_lossy().into_owned();
+        let current = modified_secs(path);
+        match stored.remove(&key) {
+            Some(mtime) if mtime.is_some() && mtime == current => {}
+            _ => sync_path(server, table_name, path).await,
+        }
+    }
+
+    // Whatever is left was indexed before but no longer exists on disk
+    for source in stored.into_keys() {
+        sync_path(server, table_name, Path::new(&source)).await;
+    }
+
+    Ok(())
+}
+
+/// Bring the chunks for `path` in line with what is on disk.
+async fn sync_path(server: &LanceDBServer, table_name: &str, path: &Path) {
+    if path.is_dir() {
+        let mut files = Vec::new();
+        walk_files(path, &mut files);
+        for file in files {
+            Box::pin(sync_path(server, table_name, &file)).await;
+        }
+    } else if path.is_file() {
+        if is_supported(path) {
+            reindex_file(server, table_name, path).await;
+        }
+    } else {
+        remove_path(server, table_name, path).await;
+    }
+}
+
+async fn reindex_file(server: &LanceDBServer, table_name: &str, path: &Path) {
+    let source = path.to_string_lossy().into_owned();
+
+    // The old chunks are only removed once the new ones are stored, so a file
+    // that fails to extract or embed keeps its previous version searchable
+    let old_ids = match server.db.ids_by_metadata(table_name, "source_path", &source).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::error!("Failed to look up old chunks of {}: {}", source, e);
+            return;
+        }
+    };
+
+    let req = AddFileRequest {
+        table_name: Some(table_name.to_string()),
+        path: source.clone(),
+        name: None,
+        description: None,
+        chunk_size: None,
+        chunk_overlap: None,
+        metadata: Some(serde_json::json!({ "source_mtime": modified_secs(path) })),
+        importance: None,
+    };
+    let chunks = match server.ingest_file(table_name, &req, &CallContext::default()).await {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            tracing::warn!("Failed to index {}, keeping its previous chunks: {}", source, e);
+            return;
+        }
+    };
+    match server.db.delete_ids(table_name, &old_ids).await {
+        Ok(()) => tracing::info!("Indexed {} ({} chunks)", source, chunks),
+        // The next change to the file replaces both versions
+        Err(e) => tracing::error!("Indexed {} but failed to remove its {} old chunks: {}", source, old_ids.len(), e),
+    }
+}
+
+/// Delete the chunks of a removed file, or of every file under a removed directory.
+async fn remove_path(server: &LanceDBServer, table_name: &str, path: &Path) {
+    let source = path.to_string_lossy().into_owned();
+    // Prefix of the serialized metadata, matching the path itself and anything below it
+    let escaped = serde_json::json!(source).to_string();
+    let needle = format!("\"source_path\":{}", escaped.trim_end_matches('"'));
+
+    let rows = match server.db.scan_metadata(table_name, Some(&needle)).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!("Failed to look up chunks of {}: {}", source, e);
+            return;
+        }
+    };
+    let ids: Vec<String> = rows
+        .into_iter()
+        .filter(|(_, meta)| {
+            meta.get("source_path")
+                .and_then(|v| v.as_str())
+                .map(|p| Path::new(p).starts_with(path))
+                .unwrap_or(false)
+        })
+        .map(|(id, _)| id)
+        .collect();
+
+    if ids.is_empty() {
+        return;
+    }
+    match server.db.delete_ids(table_name, &ids).await {
+        Ok(()) => tracing::info!("Removed {} chunks of {}", ids.len(), source),
+        Err(e) => tracing::error!("Failed to remove chunks of {}: {}", source, e),
+    }
+}